@@ -0,0 +1,68 @@
+//! Throughput of reading a bin file of a million records
+//! through read buffers of different sizes.
+//! Results are written to target/criterion/bin_reader
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::Duration;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use parserde::{BinReader, DataProducer, Record, Status, TxType, build_serializer};
+
+const RECORDS: u64 = 1_000_000;
+
+fn write_input() -> (tempfile::TempPath, u64) {
+    let serializer = build_serializer("bin").unwrap();
+    let file = tempfile::NamedTempFile::new().unwrap();
+    let mut writer = BufWriter::new(file.as_file());
+    for i in 0..RECORDS {
+        let record = Record::new(
+            i,
+            TxType::Transfer,
+            i % 1000,
+            i % 997,
+            i * 100,
+            1633036860000 + i,
+            Status::Success,
+            format!("Record number {}", i),
+        );
+        writer
+            .write_all(&serializer.serialize(&record).unwrap())
+            .unwrap();
+    }
+    writer.flush().unwrap();
+    drop(writer);
+    let size = file.as_file().metadata().unwrap().len();
+    (file.into_temp_path(), size)
+}
+
+fn bench_buf_capacity(c: &mut Criterion) {
+    let (path, size) = write_input();
+    let mut group = c.benchmark_group("bin_reader");
+    group
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(20))
+        .throughput(Throughput::Bytes(size));
+    for capacity in [1 << 20, 8 << 20, 64 << 20] {
+        group.bench_with_input(
+            BenchmarkId::new("buf_capacity_mib", capacity >> 20),
+            &capacity,
+            |b, &capacity| {
+                b.iter(|| {
+                    let file = File::open(&path).unwrap();
+                    let mut reader = BinReader::with_buf_capacity(file, capacity).unwrap();
+                    let mut count = 0;
+                    while let Some(record) = reader.produce_record() {
+                        record.unwrap();
+                        count += 1;
+                    }
+                    assert_eq!(count, RECORDS);
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_buf_capacity);
+criterion_main!(benches);
@@ -0,0 +1,266 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error::{RecordProduceError, RecordWriteError};
+use crate::record::{DataProducer, Record, RecordSerialize, RecordWriter};
+#[cfg(feature = "rayon")]
+use crate::result::RecordSerializeResult;
+use crate::result::RecordWriteResult;
+
+/// Records held in memory for operations needing all of them at once
+///
+/// ```
+/// use parserde::{Record, RecordBatch, Status};
+/// let mut batch = RecordBatch::from(vec![Record::default(), Record::default()]);
+/// batch.deduplicate_by_tx_id();
+/// assert_eq!(batch.len(), 1);
+/// assert_eq!(batch.count_by_status()[&Status::Pending.to_string()], 1);
+/// ```
+///
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RecordBatch {
+    records: Vec<Record>,
+}
+
+impl RecordBatch {
+    /// Reads every record of the producer stopping at the first error
+    pub fn from_producer(
+        producer: &mut dyn DataProducer,
+    ) -> Result<RecordBatch, RecordProduceError> {
+        let mut records = Vec::new();
+        while let Some(record) = producer.produce_record() {
+            records.push(record?);
+        }
+        Ok(RecordBatch { records })
+    }
+
+    /// Records of the batch
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Count of records
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether the batch has no records
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Sorts the records by TX_ID keeping the order of equal ones
+    pub fn sort_by_tx_id(&mut self) {
+        self.records.sort_by_key(|r| r.tx_id);
+    }
+
+    /// Sorts the records by TIMESTAMP keeping the order of equal ones
+    pub fn sort_by_timestamp(&mut self) {
+        self.records.sort_by_key(|r| r.timestamp);
+    }
+
+    /// Sorts the records by AMOUNT keeping the order of equal ones
+    pub fn sort_by_amount(&mut self) {
+        self.records.sort_by_key(|r| r.amount);
+    }
+
+    /// Removes records whose TX_ID appeared before
+    pub fn deduplicate_by_tx_id(&mut self) {
+        let mut seen = HashSet::new();
+        self.records.retain(|r| seen.insert(r.tx_id));
+    }
+
+    /// A batch of the records the predicate accepts
+    pub fn filter(&self, f: impl Fn(&Record) -> bool) -> RecordBatch {
+        RecordBatch {
+            records: self.records.iter().filter(|r| f(r)).cloned().collect(),
+        }
+    }
+
+    /// Sum of AMOUNT saturating at `u64::MAX`
+    pub fn total_amount(&self) -> u64 {
+        self.records
+            .iter()
+            .fold(0, |total: u64, r| total.saturating_add(r.amount))
+    }
+
+    /// Count of records of each STATUS
+    pub fn count_by_status(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for record in &self.records {
+            *counts.entry(record.status.to_string()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Serializes the records on the rayon thread pool.
+    /// Results are in the order of the records
+    #[cfg(feature = "rayon")]
+    pub fn par_serialize(
+        &self,
+        serializer: &(dyn RecordSerialize + Sync),
+    ) -> Vec<RecordSerializeResult<Vec<u8>>> {
+        use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+        self.records
+            .par_iter()
+            .map(|record| serializer.serialize(record))
+            .collect()
+    }
+
+    /// Serializes every record and writes it.
+    /// The writer isn't finished
+    pub fn write_to(
+        &self,
+        writer: &mut dyn RecordWriter,
+        serializer: &dyn RecordSerialize,
+    ) -> RecordWriteResult<()> {
+        for record in &self.records {
+            let data = serializer.serialize(record).map_err(|e| RecordWriteError {
+                text: format!("failed to serialize record {}", record.tx_id),
+                source: Some(Box::new(e)),
+            })?;
+            writer.write_record(record, data)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Vec<Record>> for RecordBatch {
+    fn from(records: Vec<Record>) -> Self {
+        RecordBatch { records }
+    }
+}
+
+impl IntoIterator for RecordBatch {
+    type Item = Record;
+    type IntoIter = std::vec::IntoIter<Record>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.records.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::builder::{build_reader, build_serializer, build_writer};
+    use crate::record::{Status, TxType};
+
+    /// Sink keeping the written bytes readable after the writer is dropped
+    #[derive(Clone, Default)]
+    struct Shared(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl std::io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record(tx_id: u64, amount: u64, timestamp: u64, status: Status) -> Record {
+        Record::new(
+            tx_id,
+            TxType::Transfer,
+            1,
+            2,
+            amount,
+            timestamp,
+            status,
+            format!("Record number {}", tx_id),
+        )
+    }
+
+    fn batch() -> RecordBatch {
+        RecordBatch::from(vec![
+            record(3, 10, 300, Status::Success),
+            record(1, 30, 200, Status::Failure),
+            record(2, 20, 100, Status::Success),
+            record(1, 40, 400, Status::Pending),
+        ])
+    }
+
+    fn ids(batch: &RecordBatch) -> Vec<u64> {
+        batch.records().iter().map(|r| r.tx_id).collect()
+    }
+
+    #[test]
+    fn test_sort_and_deduplicate() {
+        let mut batch = batch();
+        batch.sort_by_tx_id();
+        assert_eq!(ids(&batch), [1, 1, 2, 3]);
+        assert_eq!(batch.records()[0].amount, 30);
+        batch.sort_by_timestamp();
+        assert_eq!(ids(&batch), [2, 1, 3, 1]);
+        batch.sort_by_amount();
+        assert_eq!(ids(&batch), [3, 2, 1, 1]);
+
+        let mut batch = self::batch();
+        batch.deduplicate_by_tx_id();
+        assert_eq!(ids(&batch), [3, 1, 2]);
+        assert_eq!(batch.records()[1].amount, 30);
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let batch = batch();
+        assert_eq!(batch.total_amount(), 100);
+        assert_eq!(ids(&batch.filter(|r| r.status == Status::Success)), [3, 2]);
+        let counts = batch.count_by_status();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts["SUCCESS"], 2);
+        assert_eq!(counts["FAILURE"], 1);
+
+        let huge = RecordBatch::from(vec![
+            record(1, u64::MAX, 0, Status::Success),
+            record(2, 1, 0, Status::Success),
+        ]);
+        assert_eq!(huge.total_amount(), u64::MAX);
+        assert_eq!(RecordBatch::default().total_amount(), 0);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_serialize() {
+        let batch = RecordBatch::from(
+            (0..1000)
+                .map(|i| record(i, i * 10, i, Status::Success))
+                .collect::<Vec<_>>(),
+        );
+        for format in ["csv", "txt", "bin", "json"] {
+            let serializer = crate::builder::build_sync_serializer(format).unwrap();
+            let expected = batch
+                .records()
+                .iter()
+                .map(|r| serializer.serialize(r).unwrap())
+                .collect::<Vec<_>>();
+            let serialized = batch
+                .par_serialize(serializer.as_ref())
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(serialized, expected, "{}", format);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let batch = batch();
+        let output = Shared::default();
+        let mut writer = build_writer(output.clone(), "csv").unwrap();
+        batch
+            .write_to(writer.as_mut(), build_serializer("csv").unwrap().as_ref())
+            .unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let data = output.0.borrow().clone();
+        let mut reader = build_reader(Cursor::new(data), "csv").unwrap();
+        let read = RecordBatch::from_producer(reader.as_mut()).unwrap();
+        assert_eq!(read, batch);
+        assert_eq!(read.into_iter().collect::<Vec<_>>(), batch.records());
+    }
+}
@@ -1,110 +1,319 @@
-use std::{fs::File, path::PathBuf};
+use std::{error::Error, fs::File, path::PathBuf};
 
 use clap::{Parser, ValueEnum};
 
 use std::process::ExitCode;
 
-use parserde::build_reader;
+use parserde::{
+    CompareSummary, DataProducer, FileSequence, InputEncoding, ParseOptions, RawDataProducer,
+    RecordDiff, RecordDifference, build_raw_reader_with_options, build_reader_with_options,
+    expand_input,
+};
 
 use log::{error, info, warn};
 
 #[derive(Parser, Debug)]
 #[command(version, about)]
 struct Args {
+    /// File, directory or pattern like data/2024-05-*.csv.
+    /// The files of a directory or pattern are compared as one stream
     #[arg(long)]
-    file1: PathBuf,
+    file1: String,
     #[arg(long)]
     file1_format: InputFormat,
+    /// File, directory or pattern, like --file1
     #[arg(long)]
-    file2: PathBuf,
+    file2: String,
     #[arg(short, long)]
     file2_format: InputFormat,
+    /// Compare all records instead of stopping at the first difference
+    #[arg(long)]
+    all: bool,
+    /// Print the summary as json to stdout
+    #[arg(long)]
+    json: bool,
+    /// How differing records are logged
+    #[arg(long, default_value = "summary")]
+    diff_mode: DiffMode,
+    /// Encoding of csv and txt files: utf-8, latin-1 or windows-1252
+    #[arg(long, default_value = "utf-8")]
+    input_encoding: InputEncoding,
+    /// Treat a directory or pattern without files as an empty input instead of failing
+    #[arg(long)]
+    allow_empty_glob: bool,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 enum InputFormat {
     Csv,
+    CsvAuto,
+    Tsv,
     Txt,
     Bin,
+    BinLegacy,
+    Bin64,
+    Ndjson,
+    Logfmt,
+    Json,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    #[cfg(feature = "proto")]
+    Proto,
+    #[cfg(feature = "ron")]
+    Ron,
+    Toml,
+    Xml,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "xlsx")]
+    Xlsx,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum DiffMode {
+    /// The records with the names of the differing fields
+    Summary,
+    /// Every differing field with its value in both files
+    FieldDiff,
 }
 
 impl From<InputFormat> for &str {
     fn from(value: InputFormat) -> Self {
         match value {
             InputFormat::Csv => "csv",
+            InputFormat::CsvAuto => "csv-auto",
+            InputFormat::Tsv => "tsv",
             InputFormat::Bin => "bin",
+            InputFormat::BinLegacy => "bin-legacy",
+            InputFormat::Bin64 => "bin64",
             InputFormat::Txt => "txt",
+            InputFormat::Ndjson => "ndjson",
+            InputFormat::Logfmt => "logfmt",
+            InputFormat::Json => "json",
+            #[cfg(feature = "cbor")]
+            InputFormat::Cbor => "cbor",
+            #[cfg(feature = "msgpack")]
+            InputFormat::Msgpack => "msgpack",
+            #[cfg(feature = "proto")]
+            InputFormat::Proto => "proto",
+            #[cfg(feature = "ron")]
+            InputFormat::Ron => "ron",
+            InputFormat::Toml => "toml",
+            InputFormat::Xml => "xml",
+            #[cfg(feature = "yaml")]
+            InputFormat::Yaml => "yaml",
+            #[cfg(feature = "xlsx")]
+            InputFormat::Xlsx => "xlsx",
         }
     }
 }
 
+impl InputFormat {
+    /// The format has a raw reader, so files of the same format
+    /// can be compared before their records are parsed
+    fn has_raw_reader(self) -> bool {
+        matches!(
+            self,
+            InputFormat::Csv
+                | InputFormat::Tsv
+                | InputFormat::Txt
+                | InputFormat::Bin
+                | InputFormat::BinLegacy
+        )
+    }
+}
+
 fn main() -> ExitCode {
     env_logger::init();
     let args = Args::parse();
-    let file1 = match File::open(args.file1) {
-        Ok(f) => f,
+    let options = ParseOptions {
+        encoding: args.input_encoding,
+        ..Default::default()
+    };
+    let format1: &str = args.file1_format.into();
+    let format2: &str = args.file2_format.into();
+    let input1 = match open(&args.file1, args.allow_empty_glob) {
+        Ok(input) => input,
         Err(e) => {
             error!("failed to open an input file1. {}", e);
             return ExitCode::FAILURE;
         }
     };
-    let file2 = match File::open(args.file2) {
-        Ok(f) => f,
+    let input2 = match open(&args.file2, args.allow_empty_glob) {
+        Ok(input) => input,
         Err(e) => {
             error!("failed to open an input file2. {}", e);
             return ExitCode::FAILURE;
         }
     };
-    let mut reader1 = match build_reader(file1, args.file1_format.into()) {
-        Ok(r) => r,
-        Err(e) => {
-            error!("failed to create reader from file1. {}", e);
-            return ExitCode::FAILURE;
+    let readers = match (input1, input2) {
+        (Opened::File(file1), Opened::File(file2))
+            if args.file1_format == args.file2_format && args.file1_format.has_raw_reader() =>
+        {
+            build_raw_reader_with_options(file1, format1, &options)
+                .and_then(|r1| Ok((r1, build_raw_reader_with_options(file2, format2, &options)?)))
+                .map(|(r1, r2)| Readers::Raw(r1, r2))
         }
+        (input1, input2) => input1
+            .reader(format1, &options)
+            .and_then(|r1| Ok((r1, input2.reader(format2, &options)?)))
+            .map(|(r1, r2)| Readers::Parsed(r1, r2)),
     };
-    let mut reader2 = match build_reader(file2, args.file2_format.into()) {
+    let mut readers = match readers {
+        Ok(Readers::Raw(r1, r2)) if r1.raw_header() != r2.raw_header() => {
+            info!("Files have different headers, comparing parsed records");
+            Readers::Parsed(Box::new(r1), Box::new(r2))
+        }
         Ok(r) => r,
         Err(e) => {
-            error!("failed to create reader from file2. {}", e);
+            error!("failed to create readers from the files. {}", e);
             return ExitCode::FAILURE;
         }
     };
 
-    let files_are_identical = loop {
-        let record_result1 = reader1.produce_record();
-        let record_result2 = reader2.produce_record();
-        match (record_result1, record_result2) {
-            (Some(result1), Some(result2)) => {
-                let record1 = match result1 {
-                    Ok(r1) => r1,
-                    Err(e) => {
-                        info!("Failed to get record from file1. {}", e);
-                        break false;
+    let mut summary = CompareSummary::default();
+    let mut failed = false;
+    loop {
+        let step = match &mut readers {
+            Readers::Parsed(r1, r2) => next_parsed(r1.as_mut(), r2.as_mut(), &mut summary),
+            Readers::Raw(r1, r2) => next_raw(r1.as_mut(), r2.as_mut(), &mut summary),
+        };
+        match step {
+            Step::Equal => (),
+            Step::Differ((record1, record2, differing)) => {
+                match args.diff_mode {
+                    DiffMode::Summary => {
+                        let names = differing.iter().map(|n| n.as_str()).collect::<Vec<_>>();
+                        info!(
+                            "record from file1 {} not equal to record {} from file2. differing fields {}",
+                            record1,
+                            record2,
+                            names.join(",")
+                        );
                     }
-                };
-                let record2 = match result2 {
-                    Ok(r2) => r2,
-                    Err(e) => {
-                        info!("Failed to get record from file2. {}", e);
-                        break false;
+                    DiffMode::FieldDiff => {
+                        info!("record {} differs between the files", record1.tx_id());
+                        for field in RecordDiff::compute(&record1, &record2).fields {
+                            info!(
+                                "  {}: {} in file1, {} in file2",
+                                field.name, field.left, field.right
+                            );
+                        }
                     }
-                };
-                if record1 != record2 {
-                    info!(
-                        "record from file1 {} not equal to record {} from file2",
-                        record1, record2
-                    );
-                    break false;
                 }
+                if !args.all {
+                    break;
+                }
+            }
+            Step::Unmatched => {
+                summary.unmatched += 1;
+                if !args.all {
+                    break;
+                }
+            }
+            Step::Failed(e) => {
+                info!("{}", e);
+                failed = true;
+                break;
             }
-            (Some(_), None) | (None, Some(_)) => break false,
-            (_, _) => break true,
+            Step::Done => break,
         }
-    };
-    if files_are_identical {
+    }
+    info!("Summary\n{}", summary);
+    if args.json {
+        println!("{}", summary.to_json());
+    }
+    if summary.is_identical() && !failed {
         info!("Data in files are identical");
     } else {
         warn!("Data in files are not identical");
     }
     ExitCode::SUCCESS
 }
+
+enum Opened {
+    File(File),
+    Files(Vec<PathBuf>),
+}
+
+impl Opened {
+    fn reader(
+        self,
+        format: &str,
+        options: &ParseOptions,
+    ) -> Result<Box<dyn DataProducer>, Box<dyn Error>> {
+        match self {
+            Opened::File(file) => build_reader_with_options(file, format, options),
+            Opened::Files(paths) => Ok(Box::new(FileSequence::new(paths, format, options)?)),
+        }
+    }
+}
+
+/// Opens a single file, or lists the files of a directory or pattern
+fn open(input: &str, allow_empty: bool) -> Result<Opened, Box<dyn Error>> {
+    let mut paths = expand_input(input)?;
+    if paths.is_empty() && !allow_empty {
+        return Err(format!("no files match {}", input).into());
+    }
+    if paths.len() == 1 {
+        return Ok(Opened::File(File::open(paths.remove(0))?));
+    }
+    Ok(Opened::Files(paths))
+}
+
+enum Readers {
+    Parsed(Box<dyn DataProducer>, Box<dyn DataProducer>),
+    /// Same format readers comparing record bytes before parsing them
+    Raw(Box<dyn RawDataProducer>, Box<dyn RawDataProducer>),
+}
+
+enum Step {
+    Equal,
+    Differ(RecordDifference),
+    Unmatched,
+    Failed(String),
+    Done,
+}
+
+fn next_parsed(
+    reader1: &mut dyn DataProducer,
+    reader2: &mut dyn DataProducer,
+    summary: &mut CompareSummary,
+) -> Step {
+    match (reader1.produce_record(), reader2.produce_record()) {
+        (Some(Err(e)), _) => Step::Failed(format!("Failed to get record from file1. {}", e)),
+        (_, Some(Err(e))) => Step::Failed(format!("Failed to get record from file2. {}", e)),
+        (Some(Ok(record1)), Some(Ok(record2))) => {
+            let differing = summary.add_pair(&record1, &record2);
+            if differing.is_empty() {
+                Step::Equal
+            } else {
+                Step::Differ((record1, record2, differing))
+            }
+        }
+        (Some(_), None) | (None, Some(_)) => Step::Unmatched,
+        (None, None) => Step::Done,
+    }
+}
+
+fn next_raw(
+    reader1: &mut dyn RawDataProducer,
+    reader2: &mut dyn RawDataProducer,
+    summary: &mut CompareSummary,
+) -> Step {
+    match (reader1.produce_raw(), reader2.produce_raw()) {
+        (Some(Err(e)), _) => Step::Failed(format!("Failed to get record from file1. {}", e)),
+        (_, Some(Err(e))) => Step::Failed(format!("Failed to get record from file2. {}", e)),
+        (Some(Ok(raw1)), Some(Ok(raw2))) => {
+            match summary.add_raw_pair(reader1, &raw1, reader2, &raw2) {
+                Ok(None) => Step::Equal,
+                Ok(Some(difference)) => Step::Differ(difference),
+                Err(e) => Step::Failed(format!("Failed to compare records. {}", e)),
+            }
+        }
+        (Some(_), None) | (None, Some(_)) => Step::Unmatched,
+        (None, None) => Step::Done,
+    }
+}
@@ -1,115 +1,1029 @@
-use std::{fs::File, io::stdout};
+use std::{
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{Read, Write, stdout},
+    path::{Path, PathBuf},
+};
 
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 
 use std::process::ExitCode;
 
-use parserde::{build_reader, build_serializer, build_writer};
+use parserde::{
+    AmountUnit, Checkpoint, CommentCapture, DataProducer, FieldDefaults, FieldName, FileSequence,
+    InputEncoding, InspectDepth, ParseOptions, RawDataProducer, Record, RejectedRecord,
+    SanitizePolicy, SerializeOptions, SinkFailure, SortKey, TeeSink, TimestampUnit, TxtReader,
+    TxtStyle, UnknownFields, build_append_writer_with_options, build_raw_reader_with_options,
+    build_reader_at, build_reader_from_path_with_options, build_reader_with_options,
+    build_tee_writer, build_writer_with_options, detect_format, expand_input, inspect,
+};
+
+#[cfg(feature = "parquet")]
+use parserde::ParquetWriter;
+#[cfg(feature = "sqlite")]
+use parserde::SqliteWriter;
 
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Input file, directory or pattern like data/2024-05-*.csv.
+    /// The files of a directory or pattern are read in path order as one input
+    #[arg(long, required = true)]
+    input: Option<String>,
+    /// Convert nothing instead of failing when the input directory or pattern has no files
+    #[arg(long)]
+    allow_empty_glob: bool,
+    /// Input format. Guessed from the extension or the content
+    /// of the first input file if omitted
+    #[arg(long)]
+    input_format: Option<InputFormat>,
+    /// Output format. Repeat it along with --output to write several
+    /// outputs in one pass, matched by position
+    #[arg(short, long, required = true)]
+    output_format: Vec<OutputFormat>,
+    /// Output file. Records are written to stdout if omitted.
+    /// A sqlite output is given as sqlite://path#table
+    #[arg(long)]
+    output: Vec<PathBuf>,
+    /// File records are written to along with stdout
+    #[arg(long, conflicts_with = "output")]
+    output_file: Option<PathBuf>,
+    /// Keep writing the other outputs when one fails and report it at the end
+    #[arg(long)]
+    continue_on_output_error: bool,
+    /// Append to the output file instead of overwriting it. Formats wrapping
+    /// the records in a document or preceding them with a header other than
+    /// the csv one, such as json, xml or bin-dict, can't be appended to
+    #[arg(long, requires = "output")]
+    append: bool,
+    /// Write every output file to a temporary file next to it and rename it
+    /// over the output only once the conversion succeeds. Appending can't be atomic
+    #[arg(long, requires = "output", conflicts_with_all = ["append", "checkpoint"])]
+    atomic: bool,
+    /// Unit of AMOUNT in csv input: minor, major or major:<scale>
+    #[arg(long, default_value = "minor")]
+    input_amount_unit: AmountUnit,
+    /// Unit of AMOUNT in csv output: minor, major or major:<scale>
+    #[arg(long, default_value = "minor")]
+    output_amount_unit: AmountUnit,
+    /// Unit of TIMESTAMP in csv and txt input: s, ms or us
+    #[arg(long, default_value = "ms")]
+    input_timestamp_unit: TimestampUnit,
+    /// Unit of TIMESTAMP in csv and txt output: s, ms or us
+    #[arg(long, default_value = "ms")]
+    output_timestamp_unit: TimestampUnit,
+    /// Control characters in descriptions of csv and txt output:
+    /// preserve, strip, error, replace or replace:<char>
+    #[arg(long, default_value = "preserve")]
+    sanitize_descriptions: SanitizePolicy,
+    /// Align field names and values in txt output, one field per line in ron output
+    #[arg(long)]
+    pretty: bool,
+    /// Columns of csv, md and html output in order, e.g. TX_ID,DATE,AMOUNT,DIRECTION.
+    /// Besides the record fields DATE, TIME and DIRECTION are derived from the record
+    #[arg(long, visible_alias = "csv-fields", value_delimiter = ',')]
+    csv_columns: Vec<String>,
+    /// Table of the `COPY ... FROM stdin;` statement preceding pgcopy output,
+    /// which then ends with `\.` and can be run by psql
+    #[arg(long, value_name = "NAME")]
+    pgcopy_table: Option<String>,
+    /// Sort records in memory before writing, e.g. TIMESTAMP:desc,TX_ID
+    #[arg(long)]
+    sort_by: Option<SortKey>,
+    /// Checkpoint file. Progress is saved to it and an interrupted
+    /// conversion resumes from it when it exists. Supported for csv, tsv,
+    /// txt and bin input and csv, tsv, txt, bin, bin-compact, bin2, ndjson
+    /// and logfmt output
+    #[arg(long, requires = "output", conflicts_with_all = ["append", "sort_by"])]
+    checkpoint: Option<PathBuf>,
+    /// Count of records between checkpoint saves
+    #[arg(long, default_value_t = 10000, value_parser = clap::value_parser!(u64).range(1..))]
+    checkpoint_every: u64,
+    /// Precede every record in txt output with a `# Record N (TX_TYPE)` comment
     #[arg(long)]
-    input: String,
+    section_comments: bool,
+    /// Treat an empty csv input without a header as having no records
     #[arg(long)]
-    input_format: InputFormat,
-    #[arg(short, long)]
-    output_format: OutputFormat,
+    allow_empty: bool,
+    /// Skip csv input columns which aren't record fields instead of failing
+    #[arg(long)]
+    ignore_unknown_columns: bool,
+    /// Encoding of csv and txt input: utf-8, latin-1 or windows-1252.
+    /// Outputs are always UTF-8
+    #[arg(long, default_value = "utf-8", conflicts_with_all = ["checkpoint", "preserve_comments"])]
+    input_encoding: InputEncoding,
+    /// Accept common variants of field names in csv and txt input, e.g. ID or FROM_USER
+    #[arg(long)]
+    allow_aliases: bool,
+    /// Value of a field missing from csv or txt input, e.g. DESCRIPTION= or TO_USER_ID=0.
+    /// Can be repeated
+    #[arg(long = "default", value_name = "FIELD=VALUE", value_parser = parse_default)]
+    defaults: Vec<(FieldName, String)>,
+    /// Copy the comments at the start of a txt input to txt outputs
+    #[arg(long, conflicts_with = "checkpoint")]
+    preserve_comments: bool,
+    /// Stop after converting this count of records
+    #[arg(long)]
+    limit: Option<u64>,
+    /// Write records failing to parse to this file in the input format and
+    /// carry on. Their positions and errors are listed in PATH.index.
+    /// Records of a dictionary bin input referencing its string table
+    /// are written as they are and can't be read from this file alone
+    #[arg(long, conflicts_with_all = ["checkpoint", "preserve_comments"])]
+    error_output: Option<PathBuf>,
+    /// Rewrite the input in the canonical form of its own format,
+    /// the output of the default options
+    ///
+    /// csv: the header and columns in the order TX_ID, TX_TYPE, FROM_USER_ID,
+    /// TO_USER_ID, AMOUNT, TIMESTAMP, STATUS, DESCRIPTION, comma separated,
+    /// amounts in minor units, one record per line
+    ///
+    /// txt: `NAME: value` lines in the order TX_ID, AMOUNT, TIMESTAMP, DESCRIPTION,
+    /// TX_TYPE, FROM_USER_ID, TO_USER_ID, STATUS with a single space after
+    /// the colon, records separated by one blank line, no comments
+    ///
+    /// bin: the original big-endian YPBN frames
+    ///
+    /// Only the layout is normalized, the values are kept as they are.
+    /// Descriptions are kept unless --sanitize-descriptions is given
+    #[arg(long, conflicts_with_all = ["pretty", "section_comments", "preserve_comments", "output_amount_unit", "output_timestamp_unit", "csv_columns"])]
+    canonicalize: bool,
+}
+
+fn parse_default(value: &str) -> Result<(FieldName, String), String> {
+    let (field, value) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected FIELD=VALUE, found {}", value))?;
+    Ok((field.parse()?, value.to_string()))
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Report file metadata without conversion
+    Info {
+        #[arg(long)]
+        input: PathBuf,
+        #[arg(long)]
+        format: InputFormat,
+        /// Parse every record and report per TxType and Status counts
+        #[arg(long)]
+        full: bool,
+        /// Print the report as json
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 enum InputFormat {
     Csv,
+    CsvAuto,
+    Tsv,
     Txt,
     Bin,
+    BinLegacy,
+    Bin64,
+    Ndjson,
+    Logfmt,
+    Json,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    #[cfg(feature = "proto")]
+    Proto,
+    #[cfg(feature = "ron")]
+    Ron,
+    Toml,
+    Xml,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "xlsx")]
+    Xlsx,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 enum OutputFormat {
     Csv,
+    Tsv,
     Txt,
     Bin,
+    BinCompact,
+    BinDict,
+    Bin2,
+    Bin64,
+    Hexdump,
+    Ndjson,
+    Logfmt,
+    Json,
+    Md,
+    Html,
+    Pgcopy,
+    #[cfg(feature = "parquet")]
+    Parquet,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    #[cfg(feature = "proto")]
+    Proto,
+    #[cfg(feature = "ron")]
+    Ron,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+    Toml,
+    Xml,
+    #[cfg(feature = "yaml")]
+    Yaml,
 }
 
 impl From<InputFormat> for &str {
     fn from(value: InputFormat) -> Self {
         match value {
             InputFormat::Csv => "csv",
+            InputFormat::CsvAuto => "csv-auto",
+            InputFormat::Tsv => "tsv",
             InputFormat::Bin => "bin",
+            InputFormat::BinLegacy => "bin-legacy",
+            InputFormat::Bin64 => "bin64",
             InputFormat::Txt => "txt",
+            InputFormat::Ndjson => "ndjson",
+            InputFormat::Logfmt => "logfmt",
+            InputFormat::Json => "json",
+            #[cfg(feature = "cbor")]
+            InputFormat::Cbor => "cbor",
+            #[cfg(feature = "msgpack")]
+            InputFormat::Msgpack => "msgpack",
+            #[cfg(feature = "proto")]
+            InputFormat::Proto => "proto",
+            #[cfg(feature = "ron")]
+            InputFormat::Ron => "ron",
+            InputFormat::Toml => "toml",
+            InputFormat::Xml => "xml",
+            #[cfg(feature = "yaml")]
+            InputFormat::Yaml => "yaml",
+            #[cfg(feature = "xlsx")]
+            InputFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+impl OutputFormat {
+    /// The format has a --pretty layout
+    fn has_pretty_layout(self) -> bool {
+        match self {
+            OutputFormat::Txt => true,
+            #[cfg(feature = "ron")]
+            OutputFormat::Ron => true,
+            _ => false,
         }
     }
+
+    /// Records of the format follow one another with nothing before or after
+    /// them, so the output can be cut at a checkpoint and appended to
+    fn can_resume(self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Csv
+                | OutputFormat::Tsv
+                | OutputFormat::Txt
+                | OutputFormat::Bin
+                | OutputFormat::BinCompact
+                | OutputFormat::Bin2
+                | OutputFormat::Ndjson
+                | OutputFormat::Logfmt
+        )
+    }
+}
+
+impl InputFormat {
+    /// A reader of the format can be moved to a saved position
+    fn can_resume(self) -> bool {
+        matches!(
+            self,
+            InputFormat::Csv
+                | InputFormat::Tsv
+                | InputFormat::Txt
+                | InputFormat::Bin
+                | InputFormat::BinLegacy
+        )
+    }
 }
 
 impl From<OutputFormat> for &str {
     fn from(value: OutputFormat) -> Self {
         match value {
             OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
             OutputFormat::Bin => "bin",
             OutputFormat::Txt => "txt",
+            OutputFormat::BinCompact => "bin-compact",
+            OutputFormat::BinDict => "bin-dict",
+            OutputFormat::Bin2 => "bin2",
+            OutputFormat::Bin64 => "bin64",
+            OutputFormat::Hexdump => "hexdump",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Logfmt => "logfmt",
+            OutputFormat::Json => "json",
+            OutputFormat::Md => "md",
+            OutputFormat::Html => "html",
+            OutputFormat::Pgcopy => "pgcopy",
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => "parquet",
+            #[cfg(feature = "cbor")]
+            OutputFormat::Cbor => "cbor",
+            #[cfg(feature = "msgpack")]
+            OutputFormat::Msgpack => "msgpack",
+            #[cfg(feature = "proto")]
+            OutputFormat::Proto => "proto",
+            #[cfg(feature = "ron")]
+            OutputFormat::Ron => "ron",
+            #[cfg(feature = "sqlite")]
+            OutputFormat::Sqlite => "sqlite",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Xml => "xml",
+            #[cfg(feature = "yaml")]
+            OutputFormat::Yaml => "yaml",
         }
     }
 }
 
-fn main() -> ExitCode {
-    let args = Args::parse();
-    let stdout = stdout();
-    let file = match File::open(args.input) {
+/// Database file and table of a sqlite output given as
+/// `sqlite://path#table` or a plain path of a `records` table
+#[cfg(feature = "sqlite")]
+fn sqlite_output(output: &Path) -> (PathBuf, String) {
+    let output = output.to_string_lossy();
+    match output.strip_prefix("sqlite://") {
+        Some(rest) => match rest.rsplit_once('#') {
+            Some((path, table)) if !table.is_empty() => (path.into(), table.into()),
+            Some((path, _)) => (path.into(), "records".into()),
+            None => (rest.into(), "records".into()),
+        },
+        None => (output.as_ref().into(), "records".into()),
+    }
+}
+
+fn info(input: PathBuf, format: InputFormat, full: bool, json: bool) -> ExitCode {
+    let file = match File::open(input) {
         Ok(f) => f,
         Err(e) => {
             eprintln!("failed to open an input file. {}", e);
             return ExitCode::FAILURE;
         }
     };
-    let mut reader = match build_reader(file, args.input_format.into()) {
-        Ok(r) => r,
+    let depth = if full {
+        InspectDepth::Full
+    } else {
+        InspectDepth::Quick
+    };
+    match inspect(file, format.into(), depth) {
+        Ok(info) if json => println!("{}", info.to_json()),
+        Ok(info) => print!("{}", info),
         Err(e) => {
-            eprintln!("failed to create reader from input. {}", e);
+            eprintln!("failed to inspect the input. {}", e);
             return ExitCode::FAILURE;
         }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Checks that the output matches the checkpoint and drops
+/// whatever was written to it after the checkpoint was saved
+fn prepare_resume(
+    output: &Path,
+    format: OutputFormat,
+    checkpoint: &Checkpoint,
+) -> Result<(), Box<dyn Error>> {
+    let file = OpenOptions::new().read(true).write(true).open(output)?;
+    let len = file.metadata()?.len();
+    if len < checkpoint.output_bytes {
+        return Err(format!(
+            "output is {} bytes long but the checkpoint expects at least {}",
+            len, checkpoint.output_bytes
+        )
+        .into());
+    }
+    let inspect_format = match format {
+        OutputFormat::BinCompact | OutputFormat::Bin2 => "bin",
+        format => format.into(),
     };
-    let serializer = match build_serializer(args.output_format.into()) {
-        Ok(s) => s,
+    let info = inspect(
+        file.try_clone()?.take(checkpoint.output_bytes),
+        inspect_format,
+        InspectDepth::Quick,
+    )?;
+    if let Some(e) = info.errors.first() {
+        return Err(format!("output is malformed. {}", e).into());
+    }
+    if info.record_count != checkpoint.output_records {
+        return Err(format!(
+            "output has {} records but the checkpoint expects {}",
+            info.record_count, checkpoint.output_records
+        )
+        .into());
+    }
+    file.set_len(checkpoint.output_bytes)?;
+    Ok(())
+}
+
+/// Reader of the input. Raw readers hand failed records back with their bytes
+enum Input {
+    Records(Box<dyn DataProducer>),
+    Raw(Box<dyn RawDataProducer>),
+    /// Files of a directory or pattern
+    Files(FileSequence),
+}
+
+impl Input {
+    fn next(&mut self) -> Option<Result<Record, RejectedRecord>> {
+        let reader: &mut dyn DataProducer = match self {
+            Input::Records(reader) => reader.as_mut(),
+            Input::Files(files) => files,
+            Input::Raw(reader) => return reader.produce_or_reject(),
+        };
+        let position = reader.position();
+        let result = reader.produce_record()?;
+        Some(result.map_err(|error| RejectedRecord {
+            raw: Vec::new(),
+            error,
+            position,
+        }))
+    }
+
+    fn producer(&self) -> &dyn DataProducer {
+        match self {
+            Input::Records(reader) => reader.as_ref(),
+            Input::Raw(reader) => reader.as_ref(),
+            Input::Files(files) => files,
+        }
+    }
+}
+
+/// Sidecar output of the records failing to parse, in the input format,
+/// along with an index of their positions and errors
+struct DeadLetter {
+    output: File,
+    index: File,
+    records: u64,
+}
+
+impl DeadLetter {
+    fn create(path: &Path, input: &Input) -> std::io::Result<DeadLetter> {
+        let mut index_path = path.as_os_str().to_owned();
+        index_path.push(".index");
+        let mut output = File::create(path)?;
+        if let Input::Raw(reader) = input {
+            output.write_all(&reader.raw_header())?;
+        }
+        Ok(DeadLetter {
+            output,
+            index: File::create(index_path)?,
+            records: 0,
+        })
+    }
+
+    fn write(&mut self, rejected: &RejectedRecord) -> std::io::Result<()> {
+        self.output.write_all(&rejected.raw)?;
+        self.records += 1;
+        let position = rejected.position.unwrap_or_default();
+        writeln!(
+            self.index,
+            "input_record={} input_byte={} input_line={} raw_bytes={} error={}",
+            position.record + 1,
+            position.byte,
+            position.line,
+            rejected.raw.len(),
+            rejected.error
+        )
+    }
+}
+
+/// Output file written to a temporary file in the same directory
+/// and renamed over the target once complete.
+/// The temporary file is removed unless committed
+struct AtomicOutput {
+    file: File,
+    temp: PathBuf,
+    target: PathBuf,
+    committed: bool,
+}
+
+impl AtomicOutput {
+    /// Creates the temporary file returning a handle for the writer
+    fn create(target: &Path) -> std::io::Result<(AtomicOutput, File)> {
+        let name = target.file_name().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is not a file path", target.display()),
+            )
+        })?;
+        let mut temp = std::ffi::OsString::from(".");
+        temp.push(name);
+        temp.push(format!(".{}.tmp", std::process::id()));
+        let temp = target.with_file_name(temp);
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)?;
+        let writer = file.try_clone()?;
+        let output = AtomicOutput {
+            file,
+            temp,
+            target: target.to_path_buf(),
+            committed: false,
+        };
+        Ok((output, writer))
+    }
+
+    fn commit(mut self) -> std::io::Result<()> {
+        self.file.sync_all()?;
+        fs::rename(&self.temp, &self.target)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicOutput {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+fn save_checkpoint(
+    path: &Path,
+    reader: &dyn DataProducer,
+    output: &Path,
+    output_records: u64,
+) -> Result<(), Box<dyn Error>> {
+    let position = reader
+        .position()
+        .ok_or("the input reader doesn't report its position")?;
+    let checkpoint = Checkpoint {
+        position,
+        output_bytes: fs::metadata(output)?.len(),
+        output_records,
+    };
+    Ok(checkpoint.save(path)?)
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    if let Some(Command::Info {
+        input,
+        format,
+        full,
+        json,
+    }) = args.command
+    {
+        return info(input, format, full, json);
+    }
+    let Some(input) = args.input else {
+        unreachable!("conversion arguments are required without a subcommand");
+    };
+    let paths = match expand_input(&input) {
+        Ok(paths) => paths,
         Err(e) => {
-            eprintln!("failed to create serializer. {}", e);
+            eprintln!("failed to expand the input. {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if paths.is_empty() && !args.allow_empty_glob {
+        eprintln!("no input files match {}", input);
+        return ExitCode::FAILURE;
+    }
+    let input_format = match (args.input_format, paths.first()) {
+        (Some(format), _) => format,
+        (None, Some(path)) => match detect_format(path) {
+            Ok(format) => InputFormat::from_str(format, false)
+                .expect("every detected format is an input format"),
+            Err(e) => {
+                eprintln!("failed to detect the input format. {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+        // no files to read, so any format does
+        (None, None) => InputFormat::Txt,
+    };
+    let output_formats = args.output_format;
+    if !args.output.is_empty() && args.output.len() != output_formats.len() {
+        eprintln!("every --output needs a matching --output-format");
+        return ExitCode::FAILURE;
+    }
+    if args.output.is_empty() && output_formats.len() > 1 {
+        eprintln!("several output formats need an --output each");
+        return ExitCode::FAILURE;
+    }
+    if args.output.len() > 1 && (args.append || args.checkpoint.is_some()) {
+        eprintln!("--append and --checkpoint support a single output only");
+        return ExitCode::FAILURE;
+    }
+    if args.preserve_comments
+        && (input_format != InputFormat::Txt
+            || output_formats.iter().any(|f| *f != OutputFormat::Txt))
+    {
+        eprintln!("--preserve-comments is only valid for txt input and output");
+        return ExitCode::FAILURE;
+    }
+    let same_format = output_formats
+        .iter()
+        .all(|f| <&str>::from(*f) == <&str>::from(input_format));
+    if args.canonicalize && !same_format {
+        eprintln!("--canonicalize requires the output format to match the input format");
+        return ExitCode::FAILURE;
+    }
+    if !args.canonicalize && same_format {
+        eprintln!(
+            "warning: the output format matches the input format. \
+            The output is a rewrite of the input rather than a copy, \
+            use --canonicalize to normalize it explicitly"
+        );
+    }
+    let stdout = stdout();
+    let file = match paths.as_slice() {
+        [path] => match File::open(path) {
+            Ok(f) => Some(f),
+            Err(e) => {
+                eprintln!("failed to open an input file. {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+        _ if args.error_output.is_some() || args.checkpoint.is_some() || args.preserve_comments => {
+            eprintln!(
+                "--error-output, --checkpoint and --preserve-comments need a single input file"
+            );
             return ExitCode::FAILURE;
         }
+        _ => None,
     };
-    let mut output_writer = match build_writer(stdout, args.output_format.into()) {
-        Ok(w) => w,
+    let parse_options = ParseOptions {
+        amount_unit: args.input_amount_unit,
+        allow_empty: args.allow_empty,
+        timestamp_unit: args.input_timestamp_unit,
+        unknown_fields: if args.ignore_unknown_columns {
+            UnknownFields::Ignore
+        } else {
+            UnknownFields::Error
+        },
+        defaults: {
+            let mut defaults = FieldDefaults::new();
+            for (field, value) in &args.defaults {
+                defaults.set(*field, value.as_str());
+            }
+            defaults
+        },
+        allow_aliases: args.allow_aliases,
+        encoding: args.input_encoding,
+        ..Default::default()
+    };
+    let checkpoint = match &args.checkpoint {
+        Some(_) if !output_formats[0].can_resume() => {
+            eprintln!(
+                "--checkpoint is not supported for {} output",
+                <&str>::from(output_formats[0])
+            );
+            return ExitCode::FAILURE;
+        }
+        Some(_) if !input_format.can_resume() => {
+            eprintln!(
+                "--checkpoint is not supported for {} input",
+                <&str>::from(input_format)
+            );
+            return ExitCode::FAILURE;
+        }
+        Some(path) => match Checkpoint::load(path) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("failed to load the checkpoint. {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+    let mut leading_comments = Vec::new();
+    let reader = match (file, &checkpoint) {
+        (None, _) => {
+            FileSequence::new(paths, input_format.into(), &parse_options).map(Input::Files)
+        }
+        (Some(file), _) if args.error_output.is_some() => {
+            build_raw_reader_with_options(file, input_format.into(), &parse_options).map(Input::Raw)
+        }
+        (Some(file), Some(c)) => {
+            build_reader_at(file, input_format.into(), &parse_options, c.position)
+                .map(Input::Records)
+        }
+        (Some(file), None) if args.preserve_comments => {
+            let mut reader = TxtReader::with_comments(file, CommentCapture::Leading);
+            match reader.read_leading_comments() {
+                Ok(comments) => {
+                    leading_comments = comments.to_vec();
+                    Ok(Input::Records(Box::new(reader)))
+                }
+                Err(e) => Err(e.into()),
+            }
+        }
+        (Some(_), None) if args.input_format.is_none() => {
+            build_reader_from_path_with_options(&paths[0], &parse_options).map(Input::Records)
+        }
+        (Some(file), None) => {
+            build_reader_with_options(file, input_format.into(), &parse_options).map(Input::Records)
+        }
+    };
+    let mut input = match reader {
+        Ok(r) => r,
         Err(e) => {
-            eprintln!("failed to create writer from stdout. {}", e);
+            eprintln!("failed to create reader from input. {}", e);
             return ExitCode::FAILURE;
         }
     };
+    if args.pretty && !output_formats.iter().any(|f| f.has_pretty_layout()) {
+        eprintln!("--pretty is only valid for txt and ron output");
+        return ExitCode::FAILURE;
+    }
+    if args.section_comments && !output_formats.contains(&OutputFormat::Txt) {
+        eprintln!("--section-comments is only valid for txt output");
+        return ExitCode::FAILURE;
+    }
+    if !args.csv_columns.is_empty()
+        && !output_formats
+            .iter()
+            .any(|f| matches!(f, OutputFormat::Csv | OutputFormat::Md | OutputFormat::Html))
+    {
+        eprintln!("--csv-columns is only valid for csv, md and html output");
+        return ExitCode::FAILURE;
+    }
+    if args.pgcopy_table.is_some() && !output_formats.contains(&OutputFormat::Pgcopy) {
+        eprintln!("--pgcopy-table is only valid for pgcopy output");
+        return ExitCode::FAILURE;
+    }
+    #[cfg(feature = "parquet")]
+    if output_formats.contains(&OutputFormat::Parquet)
+        && (args.output.is_empty() || args.append || checkpoint.is_some())
+    {
+        eprintln!("parquet output needs --output and can't be appended or checkpointed");
+        return ExitCode::FAILURE;
+    }
+    #[cfg(feature = "sqlite")]
+    if output_formats.contains(&OutputFormat::Sqlite)
+        && (args.output.is_empty() || args.atomic || checkpoint.is_some())
+    {
+        eprintln!("sqlite output needs --output and can't be --atomic or checkpointed");
+        return ExitCode::FAILURE;
+    }
+    let serialize_options = SerializeOptions {
+        amount_unit: args.output_amount_unit,
+        txt_style: if args.pretty {
+            TxtStyle::Aligned
+        } else {
+            TxtStyle::Plain
+        },
+        right_align_numbers: args.pretty,
+        sanitize: args.sanitize_descriptions,
+        txt_section_comments: args.section_comments,
+        timestamp_unit: args.output_timestamp_unit,
+        csv_columns: args.csv_columns.clone(),
+        ron_pretty: args.pretty,
+        pgcopy_table: args.pgcopy_table.clone(),
+        ..Default::default()
+    };
+    if let (Some(c), Some(output)) = (&checkpoint, args.output.first())
+        && let Err(e) = prepare_resume(output, output_formats[0], c)
+    {
+        eprintln!("can't resume from the checkpoint. {}", e);
+        return ExitCode::FAILURE;
+    }
+    let on_failure = if args.continue_on_output_error {
+        SinkFailure::Continue
+    } else {
+        SinkFailure::Abort
+    };
+    let mut sink = TeeSink::new(&serialize_options, on_failure);
+    let append = args.append || checkpoint.is_some();
+    let mut atomic_outputs = Vec::new();
+    for (i, output_format) in output_formats.iter().enumerate() {
+        #[cfg(feature = "sqlite")]
+        if *output_format == OutputFormat::Sqlite {
+            let (path, table) = sqlite_output(&args.output[i]);
+            match SqliteWriter::open(&path, &table) {
+                Ok(w) => sink.add_record_writer(Box::new(w)),
+                Err(e) => {
+                    eprintln!("failed to create writer for the output. {}", e);
+                    return ExitCode::FAILURE;
+                }
+            }
+            continue;
+        }
+        #[cfg(feature = "parquet")]
+        if *output_format == OutputFormat::Parquet {
+            let path = &args.output[i];
+            let file = if args.atomic {
+                AtomicOutput::create(path).map(|(output, f)| {
+                    atomic_outputs.push((i, output));
+                    f
+                })
+            } else {
+                File::create(path)
+            };
+            match file
+                .map_err(Box::<dyn Error>::from)
+                .and_then(ParquetWriter::new)
+            {
+                Ok(w) => sink.add_record_writer(Box::new(w)),
+                Err(e) => {
+                    eprintln!("failed to create writer for the output. {}", e);
+                    return ExitCode::FAILURE;
+                }
+            }
+            continue;
+        }
+        let format = (*output_format).into();
+        let output_writer = match args.output.get(i) {
+            Some(path) if append => {
+                build_append_writer_with_options(path, format, &serialize_options)
+            }
+            Some(path) if args.atomic => match AtomicOutput::create(path) {
+                Ok((output, f)) => {
+                    atomic_outputs.push((i, output));
+                    build_writer_with_options(f, format, &serialize_options)
+                }
+                Err(e) => Err(e.into()),
+            },
+            Some(path) => match File::create(path) {
+                Ok(f) => build_writer_with_options(f, format, &serialize_options),
+                Err(e) => Err(e.into()),
+            },
+            None => match &args.output_file {
+                Some(path) => File::create(path)
+                    .map_err(Box::<dyn Error>::from)
+                    .and_then(|f| build_writer_with_options(f, format, &serialize_options))
+                    .and_then(|file_writer| {
+                        let stdout_writer =
+                            build_writer_with_options(stdout.lock(), format, &serialize_options)?;
+                        Ok(build_tee_writer(stdout_writer, file_writer))
+                    }),
+                None => build_writer_with_options(stdout.lock(), format, &serialize_options),
+            },
+        };
+        let added = output_writer.and_then(|w| sink.add(format, w));
+        if let Err(e) = added {
+            eprintln!("failed to create writer for the output. {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
 
-    if let Err(e) = output_writer.write_header() {
+    if let Err(e) = sink.write_header() {
         eprintln!("failed to write header. {}", e);
         return ExitCode::FAILURE;
     }
+    for comment in &leading_comments {
+        if let Err(e) = sink.write_comment(comment) {
+            eprintln!("failed to write comment. {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
 
-    while let Some(record_result) = reader.produce_record() {
-        let record = match record_result {
-            Ok(record) => record,
+    let mut dead_letter = match &args.error_output {
+        Some(path) => match DeadLetter::create(path, &input) {
+            Ok(d) => Some(d),
             Err(e) => {
-                eprintln!("an error occured while reading and parsing record. {}", e);
+                eprintln!("failed to create the error output. {}", e);
                 return ExitCode::FAILURE;
             }
+        },
+        None => None,
+    };
+    let mut reject = |rejected: RejectedRecord| match dead_letter.as_mut() {
+        Some(dead_letter) => dead_letter.write(&rejected).map_err(|e| {
+            eprintln!("failed to write to the error output. {}", e);
+        }),
+        None => {
+            eprintln!(
+                "an error occured while reading and parsing record. {}",
+                rejected.error
+            );
+            Err(())
+        }
+    };
+
+    let mut sorted = match &args.sort_by {
+        Some(key) => {
+            let mut records = Vec::new();
+            while let Some(record_result) = input.next() {
+                match record_result {
+                    Ok(record) => records.push(record),
+                    Err(rejected) => {
+                        if reject(rejected).is_err() {
+                            return ExitCode::FAILURE;
+                        }
+                    }
+                }
+            }
+            key.sort(&mut records);
+            Some(records.into_iter())
+        }
+        None => None,
+    };
+
+    let mut output_records = checkpoint.map_or(0, |c| c.output_records);
+    let mut converted = 0;
+    let mut stopped = false;
+    loop {
+        if args.limit == Some(converted) {
+            stopped = true;
+            break;
+        }
+        let record_result = match sorted.as_mut() {
+            Some(records) => records.next().map(Ok),
+            None => input.next(),
         };
-        match serializer.serialize(&record) {
-            Ok(result) => {
-                if let Err(e) = output_writer.write(result) {
-                    eprintln!("an error occured while writing data. {}", e);
+        let Some(record_result) = record_result else {
+            break;
+        };
+        let record = match record_result {
+            Ok(record) => record,
+            Err(rejected) => {
+                if reject(rejected).is_err() {
                     return ExitCode::FAILURE;
                 }
-            }
-            Err(e) => {
-                eprintln!("an error occured while serializing the record. {}", e);
-                return ExitCode::FAILURE;
+                continue;
             }
         };
+        if let Err(e) = sink.write(&record) {
+            eprintln!("an error occured while writing data. {}", e);
+            return ExitCode::FAILURE;
+        }
+        converted += 1;
+        output_records += 1;
+        if let (Some(path), Some(output)) = (&args.checkpoint, args.output.first())
+            && output_records % args.checkpoint_every == 0
+            && let Err(e) = save_checkpoint(path, input.producer(), output, output_records)
+        {
+            eprintln!("failed to save the checkpoint. {}", e);
+            return ExitCode::FAILURE;
+        }
     }
 
-    eprintln!("convert is successful");
+    if let Err(e) = sink.finish() {
+        eprintln!("failed to finish the output. {}", e);
+        return ExitCode::FAILURE;
+    }
+    let failures = sink.failures();
+    for (i, failure) in &failures {
+        eprintln!("output {} failed. {}", i + 1, failure);
+    }
+    let failed = failures.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+    drop(sink);
+    for (i, output) in atomic_outputs {
+        if failed.contains(&i) {
+            continue;
+        }
+        if let Err(e) = output.commit() {
+            eprintln!("failed to move the output {} into place. {}", i + 1, e);
+            return ExitCode::FAILURE;
+        }
+    }
+    if !failed.is_empty() {
+        return ExitCode::FAILURE;
+    }
+    if let (Some(path), Some(output)) = (&args.checkpoint, args.output.first()) {
+        let result = if stopped {
+            save_checkpoint(path, input.producer(), output, output_records)
+        } else {
+            fs::remove_file(path).or_else(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(e.into()),
+            })
+        };
+        if let Err(e) = result {
+            eprintln!("failed to update the checkpoint. {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+    if let Input::Files(files) = &input {
+        for (path, records) in files.records_by_file() {
+            eprintln!("{} records read from {}", records, path.display());
+        }
+    }
+    if let Some(dead_letter) = &dead_letter
+        && dead_letter.records > 0
+    {
+        eprintln!(
+            "{} records failed and were written to the error output",
+            dead_letter.records
+        );
+    }
+    if stopped {
+        eprintln!("stopped after {} records", converted);
+        return ExitCode::SUCCESS;
+    }
+    eprintln!("convert is successful. {} records", converted);
     ExitCode::SUCCESS
 }
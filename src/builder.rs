@@ -1,12 +1,100 @@
 /// sdfsdf
-use crate::bin::{BinReader, RecordBytes, RecordWrite as BinWrite};
-use crate::csv::{CsvReader, CsvSerialize, RecordWrite as CsvWrite};
-use crate::record::{DataProducer, RecordSerialize, RecordWriter, fields};
+use crate::bin::{
+    BinEncoding, BinReader, DictionaryRecordBytes, MAGIC, RecordBytes, RecordWrite as BinWrite,
+    VERSIONED_MAGIC,
+};
+#[cfg(feature = "cbor")]
+use crate::cbor::{CborReader, CborSerialize, RecordWrite as CborWrite};
+use crate::csv::{
+    CsvAppendWriter, CsvReader, CsvSerialize, RecordWrite as CsvWrite, detect_separator,
+};
+use crate::derived::DerivedColumn;
+use crate::encoding::DecodeReader;
+use crate::formats::bin64::{Bin64Reader, Bin64Serialize, RecordWrite as Bin64Write};
+use crate::formats::hexdump::HexdumpSerialize;
+use crate::formats::html::{HtmlSerialize, RecordWrite as HtmlWrite};
+use crate::formats::json::{JsonReader, JsonSerialize, RecordWrite as JsonWrite};
+use crate::formats::logfmt::{LogfmtReader, LogfmtSerialize, RecordWrite as LogfmtWrite};
+use crate::formats::markdown::{MarkdownSerialize, RecordWrite as MarkdownWrite};
+#[cfg(feature = "rayon")]
+use crate::formats::parallel::ParallelBinReader;
+use crate::formats::pgcopy::{PgcopySerialize, RecordWrite as PgcopyWrite};
+use crate::formats::toml::{RecordWrite as TomlWrite, TomlReader, TomlSerialize};
+#[cfg(feature = "xlsx")]
+use crate::formats::xlsx::XlsxReader;
+use crate::gz::GzWrite;
+use crate::intern::CompactReader;
+#[cfg(feature = "msgpack")]
+use crate::msgpack::{MsgpackReader, MsgpackSerialize, RecordWrite as MsgpackWrite};
+use crate::ndjson::{NdjsonReader, NdjsonSerialize, RecordWrite as NdjsonWrite};
+use crate::options::{InputEncoding, ParseOptions, SerializeOptions};
+#[cfg(feature = "proto")]
+use crate::proto::{ProtoReader, ProtoSerialize, RecordWrite as ProtoWrite};
+use crate::record::{
+    DataProducer, FieldName, PartialDataProducer, Position, RawDataProducer, RecordSerialize,
+    RecordWriter, TeeWriter,
+};
+#[cfg(feature = "ron")]
+use crate::ron::{RecordWrite as RonWrite, RonReader, RonSerialize};
 use crate::txt::{RecordWrite as TxtWrite, TxtReader, TxtSerialize};
+use crate::xml::{RecordWrite as XmlWrite, XmlReader, XmlSerialize};
+#[cfg(feature = "yaml")]
+use crate::yaml::{RecordWrite as YamlWrite, YamlReader, YamlSerialize};
+#[cfg(feature = "tokio")]
+use crate::{
+    async_bin::AsyncBinReader, async_csv::AsyncCsvReader, async_txt::AsyncTxtReader,
+    record::AsyncDataProducer,
+};
+use flate2::read::MultiGzDecoder;
+use std::borrow::Cow;
 use std::error::Error;
-use std::io::{Read, Write};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+use std::path::Path;
+#[cfg(feature = "tokio")]
+use tokio::io::AsyncRead;
 
-///Creates an appropriate reader depending on input format
+/// Default column order of csv output
+pub(crate) const DEFAULT_CSV_FIELDS: &[&str] = &[
+    FieldName::TxId.as_str(),
+    FieldName::TxType.as_str(),
+    FieldName::FromUser.as_str(),
+    FieldName::ToUser.as_str(),
+    FieldName::Amount.as_str(),
+    FieldName::Timestamp.as_str(),
+    FieldName::Status.as_str(),
+    FieldName::Description.as_str(),
+];
+
+/// Columns of csv output, failing on names which are neither
+/// record fields nor derived columns
+fn csv_columns(options: &SerializeOptions) -> Result<Vec<Cow<'static, str>>, Box<dyn Error>> {
+    if options.csv_columns.is_empty() {
+        return Ok(DEFAULT_CSV_FIELDS
+            .iter()
+            .map(|&f| Cow::Borrowed(f))
+            .collect());
+    }
+    options
+        .csv_columns
+        .iter()
+        .map(|column| {
+            if DEFAULT_CSV_FIELDS.contains(&column.as_str())
+                || DerivedColumn::resolve(column, &options.derived_columns).is_some()
+            {
+                Ok(Cow::Owned(column.clone()))
+            } else {
+                Err(format!("unknown csv column {}", column).into())
+            }
+        })
+        .collect()
+}
+
+///Creates an appropriate reader depending on input format.
+/// A format with the `.gz` suffix like `csv.gz` reads gzip compressed input.
+/// The `csv-auto` format reads csv whose separator is detected
+/// from the header line with [`detect_separator`](crate::detect_separator),
+/// a comma when detection fails
 ///
 /// ```
 /// use parserde::build_reader;
@@ -18,11 +106,395 @@ pub fn build_reader<T: Read + 'static>(
     reader: T,
     format: &str,
 ) -> Result<Box<dyn DataProducer>, Box<dyn Error>> {
+    build_reader_with_options(reader, format, &ParseOptions::default())
+}
+
+/// Creates an appropriate reader depending on input format
+/// and configured with the given parse options
+///
+/// ```
+/// use parserde::{build_reader_with_options, AmountUnit, ParseOptions};
+/// let cursor = std::io::Cursor::new("hello");
+/// let options = ParseOptions {
+///     amount_unit: AmountUnit::Major { scale: 2 },
+///     ..Default::default()
+/// };
+/// let reader = build_reader_with_options(cursor, "csv", &options);
+/// ```
+///
+pub fn build_reader_with_options<T: Read + 'static>(
+    reader: T,
+    format: &str,
+    options: &ParseOptions,
+) -> Result<Box<dyn DataProducer>, Box<dyn Error>> {
+    if let Some(format) = format.strip_suffix(".gz") {
+        let reader: Box<dyn Read> = Box::new(MultiGzDecoder::new(reader));
+        return build_reader_with_options(reader, format, options);
+    }
+    Ok(match format {
+        "csv" => Box::new(CsvReader::with_options(
+            DecodeReader::new(reader, options.encoding),
+            b',',
+            options,
+        )?),
+        "tsv" => Box::new(CsvReader::with_options(
+            DecodeReader::new(reader, options.encoding),
+            b'\t',
+            options,
+        )?),
+        "csv-auto" => {
+            let mut reader = BufReader::new(reader);
+            let mut header = Vec::new();
+            reader.read_until(b'\n', &mut header)?;
+            let separator = detect_separator(&String::from_utf8_lossy(&header)).unwrap_or(b',');
+            Box::new(CsvReader::with_options(
+                DecodeReader::new(io::Cursor::new(header).chain(reader), options.encoding),
+                separator,
+                options,
+            )?)
+        }
+        "txt" => Box::new(TxtReader::with_options(
+            DecodeReader::new(reader, options.encoding),
+            options,
+        )?),
+        "bin" => Box::new(BinReader::new(reader)?),
+        "bin-legacy" => Box::new(BinReader::new_no_checksum(reader)?),
+        "bin64" => Box::new(Bin64Reader::new(reader)),
+        "ndjson" => Box::new(NdjsonReader::with_options(reader, options)?),
+        "logfmt" => Box::new(LogfmtReader::with_options(reader, options)?),
+        "json" => Box::new(JsonReader::with_options(
+            DecodeReader::new(reader, options.encoding),
+            options,
+        )?),
+        #[cfg(feature = "cbor")]
+        "cbor" => Box::new(CborReader::new(reader)),
+        #[cfg(feature = "msgpack")]
+        "msgpack" => Box::new(MsgpackReader::new(reader)),
+        #[cfg(feature = "proto")]
+        "proto" => Box::new(ProtoReader::new(reader)),
+        #[cfg(feature = "ron")]
+        "ron" => Box::new(RonReader::with_options(
+            DecodeReader::new(reader, options.encoding),
+            options,
+        )?),
+        "toml" => Box::new(TomlReader::with_options(
+            DecodeReader::new(reader, options.encoding),
+            options,
+        )?),
+        "xml" => Box::new(XmlReader::with_options(
+            DecodeReader::new(reader, options.encoding),
+            options,
+        )?),
+        #[cfg(feature = "yaml")]
+        "yaml" => Box::new(YamlReader::with_options(
+            DecodeReader::new(reader, options.encoding),
+            options,
+        )?),
+        #[cfg(feature = "xlsx")]
+        "xlsx" => {
+            // the zip container is read from its end, so the workbook is read in full
+            let mut workbook = Vec::new();
+            let mut reader = reader;
+            reader.read_to_end(&mut workbook)?;
+            Box::new(XlsxReader::new(io::Cursor::new(workbook), None)?)
+        }
+        _ => return Err(format!("given an unsupported format {}", format).into()),
+    })
+}
+
+/// Creates a reader of records holding their descriptions in a pool
+/// depending on input format. Every reader gets a pool of its own
+///
+/// ```
+/// use parserde::build_compact_reader;
+/// let cursor = std::io::Cursor::new("hello");
+/// let reader = build_compact_reader(cursor, "txt");
+/// ```
+///
+pub fn build_compact_reader<T: Read + 'static>(
+    reader: T,
+    format: &str,
+) -> Result<CompactReader, Box<dyn Error>> {
+    build_compact_reader_with_options(reader, format, &ParseOptions::default())
+}
+
+/// Creates a reader of records holding their descriptions in a pool
+/// depending on input format and configured with the given parse options.
+/// Descriptions are interned into the pool of the options, so readers
+/// given the same options share their descriptions
+pub fn build_compact_reader_with_options<T: Read + 'static>(
+    reader: T,
+    format: &str,
+    options: &ParseOptions,
+) -> Result<CompactReader, Box<dyn Error>> {
+    Ok(CompactReader::new(
+        build_reader_with_options(reader, format, options)?,
+        options.interner.clone().unwrap_or_default(),
+    ))
+}
+
+/// Format of the file at the path guessed from its extension:
+/// `.csv`, `.tsv`, `.bin` or `.ypbn`, `.bin64` or `.b64`, `.txt`, `.ndjson` or `.jsonl`, `.logfmt`, `.json`, `.toml`, `.xml`,
+/// `.cbor`, `.msgpack`, `.proto` or `.pb` and `.ron` with the features of the format names,
+/// `.yaml` or `.yml` with the `yaml` feature and `.xlsx` with the `xlsx` feature.
+/// A file with another or no extension is bin when it starts with
+/// the bin magic bytes and txt otherwise
+///
+/// ```no_run
+/// use parserde::detect_format;
+/// assert_eq!(detect_format("records.jsonl".as_ref()).unwrap(), "ndjson");
+/// ```
+///
+pub fn detect_format(path: &Path) -> io::Result<&'static str> {
+    if let Some(format) = format_by_extension(path) {
+        return Ok(format);
+    }
+    let mut magic = Vec::with_capacity(MAGIC.len());
+    File::open(path)?
+        .take(MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    Ok(if magic == MAGIC || magic == VERSIONED_MAGIC {
+        "bin"
+    } else {
+        "txt"
+    })
+}
+
+/// Format named by the extension of the path, see [`detect_format`]
+pub(crate) fn format_by_extension(path: &Path) -> Option<&'static str> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => Some("csv"),
+        Some("tsv") => Some("tsv"),
+        Some("bin" | "ypbn") => Some("bin"),
+        Some("bin64" | "b64") => Some("bin64"),
+        Some("txt") => Some("txt"),
+        Some("ndjson" | "jsonl") => Some("ndjson"),
+        Some("logfmt") => Some("logfmt"),
+        Some("json") => Some("json"),
+        #[cfg(feature = "cbor")]
+        Some("cbor") => Some("cbor"),
+        #[cfg(feature = "msgpack")]
+        Some("msgpack") => Some("msgpack"),
+        #[cfg(feature = "proto")]
+        Some("proto" | "pb") => Some("proto"),
+        #[cfg(feature = "ron")]
+        Some("ron") => Some("ron"),
+        Some("toml") => Some("toml"),
+        Some("xml") => Some("xml"),
+        #[cfg(feature = "yaml")]
+        Some("yaml" | "yml") => Some("yaml"),
+        #[cfg(feature = "xlsx")]
+        Some("xlsx") => Some("xlsx"),
+        _ => None,
+    }
+}
+
+/// Creates a reader of the file at the path in the format given by [`detect_format`]
+///
+/// ```no_run
+/// use parserde::build_reader_from_path;
+/// let reader = build_reader_from_path("records.csv".as_ref()).unwrap();
+/// ```
+///
+pub fn build_reader_from_path(path: &Path) -> Result<Box<dyn DataProducer>, Box<dyn Error>> {
+    build_reader_from_path_with_options(path, &ParseOptions::default())
+}
+
+/// Creates a reader of the file at the path in the format given by [`detect_format`]
+/// and configured with the given parse options
+pub fn build_reader_from_path_with_options(
+    path: &Path,
+    options: &ParseOptions,
+) -> Result<Box<dyn DataProducer>, Box<dyn Error>> {
+    let format = detect_format(path)?;
+    build_reader_with_options(File::open(path)?, format, options)
+}
+
+/// Creates an appropriate async reader depending on input format:
+/// `csv`, `txt`, `bin` or `bin-legacy`
+///
+/// ```
+/// use parserde::build_async_reader;
+/// let reader = build_async_reader(&b"TX_ID,AMOUNT\n"[..], "csv");
+/// ```
+///
+#[cfg(feature = "tokio")]
+pub fn build_async_reader<T: AsyncRead + Unpin + Send + 'static>(
+    reader: T,
+    format: &str,
+) -> Result<Box<dyn AsyncDataProducer>, Box<dyn Error>> {
+    build_async_reader_with_options(reader, format, &ParseOptions::default())
+}
+
+/// Creates an appropriate async reader depending on input format
+/// and configured with the given parse options
+#[cfg(feature = "tokio")]
+pub fn build_async_reader_with_options<T: AsyncRead + Unpin + Send + 'static>(
+    reader: T,
+    format: &str,
+    options: &ParseOptions,
+) -> Result<Box<dyn AsyncDataProducer>, Box<dyn Error>> {
+    Ok(match format {
+        "csv" => Box::new(AsyncCsvReader::with_options(reader, b',', options)),
+        "tsv" => Box::new(AsyncCsvReader::with_options(reader, b'\t', options)),
+        "txt" => Box::new(AsyncTxtReader::with_options(reader, options)?),
+        "bin" => Box::new(AsyncBinReader::new(reader)?),
+        "bin-legacy" => Box::new(AsyncBinReader::new_no_checksum(reader)?),
+        _ => return Err(format!("given an unsupported async format {}", format).into()),
+    })
+}
+
+/// Creates a reader of the bin or bin-legacy file at the path parsing
+/// chunks of `chunk_size` records on the rayon thread pool.
+/// Records are produced in the same order and with the same errors
+/// as a reader built by [`build_reader`]
+///
+/// ```no_run
+/// use parserde::build_parallel_reader;
+/// let reader = build_parallel_reader("records.bin".as_ref(), "bin", 10_000).unwrap();
+/// ```
+///
+#[cfg(feature = "rayon")]
+pub fn build_parallel_reader(
+    path: &Path,
+    format: &str,
+    chunk_size: usize,
+) -> Result<Box<dyn DataProducer>, Box<dyn Error>> {
+    Ok(match format {
+        "bin" => Box::new(ParallelBinReader::open(path, chunk_size, true)?),
+        "bin-legacy" => Box::new(ParallelBinReader::open(path, chunk_size, false)?),
+        _ => return Err(format!("given an unsupported parallel format {}", format).into()),
+    })
+}
+
+/// Creates a reader that starts at a position previously returned
+/// by [`DataProducer::position`] of a reader of the same input
+///
+/// ```
+/// use parserde::{build_reader, build_reader_at, ParseOptions};
+/// let input = "TX_ID: 1\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 2\nAMOUNT: 5\nTIMESTAMP: 9\nSTATUS: SUCCESS\nDESCRIPTION: \"a\"\n\nTX_ID: 2\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 2\nAMOUNT: 5\nTIMESTAMP: 9\nSTATUS: SUCCESS\nDESCRIPTION: \"b\"\n";
+/// let mut reader = build_reader(std::io::Cursor::new(input), "txt").unwrap();
+/// reader.produce_record();
+/// let position = reader.position().unwrap();
+/// let mut resumed = build_reader_at(
+///     std::io::Cursor::new(input),
+///     "txt",
+///     &ParseOptions::default(),
+///     position,
+/// )
+/// .unwrap();
+/// assert!(resumed.produce_record().unwrap().is_ok());
+/// assert!(resumed.produce_record().is_none());
+/// ```
+///
+pub fn build_reader_at<T: Read + Seek + 'static>(
+    reader: T,
+    format: &str,
+    options: &ParseOptions,
+    position: Position,
+) -> Result<Box<dyn DataProducer>, Box<dyn Error>> {
+    if options.encoding != InputEncoding::Utf8 {
+        return Err("positions of decoded input can't be sought".into());
+    }
+    Ok(match format {
+        "csv" => {
+            let mut reader = CsvReader::with_options(reader, b',', options)?;
+            reader.seek(position)?;
+            Box::new(reader)
+        }
+        "tsv" => {
+            let mut reader = CsvReader::with_options(reader, b'\t', options)?;
+            reader.seek(position)?;
+            Box::new(reader)
+        }
+        "txt" => {
+            let mut reader = TxtReader::with_options(reader, options)?;
+            reader.seek(position)?;
+            Box::new(reader)
+        }
+        "bin" => {
+            let mut reader = BinReader::new(reader)?;
+            reader.seek(position)?;
+            Box::new(reader)
+        }
+        "bin-legacy" => {
+            let mut reader = BinReader::new_no_checksum(reader)?;
+            reader.seek(position)?;
+            Box::new(reader)
+        }
+        _ => return Err(format!("given an unsupported format {}", format).into()),
+    })
+}
+
+/// Creates a reader of partial records (patches) depending on input format.
+/// Only text formats are supported
+///
+/// ```
+/// use parserde::build_partial_reader;
+/// let cursor = std::io::Cursor::new("TX_ID,STATUS\n1,SUCCESS\n");
+/// let mut reader = build_partial_reader(cursor, "csv").unwrap();
+/// let patch = reader.produce_partial_record().unwrap().unwrap();
+/// assert_eq!(patch.status, Some(parserde::Status::Success));
+/// ```
+///
+pub fn build_partial_reader<T: Read + 'static>(
+    reader: T,
+    format: &str,
+) -> Result<Box<dyn PartialDataProducer>, Box<dyn Error>> {
     Ok(match format {
         "csv" => Box::new(CsvReader::new(reader, b',')?),
+        "tsv" => Box::new(CsvReader::new(reader, b'\t')?),
         "txt" => Box::new(TxtReader::new(reader)?),
+        _ => return Err(format!("given an unsupported partial format {}", format).into()),
+    })
+}
+
+/// Creates a reader producing raw records for the format
+///
+/// ```
+/// use parserde::build_raw_reader;
+/// let cursor = std::io::Cursor::new("TX_ID,STATUS\n1,SUCCESS\n");
+/// let mut reader = build_raw_reader(cursor, "csv").unwrap();
+/// let raw = reader.produce_raw().unwrap().unwrap();
+/// assert!(reader.parse_raw(&raw).is_err());
+/// ```
+///
+pub fn build_raw_reader<T: Read + 'static>(
+    reader: T,
+    format: &str,
+) -> Result<Box<dyn RawDataProducer>, Box<dyn Error>> {
+    build_raw_reader_with_options(reader, format, &ParseOptions::default())
+}
+
+/// Creates a reader producing raw records for the format
+/// and parsing them with the given parse options
+pub fn build_raw_reader_with_options<T: Read + 'static>(
+    reader: T,
+    format: &str,
+    options: &ParseOptions,
+) -> Result<Box<dyn RawDataProducer>, Box<dyn Error>> {
+    if let Some(format) = format.strip_suffix(".gz") {
+        let reader: Box<dyn Read> = Box::new(MultiGzDecoder::new(reader));
+        return build_raw_reader_with_options(reader, format, options);
+    }
+    Ok(match format {
+        "csv" => Box::new(CsvReader::with_options(
+            DecodeReader::new(reader, options.encoding),
+            b',',
+            options,
+        )?),
+        "tsv" => Box::new(CsvReader::with_options(
+            DecodeReader::new(reader, options.encoding),
+            b'\t',
+            options,
+        )?),
+        "txt" => Box::new(TxtReader::with_options(
+            DecodeReader::new(reader, options.encoding),
+            options,
+        )?),
         "bin" => Box::new(BinReader::new(reader)?),
-        _ => return Err(format!("given an unsupported format {}", format).into()),
+        "bin-legacy" => Box::new(BinReader::new_no_checksum(reader)?),
+        _ => return Err(format!("given an unsupported raw format {}", format).into()),
     })
 }
 
@@ -34,27 +506,133 @@ pub fn build_reader<T: Read + 'static>(
 /// ```
 ///
 pub fn build_serializer(format: &str) -> Result<Box<dyn RecordSerialize>, Box<dyn Error>> {
+    build_serializer_with_options(format, &SerializeOptions::default())
+}
+
+/// Creates a csv or tsv serializer writing the given columns in order.
+/// Unknown column names and other formats are an error
+///
+/// ```
+/// use parserde::build_serializer_with_fields;
+/// assert!(build_serializer_with_fields("csv", &["AMOUNT", "TX_ID"]).is_ok());
+/// assert!(build_serializer_with_fields("csv", &["NAME"]).is_err());
+/// assert!(build_serializer_with_fields("bin", &["TX_ID"]).is_err());
+/// ```
+///
+pub fn build_serializer_with_fields(
+    format: &str,
+    fields: &[&str],
+) -> Result<Box<dyn RecordSerialize>, Box<dyn Error>> {
+    if !matches!(format, "csv" | "tsv") {
+        return Err(format!("fields can't be selected for {} output", format).into());
+    }
+    let options = SerializeOptions {
+        csv_columns: fields.iter().map(|f| f.to_string()).collect(),
+        ..Default::default()
+    };
+    build_serializer_with_options(format, &options)
+}
+
+/// Creates a serializer depending on input format
+/// and configured with the given serialize options
+///
+/// ```
+/// use parserde::{build_serializer_with_options, AmountUnit, SerializeOptions};
+/// let options = SerializeOptions {
+///     amount_unit: AmountUnit::Major { scale: 2 },
+///     ..Default::default()
+/// };
+/// let serializer = build_serializer_with_options("csv", &options);
+/// ```
+///
+pub fn build_serializer_with_options(
+    format: &str,
+    options: &SerializeOptions,
+) -> Result<Box<dyn RecordSerialize>, Box<dyn Error>> {
+    if format == "bin-dict" {
+        return Ok(Box::new(DictionaryRecordBytes::default()));
+    }
+    Ok(shared_serializer(format, options)?)
+}
+
+/// Creates a serializer that can be shared between threads, e.g. by
+/// [`RecordBatch::par_serialize`](crate::RecordBatch::par_serialize).
+/// `bin-dict` keeps state between records and isn't supported
+///
+/// ```
+/// use parserde::build_sync_serializer;
+/// assert!(build_sync_serializer("bin").is_ok());
+/// assert!(build_sync_serializer("bin-dict").is_err());
+/// ```
+///
+#[cfg(feature = "rayon")]
+pub fn build_sync_serializer(
+    format: &str,
+) -> Result<Box<dyn RecordSerialize + Send + Sync>, Box<dyn Error>> {
+    build_sync_serializer_with_options(format, &SerializeOptions::default())
+}
+
+/// Creates a serializer that can be shared between threads
+/// configured with the given serialize options
+#[cfg(feature = "rayon")]
+pub fn build_sync_serializer_with_options(
+    format: &str,
+    options: &SerializeOptions,
+) -> Result<Box<dyn RecordSerialize + Send + Sync>, Box<dyn Error>> {
+    shared_serializer(format, options)
+}
+
+/// Serializers of the formats keeping no state between records
+fn shared_serializer(
+    format: &str,
+    options: &SerializeOptions,
+) -> Result<Box<dyn RecordSerialize + Send + Sync>, Box<dyn Error>> {
     Ok(match format {
-        "csv" => Box::new(CsvSerialize::new(
-            &[
-                fields::str::TX_ID,
-                fields::str::TX_TYPE,
-                fields::str::FROM_USER,
-                fields::str::TO_USER,
-                fields::str::AMOUNT,
-                fields::str::TIMESTAMP,
-                fields::str::STATUS,
-                fields::str::DESCRIPTION,
-            ],
+        "csv" => Box::new(CsvSerialize::with_options(
+            csv_columns(options)?,
             ",",
+            options,
+        )),
+        "tsv" => Box::new(CsvSerialize::with_options(
+            csv_columns(options)?,
+            "\t",
+            options,
         )),
-        "bin" => Box::new(RecordBytes),
-        "txt" => Box::new(TxtSerialize),
+        "bin" => Box::new(RecordBytes::default()),
+        "bin-compact" => Box::new(RecordBytes::new(BinEncoding::Compact)),
+        "bin2" => Box::new(RecordBytes::new(BinEncoding::LittleEndian)),
+        "bin64" => Box::new(Bin64Serialize::default()),
+        "hexdump" => Box::new(HexdumpSerialize),
+        "txt" => Box::new(TxtSerialize::with_options(options)),
+        "ndjson" => Box::new(NdjsonSerialize::with_options(options)),
+        "logfmt" => Box::new(LogfmtSerialize::with_options(options)),
+        "json" => Box::new(JsonSerialize::with_options(options)),
+        "md" => Box::new(MarkdownSerialize::with_options(
+            csv_columns(options)?,
+            options,
+        )),
+        "html" => Box::new(HtmlSerialize::with_options(csv_columns(options)?, options)),
+        "pgcopy" => Box::new(PgcopySerialize),
+        #[cfg(feature = "cbor")]
+        "cbor" => Box::new(CborSerialize),
+        #[cfg(feature = "msgpack")]
+        "msgpack" => Box::new(MsgpackSerialize),
+        #[cfg(feature = "proto")]
+        "proto" => Box::new(ProtoSerialize),
+        #[cfg(feature = "ron")]
+        "ron" => Box::new(RonSerialize::with_options(options)),
+        "toml" => Box::new(TomlSerialize::with_options(options)),
+        "xml" => Box::new(XmlSerialize::with_options(options)),
+        #[cfg(feature = "yaml")]
+        "yaml" => Box::new(YamlSerialize::with_options(options)),
+        "bin-dict" => return Err("bin-dict serializes records in order and can't be shared".into()),
         _ => return Err(format!("given an unsupported format {}", format).into()),
     })
 }
 
-/// Creates a writer depending on input format
+/// Creates a writer depending on input format.
+/// A format with the `.gz` suffix like `bin.gz` compresses the output with gzip,
+/// the stream is completed by [`RecordWriter::finish`]
 ///
 /// ```
 /// use parserde::build_writer;
@@ -66,23 +644,131 @@ pub fn build_writer<W: Write + 'static>(
     writer: W,
     output_format: &str,
 ) -> Result<Box<dyn RecordWriter>, Box<dyn Error>> {
+    build_writer_with_options(writer, output_format, &SerializeOptions::default())
+}
+
+/// Creates a writer depending on output format
+/// and configured with the given serialize options
+///
+/// ```
+/// use parserde::{build_writer_with_options, SerializeOptions};
+/// let options = SerializeOptions {
+///     txt_section_comments: true,
+///     ..Default::default()
+/// };
+/// let writer = build_writer_with_options(Vec::new(), "txt", &options);
+/// ```
+///
+pub fn build_writer_with_options<W: Write + 'static>(
+    writer: W,
+    output_format: &str,
+    options: &SerializeOptions,
+) -> Result<Box<dyn RecordWriter>, Box<dyn Error>> {
+    if let Some(format) = output_format.strip_suffix(".gz") {
+        return Ok(Box::new(GzWrite::new(writer, |encoder| {
+            build_writer_with_options(Box::new(encoder) as Box<dyn Write>, format, options)
+        })?));
+    }
     Ok(match output_format {
-        "csv" => Box::new(CsvWrite::new(
-            writer,
-            &[
-                fields::str::TX_ID,
-                fields::str::TX_TYPE,
-                fields::str::FROM_USER,
-                fields::str::TO_USER,
-                fields::str::AMOUNT,
-                fields::str::TIMESTAMP,
-                fields::str::STATUS,
-                fields::str::DESCRIPTION,
-            ],
-            b'\n',
-        )),
-        "txt" => Box::new(TxtWrite::new(writer)),
-        "bin" => Box::new(BinWrite::new(writer)),
+        "csv" => Box::new(CsvWrite::new(writer, csv_columns(options)?, b',')),
+        "tsv" => Box::new(CsvWrite::new(writer, csv_columns(options)?, b'\t')),
+        "txt" => Box::new(TxtWrite::with_options(writer, options)),
+        "bin" | "bin-compact" | "bin-dict" | "bin2" | "hexdump" => Box::new(BinWrite::new(writer)),
+        "bin64" => Box::new(Bin64Write::new(writer)),
+        "ndjson" => Box::new(NdjsonWrite::new(writer)),
+        "logfmt" => Box::new(LogfmtWrite::new(writer)),
+        "json" => Box::new(JsonWrite::new(writer)),
+        "md" => Box::new(MarkdownWrite::new(writer, csv_columns(options)?)),
+        "html" => Box::new(HtmlWrite::new(writer, csv_columns(options)?)),
+        "pgcopy" => Box::new(PgcopyWrite::new(writer, options.pgcopy_table.clone())),
+        #[cfg(feature = "cbor")]
+        "cbor" => Box::new(CborWrite::new(writer)),
+        #[cfg(feature = "msgpack")]
+        "msgpack" => Box::new(MsgpackWrite::new(writer)),
+        #[cfg(feature = "proto")]
+        "proto" => Box::new(ProtoWrite::new(writer)),
+        #[cfg(feature = "ron")]
+        "ron" => Box::new(RonWrite::new(writer)),
+        "toml" => Box::new(TomlWrite::new(writer)),
+        "xml" => Box::new(XmlWrite::new(writer)),
+        #[cfg(feature = "yaml")]
+        "yaml" => Box::new(YamlWrite::new(writer)),
         _ => return Err(format!("given an unsupported format {}", output_format).into()),
     })
 }
+
+/// Creates a writer forwarding every call to both writers
+///
+/// ```
+/// use parserde::{build_tee_writer, build_writer};
+/// let preview = build_writer(std::io::stdout(), "txt").unwrap();
+/// let storage = build_writer(std::io::sink(), "txt").unwrap();
+/// let writer = build_tee_writer(preview, storage);
+/// ```
+///
+pub fn build_tee_writer(
+    first: Box<dyn RecordWriter>,
+    second: Box<dyn RecordWriter>,
+) -> Box<dyn RecordWriter> {
+    Box::new(TeeWriter::new(first, second))
+}
+
+/// Creates a writer appending to the file at the given path depending on output format.
+/// For csv the existing header is checked for compatibility
+/// and a header is only written when the file is empty
+///
+/// ```no_run
+/// use parserde::build_append_writer;
+/// let writer = build_append_writer(std::path::Path::new("out.csv"), "csv");
+/// ```
+///
+pub fn build_append_writer(
+    path: &Path,
+    output_format: &str,
+) -> Result<Box<dyn RecordWriter>, Box<dyn Error>> {
+    build_append_writer_with_options(path, output_format, &SerializeOptions::default())
+}
+
+/// Whether records of the output format written by a new writer can follow
+/// the records already in a file. Formats wrapping the records in a document,
+/// preceding them with a header or numbering them from the start of the file
+/// can't be appended to
+fn is_appendable(output_format: &str, options: &SerializeOptions) -> bool {
+    if let Some(format) = output_format.strip_suffix(".gz") {
+        // gzip members are read one after another
+        return is_appendable(format, options);
+    }
+    match output_format {
+        "csv" | "tsv" | "txt" | "bin" | "bin-compact" | "bin2" | "bin64" | "hexdump" | "ndjson"
+        | "logfmt" | "cbor" | "msgpack" | "proto" | "ron" | "yaml" => true,
+        "pgcopy" => options.pgcopy_table.is_none(),
+        _ => false,
+    }
+}
+
+/// Creates a writer appending to the file at the given path depending on output format
+/// and configured with the given serialize options.
+/// Fails for formats with a document wrapper, a header other than the csv one
+/// or a string table, such as json, xml, md, html, toml and bin-dict
+pub fn build_append_writer_with_options(
+    path: &Path,
+    output_format: &str,
+    options: &SerializeOptions,
+) -> Result<Box<dyn RecordWriter>, Box<dyn Error>> {
+    if !is_appendable(output_format, options) {
+        return Err(format!("{} output can't be appended to", output_format).into());
+    }
+    if let Some(separator) = match output_format {
+        "csv" => Some(b','),
+        "tsv" => Some(b'\t'),
+        _ => None,
+    } {
+        return Ok(Box::new(CsvAppendWriter::open_columns(
+            path,
+            csv_columns(options)?,
+            separator,
+        )?));
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    build_writer_with_options(file, output_format, options)
+}
@@ -0,0 +1,117 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::record::Position;
+
+/// Progress of a conversion persisted to resume it after an interruption
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Position of the input reader after the last converted record
+    pub position: Position,
+    /// Length of the output in bytes
+    pub output_bytes: u64,
+    /// Count of records in the output
+    pub output_records: u64,
+}
+
+impl Checkpoint {
+    /// Loads a checkpoint. Returns None if the file doesn't exist
+    pub fn load(path: &Path) -> Result<Option<Checkpoint>, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(content.parse()?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Saves the checkpoint atomically. It is written to a temporary file
+    /// next to the target which is then renamed over it
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(self.to_string().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+impl Display for Checkpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "input_byte={}", self.position.byte)?;
+        writeln!(f, "input_line={}", self.position.line)?;
+        writeln!(f, "input_record={}", self.position.record)?;
+        writeln!(f, "output_bytes={}", self.output_bytes)?;
+        writeln!(f, "output_records={}", self.output_records)
+    }
+}
+
+impl FromStr for Checkpoint {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut checkpoint = Checkpoint::default();
+        let mut seen = 0;
+        for line in s.lines().filter(|l| !l.trim().is_empty()) {
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("malformed checkpoint line {}", line));
+            };
+            let value = value
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| format!("malformed checkpoint value of {}. {}", key, e))?;
+            let slot = match key.trim() {
+                "input_byte" => &mut checkpoint.position.byte,
+                "input_line" => &mut checkpoint.position.line,
+                "input_record" => &mut checkpoint.position.record,
+                "output_bytes" => &mut checkpoint.output_bytes,
+                "output_records" => &mut checkpoint.output_records,
+                key => return Err(format!("unknown checkpoint key {}", key)),
+            };
+            *slot = value;
+            seen += 1;
+        }
+        if seen != 5 {
+            return Err(format!("checkpoint has {} of 5 values", seen));
+        }
+        Ok(checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("convert.checkpoint");
+        assert_eq!(Checkpoint::load(&path).unwrap(), None);
+
+        let checkpoint = Checkpoint {
+            position: Position {
+                byte: 1024,
+                line: 90,
+                record: 10,
+            },
+            output_bytes: 700,
+            output_records: 10,
+        };
+        checkpoint.save(&path).unwrap();
+        assert_eq!(Checkpoint::load(&path).unwrap(), Some(checkpoint));
+        assert!(!dir.path().join("convert.checkpoint.tmp").exists());
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!("input_byte=1\n".parse::<Checkpoint>().is_err());
+        assert!("input_byte=x\n".parse::<Checkpoint>().is_err());
+        assert!("offset=1\n".parse::<Checkpoint>().is_err());
+        assert!("garbage".parse::<Checkpoint>().is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt::Display;
+
+use crate::error::RecordProduceError;
+use crate::record::{FieldName, RawDataProducer, RawRecord, Record};
+use crate::result::RecordProduceResult;
+
+/// Pair of records differing in the listed fields
+pub type RecordDifference = (Record, Record, Vec<FieldName>);
+
+/// Running summary of a pairwise comparison of two record streams
+#[derive(Debug, PartialEq)]
+pub struct CompareSummary {
+    /// Count of compared record pairs
+    pub compared: u64,
+    /// Count of pairs differing in at least one field
+    pub differing: u64,
+    /// Count of records present in only one of the streams
+    pub unmatched: u64,
+    /// Count of differing pairs per field, in the default column order
+    pub field_mismatches: [(FieldName, u64); 8],
+}
+
+impl Default for CompareSummary {
+    fn default() -> Self {
+        CompareSummary {
+            compared: 0,
+            differing: 0,
+            unmatched: 0,
+            field_mismatches: FieldName::ALL.map(|name| (name, 0)),
+        }
+    }
+}
+
+impl CompareSummary {
+    /// Compares a pair of records and accounts for the result.
+    /// Returns the fields the records differ in
+    pub fn add_pair(&mut self, left: &Record, right: &Record) -> Vec<FieldName> {
+        self.compared += 1;
+        let differing = left.differing_fields(right);
+        if !differing.is_empty() {
+            self.differing += 1;
+        }
+        for (name, count) in self.field_mismatches.iter_mut() {
+            if differing.contains(name) {
+                *count += 1;
+            }
+        }
+        differing
+    }
+
+    /// Compares a pair of raw records produced by the given producers.
+    /// The records are parsed and compared field by field only when their
+    /// bytes differ. Returns the parsed pair with the differing fields
+    /// or None if the records are equal
+    pub fn add_raw_pair(
+        &mut self,
+        left: &dyn RawDataProducer,
+        left_raw: &RawRecord,
+        right: &dyn RawDataProducer,
+        right_raw: &RawRecord,
+    ) -> RecordProduceResult<Option<RecordDifference>> {
+        if left_raw.same_bytes(right_raw) {
+            self.compared += 1;
+            return Ok(None);
+        }
+        let parse = |producer: &dyn RawDataProducer, raw, side| {
+            producer.parse_raw(raw).map_err(|e| RecordProduceError {
+                kind: e.kind(),
+                text: format!("failed to parse the {} record", side),
+                source: Some(Box::new(e)),
+            })
+        };
+        let left = parse(left, left_raw, "left")?;
+        let right = parse(right, right_raw, "right")?;
+        let differing = self.add_pair(&left, &right);
+        if differing.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((left, right, differing)))
+    }
+
+    /// Count of differing pairs that differ in the field
+    pub fn mismatches(&self, field: FieldName) -> u64 {
+        self.field_mismatches
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    /// Whether no differences were found
+    pub fn is_identical(&self) -> bool {
+        self.differing == 0 && self.unmatched == 0
+    }
+
+    /// Renders the summary as a json object
+    pub fn to_json(&self) -> String {
+        let fields = self
+            .field_mismatches
+            .iter()
+            .map(|(name, count)| format!("\"{}\":{}", name.as_str(), count))
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"compared\":{},\"differing\":{},\"unmatched\":{},\"field_mismatches\":{{{}}}}}",
+            self.compared,
+            self.differing,
+            self.unmatched,
+            fields.join(",")
+        )
+    }
+}
+
+impl Display for CompareSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:<14}{}", "compared", self.compared)?;
+        writeln!(f, "{:<14}{}", "differing", self.differing)?;
+        writeln!(f, "{:<14}{}", "unmatched", self.unmatched)?;
+        for (name, count) in &self.field_mismatches {
+            writeln!(f, "{:<14}{}", name.as_str(), count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, io::Cursor};
+
+    use super::*;
+    use crate::builder::{build_raw_reader, build_serializer};
+    use crate::record::{DataProducer, Status, TxType};
+
+    fn record(tx_id: u64, amount: u64, timestamp: u64) -> Record {
+        Record::new(
+            tx_id,
+            TxType::Deposit,
+            0,
+            1,
+            amount,
+            timestamp,
+            Status::Success,
+            "fee".into(),
+        )
+    }
+
+    #[test]
+    fn test_single_field_histogram() {
+        let mut summary = CompareSummary::default();
+        for i in 0..5 {
+            summary.add_pair(&record(i, 10, i), &record(i, 10, i + 1));
+        }
+        summary.add_pair(&record(9, 10, 9), &record(9, 10, 9));
+        assert_eq!(summary.compared, 6);
+        assert_eq!(summary.differing, 5);
+        let non_zero = summary
+            .field_mismatches
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .collect::<Vec<_>>();
+        assert_eq!(non_zero, vec![&(FieldName::Timestamp, 5)]);
+    }
+
+    #[test]
+    fn test_mixed_histogram() {
+        let mut summary = CompareSummary::default();
+        assert_eq!(
+            summary.add_pair(&record(1, 10, 1), &record(1, 20, 2)),
+            vec![FieldName::Amount, FieldName::Timestamp]
+        );
+        summary.add_pair(&record(2, 10, 1), &record(2, 20, 1));
+        summary.add_pair(&record(3, 10, 1), &record(4, 10, 1));
+        assert_eq!(summary.differing, 3);
+        assert_eq!(summary.mismatches(FieldName::Amount), 2);
+        assert_eq!(summary.mismatches(FieldName::Timestamp), 1);
+        assert_eq!(summary.mismatches(FieldName::TxId), 1);
+        assert_eq!(summary.mismatches(FieldName::Description), 0);
+        assert_eq!(
+            summary.to_json(),
+            "{\"compared\":3,\"differing\":3,\"unmatched\":0,\"field_mismatches\":{\"TX_ID\":1,\"TX_TYPE\":0,\"FROM_USER_ID\":0,\"TO_USER_ID\":0,\"AMOUNT\":2,\"TIMESTAMP\":1,\"STATUS\":0,\"DESCRIPTION\":0}}"
+        );
+    }
+
+    /// Producer counting the parsed records
+    struct Counting {
+        inner: Box<dyn RawDataProducer>,
+        parsed: Cell<u64>,
+    }
+
+    impl DataProducer for Counting {
+        fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+            self.parsed.set(self.parsed.get() + 1);
+            self.inner.produce_record()
+        }
+    }
+
+    impl RawDataProducer for Counting {
+        fn produce_raw(&mut self) -> Option<RecordProduceResult<RawRecord>> {
+            self.inner.produce_raw()
+        }
+        fn parse_raw(&self, raw: &RawRecord) -> RecordProduceResult<Record> {
+            self.parsed.set(self.parsed.get() + 1);
+            self.inner.parse_raw(raw)
+        }
+    }
+
+    fn counting(input: Vec<u8>, format: &str) -> Counting {
+        Counting {
+            inner: build_raw_reader(Cursor::new(input), format).unwrap(),
+            parsed: Cell::new(0),
+        }
+    }
+
+    fn compare_raw(
+        left: &mut Counting,
+        right: &mut Counting,
+    ) -> (CompareSummary, Vec<RecordDifference>) {
+        let mut summary = CompareSummary::default();
+        let mut differences = Vec::new();
+        while let (Some(l), Some(r)) = (left.produce_raw(), right.produce_raw()) {
+            let (l, r) = (l.unwrap(), r.unwrap());
+            if let Some(difference) = summary.add_raw_pair(left, &l, right, &r).unwrap() {
+                differences.push(difference);
+            }
+        }
+        (summary, differences)
+    }
+
+    fn serialize(format: &str, amount_of_4th: u64) -> Vec<u8> {
+        let serializer = build_serializer(format).unwrap();
+        let mut input = Vec::new();
+        if format == "csv" {
+            input.extend(
+                b"TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n",
+            );
+        }
+        for i in 0..10 {
+            let amount = if i == 3 { amount_of_4th } else { 10 };
+            input.extend(serializer.serialize(&record(i, amount, i)).unwrap());
+            if format == "csv" {
+                input.push(b'\n');
+            }
+        }
+        input
+    }
+
+    #[test]
+    fn test_raw_fast_path() {
+        for format in ["bin", "csv"] {
+            let input = serialize(format, 10);
+            let (mut left, mut right) = (
+                counting(input.clone(), format),
+                counting(input.clone(), format),
+            );
+            let (summary, differences) = compare_raw(&mut left, &mut right);
+            assert_eq!(summary.compared, 10);
+            assert!(summary.is_identical() && differences.is_empty());
+            assert_eq!((left.parsed.get(), right.parsed.get()), (0, 0));
+
+            let changed = serialize(format, 11);
+            let diff = input.iter().zip(&changed).filter(|(l, r)| l != r).count();
+            // bin frames also differ in the checksum
+            let max_diff = if format == "bin" { 5 } else { 1 };
+            assert_eq!(input.len(), changed.len());
+            assert!((1..=max_diff).contains(&diff), "{}", diff);
+            let (mut left, mut right) = (counting(input, format), counting(changed, format));
+            let (summary, differences) = compare_raw(&mut left, &mut right);
+            assert_eq!((summary.compared, summary.differing), (10, 1));
+            assert_eq!((left.parsed.get(), right.parsed.get()), (1, 1));
+            assert_eq!(differences[0].0.tx_id, 3);
+            assert_eq!(differences[0].2, vec![FieldName::Amount]);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::record::{Record, TxType};
+
+const MILLIS_PER_DAY: u64 = 86_400_000;
+
+/// Output-only csv column computed from the record.
+/// Readers don't parse derived columns back
+///
+/// ```
+/// use parserde::{DerivedColumn, Record};
+/// let column = DerivedColumn::builtin("DATE").unwrap();
+/// assert_eq!((column.derive)(&Record::default()), "1970-01-01");
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct DerivedColumn {
+    /// Name of the column in the header
+    pub name: String,
+    /// Computes the value of the column
+    pub derive: fn(&Record) -> String,
+}
+
+impl DerivedColumn {
+    /// Built-in columns by name:
+    /// - DATE: UTC `yyyy-mm-dd` of TIMESTAMP
+    /// - TIME: UTC `hh:mm:ss` of TIMESTAMP
+    /// - DIRECTION: IN for deposits, OUT for withdrawals and transfers
+    pub fn builtin(name: &str) -> Option<DerivedColumn> {
+        let derive: fn(&Record) -> String = match name {
+            "DATE" => date,
+            "TIME" => time,
+            "DIRECTION" => direction,
+            _ => return None,
+        };
+        Some(DerivedColumn {
+            name: name.to_string(),
+            derive,
+        })
+    }
+
+    /// Finds a column among the registered ones, then among the built-in ones
+    pub(crate) fn resolve(name: &str, registered: &[DerivedColumn]) -> Option<DerivedColumn> {
+        registered
+            .iter()
+            .find(|c| c.name == name)
+            .cloned()
+            .or_else(|| DerivedColumn::builtin(name))
+    }
+}
+
+fn date(record: &Record) -> String {
+    // days to a proleptic Gregorian date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = record.timestamp / MILLIS_PER_DAY + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn time(record: &Record) -> String {
+    let seconds = record.timestamp % MILLIS_PER_DAY / 1000;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn direction(record: &Record) -> String {
+    match record.tx_type {
+        TxType::Deposit => "IN",
+        TxType::Withdrawal | TxType::Transfer => "OUT",
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::Status;
+
+    fn record(tx_type: TxType, timestamp: u64) -> Record {
+        Record::new(
+            1,
+            tx_type,
+            0,
+            1,
+            10,
+            timestamp,
+            Status::Success,
+            "fee".into(),
+        )
+    }
+
+    #[test]
+    fn test_builtin_columns() {
+        let derive =
+            |name: &str, record: &Record| (DerivedColumn::builtin(name).unwrap().derive)(record);
+        let cases = [
+            (0, "1970-01-01", "00:00:00"),
+            (1633036860000, "2021-09-30", "21:21:00"),
+            (951782400000, "2000-02-29", "00:00:00"),
+            (4107542399999, "2100-02-28", "23:59:59"),
+        ];
+        for (timestamp, date, time) in cases {
+            let record = record(TxType::Deposit, timestamp);
+            assert_eq!(derive("DATE", &record), date);
+            assert_eq!(derive("TIME", &record), time);
+        }
+        assert_eq!(derive("DIRECTION", &record(TxType::Deposit, 0)), "IN");
+        assert_eq!(derive("DIRECTION", &record(TxType::Withdrawal, 0)), "OUT");
+        assert!(DerivedColumn::builtin("TX_ID").is_none());
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::{self, Read};
+
+use crate::options::InputEncoding;
+
+/// Size of the chunks read from the undecoded input
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Reader decoding a single-byte encoded input into UTF-8.
+/// UTF-8 input is passed through
+pub(crate) struct DecodeReader<R: Read> {
+    inner: R,
+    encoding: InputEncoding,
+    decoded: Vec<u8>,
+    consumed: usize,
+}
+
+impl<R: Read> DecodeReader<R> {
+    pub(crate) fn new(inner: R, encoding: InputEncoding) -> DecodeReader<R> {
+        DecodeReader {
+            inner,
+            encoding,
+            decoded: Vec::new(),
+            consumed: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.encoding == InputEncoding::Utf8 {
+            return self.inner.read(buf);
+        }
+        if self.consumed == self.decoded.len() {
+            let mut chunk = [0; CHUNK_SIZE];
+            let read = self.inner.read(&mut chunk)?;
+            self.decoded.clear();
+            self.consumed = 0;
+            let mut utf8 = [0; 4];
+            for &byte in &chunk[..read] {
+                let c = self.encoding.decode(byte);
+                self.decoded
+                    .extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            }
+        }
+        let count = buf.len().min(self.decoded.len() - self.consumed);
+        buf[..count].copy_from_slice(&self.decoded[self.consumed..self.consumed + count]);
+        self.consumed += count;
+        Ok(count)
+    }
+}
+
+/// Decodes a line of single-byte encoded or UTF-8 input
+#[cfg(feature = "tokio")]
+pub(crate) fn decode_line(line: Vec<u8>, encoding: InputEncoding) -> io::Result<String> {
+    match encoding {
+        InputEncoding::Utf8 => {
+            String::from_utf8(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+        _ => Ok(line.into_iter().map(|b| encoding.decode(b)).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(input: &[u8], encoding: InputEncoding) -> String {
+        let mut decoded = String::new();
+        DecodeReader::new(input, encoding)
+            .read_to_string(&mut decoded)
+            .unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_decode() {
+        let input = b"caf\xe9 \x80 \x93ok\x94 \xfc";
+        assert_eq!(decode(input, InputEncoding::Windows1252), "café € “ok” ü");
+        assert_eq!(
+            decode(input, InputEncoding::Latin1),
+            "café \u{80} \u{93}ok\u{94} ü"
+        );
+        let mut invalid = String::new();
+        assert!(
+            DecodeReader::new(&input[..], InputEncoding::Utf8)
+                .read_to_string(&mut invalid)
+                .is_err()
+        );
+        let long = vec![0xe9; CHUNK_SIZE * 2 + 1];
+        assert_eq!(
+            decode(&long, InputEncoding::Latin1),
+            "é".repeat(CHUNK_SIZE * 2 + 1)
+        );
+    }
+}
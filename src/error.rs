@@ -1,9 +1,11 @@
-use std::{error::Error, fmt::Display};
+use std::{error::Error, fmt::Display, io};
+
+use crate::record::FieldName;
 
 #[derive(Debug)]
 pub struct FieldParseError {
     pub(crate) text: String,
-    pub(crate) source: Option<Box<dyn Error>>,
+    pub(crate) source: Option<Box<dyn Error + Send + Sync>>,
 }
 
 impl Display for FieldParseError {
@@ -14,14 +16,34 @@ impl Display for FieldParseError {
 
 impl Error for FieldParseError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        self.source.as_deref()
+        self.source.as_deref().map(|e| e as &(dyn Error + 'static))
     }
 }
 
 #[derive(Debug)]
 pub(crate) struct RecordReadError {
     pub(crate) text: String,
-    pub(crate) source: Option<Box<dyn Error>>,
+    pub(crate) source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl RecordReadError {
+    /// Kind of the error of producing a record this one causes
+    pub(crate) fn kind(&self) -> RecordProduceErrorKind {
+        let is_io = self.source.as_ref().is_some_and(|source| {
+            source.is::<io::Error>()
+                || source
+                    .downcast_ref::<csv::Error>()
+                    .is_some_and(|e| e.is_io_error())
+                || source
+                    .downcast_ref::<quick_xml::Error>()
+                    .is_some_and(|e| matches!(e, quick_xml::Error::Io(_)))
+        });
+        if is_io {
+            RecordProduceErrorKind::Io
+        } else {
+            RecordProduceErrorKind::MalformedRecord
+        }
+    }
 }
 
 impl Display for RecordReadError {
@@ -32,7 +54,7 @@ impl Display for RecordReadError {
 
 impl Error for RecordReadError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        self.source.as_deref()
+        self.source.as_deref().map(|e| e as &(dyn Error + 'static))
     }
 }
 
@@ -40,7 +62,7 @@ impl Error for RecordReadError {
 #[derive(Debug)]
 pub struct RecordSerializeError {
     pub(crate) text: String,
-    pub(crate) source: Option<Box<dyn Error>>,
+    pub(crate) source: Option<Box<dyn Error + Send + Sync>>,
 }
 
 impl Display for RecordSerializeError {
@@ -57,11 +79,79 @@ impl Display for RecordSerializeError {
     }
 }
 
+impl Error for RecordSerializeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|e| e as &(dyn Error + 'static))
+    }
+}
+
 /// An error that occures while reading data or parsing data
 #[derive(Debug)]
 pub struct RecordProduceError {
+    pub(crate) kind: RecordProduceErrorKind,
     pub(crate) text: String,
-    pub(crate) source: Option<Box<dyn Error>>,
+    pub(crate) source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl RecordProduceError {
+    /// What caused the error
+    ///
+    /// ```
+    /// use parserde::{DataProducer, RecordProduceErrorKind, build_reader};
+    /// let input = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+    ///     1,REFUND,0,2,5,9,SUCCESS,fee\n";
+    /// let mut reader = build_reader(input.as_bytes(), "csv").unwrap();
+    /// let e = reader.produce_record().unwrap().unwrap_err();
+    /// assert_eq!(
+    ///     e.kind(),
+    ///     RecordProduceErrorKind::ParseField {
+    ///         field_name: "TX_TYPE".into()
+    ///     }
+    /// );
+    /// ```
+    ///
+    pub fn kind(&self) -> RecordProduceErrorKind {
+        self.kind.clone()
+    }
+}
+
+/// Cause of a [`RecordProduceError`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordProduceErrorKind {
+    /// The input couldn't be read
+    Io,
+    /// The value of a field is invalid
+    ParseField {
+        /// Name of the field
+        field_name: String,
+    },
+    /// A required field is absent
+    MissingField {
+        /// Name of the field
+        field_name: String,
+    },
+    /// The record is not well formed, e.g. it has unknown or duplicate fields
+    /// or a broken frame
+    MalformedRecord,
+    /// Any other error
+    Unknown,
+}
+
+impl RecordProduceErrorKind {
+    /// Kind of an error of building a record from its field values
+    pub(crate) fn of_record(text: &str) -> RecordProduceErrorKind {
+        let field = text.strip_prefix("missing field ").and_then(|name| {
+            FieldName::ALL
+                .into_iter()
+                .find(|field| field.as_str().to_ascii_lowercase().starts_with(name))
+        });
+        match field {
+            Some(field) => RecordProduceErrorKind::MissingField {
+                field_name: field.as_str().into(),
+            },
+            None => RecordProduceErrorKind::MalformedRecord,
+        }
+    }
 }
 
 impl Display for RecordProduceError {
@@ -80,14 +170,27 @@ impl Display for RecordProduceError {
 
 impl Error for RecordProduceError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        self.source.as_deref()
+        self.source.as_deref().map(|e| e as &(dyn Error + 'static))
     }
 }
 
 #[derive(Debug)]
 pub(crate) struct RecordParseError {
     pub(crate) text: String,
-    pub(crate) source: Option<Box<dyn Error>>,
+    /// Field whose value is invalid
+    pub(crate) field: Option<FieldName>,
+    pub(crate) source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl RecordParseError {
+    pub(crate) fn kind(&self) -> RecordProduceErrorKind {
+        match self.field {
+            Some(field) => RecordProduceErrorKind::ParseField {
+                field_name: field.as_str().into(),
+            },
+            None => RecordProduceErrorKind::MalformedRecord,
+        }
+    }
 }
 
 impl Display for RecordParseError {
@@ -106,7 +209,7 @@ impl Display for RecordParseError {
 
 impl Error for RecordParseError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        self.source.as_deref()
+        self.source.as_deref().map(|e| e as &(dyn Error + 'static))
     }
 }
 
@@ -138,6 +241,7 @@ impl Error for ReaderCreateError {
 
 use thiserror::Error;
 
+/// An error that occurs while writing data
 #[derive(Error, Debug)]
 #[error("write error: {text}")]
 pub struct RecordWriteError {
@@ -171,3 +275,18 @@ pub struct RecordWriteError {
 //         self.source.as_deref()
 //     }
 // }
+
+/// An error that occurs while parsing a transaction type or status from text
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("invalid {kind}: {value}")]
+pub struct EnumParseError {
+    pub(crate) kind: &'static str,
+    pub(crate) value: String,
+}
+
+impl EnumParseError {
+    /// The text that isn't a valid value
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
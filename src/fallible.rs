@@ -0,0 +1,97 @@
+use fallible_iterator::FallibleIterator;
+
+use crate::error::RecordProduceError;
+use crate::record::{DataProducer, Record};
+
+/// Adapts a record producer to [`FallibleIterator`].
+/// A failed record is returned as an error and the iteration goes on
+/// with the next one. Once the producer is exhausted the iterator is fused
+///
+/// ```
+/// use fallible_iterator::FallibleIterator;
+/// use parserde::{build_reader, FallibleRecords};
+/// let cursor = std::io::Cursor::new("TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,2,100,5,SUCCESS,fee\n");
+/// let records = FallibleRecords::new(build_reader(cursor, "csv").unwrap());
+/// assert_eq!(records.count().unwrap(), 1);
+/// ```
+///
+pub struct FallibleRecords<P: DataProducer> {
+    producer: P,
+    is_exhausted: bool,
+}
+
+impl<P: DataProducer> FallibleRecords<P> {
+    /// Wraps the producer
+    pub fn new(producer: P) -> FallibleRecords<P> {
+        FallibleRecords {
+            producer,
+            is_exhausted: false,
+        }
+    }
+
+    /// Returns the wrapped producer
+    pub fn into_inner(self) -> P {
+        self.producer
+    }
+}
+
+impl<P: DataProducer> FallibleIterator for FallibleRecords<P> {
+    type Item = Record;
+    type Error = RecordProduceError;
+
+    fn next(&mut self) -> Result<Option<Record>, RecordProduceError> {
+        if self.is_exhausted {
+            return Ok(None);
+        }
+        match self.producer.produce_record() {
+            Some(result) => result.map(Some),
+            None => {
+                self.is_exhausted = true;
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Cursor};
+
+    use super::*;
+    use crate::builder::build_reader;
+    use crate::record::TxType;
+
+    #[test]
+    fn test_filter_collect() {
+        let file = File::open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/source/records_example.csv"
+        ))
+        .unwrap();
+        let deposits = FallibleRecords::new(build_reader(file, "csv").unwrap())
+            .filter(|r| Ok(r.tx_type == TxType::Deposit))
+            .collect::<Vec<_>>()
+            .unwrap();
+        assert!(!deposits.is_empty());
+        assert!(deposits.iter().all(|r| r.tx_type == TxType::Deposit));
+    }
+
+    #[test]
+    fn test_errors_are_not_terminal() {
+        let input = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+            1,DEPOSIT,0,2,100,5,SUCCESS,a\n\
+            2,DEPOSIT,0,2,x,5,SUCCESS,b\n\
+            3,DEPOSIT,0,2,100,5,SUCCESS,c\n";
+        let mut records = FallibleRecords::new(build_reader(Cursor::new(input), "csv").unwrap())
+            .map_err(|e| e.to_string());
+        assert_eq!(records.next().unwrap().unwrap().tx_id, 1);
+        assert!(records.next().is_err());
+        assert_eq!(records.next().unwrap().unwrap().tx_id, 3);
+        assert_eq!(records.next(), Ok(None));
+        assert_eq!(records.next(), Ok(None));
+
+        let collected = FallibleRecords::new(build_reader(Cursor::new(input), "csv").unwrap())
+            .collect::<Vec<_>>();
+        assert!(collected.is_err());
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::builder::{build_reader_with_options, format_by_extension};
+use crate::error::{RecordProduceError, RecordProduceErrorKind};
+use crate::options::ParseOptions;
+use crate::record::{DataProducer, Position, Record};
+use crate::result::RecordProduceResult;
+
+/// Expands an input to the files it names, sorted by path:
+/// - a directory gives its files, except hidden ones
+/// - a path with `*` or `?` in the file name gives the matching files
+/// - any other path is returned as is
+///
+/// ```no_run
+/// use parserde::expand_input;
+/// let parts = expand_input("data/2024-05-*.csv").unwrap();
+/// ```
+///
+pub fn expand_input(input: &str) -> io::Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    if path.is_dir() {
+        return list_files(path, |_| true);
+    }
+    let dir = match path.parent() {
+        Some(dir) if dir.as_os_str().is_empty() => Path::new("."),
+        Some(dir) => dir,
+        None => Path::new("."),
+    };
+    if dir.to_str().is_some_and(is_pattern) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("wildcards are only supported in the file name of {}", input),
+        ));
+    }
+    let Some(pattern) = path.file_name().and_then(|name| name.to_str()) else {
+        return Ok(vec![path.to_path_buf()]);
+    };
+    if !is_pattern(pattern) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    list_files(dir, |name| {
+        name.to_str()
+            .is_some_and(|name| matches(&pattern, &name.chars().collect::<Vec<_>>()))
+    })
+}
+
+fn is_pattern(name: &str) -> bool {
+    name.contains(['*', '?'])
+}
+
+fn list_files(dir: &Path, keep: impl Fn(&std::ffi::OsStr) -> bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') || !entry.file_type()?.is_file() {
+            continue;
+        }
+        if keep(&name) {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Whether the name matches the pattern where `*` stands for any characters
+/// and `?` for a single one. On a mismatch the last `*` takes one more
+/// character, so the match takes at most pattern length times name length steps
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // the last `*` with the position in the name it was tried at
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => {
+                let Some((star_p, star_n)) = star else {
+                    return false;
+                };
+                star = Some((star_p, star_n + 1));
+                p = star_p + 1;
+                n = star_n + 1;
+            }
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether a file with an extension of the given format can be read in the format
+fn is_readable_as(extension_format: &str, format: &str) -> bool {
+    match format.strip_suffix(".gz").unwrap_or(format) {
+        "csv-auto" => matches!(extension_format, "csv" | "tsv"),
+        "bin-legacy" => extension_format == "bin",
+        format => extension_format == format,
+    }
+}
+
+/// Reader of several files in one format read one after another as one stream.
+/// Every file is read with its own reader, so each csv file has its own header.
+/// Errors name the file they come from
+pub struct FileSequence {
+    paths: VecDeque<PathBuf>,
+    current: Option<Box<dyn DataProducer>>,
+    format: String,
+    options: ParseOptions,
+    records: Vec<(PathBuf, u64)>,
+}
+
+impl FileSequence {
+    /// Checks that the files have no extension of another format than the given one.
+    /// Files are opened when the previous one is exhausted
+    pub fn new(
+        paths: Vec<PathBuf>,
+        format: &str,
+        options: &ParseOptions,
+    ) -> Result<FileSequence, Box<dyn Error>> {
+        for path in &paths {
+            if let Some(extension_format) = format_by_extension(path)
+                && !is_readable_as(extension_format, format)
+            {
+                return Err(format!("{} is not a {} file", path.display(), format).into());
+            }
+        }
+        Ok(FileSequence {
+            paths: paths.into(),
+            current: None,
+            format: format.to_string(),
+            options: options.clone(),
+            records: Vec::new(),
+        })
+    }
+
+    /// Files opened so far with the count of records read from each
+    pub fn records_by_file(&self) -> &[(PathBuf, u64)] {
+        &self.records
+    }
+
+    fn open_next(&mut self) -> Option<RecordProduceResult<()>> {
+        let path = self.paths.pop_front()?;
+        let reader = File::open(&path)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|file| build_reader_with_options(file, &self.format, &self.options));
+        let result = match reader {
+            Ok(reader) => {
+                self.current = Some(reader);
+                Ok(())
+            }
+            Err(e) => Err(RecordProduceError {
+                kind: if e.is::<io::Error>() {
+                    RecordProduceErrorKind::Io
+                } else {
+                    RecordProduceErrorKind::Unknown
+                },
+                text: format!("failed to open {}", path.display()),
+                source: Some(e.to_string().into()),
+            }),
+        };
+        self.records.push((path, 0));
+        Some(result)
+    }
+}
+
+impl DataProducer for FileSequence {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        loop {
+            let Some(reader) = self.current.as_mut() else {
+                if let Err(e) = self.open_next()? {
+                    return Some(Err(e));
+                }
+                continue;
+            };
+            let (path, count) = self.records.last_mut()?;
+            match reader.produce_record() {
+                Some(Ok(record)) => {
+                    *count += 1;
+                    return Some(Ok(record));
+                }
+                Some(Err(e)) => {
+                    return Some(Err(RecordProduceError {
+                        kind: e.kind(),
+                        text: format!("failed to read {}", path.display()),
+                        source: Some(Box::new(e)),
+                    }));
+                }
+                None => self.current = None,
+            }
+        }
+    }
+
+    /// Positions within a sequence of files aren't tracked
+    fn position(&self) -> Option<Position> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let matches = |pattern: &str, name: &str| {
+            matches(
+                &pattern.chars().collect::<Vec<_>>(),
+                &name.chars().collect::<Vec<_>>(),
+            )
+        };
+        assert!(matches("2024-05-*.csv", "2024-05-01.csv"));
+        assert!(matches("2024-05-*.csv", "2024-05-.csv"));
+        assert!(!matches("2024-05-*.csv", "2024-06-01.csv"));
+        assert!(!matches("2024-05-*.csv", "2024-05-01.csv.bak"));
+        assert!(matches("part-?.txt", "part-1.txt"));
+        assert!(!matches("part-?.txt", "part-10.txt"));
+        assert!(matches("*", "anything"));
+        assert!(matches("*", ""));
+        assert!(matches("a*b*c", "aXbYbc"));
+        assert!(!matches("a*b*c", "aXbYb"));
+        assert!(matches("*?", "x"));
+        assert!(!matches("*?", ""));
+        // a pattern of many stars fails without trying every split of the name
+        let pattern = "*a".repeat(30) + "b";
+        assert!(!matches(&pattern, &"a".repeat(60)));
+        assert!(matches(&pattern, &("a".repeat(60) + "b")));
+    }
+
+    #[test]
+    fn test_extension_check() {
+        let check = |name: &str, format: &str| {
+            FileSequence::new(vec![PathBuf::from(name)], format, &ParseOptions::default()).is_ok()
+        };
+        assert!(!check("x.bin", "bin64"));
+        assert!(!check("x.b64", "bin"));
+        assert!(!check("x.ypbn", "csv"));
+        assert!(!check("x.tsv", "csv"));
+        assert!(check("x.jsonl", "ndjson"));
+        assert!(check("x.ypbn", "bin"));
+        assert!(check("x.bin", "bin-legacy"));
+        assert!(check("x.b64", "bin64"));
+        assert!(check("x.tsv", "csv-auto"));
+        assert!(check("x.csv.gz", "csv.gz"));
+        assert!(check("x.dat", "csv"));
+        assert!(check("x", "txt"));
+    }
+
+    #[test]
+    fn test_expand_input() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.csv", "a.csv", "c.txt", ".hidden.csv"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        fs::create_dir(dir.path().join("sub.csv")).unwrap();
+        let names = |input: &Path| {
+            expand_input(input.to_str().unwrap())
+                .unwrap()
+                .iter()
+                .map(|p| p.file_name().unwrap().to_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(dir.path()), ["a.csv", "b.csv", "c.txt"]);
+        assert_eq!(names(&dir.path().join("*.csv")), ["a.csv", "b.csv"]);
+        assert!(names(&dir.path().join("*.bin")).is_empty());
+        assert_eq!(names(&dir.path().join("missing.csv")), ["missing.csv"]);
+        assert!(expand_input(dir.path().join("*/a.csv").to_str().unwrap()).is_err());
+    }
+}
@@ -1,3 +1,40 @@
+#[cfg(feature = "tokio")]
+pub(crate) mod async_bin;
+#[cfg(feature = "tokio")]
+pub(crate) mod async_csv;
+#[cfg(feature = "tokio")]
+pub(crate) mod async_txt;
 pub(crate) mod bin;
+pub(crate) mod bin64;
+#[cfg(feature = "cbor")]
+pub(crate) mod cbor;
 pub(crate) mod csv;
+pub(crate) mod gz;
+pub(crate) mod hexdump;
+pub(crate) mod html;
+pub(crate) mod json;
+pub(crate) mod logfmt;
+pub(crate) mod markdown;
+#[cfg(feature = "msgpack")]
+pub(crate) mod msgpack;
+pub(crate) mod ndjson;
+#[cfg(feature = "rayon")]
+pub(crate) mod parallel;
+#[cfg(feature = "parquet")]
+pub(crate) mod parquet;
+pub(crate) mod pgcopy;
+#[cfg(feature = "proto")]
+pub(crate) mod proto;
+#[cfg(feature = "ron")]
+pub(crate) mod ron;
+#[cfg(feature = "sqlite")]
+pub(crate) mod sqlite;
+#[cfg(all(test, any(feature = "cbor", feature = "msgpack", feature = "proto")))]
+pub(crate) mod testing;
+pub(crate) mod toml;
 pub(crate) mod txt;
+#[cfg(feature = "xlsx")]
+pub(crate) mod xlsx;
+pub(crate) mod xml;
+#[cfg(feature = "yaml")]
+pub(crate) mod yaml;
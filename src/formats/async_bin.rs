@@ -0,0 +1,177 @@
+use std::error::Error;
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
+
+use crate::bin::{BinReader, Frame, body_size};
+use crate::error::{RecordProduceError, RecordReadError};
+use crate::record::{AsyncDataProducer, Record};
+use crate::result::{RecordProduceResult, RecordReadResult};
+
+/// Async reader of the bin format. Frames are read from the input
+/// and parsed by a [`BinReader`] keeping the string table.
+/// Magic bytes are checked when a frame is parsed
+pub(crate) struct AsyncBinReader<T: AsyncRead + Unpin> {
+    reader: BufReader<T>,
+    parser: BinReader<io::Empty>,
+    is_exhausted: bool,
+}
+
+impl<T: AsyncRead + Unpin> AsyncBinReader<T> {
+    /// Creates a reader whose YPBN frames must end with the CRC32 of the body
+    pub(crate) fn new(reader: T) -> Result<AsyncBinReader<T>, Box<dyn Error>> {
+        Ok(AsyncBinReader {
+            reader: BufReader::new(reader),
+            parser: BinReader::new(io::empty())?,
+            is_exhausted: false,
+        })
+    }
+
+    /// Creates a reader of legacy input whose YPBN frames have no checksum
+    pub(crate) fn new_no_checksum(reader: T) -> Result<AsyncBinReader<T>, Box<dyn Error>> {
+        Ok(AsyncBinReader {
+            reader: BufReader::new(reader),
+            parser: BinReader::new_no_checksum(io::empty())?,
+            is_exhausted: false,
+        })
+    }
+
+    /// Reads the next frame. None at the end of the input.
+    /// The input is exhausted after an error leaving it unaligned
+    async fn read_frame(&mut self) -> Option<RecordReadResult<Frame>> {
+        let mut head = [0u8; 8];
+        let mut head_len = 0;
+        while head_len < head.len() {
+            match self.reader.read(&mut head[head_len..]).await {
+                Ok(0) if head_len == 0 => {
+                    self.is_exhausted = true;
+                    return None;
+                }
+                Ok(0) => {
+                    self.is_exhausted = true;
+                    return Some(Err(RecordReadError {
+                        text: format!("truncated record header of {} bytes", head_len),
+                        source: None,
+                    }));
+                }
+                Ok(count) => head_len += count,
+                Err(e) => {
+                    self.is_exhausted = true;
+                    return Some(Err(RecordReadError {
+                        text: "failed to read head bytes".into(),
+                        source: Some(Box::new(e)),
+                    }));
+                }
+            }
+        }
+        let size = match body_size(&head) {
+            Ok(size) => size,
+            Err(e) => {
+                self.is_exhausted = true;
+                return Some(Err(e));
+            }
+        };
+        let mut body = vec![0u8; size];
+        if let Err(e) = self.reader.read_exact(&mut body).await {
+            self.is_exhausted = true;
+            return Some(Err(RecordReadError {
+                text: format!("truncated record body. expected {} bytes", size),
+                source: Some(Box::new(e)),
+            }));
+        }
+        Some(self.parser.frame(&head, body))
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: AsyncRead + Unpin + Send> AsyncDataProducer for AsyncBinReader<T> {
+    async fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        while !self.is_exhausted {
+            let frame = match self.read_frame().await? {
+                Ok(frame) => frame,
+                Err(e) => {
+                    return Some(Err(RecordProduceError {
+                        kind: e.kind(),
+                        text: "failed to read record".into(),
+                        source: Some(Box::new(e)),
+                    }));
+                }
+            };
+            if let Some(frame) = self.parser.take_frame(frame) {
+                return Some(frame.and_then(|frame| self.parser.parse_frame(&frame)));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bin::{BinEncoding, DictionaryRecordBytes, RecordBytes};
+    use crate::record::{RecordSerialize, Status, TxType};
+
+    fn records() -> Vec<Record> {
+        (0..4)
+            .map(|i| {
+                Record::new(
+                    i,
+                    TxType::Transfer,
+                    i + 1,
+                    i + 2,
+                    100 * i,
+                    1633036860000 + i,
+                    Status::Pending,
+                    format!("Record number {}", i % 2),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let serializers: [Box<dyn RecordSerialize>; 3] = [
+            Box::new(RecordBytes::default()),
+            Box::new(RecordBytes::new(BinEncoding::Compact)),
+            Box::new(DictionaryRecordBytes::default()),
+        ];
+        for serializer in serializers {
+            let mut input = Vec::new();
+            for record in records() {
+                input.extend(serializer.serialize(&record).unwrap());
+            }
+            let mut reader = AsyncBinReader::new(&input[..]).unwrap();
+            for record in records() {
+                assert_eq!(reader.produce_record().await.unwrap().unwrap(), record);
+            }
+            assert!(reader.produce_record().await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_malformed_frames() {
+        let mut input = RecordBytes::default().serialize(&records()[0]).unwrap();
+        let last = input.len() - 1;
+        input[last] ^= 1;
+        input.extend(RecordBytes::default().serialize(&records()[1]).unwrap());
+        let mut reader = AsyncBinReader::new(&input[..]).unwrap();
+        let err = reader.produce_record().await.unwrap().unwrap_err();
+        assert!(
+            format!("{:?}", err).contains("checksum mismatch"),
+            "{:?}",
+            err
+        );
+        assert_eq!(
+            reader.produce_record().await.unwrap().unwrap(),
+            records()[1]
+        );
+
+        let mut reader = AsyncBinReader::new_no_checksum(&input[..20]).unwrap();
+        let err = reader.produce_record().await.unwrap().unwrap_err();
+        assert!(format!("{:?}", err).contains("truncated"), "{:?}", err);
+        assert!(reader.produce_record().await.is_none());
+
+        let mut reader = AsyncBinReader::new(&b"NOPE\0\0\0\x01\0"[..]).unwrap();
+        assert!(reader.produce_record().await.unwrap().is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::error::Error;
+use std::io::{self, Cursor};
+
+use csv::{ReaderBuilder, StringRecord};
+use tokio::io::{AsyncRead, BufReader};
+
+use crate::async_txt::read_line;
+use crate::csv::CsvReader;
+use crate::error::{RecordProduceError, RecordProduceErrorKind};
+use crate::options::ParseOptions;
+use crate::record::{AsyncDataProducer, Record};
+use crate::result::RecordProduceResult;
+
+/// Async reader of the csv format. Rows are read from the input and parsed
+/// by a csv reader created from the header, which is read with the first row.
+/// Quoted fields may span lines
+pub(crate) struct AsyncCsvReader<T: AsyncRead + Unpin> {
+    reader: BufReader<T>,
+    options: ParseOptions,
+    separator: u8,
+    parser: Option<CsvReader<Cursor<String>>>,
+    current_line: u64,
+    is_exhausted: bool,
+}
+
+impl<T: AsyncRead + Unpin> AsyncCsvReader<T> {
+    pub(crate) fn with_options(
+        reader: T,
+        separator: u8,
+        options: &ParseOptions,
+    ) -> AsyncCsvReader<T> {
+        AsyncCsvReader {
+            reader: BufReader::new(reader),
+            options: options.clone(),
+            separator,
+            parser: None,
+            current_line: 0,
+            is_exhausted: false,
+        }
+    }
+
+    /// Reads the lines of the next non-blank row.
+    /// Lines are joined until every quote is closed
+    async fn read_row(&mut self) -> io::Result<Option<String>> {
+        let mut row = String::new();
+        while let Some(line) = read_line(&mut self.reader, self.options.encoding).await? {
+            self.current_line += 1;
+            if row.is_empty() && line.trim_end_matches('\r').is_empty() {
+                continue;
+            }
+            row.push_str(&line);
+            row.push('\n');
+            if row.matches('"').count().is_multiple_of(2) {
+                return Ok(Some(row));
+            }
+        }
+        Ok((!row.is_empty()).then_some(row))
+    }
+
+    /// Reads the header creating the parser. None for an empty input
+    async fn read_header(&mut self) -> Result<Option<CsvReader<Cursor<String>>>, Box<dyn Error>> {
+        let header = self.read_row().await?.unwrap_or_default();
+        let parser = CsvReader::with_options(Cursor::new(header), self.separator, &self.options)?;
+        Ok(parser.has_header().then_some(parser))
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: AsyncRead + Unpin + Send> AsyncDataProducer for AsyncCsvReader<T> {
+    async fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        if self.is_exhausted {
+            return None;
+        }
+        if self.parser.is_none() {
+            match self.read_header().await {
+                Ok(Some(parser)) => self.parser = Some(parser),
+                Ok(None) => {
+                    self.is_exhausted = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.is_exhausted = true;
+                    return Some(Err(RecordProduceError {
+                        kind: if e.is::<io::Error>() {
+                            RecordProduceErrorKind::Io
+                        } else {
+                            RecordProduceErrorKind::MalformedRecord
+                        },
+                        text: "failed to read the header".into(),
+                        source: Some(e.to_string().into()),
+                    }));
+                }
+            }
+        }
+        let row = match self.read_row().await {
+            Ok(Some(row)) => row,
+            Ok(None) => {
+                self.is_exhausted = true;
+                return None;
+            }
+            Err(e) => {
+                self.is_exhausted = true;
+                return Some(Err(RecordProduceError {
+                    kind: RecordProduceErrorKind::Io,
+                    text: format!("failed to read line {}", self.current_line + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        let mut fields = StringRecord::new();
+        if let Err(e) = ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(self.separator)
+            .from_reader(row.as_bytes())
+            .read_record(&mut fields)
+        {
+            return Some(Err(RecordProduceError {
+                kind: RecordProduceErrorKind::MalformedRecord,
+                text: format!("failed to read row ending at line {}", self.current_line),
+                source: Some(Box::new(e)),
+            }));
+        }
+        Some(self.parser.as_mut()?.parse_row(&fields))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv::csv_line;
+    use crate::record::{Status, TxType};
+
+    fn reader(input: &str) -> AsyncCsvReader<&[u8]> {
+        AsyncCsvReader::with_options(input.as_bytes(), b',', &ParseOptions::default())
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let records = (0..3)
+            .map(|i| {
+                Record::new(
+                    i,
+                    TxType::Deposit,
+                    0,
+                    i + 1,
+                    100 * i,
+                    1633036860000 + i,
+                    Status::Success,
+                    format!("Record \"{}\",\nwith a line break", i),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut input =
+            "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\n"
+                .to_string();
+        for r in &records {
+            let fields = [
+                r.tx_id.to_string(),
+                r.tx_type.to_string(),
+                r.from_user.to_string(),
+                r.to_user.to_string(),
+                r.amount.to_string(),
+                r.timestamp.to_string(),
+                r.status.to_string(),
+                r.description.clone(),
+            ];
+            let line = csv_line(fields.iter().map(String::as_str), b',');
+            input.push_str(&String::from_utf8(line).unwrap());
+        }
+        let mut reader = reader(&input);
+        for record in &records {
+            assert_eq!(&reader.produce_record().await.unwrap().unwrap(), record);
+        }
+        assert!(reader.produce_record().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_malformed_input() {
+        assert!(reader("").produce_record().await.unwrap().is_err());
+        let options = ParseOptions {
+            allow_empty: true,
+            ..Default::default()
+        };
+        let mut empty = AsyncCsvReader::with_options(&b""[..], b',', &options);
+        assert!(empty.produce_record().await.is_none());
+
+        let mut short = reader("TX_ID,AMOUNT\n1,5\n2\n");
+        let err = short.produce_record().await.unwrap().unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            RecordProduceErrorKind::MissingField { .. }
+        ));
+        let err = short.produce_record().await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), RecordProduceErrorKind::MalformedRecord);
+        assert!(err.to_string().contains("line 2"), "{}", err);
+        assert!(short.produce_record().await.is_none());
+    }
+}
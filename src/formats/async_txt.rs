@@ -0,0 +1,139 @@
+use std::io;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+use crate::encoding::decode_line;
+use crate::error::{RecordProduceError, RecordProduceErrorKind};
+use crate::options::{InputEncoding, ParseOptions};
+use crate::record::{AsyncDataProducer, Record};
+use crate::result::{ReaderCreateResult, RecordProduceResult};
+use crate::txt::TxtReader;
+
+/// Reads a line without the line break. None at the end of the input
+pub(crate) async fn read_line<T: AsyncRead + Unpin>(
+    reader: &mut BufReader<T>,
+    encoding: InputEncoding,
+) -> io::Result<Option<String>> {
+    let mut buf = Vec::new();
+    if reader.read_until(b'\n', &mut buf).await? == 0 {
+        return Ok(None);
+    }
+    if buf.ends_with(b"\n") {
+        buf.pop();
+    }
+    decode_line(buf, encoding).map(Some)
+}
+
+/// Async reader of the txt format. Blocks of lines are read
+/// from the input and parsed the same way [`TxtReader`] does
+pub(crate) struct AsyncTxtReader<T: AsyncRead + Unpin> {
+    reader: BufReader<T>,
+    parser: TxtReader<io::Empty>,
+    encoding: InputEncoding,
+    current_line: u64,
+    is_exhausted: bool,
+}
+
+impl<T: AsyncRead + Unpin> AsyncTxtReader<T> {
+    pub(crate) fn with_options(
+        reader: T,
+        options: &ParseOptions,
+    ) -> ReaderCreateResult<AsyncTxtReader<T>> {
+        Ok(AsyncTxtReader {
+            reader: BufReader::new(reader),
+            parser: TxtReader::configured(io::empty(), options)?,
+            encoding: options.encoding,
+            current_line: 0,
+            is_exhausted: false,
+        })
+    }
+
+    /// Reads the lines of the next record skipping comments
+    async fn read_block(&mut self) -> io::Result<Vec<String>> {
+        let mut lines = Vec::new();
+        while let Some(line) = read_line(&mut self.reader, self.encoding).await? {
+            self.current_line += 1;
+            if line.starts_with('#') {
+                continue;
+            }
+            // a run of blank lines is a single separator
+            if line.is_empty() {
+                if lines.is_empty() {
+                    continue;
+                }
+                return Ok(lines);
+            }
+            lines.push(line);
+        }
+        self.is_exhausted = true;
+        Ok(lines)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: AsyncRead + Unpin + Send> AsyncDataProducer for AsyncTxtReader<T> {
+    async fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        if self.is_exhausted {
+            return None;
+        }
+        let lines = match self.read_block().await {
+            Ok(lines) => lines,
+            Err(e) => {
+                self.is_exhausted = true;
+                return Some(Err(RecordProduceError {
+                    kind: RecordProduceErrorKind::Io,
+                    text: format!("failed to read line {}", self.current_line + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        if lines.is_empty() {
+            return None;
+        }
+        Some(self.parser.parse_lines(&lines, self.current_line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::build_serializer;
+    use crate::record::{Status, TxType};
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let records = (0..3)
+            .map(|i| {
+                Record::new(
+                    i,
+                    TxType::Withdrawal,
+                    i + 1,
+                    0,
+                    100 * i,
+                    1633036860000 + i,
+                    Status::Failure,
+                    format!("Record number {}", i),
+                )
+            })
+            .collect::<Vec<_>>();
+        let serializer = build_serializer("txt").unwrap();
+        let mut input = b"# leading comment\n\n".to_vec();
+        for record in &records {
+            input.extend(serializer.serialize(record).unwrap());
+            input.extend(b"\n\n\n");
+        }
+        let mut reader =
+            AsyncTxtReader::with_options(&input[..], &ParseOptions::default()).unwrap();
+        for record in &records {
+            assert_eq!(&reader.produce_record().await.unwrap().unwrap(), record);
+        }
+        assert!(reader.produce_record().await.is_none());
+
+        let mut reader =
+            AsyncTxtReader::with_options(&b"TX_ID: 1\nAMOUNT: x\n"[..], &ParseOptions::default())
+                .unwrap();
+        let err = reader.produce_record().await.unwrap().unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+        assert!(reader.produce_record().await.is_none());
+    }
+}
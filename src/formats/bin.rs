@@ -1,83 +1,359 @@
+//! The bin format, a sequence of frames of a 4 byte magic, the big-endian u32
+//! size of the body and the body.
+//!
+//! A `YPBN` body is the original fixed width big-endian record followed by
+//! the CRC32 of the body. It starts right away with the TX_ID, so every
+//! value of its first byte is a valid record and there is no room to tell
+//! a version byte from data. The other encodings therefore use the `YPB2`
+//! magic with the same framing, where the first body byte is the version.
+//! Readers skip and index frames by the declared body size whatever the magic
+
 use std::{
     array::TryFromSliceError,
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
     error::Error,
-    io::{self, BufReader, Read, Write},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    ops::Range,
 };
 
 use crate::{
     error::RecordWriteError,
     record::{
-        DataConsumer, DataProducer, Field, FieldValue, Record, RecordSerialize, RecordWriter,
-        Status, TxType, fields,
+        DataConsumer, DataProducer, Field, FieldName, FieldValue, Position, RawDataProducer,
+        RawRecord, RawRepr, Record, RecordSerialize, RecordWriter, Status, TxType,
     },
 };
 
-use crate::error::{FieldParseError, RecordParseError, RecordProduceError, RecordReadError};
+use crate::error::{
+    FieldParseError, ReaderCreateError, RecordParseError, RecordProduceError,
+    RecordProduceErrorKind, RecordReadError,
+};
 use crate::result::{
     FieldParseResult, RecordParseResult, RecordProduceResult, RecordReadResult,
     RecordSerializeResult,
 };
 
 const MAX_DESCRIPTION_LENGTH: u32 = 1048576; // 1 MIB
+/// Max length of a record body. The description with room for the other fields
+const MAX_BODY_LENGTH: u32 = MAX_DESCRIPTION_LENGTH + 64;
+
+/// Magic of the original big-endian record frame without a version byte
+pub(crate) const MAGIC: &[u8; 4] = b"YPBN";
+/// Magic of a versioned record frame. The first body byte is the version
+pub(crate) const VERSIONED_MAGIC: &[u8; 4] = b"YPB2";
+/// Version byte of the compact encoding using LEB128 varints
+pub(crate) const COMPACT_VERSION: u8 = 3;
+/// Version byte of the compact encoding with a description string table
+pub(crate) const DICTIONARY_VERSION: u8 = 4;
+/// Version byte of the fixed width little-endian encoding
+pub(crate) const LITTLE_ENDIAN_VERSION: u8 = 5;
+
+/// Dictionary frame kind defining the next string table entry
+const TABLE_ENTRY_FRAME: u8 = 0;
+/// Dictionary frame kind carrying a record
+const RECORD_FRAME: u8 = 1;
+/// Description is stored inline in a dictionary record frame
+const INLINE_DESCRIPTION: u8 = 0;
+/// Description is a reference to a string table entry
+const TABLE_DESCRIPTION: u8 = 1;
+
+/// Max length of a LEB128 encoded u64
+pub(crate) const MAX_VARINT_LENGTH: usize = 10;
+
+/// Length of the CRC32 that ends the body of a YPBN frame
+pub(crate) const CHECKSUM_LENGTH: usize = 4;
+
+/// Byte ranges of the fixed width fields of a YPBN body
+pub(crate) const FIXED_FIELDS: [(FieldName, Range<usize>); 7] = [
+    (FieldName::TxId, 0..8),
+    (FieldName::TxType, 8..9),
+    (FieldName::FromUser, 9..17),
+    (FieldName::ToUser, 17..25),
+    (FieldName::Amount, 25..33),
+    (FieldName::Timestamp, 33..41),
+    (FieldName::Status, 41..42),
+];
+/// Byte range of the u32 length of the description following the fixed fields
+pub(crate) const DESCRIPTION_LENGTH: Range<usize> = 42..46;
+/// Length of a YPBN body without the description and the checksum
+pub(crate) const FIXED_BODY_LENGTH: usize = DESCRIPTION_LENGTH.end;
+
+/// CRC-32 (IEEE) lookup table
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE) of the bytes
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &b| {
+        (crc >> 8) ^ CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize]
+    })
+}
+
+/// Raw record frame. Header magic and the body
+#[derive(Debug, PartialEq)]
+pub(crate) struct Frame {
+    pub(crate) magic: [u8; 4],
+    pub(crate) body: Vec<u8>,
+}
+
+impl Frame {
+    /// The frame as it appears in the output.
+    /// A YPBN body is followed by its checksum, also when it was read without one
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let checksum = &self.magic == MAGIC;
+        let body_size = self.body.len() + if checksum { CHECKSUM_LENGTH } else { 0 };
+        let mut bytes = Vec::with_capacity(8 + body_size);
+        bytes.extend_from_slice(&self.magic);
+        bytes.extend_from_slice(&(body_size as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.body);
+        if checksum {
+            bytes.extend_from_slice(&crc32(&self.body).to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Whether the frame parses without a string table
+    pub(crate) fn is_self_contained(&self) -> bool {
+        is_self_contained(&self.magic, &self.body)
+    }
+}
+
+/// Whether a frame of the magic and body parses without a string table
+pub(crate) fn is_self_contained(magic: &[u8], body: &[u8]) -> bool {
+    magic != VERSIONED_MAGIC || body.first() != Some(&DICTIONARY_VERSION)
+}
 
-pub(crate) struct BinReader<T: Read> {
+/// Reader of the bin format. Readers of bin input are usually created
+/// by [`build_reader`](crate::build_reader), this one is for reading
+/// legacy input without checksums and for tuning the size of the read buffer
+pub struct BinReader<T: Read> {
     reader: BufReader<T>,
     is_exhausted: bool,
+    string_table: Vec<String>,
+    offset: u64,
+    records: u64,
+    checksum: bool,
 }
 
 impl<T: Read> BinReader<T> {
-    pub(crate) fn new(reader: T) -> Result<BinReader<T>, Box<dyn Error>> {
+    /// Creates a reader checking that a non-empty stream starts with a record magic.
+    /// YPBN frames must end with the CRC32 of the body
+    ///
+    /// ```
+    /// use parserde::{BinReader, DataProducer, Record, build_serializer};
+    /// let data = build_serializer("bin").unwrap().serialize(&Record::default()).unwrap();
+    /// let mut reader = BinReader::new(&data[..]).unwrap();
+    /// assert_eq!(reader.produce_record().unwrap().unwrap(), Record::default());
+    /// assert!(BinReader::new(&b"nope"[..]).is_err());
+    /// ```
+    pub fn new(reader: T) -> Result<BinReader<T>, Box<dyn Error>> {
+        BinReader::with_checksum(BufReader::new(reader), true)
+    }
+
+    /// Creates a reader of legacy input whose YPBN frames have no checksum
+    ///
+    /// ```
+    /// use parserde::{BinReader, DataProducer, Record, build_serializer};
+    /// let frame = build_serializer("bin").unwrap().serialize(&Record::default()).unwrap();
+    /// // the legacy frame is the magic, the size and the body without the checksum
+    /// let body = &frame[8..frame.len() - 4];
+    /// let mut legacy = b"YPBN".to_vec();
+    /// legacy.extend((body.len() as u32).to_be_bytes());
+    /// legacy.extend(body);
+    /// let mut reader = BinReader::new_no_checksum(&legacy[..]).unwrap();
+    /// assert_eq!(reader.produce_record().unwrap().unwrap(), Record::default());
+    /// ```
+    pub fn new_no_checksum(reader: T) -> Result<BinReader<T>, Box<dyn Error>> {
+        BinReader::with_checksum(BufReader::new(reader), false)
+    }
+
+    /// Creates a reader like [`BinReader::new`], reading through
+    /// a buffer of the given capacity in bytes rather than the default 8 KiB
+    ///
+    /// ```
+    /// use parserde::BinReader;
+    /// let reader = BinReader::with_buf_capacity(std::io::empty(), 1 << 20).unwrap();
+    /// assert_eq!(reader.buf_capacity(), 1 << 20);
+    /// ```
+    pub fn with_buf_capacity(reader: T, capacity: usize) -> Result<BinReader<T>, Box<dyn Error>> {
+        BinReader::with_checksum(BufReader::with_capacity(capacity, reader), true)
+    }
+
+    /// Creates a reader like [`BinReader::new_no_checksum`], reading through
+    /// a buffer of the given capacity in bytes rather than the default 8 KiB
+    ///
+    /// ```
+    /// use parserde::BinReader;
+    /// let reader = BinReader::with_buf_capacity_no_checksum(std::io::empty(), 1 << 16).unwrap();
+    /// assert_eq!(reader.buf_capacity(), 1 << 16);
+    /// ```
+    pub fn with_buf_capacity_no_checksum(
+        reader: T,
+        capacity: usize,
+    ) -> Result<BinReader<T>, Box<dyn Error>> {
+        BinReader::with_checksum(BufReader::with_capacity(capacity, reader), false)
+    }
+
+    /// Capacity of the read buffer in bytes
+    pub fn buf_capacity(&self) -> usize {
+        self.reader.capacity()
+    }
+
+    fn with_checksum(
+        mut reader: BufReader<T>,
+        checksum: bool,
+    ) -> Result<BinReader<T>, Box<dyn Error>> {
+        let head = reader.fill_buf().map_err(|e| ReaderCreateError {
+            text: "failed to read the first record header".into(),
+            source: Some(Box::new(e)),
+        })?;
+        let head = &head[..head.len().min(MAGIC.len())];
+        if !head.is_empty() && !MAGIC.starts_with(head) && !VERSIONED_MAGIC.starts_with(head) {
+            let found = head
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" ");
+            return Err(ReaderCreateError {
+                text: format!(
+                    "input is not in bin format. expected a record magic, found bytes {}",
+                    found
+                ),
+                source: None,
+            }
+            .into());
+        }
         Ok(BinReader {
-            reader: BufReader::new(reader),
+            reader,
             is_exhausted: false,
+            string_table: Vec::new(),
+            offset: 0,
+            records: 0,
+            checksum,
         })
     }
 }
 
+impl<T: Read + Seek> BinReader<T> {
+    /// Moves the reader to a position previously returned by `position`.
+    /// The frames before the position are skipped over from the start
+    /// of the input, reading only the string table entries among them,
+    /// so a dictionary input resumes with the table it had at the position
+    pub(crate) fn seek(&mut self, position: Position) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(0))?;
+        self.string_table.clear();
+        self.offset = 0;
+        let mut head = [0u8; 8];
+        while self.offset < position.byte {
+            self.reader.read_exact(&mut head)?;
+            let size = body_size(&head)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            if &head[..4] == VERSIONED_MAGIC {
+                let mut body = vec![0u8; size];
+                self.reader.read_exact(&mut body)?;
+                let frame = self
+                    .frame(&head, body)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+                if let Some(Err(e)) = self.take_frame(frame) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+                }
+            } else {
+                self.reader.seek_relative(size as i64)?;
+            }
+            self.offset += head.len() as u64 + size as u64;
+        }
+        if self.offset != position.byte {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("byte {} isn't the start of a frame", position.byte),
+            ));
+        }
+        self.records = position.record;
+        self.is_exhausted = false;
+        Ok(())
+    }
+
+    /// Moves the reader to the frame starting at the offset,
+    /// keeping the string table read so far
+    pub(crate) fn seek_frame(&mut self, offset: u64) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.offset = offset;
+        self.is_exhausted = false;
+        Ok(())
+    }
+}
+
 fn try_u32_from_bytes(b: &[u8]) -> Result<u32, TryFromSliceError> {
     Ok(u32::from_be_bytes(b.try_into()?))
 }
 
 impl<T: Read> DataConsumer for BinReader<T> {
-    type Item = Vec<u8>;
+    type Item = Frame;
     fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
         if self.is_exhausted {
             return None;
         }
         let mut head_buf = [0u8; 8];
-        match self.reader.read_exact(&mut head_buf) {
-            // change to if let err
-            Ok(_) => (),
-            Err(e) => match e.kind() {
-                io::ErrorKind::UnexpectedEof => {
+        let mut head_len = 0;
+        while head_len < head_buf.len() {
+            match self.reader.read(&mut head_buf[head_len..]) {
+                Ok(0) if head_len == 0 => {
                     self.is_exhausted = true;
                     return None;
                 }
-                _ => {
+                Ok(0) => {
+                    self.is_exhausted = true;
+                    return Some(Err(RecordReadError {
+                        text: format!("truncated record header of {} bytes", head_len),
+                        source: None,
+                    }));
+                }
+                Ok(count) => head_len += count,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
                     return Some(Err(RecordReadError {
                         text: "failed to read head bytes".into(),
                         source: Some(Box::new(e)),
                     }));
                 }
-            },
-        };
+            }
+        }
 
-        let body_size = match try_u32_from_bytes(&head_buf[4..]) {
-            Ok(b) => b,
+        let body_size = match body_size(&head_buf) {
+            Ok(size) => size,
             Err(e) => {
-                return Some(Err(RecordReadError {
-                    text: "failed to parse body size".into(),
-                    source: Some(Box::new(e)),
-                }));
+                self.is_exhausted = true;
+                return Some(Err(e));
             }
         };
-
-        let mut body_buf = vec![0u8; body_size as usize];
+        let mut body_buf = vec![0u8; body_size];
         if let Err(e) = self.reader.read_exact(&mut body_buf) {
             match e.kind() {
                 io::ErrorKind::UnexpectedEof => {
                     self.is_exhausted = true;
-                    return None;
+                    return Some(Err(RecordReadError {
+                        text: format!("truncated record body. expected {} bytes", body_size),
+                        source: Some(Box::new(e)),
+                    }));
                 }
                 _ => {
                     return Some(Err(RecordReadError {
@@ -87,64 +363,193 @@ impl<T: Read> DataConsumer for BinReader<T> {
                 }
             }
         }
-        Some(Ok(body_buf))
+        self.offset += head_buf.len() as u64 + body_size as u64;
+        Some(self.frame(&head_buf, body_buf))
     }
 }
 
-impl<T: Read> DataProducer for BinReader<T> {
-    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
-        let result = match self.read() {
-            Some(result) => match result {
-                Ok(bytes) => bytes,
+/// Size of the body following the frame header.
+/// Unknown magic bytes and sizes above the max limit are an error
+pub(crate) fn body_size(head: &[u8; 8]) -> RecordReadResult<usize> {
+    if &head[..4] != MAGIC && &head[..4] != VERSIONED_MAGIC {
+        return Err(RecordReadError {
+            text: "invalid magic bytes, expected YPBN or YPB2".into(),
+            source: None,
+        });
+    }
+    let body_size = try_u32_from_bytes(&head[4..]).map_err(|e| RecordReadError {
+        text: "failed to parse body size".into(),
+        source: Some(Box::new(e)),
+    })?;
+    if body_size > MAX_BODY_LENGTH {
+        return Err(RecordReadError {
+            text: format!(
+                "record body of {} bytes is greater than the max limit {}",
+                body_size, MAX_BODY_LENGTH
+            ),
+            source: None,
+        });
+    }
+    Ok(body_size as usize)
+}
+
+impl<T: Read> BinReader<T> {
+    /// Frame of the header and body, checking and stripping
+    /// the checksum of a YPBN body
+    pub(crate) fn frame(&self, head: &[u8; 8], mut body: Vec<u8>) -> RecordReadResult<Frame> {
+        let mut magic = [0u8; 4];
+        magic.copy_from_slice(&head[..4]);
+        if self.checksum && &magic == MAGIC {
+            let Some(split) = body.len().checked_sub(CHECKSUM_LENGTH) else {
+                return Err(RecordReadError {
+                    text: format!("record body of {} bytes has no checksum", body.len()),
+                    source: None,
+                });
+            };
+            let expected = try_u32_from_bytes(&body[split..]).unwrap_or_default();
+            body.truncate(split);
+            if crc32(&body) != expected {
+                return Err(RecordReadError {
+                    text: "checksum mismatch".into(),
+                    source: None,
+                });
+            }
+        }
+        Ok(Frame { magic, body })
+    }
+
+    /// Reads the next record frame without parsing it.
+    /// String table entries met on the way are stored
+    pub(crate) fn next_record_frame(&mut self) -> Option<RecordProduceResult<Frame>> {
+        loop {
+            let frame = match self.read()? {
+                Ok(frame) => frame,
                 Err(e) => {
                     return Some(Err(RecordProduceError {
+                        kind: e.kind(),
                         text: "failed to read record".into(),
                         source: Some(Box::new(e)),
                     }));
                 }
-            },
-            None => return None,
-        };
-        match parse_body(result) {
-            Ok(r) => Some(Ok(r)),
+            };
+            if let Some(frame) = self.take_frame(frame) {
+                return Some(frame);
+            }
+        }
+    }
+
+    /// Stores a string table entry frame. A record frame is handed back
+    pub(crate) fn take_frame(&mut self, frame: Frame) -> Option<RecordProduceResult<Frame>> {
+        if !is_table_entry(&frame) {
+            return Some(Ok(frame));
+        }
+        match parse_table_entry(&frame.body[2..], self.string_table.len()) {
+            Ok(entry) => {
+                self.string_table.push(entry);
+                None
+            }
             Err(e) => Some(Err(RecordProduceError {
-                text: "failed to parse record".into(),
+                kind: RecordProduceErrorKind::MalformedRecord,
+                text: "failed to parse string table entry".into(),
                 source: Some(Box::new(e)),
             })),
         }
     }
+
+    pub(crate) fn parse_frame(&self, frame: &Frame) -> RecordProduceResult<Record> {
+        parse_body(&frame.magic, &frame.body, &self.string_table).map_err(|e| RecordProduceError {
+            kind: e.kind(),
+            text: "failed to parse record".into(),
+            source: Some(Box::new(e)),
+        })
+    }
+}
+
+impl<T: Read> DataProducer for BinReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let frame = match self.next_record_frame()? {
+            Ok(frame) => frame,
+            Err(e) => return Some(Err(e)),
+        };
+        self.records += 1;
+        Some(self.parse_frame(&frame))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: 0,
+            record: self.records,
+        })
+    }
+}
+
+impl<T: Read> RawDataProducer for BinReader<T> {
+    fn produce_raw(&mut self) -> Option<RecordProduceResult<RawRecord>> {
+        let frame = match self.next_record_frame()? {
+            Ok(frame) => frame,
+            Err(e) => return Some(Err(e)),
+        };
+        self.records += 1;
+        Some(Ok(RawRecord(RawRepr::Frame(frame))))
+    }
+
+    fn parse_raw(&self, raw: &RawRecord) -> RecordProduceResult<Record> {
+        match &raw.0 {
+            RawRepr::Frame(frame) => self.parse_frame(frame),
+            _ => Err(RecordProduceError {
+                kind: RecordProduceErrorKind::Unknown,
+                text: "raw record is not a bin frame".into(),
+                source: None,
+            }),
+        }
+    }
 }
 
 fn try_u64_from_bytes(bytes: &[u8]) -> Result<u64, TryFromSliceError> {
     Ok(u64::from_be_bytes(bytes.try_into()?))
 }
 
-impl Field<&str, &[u8]> {
+impl Field<&[u8]> {
     pub(crate) fn parse(&self) -> FieldParseResult<FieldValue> {
         Ok(match self.name {
-            fields::str::TX_ID => {
+            FieldName::TxId => {
                 FieldValue::TxId(try_u64_from_bytes(self.value).map_err(|_| FieldParseError {
                     text: "failed to parse tx_id".into(),
                     source: None,
                 })?)
             }
-            fields::str::TX_TYPE => {
-                FieldValue::TxType(TxType::try_from(&self.value[0]).map_err(|_| {
-                    FieldParseError {
-                        text: "failed to parse tx_type".into(),
-                        source: None,
+            FieldName::TxType => {
+                let byte = match self.value {
+                    [byte] => byte,
+                    _ => {
+                        return Err(FieldParseError {
+                            text: format!("tx_type takes 1 byte, found {}", self.value.len()),
+                            source: None,
+                        });
                     }
+                };
+                FieldValue::TxType(TxType::try_from(byte).map_err(|_| FieldParseError {
+                    text: "failed to parse tx_type".into(),
+                    source: None,
                 })?)
             }
-            fields::str::STATUS => {
-                FieldValue::Status(Status::try_from(&self.value[0]).map_err(|_| {
-                    FieldParseError {
-                        text: "failed to parse status".into(),
-                        source: None,
+            FieldName::Status => {
+                let byte = match self.value {
+                    [byte] => byte,
+                    _ => {
+                        return Err(FieldParseError {
+                            text: format!("status takes 1 byte, found {}", self.value.len()),
+                            source: None,
+                        });
                     }
+                };
+                FieldValue::Status(Status::try_from(byte).map_err(|_| FieldParseError {
+                    text: "failed to parse status".into(),
+                    source: None,
                 })?)
             }
-            fields::str::FROM_USER => {
+            FieldName::FromUser => {
                 FieldValue::FromUser(try_u64_from_bytes(self.value).map_err(|_| {
                     FieldParseError {
                         text: "failed to parse from_user".into(),
@@ -152,19 +557,19 @@ impl Field<&str, &[u8]> {
                     }
                 })?)
             }
-            fields::str::TO_USER => {
+            FieldName::ToUser => {
                 FieldValue::ToUser(try_u64_from_bytes(self.value).map_err(|_| FieldParseError {
                     text: "failed to parse to_user".into(),
                     source: None,
                 })?)
             }
-            fields::str::AMOUNT => {
+            FieldName::Amount => {
                 FieldValue::Amount(try_u64_from_bytes(self.value).map_err(|_| FieldParseError {
                     text: "failed to parse amount".into(),
                     source: None,
                 })?)
             }
-            fields::str::TIMESTAMP => {
+            FieldName::Timestamp => {
                 FieldValue::Timestamp(try_u64_from_bytes(self.value).map_err(|_| {
                     FieldParseError {
                         text: "failed to parse timestamp".into(),
@@ -172,7 +577,7 @@ impl Field<&str, &[u8]> {
                     }
                 })?)
             }
-            fields::str::DESCRIPTION => {
+            FieldName::Description => {
                 FieldValue::Description(String::from_utf8(self.value.to_owned()).map_err(|e| {
                     FieldParseError {
                         text: "failed to parse description".into(),
@@ -180,87 +585,497 @@ impl Field<&str, &[u8]> {
                     }
                 })?)
             }
-            _ => {
-                return Err(FieldParseError {
-                    text: format!("unknown field: {}", self.name),
-                    source: None,
-                });
-            }
         })
     }
 }
 
-fn parse_body(body: Vec<u8>) -> RecordParseResult<Record> {
-    if body.len() < 46 {
+fn parse_body(magic: &[u8; 4], body: &[u8], table: &[String]) -> RecordParseResult<Record> {
+    if magic != VERSIONED_MAGIC {
+        return parse_fixed_body(body, false);
+    }
+    match body.first() {
+        Some(&COMPACT_VERSION) => parse_compact_body(&body[1..]),
+        Some(&LITTLE_ENDIAN_VERSION) => parse_fixed_body(&body[1..], true),
+        Some(&DICTIONARY_VERSION) => match body.get(1) {
+            Some(&RECORD_FRAME) => parse_dictionary_body(&body[2..], table),
+            _ => Err(RecordParseError {
+                text: "unknown dictionary frame kind".into(),
+                field: None,
+                source: None,
+            }),
+        },
+        Some(version) => Err(RecordParseError {
+            text: format!("unsupported body version {}", version),
+            field: None,
+            source: None,
+        }),
+        None => Err(RecordParseError {
+            text: "empty versioned body".into(),
+            field: None,
+            source: None,
+        }),
+    }
+}
+
+/// Parses the fixed width fields of a YPBN body or a little-endian versioned one
+fn parse_fixed_body(body: &[u8], little_endian: bool) -> RecordParseResult<Record> {
+    if body.len() < FIXED_BODY_LENGTH {
         return Err(RecordParseError {
             text: format!(
-                "inconsistent body length {} expected at least 45",
-                body.len()
+                "inconsistent body length {} expected at least {}",
+                body.len(),
+                FIXED_BODY_LENGTH
             ),
+            field: None,
             source: None,
         });
     }
-    let desc_len = try_u32_from_bytes(&body[42..46]).map_err(|e| RecordParseError {
-        text: "couldn't parse desc len".into(),
-        source: Some(Box::new(e)),
-    })?;
+    let mut desc_len =
+        try_u32_from_bytes(&body[DESCRIPTION_LENGTH]).map_err(|e| RecordParseError {
+            text: "couldn't parse desc len".into(),
+            field: None,
+            source: Some(Box::new(e)),
+        })?;
+    if little_endian {
+        desc_len = desc_len.swap_bytes();
+    }
     if desc_len > MAX_DESCRIPTION_LENGTH {
         return Err(RecordParseError {
             text: "descrption length is greater than the max limit 1 MIB".into(),
+            field: None,
             source: None,
         });
     }
-    if (46 + desc_len) as usize != body.len() {
+    if FIXED_BODY_LENGTH + desc_len as usize != body.len() {
         return Err(RecordParseError {
-            text: "description length is incostinent".into(),
+            text: format!(
+                "declared description length {} doesn't match the {} bytes left in the body",
+                desc_len,
+                body.len() - FIXED_BODY_LENGTH
+            ),
+            field: None,
             source: None,
         });
     }
-    let fields_to_parse = [
-        (fields::str::TX_ID, &body[0..8]),
-        (fields::str::TX_TYPE, &body[8..9]),
-        (fields::str::FROM_USER, &body[9..17]),
-        (fields::str::TO_USER, &body[17..25]),
-        (fields::str::AMOUNT, &body[25..33]),
-        (fields::str::TIMESTAMP, &body[33..41]),
-        (fields::str::STATUS, &body[41..42]),
-        (fields::str::DESCRIPTION, &body[46..]),
-    ];
+    let fields_to_parse = FIXED_FIELDS
+        .into_iter()
+        .map(|(n, range)| (n, &body[range]))
+        .chain([(FieldName::Description, &body[FIXED_BODY_LENGTH..])]);
     let mut fields: Vec<FieldValue> = Vec::with_capacity(8);
     for (n, b) in fields_to_parse {
-        let f = Field::new(n, b).parse().map_err(|e| RecordParseError {
+        let bytes: Cow<[u8]> = if little_endian && n != FieldName::Description {
+            b.iter().rev().copied().collect()
+        } else {
+            Cow::Borrowed(b)
+        };
+        let f = Field::new(n, &bytes[..])
+            .parse()
+            .map_err(|e| RecordParseError {
+                text: format!("failed to parse field {}", n),
+                field: Some(n),
+                source: Some(Box::new(e)),
+            })?;
+        fields.push(f);
+    }
+    Record::try_from(fields).map_err(|e| RecordParseError {
+        text: "failed to parse record".into(),
+        field: None,
+        source: Some(e.into()),
+    })
+}
+
+pub(crate) fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+/// Reads a LEB128 varint and returns the value with the count of consumed bytes.
+/// Truncated and overlong (non minimal or wider than u64) encodings are rejected
+pub(crate) fn get_varint(bytes: &[u8]) -> FieldParseResult<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().enumerate() {
+        if i == MAX_VARINT_LENGTH - 1 && byte > 1 {
+            return Err(FieldParseError {
+                text: "varint overflows u64".into(),
+                source: None,
+            });
+        }
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            if byte == 0 && i > 0 {
+                return Err(FieldParseError {
+                    text: "overlong varint".into(),
+                    source: None,
+                });
+            }
+            return Ok((value, i + 1));
+        }
+        if i == MAX_VARINT_LENGTH - 1 {
+            break;
+        }
+    }
+    Err(FieldParseError {
+        text: "truncated varint".into(),
+        source: None,
+    })
+}
+
+/// Decodes the varint fields shared by the compact and dictionary bodies.
+/// Returns the fields and the position of the description part
+fn parse_compact_fields(body: &[u8]) -> RecordParseResult<(Vec<FieldValue>, usize)> {
+    let mut pos = 0;
+    let mut fields: Vec<FieldValue> = Vec::with_capacity(8);
+    let layout = [
+        FieldName::TxId,
+        FieldName::TxType,
+        FieldName::FromUser,
+        FieldName::ToUser,
+        FieldName::Amount,
+        FieldName::Timestamp,
+        FieldName::Status,
+    ];
+    for n in layout {
+        let rest = &body[pos..];
+        let value = match n {
+            FieldName::TxType | FieldName::Status => {
+                if rest.is_empty() {
+                    return Err(RecordParseError {
+                        text: format!("compact body is truncated at field {}", n),
+                        field: None,
+                        source: None,
+                    });
+                }
+                pos += 1;
+                Field::new(n, &rest[..1]).parse()
+            }
+            _ => get_varint(rest).and_then(|(v, len)| {
+                pos += len;
+                Field::new(n, &v.to_be_bytes()[..]).parse()
+            }),
+        };
+        fields.push(value.map_err(|e| RecordParseError {
             text: format!("failed to parse field {}", n),
+            field: Some(n),
             source: Some(Box::new(e)),
-        })?;
-        fields.push(f);
+        })?);
     }
+    Ok((fields, pos))
+}
+
+/// Parses a varint length prefixed string which must take the rest of the body
+fn parse_inline_string(body: &[u8]) -> RecordParseResult<String> {
+    let (len, pos) = get_varint(body).map_err(|e| RecordParseError {
+        text: "couldn't parse desc len".into(),
+        field: None,
+        source: Some(Box::new(e)),
+    })?;
+    if len > MAX_DESCRIPTION_LENGTH as u64 {
+        return Err(RecordParseError {
+            text: "descrption length is greater than the max limit 1 MIB".into(),
+            field: None,
+            source: None,
+        });
+    }
+    if pos + len as usize != body.len() {
+        return Err(RecordParseError {
+            text: format!(
+                "description length is inconsistent. declared {} remaining {}",
+                len,
+                body.len() - pos
+            ),
+            field: None,
+            source: None,
+        });
+    }
+    String::from_utf8(body[pos..].to_owned()).map_err(|e| RecordParseError {
+        text: format!("failed to parse field {}", FieldName::Description),
+        field: Some(FieldName::Description),
+        source: Some(Box::new(e)),
+    })
+}
+
+fn parse_compact_body(body: &[u8]) -> RecordParseResult<Record> {
+    let (mut fields, pos) = parse_compact_fields(body)?;
+    fields.push(FieldValue::Description(parse_inline_string(&body[pos..])?));
     Record::try_from(fields).map_err(|e| RecordParseError {
         text: "failed to parse record".into(),
+        field: None,
         source: Some(e.into()),
     })
 }
 
-#[derive(Debug)]
-pub(crate) struct RecordBytes;
+fn is_table_entry(frame: &Frame) -> bool {
+    &frame.magic == VERSIONED_MAGIC
+        && frame.body.first() == Some(&DICTIONARY_VERSION)
+        && frame.body.get(1) == Some(&TABLE_ENTRY_FRAME)
+}
 
-impl RecordSerialize for RecordBytes {
+/// Parses a string table entry. Entries are defined in order,
+/// so the declared index must be the next free one
+fn parse_table_entry(body: &[u8], next_index: usize) -> RecordParseResult<String> {
+    let (index, pos) = get_varint(body).map_err(|e| RecordParseError {
+        text: "couldn't parse table index".into(),
+        field: None,
+        source: Some(Box::new(e)),
+    })?;
+    if index != next_index as u64 {
+        return Err(RecordParseError {
+            text: format!("unexpected table index {}. expected {}", index, next_index),
+            field: None,
+            source: None,
+        });
+    }
+    parse_inline_string(&body[pos..])
+}
+
+fn parse_dictionary_body(body: &[u8], table: &[String]) -> RecordParseResult<Record> {
+    let (mut fields, pos) = parse_compact_fields(body)?;
+    let description = match body.get(pos) {
+        Some(&INLINE_DESCRIPTION) => parse_inline_string(&body[pos + 1..])?,
+        Some(&TABLE_DESCRIPTION) => {
+            let (index, len) = get_varint(&body[pos + 1..]).map_err(|e| RecordParseError {
+                text: "couldn't parse description reference".into(),
+                field: None,
+                source: Some(Box::new(e)),
+            })?;
+            if pos + 1 + len != body.len() {
+                return Err(RecordParseError {
+                    text: "trailing bytes after description reference".into(),
+                    field: None,
+                    source: None,
+                });
+            }
+            table
+                .get(index as usize)
+                .ok_or(RecordParseError {
+                    text: format!("reference to undefined table index {}", index),
+                    field: None,
+                    source: None,
+                })?
+                .clone()
+        }
+        _ => {
+            return Err(RecordParseError {
+                text: "unknown description kind".into(),
+                field: None,
+                source: None,
+            });
+        }
+    };
+    fields.push(FieldValue::Description(description));
+    Record::try_from(fields).map_err(|e| RecordParseError {
+        text: "failed to parse record".into(),
+        field: None,
+        source: Some(e.into()),
+    })
+}
+
+/// Binary record encoding used by [`RecordBytes`]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) enum BinEncoding {
+    /// Fixed width big-endian fields under the YPBN magic
+    #[default]
+    Standard,
+    /// LEB128 varint fields under the versioned magic
+    Compact,
+    /// Fixed width little-endian fields under the versioned magic
+    LittleEndian,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct RecordBytes {
+    encoding: BinEncoding,
+}
+
+impl RecordBytes {
+    pub(crate) fn new(encoding: BinEncoding) -> RecordBytes {
+        RecordBytes { encoding }
+    }
+
+    fn serialize_compact(&self, record: &Record) -> Vec<u8> {
+        let mut body = vec![COMPACT_VERSION];
+        put_compact_fields(&mut body, record);
+        put_inline_string(&mut body, &record.description);
+        versioned_frame(body)
+    }
+}
+
+fn put_compact_fields(body: &mut Vec<u8>, record: &Record) {
+    put_varint(body, record.tx_id);
+    body.push(u8::from(&record.tx_type));
+    put_varint(body, record.from_user);
+    put_varint(body, record.to_user);
+    put_varint(body, record.amount);
+    put_varint(body, record.timestamp);
+    body.push(u8::from(&record.status));
+}
+
+fn put_inline_string(body: &mut Vec<u8>, value: &str) {
+    put_varint(body, value.len() as u64);
+    body.extend_from_slice(value.as_bytes());
+}
+
+fn versioned_frame(body: Vec<u8>) -> Vec<u8> {
+    let mut r = Vec::with_capacity(8 + body.len());
+    r.extend_from_slice(VERSIONED_MAGIC);
+    r.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    r.extend_from_slice(&body);
+    r
+}
+
+/// Count of descriptions seen once that are remembered for a second occurrence
+const MAX_SEEN_DESCRIPTIONS: usize = 4096;
+
+#[derive(Debug, Default)]
+struct DescriptionTable {
+    /// Descriptions seen once. Forgotten all at once when full,
+    /// so one-off descriptions don't grow it without bound
+    seen: HashSet<String>,
+    entries: HashMap<String, u64>,
+}
+
+/// Compact serializer that moves repeated descriptions into a string table.
+/// A description is written inline on its first occurrence. On the second one
+/// a table entry frame is emitted right before the record referencing it,
+/// so the output can be streamed without knowing all strings up front.
+/// Up to 4096 descriptions seen once are remembered, a description
+/// repeating only after more one-off ones than that stays inline
+/// until it occurs twice again
+#[derive(Debug, Default)]
+pub(crate) struct DictionaryRecordBytes {
+    table: RefCell<DescriptionTable>,
+}
+
+impl RecordSerialize for DictionaryRecordBytes {
     fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
-        let mut r = Vec::from("YPBN");
-        let desc_length = record.description.len() as u32;
-        r.extend_from_slice(&(46 + desc_length).to_be_bytes());
-        r.extend_from_slice(&record.tx_id.to_be_bytes());
-        r.push(u8::from(&record.tx_type));
-        r.extend_from_slice(&record.from_user.to_be_bytes());
-        r.extend_from_slice(&record.to_user.to_be_bytes());
-        r.extend_from_slice(&record.amount.to_be_bytes());
-        r.extend_from_slice(&record.timestamp.to_be_bytes());
-        r.push(u8::from(&record.status));
-        r.extend_from_slice(&(desc_length).to_be_bytes());
-        r.extend_from_slice(record.description.as_bytes());
+        let table = &mut *self.table.borrow_mut();
+        let mut r = Vec::new();
+        let mut body = vec![DICTIONARY_VERSION, RECORD_FRAME];
+        put_compact_fields(&mut body, record);
+        let index = match table.entries.get(&record.description).copied() {
+            Some(index) => Some(index),
+            None if table.seen.remove(&record.description) => {
+                let index = table.entries.len() as u64;
+                let mut entry = vec![DICTIONARY_VERSION, TABLE_ENTRY_FRAME];
+                put_varint(&mut entry, index);
+                put_inline_string(&mut entry, &record.description);
+                r.extend(versioned_frame(entry));
+                table.entries.insert(record.description.clone(), index);
+                Some(index)
+            }
+            None => {
+                if table.seen.len() == MAX_SEEN_DESCRIPTIONS {
+                    table.seen.clear();
+                }
+                table.seen.insert(record.description.clone());
+                None
+            }
+        };
+        match index {
+            Some(index) => {
+                body.push(TABLE_DESCRIPTION);
+                put_varint(&mut body, index);
+            }
+            None => {
+                body.push(INLINE_DESCRIPTION);
+                put_inline_string(&mut body, &record.description);
+            }
+        }
+        r.extend(versioned_frame(body));
         Ok(r)
     }
 }
 
+impl RecordSerialize for RecordBytes {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        match self.encoding {
+            BinEncoding::Standard => Ok(Frame {
+                magic: *MAGIC,
+                body: standard_body(record),
+            }
+            .to_bytes()),
+            BinEncoding::Compact => Ok(self.serialize_compact(record)),
+            BinEncoding::LittleEndian => Ok(versioned_frame(little_endian_body(record))),
+        }
+    }
+}
+
+/// Body of a YPBN frame without the checksum
+fn standard_body(record: &Record) -> Vec<u8> {
+    let desc_length = record.description.len() as u32;
+    let mut r = Vec::with_capacity(FIXED_BODY_LENGTH + record.description.len());
+    r.extend_from_slice(&record.tx_id.to_be_bytes());
+    r.push(u8::from(&record.tx_type));
+    r.extend_from_slice(&record.from_user.to_be_bytes());
+    r.extend_from_slice(&record.to_user.to_be_bytes());
+    r.extend_from_slice(&record.amount.to_be_bytes());
+    r.extend_from_slice(&record.timestamp.to_be_bytes());
+    r.push(u8::from(&record.status));
+    r.extend_from_slice(&desc_length.to_be_bytes());
+    r.extend_from_slice(record.description.as_bytes());
+    r
+}
+
+/// Body of a little-endian versioned frame, the YPBN layout after the version byte
+fn little_endian_body(record: &Record) -> Vec<u8> {
+    let desc_length = record.description.len() as u32;
+    let mut r = Vec::with_capacity(1 + FIXED_BODY_LENGTH + record.description.len());
+    r.push(LITTLE_ENDIAN_VERSION);
+    r.extend_from_slice(&record.tx_id.to_le_bytes());
+    r.push(u8::from(&record.tx_type));
+    r.extend_from_slice(&record.from_user.to_le_bytes());
+    r.extend_from_slice(&record.to_user.to_le_bytes());
+    r.extend_from_slice(&record.amount.to_le_bytes());
+    r.extend_from_slice(&record.timestamp.to_le_bytes());
+    r.push(u8::from(&record.status));
+    r.extend_from_slice(&desc_length.to_le_bytes());
+    r.extend_from_slice(record.description.as_bytes());
+    r
+}
+
+impl Record {
+    /// 64-bit FNV-1a hash of the big-endian binary encoding of the record
+    /// without the frame header and checksum. It doesn't depend on the format
+    /// the record was read from and is stable across runs and platforms
+    ///
+    /// ```
+    /// use parserde::{Record, Status, TxType};
+    /// let record = |amount| Record::new(1, TxType::Deposit, 0, 2, amount, 9, Status::Success, "".into());
+    /// assert_eq!(record(5).fingerprint(), record(5).fingerprint());
+    /// assert_ne!(record(5).fingerprint(), record(6).fingerprint());
+    /// ```
+    ///
+    pub fn fingerprint(&self) -> u64 {
+        standard_body(self)
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash: u64, &b| {
+                (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+
+    /// CRC-32 of the big-endian binary encoding of the record without
+    /// the frame header and checksum, the same value a checksummed bin frame
+    /// carries after the body
+    ///
+    /// ```
+    /// use parserde::{Record, Status, TxType};
+    /// let record = |amount| Record::new(1, TxType::Deposit, 0, 2, amount, 9, Status::Success, "".into());
+    /// assert_eq!(record(5).checksum(), record(5).checksum());
+    /// assert_ne!(record(5).checksum(), record(6).checksum());
+    /// ```
+    ///
+    pub fn checksum(&self) -> u32 {
+        crc32(&standard_body(self))
+    }
+}
+
 pub(crate) struct RecordWrite<W: Write> {
     writer: W,
 }
@@ -320,7 +1135,7 @@ mod tests {
             Status::Pending,
             "Description 2".into(),
         );
-        let bin_ser = RecordBytes;
+        let bin_ser = RecordBytes::default();
         let cursor = Cursor::new(
             [
                 bin_ser.serialize(&record1).unwrap(),
@@ -340,4 +1155,483 @@ mod tests {
         assert_eq!(record2_parsed, record2);
         assert!(bin_reader.read().is_none());
     }
+
+    fn get_spectrum_records() -> Vec<Record> {
+        [0, 127, 128, 1 << 32, u64::MAX]
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| {
+                Record::new(
+                    v,
+                    TxType::Transfer,
+                    v,
+                    v,
+                    v,
+                    v,
+                    Status::Failure,
+                    format!("Description {}", i),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compact_round_trip() {
+        let records = get_spectrum_records();
+        let ser = RecordBytes::new(BinEncoding::Compact);
+        let bytes = records
+            .iter()
+            .map(|r| ser.serialize(r).unwrap())
+            .collect::<Vec<_>>()
+            .concat();
+        let mut reader = BinReader::new(Cursor::new(bytes)).unwrap();
+        for record in records {
+            assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+        }
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_buf_capacity() {
+        let records = get_spectrum_records();
+        let ser = RecordBytes::default();
+        let bytes = records
+            .iter()
+            .map(|r| ser.serialize(r).unwrap())
+            .collect::<Vec<_>>()
+            .concat();
+        for capacity in [1, 7, 64, 1 << 20] {
+            let mut reader = BinReader::with_buf_capacity(Cursor::new(&bytes), capacity).unwrap();
+            assert_eq!(reader.buf_capacity(), capacity);
+            for record in &records {
+                assert_eq!(&reader.produce_record().unwrap().unwrap(), record);
+            }
+            assert!(reader.produce_record().is_none());
+        }
+        let reader = BinReader::new(Cursor::new(&bytes)).unwrap();
+        assert_eq!(reader.buf_capacity(), 8 * 1024);
+    }
+
+    #[test]
+    fn test_little_endian_round_trip() {
+        let records = get_spectrum_records();
+        let standard = RecordBytes::default();
+        let little_endian = RecordBytes::new(BinEncoding::LittleEndian);
+        let read = |serializers: &[&RecordBytes]| {
+            let bytes = records
+                .iter()
+                .zip(serializers.iter().cycle())
+                .map(|(r, ser)| ser.serialize(r).unwrap())
+                .collect::<Vec<_>>()
+                .concat();
+            crate::record::RecordIterator::new(BinReader::new(Cursor::new(bytes)).unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+        assert_eq!(read(&[&standard]), records);
+        assert_eq!(read(&[&little_endian]), records);
+        assert_eq!(read(&[&standard, &little_endian]), records);
+        assert_eq!(read(&[&little_endian, &standard]), records);
+
+        let bytes = little_endian.serialize(&records[4]).unwrap();
+        assert_eq!(&bytes[..4], VERSIONED_MAGIC);
+        assert_eq!(bytes[8], LITTLE_ENDIAN_VERSION);
+        assert_eq!(&bytes[9..17], &records[4].tx_id.to_le_bytes());
+        let desc_len = records[4].description.len() as u32;
+        assert_eq!(&bytes[51..55], &desc_len.to_le_bytes());
+        assert_eq!(bytes.len(), 55 + desc_len as usize);
+    }
+
+    #[test]
+    fn test_compact_size_savings() {
+        let standard = RecordBytes::default();
+        let compact = RecordBytes::new(BinEncoding::Compact);
+        let (mut standard_size, mut compact_size) = (0, 0);
+        for i in 0..1000u64 {
+            let record = Record::new(
+                1_000_000 + i,
+                TxType::Transfer,
+                i * 997 % 1_000_000,
+                i * 31 % 1_000_000,
+                i * 13 % 100_000,
+                1_633_036_860_000 + i * 60_000,
+                Status::Success,
+                "Monthly fee".into(),
+            );
+            standard_size += standard.serialize(&record).unwrap().len();
+            compact_size += compact.serialize(&record).unwrap().len();
+        }
+        // fixed fields shrink the most, the shared description stays as is
+        assert!(compact_size * 3 < standard_size * 2);
+    }
+
+    #[test]
+    fn test_malformed_varints() {
+        // overlong: zero encoded with two bytes
+        assert!(get_varint(&[0x80, 0x00]).is_err());
+        // wider than u64
+        assert!(get_varint(&[0xff; 10]).is_err());
+        assert!(get_varint(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02]).is_err());
+        // truncated
+        assert!(get_varint(&[0x80, 0x80]).is_err());
+        assert!(get_varint(&[]).is_err());
+        assert_eq!(
+            get_varint(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]).unwrap(),
+            (u64::MAX, 10)
+        );
+
+        let bytes = RecordBytes::new(BinEncoding::Compact)
+            .serialize(&get_spectrum_records()[4])
+            .unwrap();
+        let frame_len = bytes.len();
+        let mut truncated = bytes[..frame_len - 30].to_vec();
+        truncated[4..8].copy_from_slice(&((frame_len - 38) as u32).to_be_bytes());
+        let mut reader = BinReader::new(Cursor::new(truncated)).unwrap();
+        assert!(reader.produce_record().unwrap().is_err());
+    }
+
+    fn get_repeated_records() -> Vec<Record> {
+        let descriptions = ["Monthly fee", "Salary", "Monthly fee", "One-off", "Salary"];
+        (0..100u64)
+            .map(|i| {
+                Record::new(
+                    i,
+                    TxType::Deposit,
+                    0,
+                    i % 7,
+                    i * 10,
+                    1633036860000 + i,
+                    Status::Success,
+                    descriptions[i as usize % descriptions.len()].into(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_dictionary_round_trip() {
+        let records = get_repeated_records();
+        let ser = DictionaryRecordBytes::default();
+        let bytes = records
+            .iter()
+            .map(|r| ser.serialize(r).unwrap())
+            .collect::<Vec<_>>()
+            .concat();
+        let mut reader = BinReader::new(Cursor::new(bytes)).unwrap();
+        for record in &records {
+            assert_eq!(&reader.produce_record().unwrap().unwrap(), record);
+        }
+        assert!(reader.produce_record().is_none());
+        assert_eq!(reader.string_table.len(), 3);
+    }
+
+    #[test]
+    fn test_dictionary_seek() {
+        let records = get_repeated_records();
+        let ser = DictionaryRecordBytes::default();
+        let bytes = records
+            .iter()
+            .map(|r| ser.serialize(r).unwrap())
+            .collect::<Vec<_>>()
+            .concat();
+        let mut reader = BinReader::new(Cursor::new(bytes.clone())).unwrap();
+        for _ in 0..5 {
+            reader.produce_record().unwrap().unwrap();
+        }
+        let position = reader.position().unwrap();
+
+        let mut resumed = BinReader::new(Cursor::new(bytes.clone())).unwrap();
+        resumed.seek(position).unwrap();
+        assert_eq!(resumed.position(), Some(position));
+        for record in &records[5..] {
+            assert_eq!(&resumed.produce_record().unwrap().unwrap(), record);
+        }
+        assert!(resumed.produce_record().is_none());
+
+        let mut resumed = BinReader::new(Cursor::new(bytes)).unwrap();
+        let within_frame = Position {
+            byte: position.byte - 1,
+            ..position
+        };
+        assert!(resumed.seek(within_frame).is_err());
+    }
+
+    #[test]
+    fn test_dictionary_size_reduction() {
+        let records = get_repeated_records();
+        let compact = RecordBytes::new(BinEncoding::Compact);
+        let dictionary = DictionaryRecordBytes::default();
+        let compact_size: usize = records
+            .iter()
+            .map(|r| compact.serialize(r).unwrap().len())
+            .sum();
+        let dictionary_size: usize = records
+            .iter()
+            .map(|r| dictionary.serialize(r).unwrap().len())
+            .sum();
+        assert!(dictionary_size < compact_size);
+    }
+
+    #[test]
+    fn test_dictionary_seen_descriptions_bounded() {
+        let ser = DictionaryRecordBytes::default();
+        let mut record = get_repeated_records().remove(0);
+        for i in 0..MAX_SEEN_DESCRIPTIONS * 3 {
+            record.description = format!("one-off {}", i);
+            ser.serialize(&record).unwrap();
+            assert!(ser.table.borrow().seen.len() <= MAX_SEEN_DESCRIPTIONS);
+        }
+        assert!(ser.table.borrow().entries.is_empty());
+
+        // repeats within the window still go to the table
+        record.description = "Monthly fee".into();
+        ser.serialize(&record).unwrap();
+        let bytes = ser.serialize(&record).unwrap();
+        assert_eq!(ser.table.borrow().entries.len(), 1);
+        let mut reader = BinReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+    }
+
+    #[test]
+    fn test_dictionary_undefined_index() {
+        let record = &get_repeated_records()[0];
+        let mut body = vec![DICTIONARY_VERSION, RECORD_FRAME];
+        put_compact_fields(&mut body, record);
+        body.push(TABLE_DESCRIPTION);
+        put_varint(&mut body, 5);
+        let mut reader = BinReader::new(Cursor::new(versioned_frame(body))).unwrap();
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert!(err.to_string().contains("undefined table index 5"));
+    }
+
+    #[test]
+    fn test_truncated_frame() {
+        let bytes = RecordBytes::default()
+            .serialize(&get_repeated_records()[0])
+            .unwrap();
+        for len in [3, 8, bytes.len() - 1] {
+            let mut reader = BinReader::new(Cursor::new(bytes[..len].to_vec())).unwrap();
+            assert!(reader.produce_record().unwrap().is_err());
+            assert!(reader.produce_record().is_none());
+        }
+    }
+
+    #[test]
+    fn test_empty_description() {
+        let record = Record::new(3, TxType::Deposit, 0, 1, 10, 5, Status::Success, "".into());
+        for encoding in [BinEncoding::Standard, BinEncoding::Compact] {
+            let bytes = RecordBytes::new(encoding).serialize(&record).unwrap();
+            let mut reader = BinReader::new(Cursor::new(bytes.clone())).unwrap();
+            assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+            if encoding == BinEncoding::Standard {
+                assert_eq!(bytes.len(), 8 + 46 + CHECKSUM_LENGTH);
+                assert_eq!(bytes[8 + 42..8 + 46], [0; 4]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_description_length_mismatch() {
+        let bytes = RecordBytes::default()
+            .serialize(&get_spectrum_records()[0])
+            .unwrap();
+        let body = bytes[8..bytes.len() - CHECKSUM_LENGTH].to_vec();
+        let actual = (body.len() - 46) as u32;
+        assert!(parse_body(MAGIC, &body, &[]).is_ok());
+        for declared in [actual - 3, actual + 3] {
+            let mut body = body.clone();
+            body[42..46].copy_from_slice(&declared.to_be_bytes());
+            let e = parse_body(MAGIC, &body, &[]).unwrap_err();
+            assert!(
+                e.to_string().contains(&format!(
+                    "declared description length {} doesn't match the {} bytes left",
+                    declared, actual
+                )),
+                "{}",
+                e
+            );
+        }
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        let record = &get_spectrum_records()[0];
+        let bytes = RecordBytes::default().serialize(record).unwrap();
+        let mut reader = BinReader::new(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(&reader.produce_record().unwrap().unwrap(), record);
+
+        let mut flipped = bytes.clone();
+        flipped[12] ^= 0x01;
+        let mut reader = BinReader::new(Cursor::new(flipped)).unwrap();
+        let e = reader.produce_record().unwrap().unwrap_err();
+        assert_eq!(e.source().unwrap().to_string(), "checksum mismatch");
+
+        let mut legacy = bytes[..bytes.len() - CHECKSUM_LENGTH].to_vec();
+        let body_size = (legacy.len() - 8) as u32;
+        legacy[4..8].copy_from_slice(&body_size.to_be_bytes());
+        let mut reader = BinReader::new_no_checksum(Cursor::new(legacy.clone())).unwrap();
+        assert_eq!(&reader.produce_record().unwrap().unwrap(), record);
+        let mut reader =
+            BinReader::with_buf_capacity_no_checksum(Cursor::new(legacy.clone()), 5).unwrap();
+        assert_eq!(reader.buf_capacity(), 5);
+        assert_eq!(&reader.produce_record().unwrap().unwrap(), record);
+        let mut reader = BinReader::new(Cursor::new(legacy)).unwrap();
+        assert!(reader.produce_record().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_new_checks_magic() {
+        let csv = std::fs::File::open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/source/records_example.csv"
+        ))
+        .unwrap();
+        let e = BinReader::new(csv).err().unwrap();
+        assert_eq!(
+            e.to_string(),
+            "input is not in bin format. expected a record magic, found bytes 54 58 5f 49. source none"
+        );
+
+        let bin = std::fs::File::open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/source/records_example.bin"
+        ))
+        .unwrap();
+        let mut reader = BinReader::new(bin).unwrap();
+        assert_eq!(
+            reader.produce_record().unwrap().unwrap().tx_id,
+            1000000000000000
+        );
+
+        let mut reader = BinReader::new(Cursor::new(Vec::new())).unwrap();
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_invalid_magic() {
+        let bytes = RecordBytes::default()
+            .serialize(&get_repeated_records()[0])
+            .unwrap();
+        let mut input = bytes.clone();
+        input.extend_from_slice(b"XXXX");
+        input.extend_from_slice(&bytes[4..]);
+        let mut reader = BinReader::new(Cursor::new(input)).unwrap();
+        assert!(reader.produce_record().unwrap().is_ok());
+        let e = reader.produce_record().unwrap().unwrap_err();
+        assert_eq!(
+            e.source().unwrap().to_string(),
+            "invalid magic bytes, expected YPBN or YPB2"
+        );
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_malformed_lengths() {
+        let mut input = Vec::from(MAGIC);
+        input.extend_from_slice(&u32::MAX.to_be_bytes());
+        let mut reader = BinReader::new(Cursor::new(input)).unwrap();
+        let e = reader.produce_record().unwrap().unwrap_err();
+        assert!(
+            e.source()
+                .unwrap()
+                .to_string()
+                .contains("greater than the max limit"),
+            "{}",
+            e
+        );
+        assert!(reader.produce_record().is_none());
+
+        let Err(e) = Field::new(FieldName::TxType, &[][..]).parse() else {
+            panic!("empty tx_type parsed");
+        };
+        assert_eq!(e.text, "tx_type takes 1 byte, found 0");
+        assert!(Field::new(FieldName::Status, &[0, 1][..]).parse().is_err());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let record = Record::new(
+            7,
+            TxType::Transfer,
+            1,
+            2,
+            300,
+            1633036860000,
+            Status::Pending,
+            "Record number 7".into(),
+        );
+        let csv = format!(
+            "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n{}",
+            String::from_utf8(
+                crate::builder::build_serializer("csv")
+                    .unwrap()
+                    .serialize(&record)
+                    .unwrap()
+            )
+            .unwrap()
+        );
+        let from_csv = crate::builder::build_reader(Cursor::new(csv), "csv")
+            .unwrap()
+            .produce_record()
+            .unwrap()
+            .unwrap();
+        let bytes = RecordBytes::default().serialize(&record).unwrap();
+        let from_bin = BinReader::new(Cursor::new(bytes))
+            .unwrap()
+            .produce_record()
+            .unwrap()
+            .unwrap();
+        assert_eq!(from_csv.fingerprint(), from_bin.fingerprint());
+        assert_eq!(from_bin.fingerprint(), record.fingerprint());
+        assert_eq!(record.fingerprint(), 2045298349041434942);
+
+        let mut changed = record.clone();
+        changed.description.push('!');
+        assert_ne!(changed.fingerprint(), record.fingerprint());
+        changed = record.clone();
+        changed.status = Status::Success;
+        assert_ne!(changed.fingerprint(), record.fingerprint());
+    }
+
+    #[test]
+    fn test_record_checksum() {
+        let record = || {
+            Record::new(
+                7,
+                TxType::Transfer,
+                1,
+                2,
+                300,
+                1633036860000,
+                Status::Pending,
+                "Record number 7".into(),
+            )
+        };
+        assert_eq!(record().checksum(), record().checksum());
+        let bytes = RecordBytes::default().serialize(&record()).unwrap();
+        let trailer = try_u32_from_bytes(&bytes[bytes.len() - CHECKSUM_LENGTH..]).unwrap();
+        assert_eq!(record().checksum(), trailer);
+
+        let changes: [fn(&mut Record); 8] = [
+            |r| r.tx_id += 1,
+            |r| r.tx_type = TxType::Deposit,
+            |r| r.from_user += 1,
+            |r| r.to_user += 1,
+            |r| r.amount += 1,
+            |r| r.timestamp += 1,
+            |r| r.status = Status::Failure,
+            |r| r.description.push('!'),
+        ];
+        for change in changes {
+            let mut changed = record();
+            change(&mut changed);
+            assert_ne!(changed.checksum(), record().checksum(), "{:?}", changed);
+        }
+
+        let mut counts = std::collections::HashMap::new();
+        *counts.entry(record()).or_insert(0) += 1;
+        *counts.entry(record()).or_insert(0) += 1;
+        assert_eq!(counts[&record()], 2);
+    }
 }
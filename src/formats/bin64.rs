@@ -0,0 +1,328 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::bin::{BinReader, RecordBytes};
+use crate::error::{RecordProduceError, RecordProduceErrorKind, RecordReadError, RecordWriteError};
+use crate::record::{DataConsumer, DataProducer, Position, Record, RecordSerialize, RecordWriter};
+use crate::result::{
+    RecordProduceResult, RecordReadResult, RecordSerializeResult, RecordWriteResult,
+};
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 with padding
+fn encode(bytes: &[u8]) -> String {
+    let mut r = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                r.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                r.push('=');
+            }
+        }
+    }
+    r
+}
+
+/// Decodes standard base64 with padding. The error names the offending character
+fn decode(text: &[u8]) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(4) {
+        return Err(format!(
+            "length {} is not a multiple of 4, the line is truncated",
+            text.len()
+        ));
+    }
+    let padding = text.iter().rev().take_while(|&&c| c == b'=').count();
+    if padding > 2 {
+        return Err("too much padding".into());
+    }
+    let mut r = Vec::with_capacity(text.len() / 4 * 3);
+    for (i, chunk) in text.chunks(4).enumerate() {
+        let mut n = 0u32;
+        for (j, &c) in chunk.iter().enumerate() {
+            let position = i * 4 + j;
+            let value = match c {
+                b'=' if position >= text.len() - padding => 0,
+                _ => match ALPHABET.iter().position(|&a| a == c) {
+                    Some(value) => value as u32,
+                    None => {
+                        return Err(format!(
+                            "invalid character {:?} at column {}",
+                            c as char,
+                            position + 1
+                        ));
+                    }
+                },
+            };
+            n = n << 6 | value;
+        }
+        r.extend_from_slice(&n.to_be_bytes()[1..]);
+    }
+    r.truncate(r.len() - padding);
+    Ok(r)
+}
+
+/// Reader of the bin64 format, one base64 encoded bin frame per line.
+/// Blank lines and lines starting with `#` are skipped
+pub(crate) struct Bin64Reader<T: Read> {
+    reader: BufReader<T>,
+    current_line: u64,
+    offset: u64,
+    records: u64,
+    is_exhausted: bool,
+}
+
+impl<T: Read> Bin64Reader<T> {
+    pub(crate) fn new(reader: T) -> Bin64Reader<T> {
+        Bin64Reader {
+            reader: BufReader::new(reader),
+            current_line: 0,
+            offset: 0,
+            records: 0,
+            is_exhausted: false,
+        }
+    }
+
+    fn error(&self, text: String) -> RecordProduceError {
+        RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!("{}. near line {}", text, self.current_line),
+            source: None,
+        }
+    }
+
+    fn parse_line(&self, line: &[u8]) -> RecordProduceResult<Record> {
+        let frame = decode(line).map_err(|e| self.error(format!("invalid base64: {}", e)))?;
+        let mut frames = BinReader::new(&frame[..]).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!("invalid bin frame. near line {}", self.current_line),
+            source: Some(e.to_string().into()),
+        })?;
+        let record = match frames.produce_record() {
+            Some(record) => record.map_err(|e| RecordProduceError {
+                // the frame is in memory, so a short read means a short frame
+                kind: match e.kind() {
+                    RecordProduceErrorKind::Io => RecordProduceErrorKind::MalformedRecord,
+                    kind => kind,
+                },
+                text: format!("failed to parse record. near line {}", self.current_line),
+                source: Some(Box::new(e)),
+            })?,
+            None => return Err(self.error("empty bin frame".into())),
+        };
+        if frames.read().is_some() {
+            return Err(self.error("trailing data after the bin frame".into()));
+        }
+        Ok(record)
+    }
+}
+
+impl<T: Read> DataConsumer for Bin64Reader<T> {
+    type Item = Vec<u8>;
+    fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
+        if self.is_exhausted {
+            return None;
+        }
+        loop {
+            let mut buf = Vec::new();
+            let bytes_read = match self.reader.read_until(b'\n', &mut buf) {
+                Ok(count) => count,
+                Err(e) => {
+                    return Some(Err(RecordReadError {
+                        text: "couldn't read data".to_string(),
+                        source: Some(Box::new(e)),
+                    }));
+                }
+            };
+            if bytes_read == 0 {
+                self.is_exhausted = true;
+                return None;
+            }
+            self.current_line += 1;
+            self.offset += bytes_read as u64;
+            let line = buf.trim_ascii();
+            if !line.is_empty() && !line.starts_with(b"#") {
+                return Some(Ok(line.to_vec()));
+            }
+        }
+    }
+}
+
+impl<T: Read> DataProducer for Bin64Reader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let line = match self.read()? {
+            Ok(line) => line,
+            Err(e) => {
+                return Some(Err(RecordProduceError {
+                    kind: e.kind(),
+                    text: format!("failed to read line {}", self.current_line + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        self.records += 1;
+        Some(self.parse_line(&line))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: self.current_line,
+            record: self.records,
+        })
+    }
+}
+
+/// Serializes a record as its bin frame in base64
+#[derive(Default)]
+pub(crate) struct Bin64Serialize {
+    bytes: RecordBytes,
+}
+
+impl RecordSerialize for Bin64Serialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        Ok(encode(&self.bytes.serialize(record)?).into_bytes())
+    }
+}
+
+/// Writes one serialized record per line
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W) -> RecordWrite<W> {
+        RecordWrite { writer }
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(line)
+            .and_then(|_| self.writer.write_all(b"\n"))
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write_comment(&mut self, text: &str) -> RecordWriteResult<()> {
+        for line in text.lines() {
+            self.write_line(format!("# {}", line).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.write_line(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::record::{RecordIterator, Status, TxType};
+
+    fn records() -> Vec<Record> {
+        (0..3)
+            .map(|i| {
+                Record::new(
+                    i,
+                    TxType::Withdrawal,
+                    u64::MAX - i,
+                    0,
+                    100 * i,
+                    1633036860000 + i,
+                    Status::Failure,
+                    "é".repeat(i as usize),
+                )
+            })
+            .collect()
+    }
+
+    fn encode_records(records: &[Record]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut writer = RecordWrite::new(&mut output);
+        writer.write_comment("exported records").unwrap();
+        for record in records {
+            writer
+                .write(Bin64Serialize::default().serialize(record).unwrap())
+                .unwrap();
+        }
+        output
+    }
+
+    #[test]
+    fn test_base64() {
+        for (bytes, text) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xff\xfe\x00", "//4A"),
+        ] {
+            assert_eq!(encode(bytes), text);
+            assert_eq!(decode(text.as_bytes()).unwrap(), bytes);
+        }
+        for text in ["Zg=", "Z===", "Zg=a", "Zm9v!A==", "=Zm9"] {
+            assert!(decode(text.as_bytes()).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut data = encode_records(&records());
+        data.extend(b"\n  \r\n# trailing comment\n");
+        let read = RecordIterator::new(Bin64Reader::new(Cursor::new(data)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, records());
+    }
+
+    #[test]
+    fn test_truncated_line() {
+        let data = String::from_utf8(encode_records(&records())).unwrap();
+        let mut lines = data.lines().map(str::to_string).collect::<Vec<_>>();
+        let length = lines[2].len() - 5;
+        lines[2].truncate(length);
+        let mut reader = Bin64Reader::new(Cursor::new(lines.join("\n")));
+        assert_eq!(reader.produce_record().unwrap().unwrap(), records()[0]);
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert_eq!(err.kind(), RecordProduceErrorKind::MalformedRecord);
+        assert!(err.to_string().contains("near line 3"), "{}", err);
+        // the next line is read as usual
+        assert_eq!(reader.produce_record().unwrap().unwrap(), records()[2]);
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_invalid_frames() {
+        let frame = RecordBytes::default().serialize(&records()[1]).unwrap();
+        let mut corrupted = frame.clone();
+        corrupted[20] ^= 1;
+        let lines = [
+            encode(&frame[..frame.len() - 4]),
+            encode(&corrupted),
+            encode(&[frame.clone(), frame].concat()),
+            encode(b"hello"),
+        ];
+        let mut reader = Bin64Reader::new(Cursor::new(lines.join("\n")));
+        for line in 1..=lines.len() {
+            let err = reader.produce_record().unwrap().unwrap_err();
+            assert_eq!(err.kind(), RecordProduceErrorKind::MalformedRecord);
+            assert!(err.to_string().contains(&format!("near line {}", line)));
+        }
+        assert!(reader.produce_record().is_none());
+    }
+}
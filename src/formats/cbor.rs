@@ -0,0 +1,506 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::error::{RecordProduceError, RecordProduceErrorKind, RecordReadError, RecordWriteError};
+use crate::record::{
+    DataConsumer, DataProducer, FieldName, FieldValue, Position, Record, RecordSerialize,
+    RecordWriter, Status, TxType,
+};
+use crate::result::{
+    RecordProduceResult, RecordReadResult, RecordSerializeResult, RecordWriteResult,
+};
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_SIMPLE: u8 = 7;
+const NULL: u64 = 22;
+const INDEFINITE: u8 = 31;
+
+/// Value of a map entry. Only the types records are made of are supported
+#[derive(Debug, PartialEq)]
+pub(crate) enum CborValue {
+    Uint(u64),
+    Text(String),
+    Null,
+}
+
+/// Reader of the cbor format, a sequence of definite-length maps.
+/// Keys are the field names as text, the enums are their byte codes.
+/// The stream ends cleanly only between two maps
+pub(crate) struct CborReader<T: Read> {
+    reader: BufReader<T>,
+    offset: u64,
+    records: u64,
+    is_exhausted: bool,
+}
+
+impl<T: Read> CborReader<T> {
+    pub(crate) fn new(reader: T) -> CborReader<T> {
+        CborReader {
+            reader: BufReader::new(reader),
+            offset: 0,
+            records: 0,
+            is_exhausted: false,
+        }
+    }
+
+    fn error(&self, text: String) -> RecordProduceError {
+        RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!("{}. record {}", text, self.records),
+            source: None,
+        }
+    }
+
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), String> {
+        self.reader.read_exact(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => format!("truncated map at byte {}", self.offset),
+            _ => format!("couldn't read data at byte {}: {}", self.offset, e),
+        })?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Major type and argument of the next item head
+    fn read_head(&mut self) -> Result<(u8, u64), String> {
+        let mut initial = [0u8; 1];
+        self.read_bytes(&mut initial)?;
+        let (major, info) = (initial[0] >> 5, initial[0] & 0x1f);
+        let length = match info {
+            0..24 => return Ok((major, info as u64)),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            INDEFINITE => {
+                return Err(format!(
+                    "indefinite-length items are not supported, found at byte {}",
+                    self.offset - 1
+                ));
+            }
+            _ => {
+                return Err(format!(
+                    "reserved additional info {} at byte {}",
+                    info,
+                    self.offset - 1
+                ));
+            }
+        };
+        let mut argument = [0u8; 8];
+        self.read_bytes(&mut argument[8 - length..])?;
+        Ok((major, u64::from_be_bytes(argument)))
+    }
+
+    fn read_text(&mut self, length: u64) -> Result<String, String> {
+        let start = self.offset;
+        // the length isn't trusted for the allocation
+        let mut text = Vec::new();
+        let count = (&mut self.reader)
+            .take(length)
+            .read_to_end(&mut text)
+            .map_err(|e| format!("couldn't read data at byte {}: {}", start, e))?;
+        self.offset += count as u64;
+        if (count as u64) < length {
+            return Err(format!("truncated map at byte {}", self.offset));
+        }
+        String::from_utf8(text).map_err(|_| format!("invalid utf-8 text at byte {}", start))
+    }
+
+    fn read_map(&mut self) -> Result<Vec<(String, CborValue)>, String> {
+        let start = self.offset;
+        let (major, length) = self.read_head()?;
+        if major != MAJOR_MAP {
+            return Err(format!(
+                "trailing data of major type {} at byte {}, expected a map",
+                major, start
+            ));
+        }
+        let mut entries = Vec::new();
+        for _ in 0..length {
+            let key = match self.read_head()? {
+                (MAJOR_TEXT, length) => self.read_text(length)?,
+                (major, _) => {
+                    return Err(format!(
+                        "keys must be text, found major type {} at byte {}",
+                        major, self.offset
+                    ));
+                }
+            };
+            let value = match self.read_head()? {
+                (MAJOR_UINT, number) => CborValue::Uint(number),
+                (MAJOR_TEXT, length) => CborValue::Text(self.read_text(length)?),
+                (MAJOR_SIMPLE, NULL) => CborValue::Null,
+                (major, _) => {
+                    return Err(format!(
+                        "unsupported value of major type {} for key {}",
+                        major, key
+                    ));
+                }
+            };
+            entries.push((key, value));
+        }
+        Ok(entries)
+    }
+
+    fn parse_entries(&self, entries: Vec<(String, CborValue)>) -> RecordProduceResult<Record> {
+        let mut values: Vec<FieldValue> = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let name = key
+                .parse::<FieldName>()
+                .map_err(|_| self.error(format!("unknown field {}", key)))?;
+            if values.iter().any(|v| v.field() == name) {
+                return Err(self.error(format!("duplicate field {}", name.as_str())));
+            }
+            let invalid = || self.error(format!("invalid value of {}", name.as_str()));
+            let value = match (name, value) {
+                (_, CborValue::Null) => continue,
+                (FieldName::Description, CborValue::Text(text)) => FieldValue::Description(text),
+                (FieldName::TxType | FieldName::Status, CborValue::Uint(code)) => {
+                    let code = u8::try_from(code).map_err(|_| invalid())?;
+                    let parsed = if name == FieldName::TxType {
+                        TxType::try_from(&code).map(FieldValue::TxType)
+                    } else {
+                        Status::try_from(&code).map(FieldValue::Status)
+                    };
+                    parsed.map_err(|e| RecordProduceError {
+                        kind: RecordProduceErrorKind::ParseField {
+                            field_name: name.as_str().into(),
+                        },
+                        text: format!("failed to parse field. record {}", self.records),
+                        source: Some(e.into()),
+                    })?
+                }
+                (FieldName::TxId, CborValue::Uint(n)) => FieldValue::TxId(n),
+                (FieldName::FromUser, CborValue::Uint(n)) => FieldValue::FromUser(n),
+                (FieldName::ToUser, CborValue::Uint(n)) => FieldValue::ToUser(n),
+                (FieldName::Amount, CborValue::Uint(n)) => FieldValue::Amount(n),
+                (FieldName::Timestamp, CborValue::Uint(n)) => FieldValue::Timestamp(n),
+                _ => return Err(invalid()),
+            };
+            values.push(value);
+        }
+        Record::try_from(values).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!("failed to parse record {}", self.records),
+            source: Some(e.into()),
+        })
+    }
+}
+
+impl<T: Read> DataConsumer for CborReader<T> {
+    type Item = Vec<(String, CborValue)>;
+    fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
+        if self.is_exhausted {
+            return None;
+        }
+        match self.reader.fill_buf() {
+            Ok([]) => {
+                self.is_exhausted = true;
+                return None;
+            }
+            Ok(_) => (),
+            Err(e) => {
+                self.is_exhausted = true;
+                return Some(Err(RecordReadError {
+                    text: "couldn't read data".into(),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        }
+        Some(self.read_map().map_err(|text| {
+            // the rest of the stream can't be aligned to maps
+            self.is_exhausted = true;
+            RecordReadError { text, source: None }
+        }))
+    }
+}
+
+impl<T: Read> DataProducer for CborReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let entries = match self.read()? {
+            Ok(entries) => entries,
+            Err(e) => {
+                return Some(Err(RecordProduceError {
+                    kind: e.kind(),
+                    text: format!("failed to read record {}", self.records + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        self.records += 1;
+        Some(self.parse_entries(entries))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: 0,
+            record: self.records,
+        })
+    }
+}
+
+/// Head of an item in its shortest encoding
+fn put_head(data: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+    match argument {
+        0..24 => data.push(major | argument as u8),
+        24..=0xff => data.extend([major | 24, argument as u8]),
+        0x100..=0xffff => {
+            data.push(major | 25);
+            data.extend((argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            data.push(major | 26);
+            data.extend((argument as u32).to_be_bytes());
+        }
+        _ => {
+            data.push(major | 27);
+            data.extend(argument.to_be_bytes());
+        }
+    }
+}
+
+fn put_text(data: &mut Vec<u8>, text: &str) {
+    put_head(data, MAJOR_TEXT, text.len() as u64);
+    data.extend_from_slice(text.as_bytes());
+}
+
+/// Serializes a record as a definite-length map keyed by the field names
+#[derive(Default)]
+pub(crate) struct CborSerialize;
+
+impl RecordSerialize for CborSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let fields = [
+            (FieldName::TxId, record.tx_id),
+            (FieldName::TxType, u8::from(&record.tx_type) as u64),
+            (FieldName::FromUser, record.from_user),
+            (FieldName::ToUser, record.to_user),
+            (FieldName::Amount, record.amount),
+            (FieldName::Timestamp, record.timestamp),
+            (FieldName::Status, u8::from(&record.status) as u64),
+        ];
+        let mut data = Vec::with_capacity(128 + record.description.len());
+        put_head(&mut data, MAJOR_MAP, fields.len() as u64 + 1);
+        for (name, value) in fields {
+            put_text(&mut data, name.as_str());
+            put_head(&mut data, MAJOR_UINT, value);
+        }
+        put_text(&mut data, FieldName::Description.as_str());
+        put_text(&mut data, &record.description);
+        Ok(data)
+    }
+}
+
+/// Writes serialized records one after another
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W) -> RecordWrite<W> {
+        RecordWrite { writer }
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(&data)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::formats::testing::{Codec, records};
+
+    fn codec() -> Codec<CborReader<Cursor<Vec<u8>>>> {
+        Codec {
+            serializer: &CborSerialize,
+            writer: |output| Box::new(RecordWrite::new(output)),
+            reader: CborReader::new,
+        }
+    }
+
+    /// Map of the first fixture record with the entry of the key
+    /// replaced by the raw key and value
+    fn map_with(key: &str, entry: &[u8]) -> Vec<u8> {
+        let record = &records()[0];
+        let mut data = Vec::new();
+        put_head(&mut data, MAJOR_MAP, 8);
+        for name in FieldName::ALL {
+            if name.as_str() == key {
+                data.extend_from_slice(entry);
+                continue;
+            }
+            put_text(&mut data, name.as_str());
+            match name {
+                FieldName::TxId => put_head(&mut data, MAJOR_UINT, record.tx_id),
+                FieldName::TxType => put_head(&mut data, MAJOR_UINT, 2),
+                FieldName::FromUser => put_head(&mut data, MAJOR_UINT, record.from_user),
+                FieldName::ToUser => put_head(&mut data, MAJOR_UINT, record.to_user),
+                FieldName::Amount => put_head(&mut data, MAJOR_UINT, record.amount),
+                FieldName::Timestamp => put_head(&mut data, MAJOR_UINT, record.timestamp),
+                FieldName::Status => put_head(&mut data, MAJOR_UINT, 1),
+                FieldName::Description => put_text(&mut data, &record.description),
+            }
+        }
+        data
+    }
+
+    /// Checks the data following a valid record fails to read with the message
+    /// and ends the stream, which can't be aligned to maps after it
+    fn assert_read_error(tail: &[u8], message: &str) {
+        let mut data = codec().encode(&records()[..1]);
+        data.extend_from_slice(tail);
+        let mut reader = codec().read(data);
+        assert!(reader.produce_record().unwrap().is_ok());
+        let err = reader.produce_record().unwrap().unwrap_err();
+        let source = std::error::Error::source(&err).unwrap().to_string();
+        assert!(source.contains(message), "{:x?}: {}", tail, source);
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let err = codec().assert_round_trip();
+        let source = std::error::Error::source(&err).unwrap().to_string();
+        assert!(source.contains("truncated map"), "{}", source);
+    }
+
+    #[test]
+    fn test_encoding() {
+        let record = Record::new(
+            23,
+            TxType::Deposit,
+            24,
+            255,
+            256,
+            65536,
+            Status::Success,
+            String::new(),
+        );
+        let data = CborSerialize.serialize(&record).unwrap();
+        // map(8), "TX_ID", 23
+        assert_eq!(&data[..8], b"\xa8\x65TX_ID\x17");
+        let mut expected = vec![0x67];
+        expected.extend(b"TX_TYPE");
+        expected.push(0x00);
+        assert_eq!(&data[8..17], &expected[..]);
+        // the arguments take the fewest bytes
+        for (value, head) in [
+            (24, &b"\x18\x18"[..]),
+            (255, b"\x18\xff"),
+            (256, b"\x19\x01\x00"),
+            (65536, b"\x1a\x00\x01\x00\x00"),
+        ] {
+            assert!(data.windows(head.len()).any(|w| w == head), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_long_heads() {
+        // heads longer than needed are valid cbor
+        let mut entry = b"\x65TX_ID\x1b".to_vec();
+        entry.extend(u64::MAX.to_be_bytes());
+        let mut data = map_with("TX_ID", &entry);
+        data.extend(map_with("TX_TYPE", b"\x67TX_TYPE\x19\x00\x02"));
+        let mut reader = codec().read(data);
+        for _ in 0..2 {
+            assert_eq!(reader.produce_record().unwrap().unwrap(), records()[0]);
+        }
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_indefinite_length_items() {
+        let message = "indefinite-length items are not supported";
+        // map, text key and text value
+        assert_read_error(b"\xbf\x61a\x01\xff", message);
+        assert_read_error(b"\xa1\x7f\x61a\xff\x01", message);
+        assert_read_error(
+            &map_with("DESCRIPTION", b"\x6bDESCRIPTION\x7f\x61a\xff"),
+            message,
+        );
+        // indefinite byte strings and arrays aren't values of records either
+        assert_read_error(
+            &map_with("DESCRIPTION", b"\x6bDESCRIPTION\x5f\xff"),
+            message,
+        );
+        assert_read_error(&map_with("TX_ID", b"\x65TX_ID\x9f\xff"), message);
+    }
+
+    #[test]
+    fn test_tags() {
+        // self-described cbor
+        assert_read_error(b"\xd9\xd9\xf7\xa0", "trailing data of major type 6");
+        // an epoch timestamp
+        assert_read_error(
+            &map_with("TIMESTAMP", b"\x69TIMESTAMP\xc1\x1a\x61\x56\x4f\x00"),
+            "unsupported value of major type 6 for key TIMESTAMP",
+        );
+        assert_read_error(
+            b"\xa1\xc0\x61a\x01",
+            "keys must be text, found major type 6",
+        );
+    }
+
+    #[test]
+    fn test_unsupported_major_types() {
+        for (entry, major) in [
+            (&b"\x66AMOUNT\x20"[..], 1),
+            (b"\x66AMOUNT\x41\x01", 2),
+            (b"\x66AMOUNT\x81\x01", 4),
+            (b"\x66AMOUNT\xa0", 5),
+            (b"\x66AMOUNT\xf5", 7),
+            (b"\x66AMOUNT\xfb\x3f\xf8\x00\x00\x00\x00\x00\x00", 7),
+        ] {
+            assert_read_error(
+                &map_with("AMOUNT", entry),
+                &format!("unsupported value of major type {} for key AMOUNT", major),
+            );
+        }
+        assert_read_error(b"\x00", "trailing data of major type 0");
+        assert_read_error(b"\x1c", "reserved additional info 28");
+        // null is the one simple value read, as a missing field
+        let mut reader = codec().read(map_with("AMOUNT", b"\x66AMOUNT\xf6"));
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            RecordProduceErrorKind::MissingField { .. }
+        ));
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let map = |entries: &[(&str, &[u8])]| {
+            let mut data = Vec::new();
+            put_head(&mut data, MAJOR_MAP, entries.len() as u64);
+            for (key, value) in entries {
+                put_text(&mut data, key);
+                data.extend_from_slice(value);
+            }
+            data
+        };
+        for entries in [
+            map(&[("NAME", b"\x01")]),
+            map(&[("TX_ID", b"\x01"), ("TX_ID", b"\x02")]),
+            map(&[("TX_ID", b"\x61a")]),
+            map(&[("TX_TYPE", b"\x07")]),
+            map(&[("STATUS", b"\x19\x01\x00")]),
+            map(&[("TX_ID", b"\x01")]),
+        ] {
+            // a map of a wrong layout doesn't break the stream
+            codec().assert_rejected_then_recovers(&entries);
+        }
+    }
+}
@@ -1,33 +1,136 @@
 use std::{
+    borrow::Cow,
     error::Error,
-    io::{Read, Write},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, Write},
+    path::Path,
 };
 
-use csv::{Reader, ReaderBuilder, StringRecord};
+use csv::{Reader, ReaderBuilder, StringRecord, Terminator, WriterBuilder};
 
 use crate::{
     error::RecordWriteError,
-    record::{DataConsumer, DataProducer, Field, Record, RecordSerialize, RecordWriter, fields},
+    record::{
+        DataConsumer, DataProducer, Field, FieldName, FieldValue, PartialDataProducer,
+        PartialRecord, Position, RawDataProducer, RawRecord, RawRepr, Record, RecordSerialize,
+        RecordWriter, resolve_field, with_defaults,
+    },
 };
 
-use crate::error::{RecordProduceError, RecordReadError, RecordSerializeError};
-use crate::result::{RecordProduceResult, RecordReadResult, RecordSerializeResult};
+use crate::derived::DerivedColumn;
+use crate::error::{
+    RecordProduceError, RecordProduceErrorKind, RecordReadError, RecordSerializeError,
+};
+use crate::options::{
+    AmountUnit, ParseOptions, SanitizePolicy, SerializeOptions, TimestampUnit, UnknownFields,
+};
+use crate::result::{
+    RecordProduceResult, RecordReadResult, RecordSerializeResult, RecordWriteResult,
+};
+
+/// Separators tried by [`detect_separator`]
+const SEPARATORS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// Bytes of the sample [`detect_separator`] looks at
+const SAMPLE_LENGTH: usize = 512;
+
+/// Separator of csv like input guessed from a sample of its start,
+/// usually the header line. Of `,`, `;`, tab and `|` outside quotes in the
+/// first 512 bytes, the most frequent one is picked when it separates at least
+/// a full header of the record fields. None when no separator is that
+/// frequent or two are equally frequent
+///
+/// ```
+/// use parserde::detect_separator;
+/// let header = "TX_ID;TX_TYPE;FROM_USER_ID;TO_USER_ID;AMOUNT;TIMESTAMP;STATUS;DESCRIPTION";
+/// assert_eq!(detect_separator(header), Some(b';'));
+/// assert_eq!(detect_separator("TX_ID,TX_TYPE;AMOUNT"), None);
+/// ```
+pub fn detect_separator(sample: &str) -> Option<u8> {
+    let sample = &sample.as_bytes()[..sample.len().min(SAMPLE_LENGTH)];
+    let mut counts = [0; SEPARATORS.len()];
+    let mut quoted = false;
+    for byte in sample {
+        if *byte == b'"' {
+            quoted = !quoted;
+        } else if !quoted && let Some(i) = SEPARATORS.iter().position(|s| s == byte) {
+            counts[i] += 1;
+        }
+    }
+    let max = *counts.iter().max()?;
+    let mut candidates = SEPARATORS
+        .iter()
+        .zip(counts)
+        .filter(|&(_, count)| count == max);
+    let (&separator, _) = candidates.next()?;
+    // a header of the fields has one separator fewer than fields
+    (max >= FieldName::ALL.len() - 1 && candidates.next().is_none()).then_some(separator)
+}
 
 pub(crate) struct CsvReader<T: Read> {
     pub(crate) reader: Reader<T>,
     pub(crate) current_line: u64,
     is_exhausted: bool,
+    amount_unit: AmountUnit,
+    timestamp_unit: TimestampUnit,
+    header: StringRecord,
+    /// Fields of the header columns, none for the skipped ones
+    columns: Vec<Option<FieldName>>,
+    defaults: Vec<FieldValue>,
+    separator: u8,
 }
 
 impl<T: Read> CsvReader<T> {
     pub(crate) fn new(reader: T, separator: u8) -> Result<CsvReader<T>, Box<dyn Error>> {
-        let reader = ReaderBuilder::new()
+        CsvReader::with_options(reader, separator, &ParseOptions::default())
+    }
+
+    pub(crate) fn with_options(
+        reader: T,
+        separator: u8,
+        options: &ParseOptions,
+    ) -> Result<CsvReader<T>, Box<dyn Error>> {
+        let mut reader = ReaderBuilder::new()
             .delimiter(separator)
+            .quoting(true)
             .from_reader(reader);
+        let header = reader.headers()?.clone();
+        let has_header = !header.is_empty();
+        if !has_header && !options.allow_empty {
+            return Err("empty input, no header found".into());
+        }
+        let aliases = options.alias_table();
+        let columns = header
+            .iter()
+            .map(|column| resolve_field(column, &aliases))
+            .collect::<Vec<_>>();
+        if options.unknown_fields == UnknownFields::Error && columns.contains(&None) {
+            let unknown = header
+                .iter()
+                .zip(&columns)
+                .filter_map(|(column, field)| field.is_none().then_some(column))
+                .collect::<Vec<_>>();
+            return Err(format!("unknown columns {}", unknown.join(",")).into());
+        }
+        if !aliases.is_empty() {
+            for (i, field) in columns.iter().enumerate() {
+                if field.is_some() && columns[..i].contains(field) {
+                    return Err(format!("duplicate column {}", &header[i]).into());
+                }
+            }
+        }
         Ok(CsvReader {
             reader,
             current_line: 0,
-            is_exhausted: false,
+            is_exhausted: !has_header,
+            amount_unit: options.amount_unit,
+            timestamp_unit: options.timestamp_unit,
+            header,
+            columns,
+            defaults: options
+                .defaults
+                .parse(options.amount_unit, options.timestamp_unit)?,
+            separator,
         })
     }
 }
@@ -58,132 +161,311 @@ impl<T: Read> DataConsumer for CsvReader<T> {
     }
 }
 
-impl<T: Read> DataProducer for CsvReader<T> {
-    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+impl<T: Read> CsvReader<T> {
+    fn read_fields(&mut self) -> Option<RecordProduceResult<Vec<FieldValue>>> {
         let read_result = self.read()?;
         let payload = match read_result {
             Ok(data) => data,
             Err(e) => {
                 return Some(Err(RecordProduceError {
+                    kind: e.kind(),
                     text: format!("failed to produce record. line {}", self.current_line),
                     source: Some(Box::new(e)),
                 }));
             }
         };
+        Some(self.parse_fields(&payload))
+    }
+
+    fn parse_fields(&self, payload: &StringRecord) -> RecordProduceResult<Vec<FieldValue>> {
         let mut fields = Vec::new();
         let mut value_iter = payload.iter();
-        let header = match self.reader.headers().map_err(|e| RecordProduceError {
-            text: "failed to read headers from csv file".into(),
-            source: Some(Box::new(e)),
-        }) {
-            Ok(r) => r,
-            Err(e) => return Some(Err(e)),
-        };
-        for f in header {
-            match value_iter.next() {
-                Some(val) => match Field::new(f, val).parse() {
+        for (f, column) in self.header.iter().zip(&self.columns) {
+            match (value_iter.next(), column) {
+                (Some(_), None) => (),
+                (Some(val), Some(field)) => match Field::new(*field, val)
+                    .parse_with_units(self.amount_unit, self.timestamp_unit)
+                {
                     Ok(val) => fields.push(val),
                     Err(e) => {
-                        return Some(Err(RecordProduceError {
+                        return Err(RecordProduceError {
+                            kind: RecordProduceErrorKind::ParseField {
+                                field_name: field.as_str().into(),
+                            },
                             text: format!("failed to produce record. line {}", self.current_line),
                             source: Some(Box::new(e)),
-                        }));
+                        });
                     }
                 },
-                None => {
-                    return Some(Err(RecordProduceError {
+                (None, _) => {
+                    return Err(RecordProduceError {
+                        kind: RecordProduceErrorKind::MissingField {
+                            field_name: f.to_string(),
+                        },
                         text: format!("missing field {}. near line {}", f, self.current_line),
                         source: None,
-                    }));
+                    });
                 }
             };
         }
-        match Record::try_from(fields) {
+        Ok(fields)
+    }
+}
+
+impl<T: Read> CsvReader<T> {
+    fn record_from_fields(&self, fields: Vec<FieldValue>) -> RecordProduceResult<Record> {
+        Record::try_from(with_defaults(&self.defaults, fields)).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!("couldn't parse record. near line {}", self.current_line),
+            source: Some(e.into()),
+        })
+    }
+
+    /// Whether the input starts with a header
+    #[cfg(feature = "tokio")]
+    pub(crate) fn has_header(&self) -> bool {
+        !self.header.is_empty()
+    }
+
+    /// Parses a row read outside of the reader as the next one.
+    /// A row must have as many fields as the header
+    #[cfg(feature = "tokio")]
+    pub(crate) fn parse_row(&mut self, row: &StringRecord) -> RecordProduceResult<Record> {
+        self.current_line += 1;
+        if row.len() != self.header.len() {
+            return Err(RecordProduceError {
+                kind: RecordProduceErrorKind::MalformedRecord,
+                text: format!(
+                    "found {} fields, expected {}. line {}",
+                    row.len(),
+                    self.header.len(),
+                    self.current_line
+                ),
+                source: None,
+            });
+        }
+        self.record_from_fields(self.parse_fields(row)?)
+    }
+}
+
+impl<T: Read> DataProducer for CsvReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let fields = match self.read_fields()? {
+            Ok(fields) => fields,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(self.record_from_fields(fields))
+    }
+
+    fn position(&self) -> Option<Position> {
+        let position = self.reader.position();
+        Some(Position {
+            byte: position.byte(),
+            line: position.line() - 1,
+            record: self.current_line,
+        })
+    }
+}
+
+impl<T: Read> RawDataProducer for CsvReader<T> {
+    fn raw_header(&self) -> Vec<u8> {
+        csv_line(self.header.iter(), self.separator)
+    }
+
+    fn produce_raw(&mut self) -> Option<RecordProduceResult<RawRecord>> {
+        match self.read()? {
+            Ok(payload) => Some(Ok(RawRecord(RawRepr::Csv(payload, self.separator)))),
+            Err(e) => Some(Err(RecordProduceError {
+                kind: e.kind(),
+                text: format!("failed to produce record. line {}", self.current_line),
+                source: Some(Box::new(e)),
+            })),
+        }
+    }
+
+    fn parse_raw(&self, raw: &RawRecord) -> RecordProduceResult<Record> {
+        match &raw.0 {
+            RawRepr::Csv(payload, _) => self.record_from_fields(self.parse_fields(payload)?),
+            _ => Err(RecordProduceError {
+                kind: RecordProduceErrorKind::Unknown,
+                text: "raw record is not a csv row".into(),
+                source: None,
+            }),
+        }
+    }
+}
+
+impl<T: Read + Seek> CsvReader<T> {
+    /// Moves the reader to a position previously returned by `position`.
+    /// The header is read first if it wasn't yet
+    pub(crate) fn seek(&mut self, position: Position) -> csv::Result<()> {
+        let mut target = csv::Position::new();
+        target
+            .set_byte(position.byte)
+            .set_line(position.line + 1)
+            .set_record(position.record + 1);
+        self.reader.seek(target)?;
+        self.current_line = position.record;
+        self.is_exhausted = false;
+        Ok(())
+    }
+}
+
+impl<T: Read> PartialDataProducer for CsvReader<T> {
+    fn produce_partial_record(&mut self) -> Option<RecordProduceResult<PartialRecord>> {
+        let fields = match self.read_fields()? {
+            Ok(fields) => fields,
+            Err(e) => return Some(Err(e)),
+        };
+        match PartialRecord::try_from(with_defaults(&self.defaults, fields)) {
             Ok(r) => Some(Ok(r)),
             Err(e) => Some(Err(RecordProduceError {
-                text: format!("couldn't parse record. near line {}", self.current_line),
+                kind: RecordProduceErrorKind::of_record(&e),
+                text: format!(
+                    "couldn't parse partial record. near line {}",
+                    self.current_line
+                ),
                 source: Some(e.into()),
             })),
         }
     }
 }
 
+/// Renders the fields as a csv line quoted where needed
+pub(crate) fn csv_line<'a>(fields: impl Iterator<Item = &'a str>, delimiter: u8) -> Vec<u8> {
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .terminator(Terminator::Any(b'\n'))
+        .from_writer(Vec::new());
+    // writing to a Vec can't fail
+    let _ = writer.write_record(fields);
+    writer.into_inner().unwrap_or_default()
+}
+
+/// Field quoted as in RFC 4180 when it has the separator, a quote or a line break
+fn quote_field<'v>(value: &'v str, separator: &str) -> Cow<'v, str> {
+    if value.contains(separator) || value.contains(['"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
 pub(crate) struct CsvSerialize<'a> {
-    fields: &'a [&'a str],
+    fields: Vec<Cow<'a, str>>,
+    derived: Vec<DerivedColumn>,
     separator: &'a str,
+    amount_unit: AmountUnit,
+    sanitize: SanitizePolicy,
+    timestamp_unit: TimestampUnit,
 }
 
 impl<'a> CsvSerialize<'a> {
-    pub(crate) fn new(fields: &'a [&'a str], separator: &'a str) -> CsvSerialize<'a> {
-        CsvSerialize { fields, separator }
+    /// Columns which aren't record fields are looked up in the derived columns
+    /// of the options, then among the built-in ones
+    pub(crate) fn with_options(
+        fields: Vec<Cow<'a, str>>,
+        separator: &'a str,
+        options: &SerializeOptions,
+    ) -> CsvSerialize<'a> {
+        let derived = fields
+            .iter()
+            .filter_map(|f| DerivedColumn::resolve(f, &options.derived_columns))
+            .collect();
+        CsvSerialize {
+            fields,
+            derived,
+            separator,
+            amount_unit: options.amount_unit,
+            sanitize: options.sanitize,
+            timestamp_unit: options.timestamp_unit,
+        }
     }
-}
 
-impl<'a> RecordSerialize for CsvSerialize<'a> {
-    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+    /// Unquoted values of the columns of the record
+    pub(crate) fn values(&self, record: &Record) -> RecordSerializeResult<Vec<String>> {
         let mut r = Vec::new();
-        for &f in self.fields {
-            match f {
-                fields::str::TX_ID => {
+        for f in &self.fields {
+            match f.parse::<FieldName>() {
+                Ok(FieldName::TxId) => {
                     r.push(record.tx_id.to_string());
                 }
-                fields::str::TX_TYPE => {
+                Ok(FieldName::TxType) => {
                     r.push(record.tx_type.to_string());
                 }
-                fields::str::FROM_USER => {
+                Ok(FieldName::FromUser) => {
                     r.push(record.from_user.to_string());
                 }
-                fields::str::TO_USER => {
+                Ok(FieldName::ToUser) => {
                     r.push(record.to_user.to_string());
                 }
-                fields::str::AMOUNT => {
-                    r.push(record.amount.to_string());
+                Ok(FieldName::Amount) => {
+                    r.push(self.amount_unit.format(record.amount));
                 }
-                fields::str::TIMESTAMP => {
-                    r.push(record.timestamp.to_string());
+                Ok(FieldName::Timestamp) => {
+                    r.push(self.timestamp_unit.timestamp(record)?.to_string());
                 }
-                fields::str::DESCRIPTION => {
-                    r.push(record.description.to_owned());
+                Ok(FieldName::Description) => {
+                    r.push(self.sanitize.description(record)?);
                 }
-                fields::str::STATUS => {
+                Ok(FieldName::Status) => {
                     r.push(record.status.to_string());
                 }
-                _ => {
-                    return Err(RecordSerializeError {
-                        text: format!("unknown field {}", f),
-                        source: None,
-                    });
-                }
+                Err(_) => match self.derived.iter().find(|d| d.name == *f) {
+                    Some(derived) => r.push((derived.derive)(record)),
+                    None => {
+                        return Err(RecordSerializeError {
+                            text: format!("unknown field {}", f),
+                            source: None,
+                        });
+                    }
+                },
             }
         }
+        Ok(r)
+    }
+}
+
+impl<'a> RecordSerialize for CsvSerialize<'a> {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let values = self.values(record)?;
+        let r = values
+            .iter()
+            .map(|v| quote_field(v, self.separator))
+            .collect::<Vec<_>>();
         Ok(r.join(self.separator).into_bytes())
     }
 }
 
 pub(crate) struct RecordWrite<'a, W: Write> {
-    fields: &'a [&'a str],
+    fields: Vec<Cow<'a, str>>,
     separator: u8,
     writer: W,
+    header_written: bool,
 }
 
 impl<'a, W: Write> RecordWrite<'a, W> {
-    pub(crate) fn new(writer: W, fields: &'a [&'a str], separator: u8) -> RecordWrite<'a, W> {
+    pub(crate) fn new(writer: W, fields: Vec<Cow<'a, str>>, separator: u8) -> RecordWrite<'a, W> {
         RecordWrite {
             fields,
             writer,
             separator,
+            header_written: false,
         }
     }
 }
 
 impl<'a, W: Write> RecordWriter for RecordWrite<'a, W> {
     fn write_header(&mut self) -> crate::result::RecordWriteResult<()> {
+        if self.header_written {
+            return Ok(());
+        }
         let header = self
             .fields
             .iter()
             .map(|v| v.to_string())
             .collect::<Vec<String>>();
-        let header = header.join(&self.separator.to_string());
+        let header = header.join(&(self.separator as char).to_string());
         let mut header = header.into_bytes();
         header.push(b'\n');
         match self.writer.write_all(&header) {
@@ -195,6 +477,7 @@ impl<'a, W: Write> RecordWriter for RecordWrite<'a, W> {
                 });
             }
         };
+        self.header_written = true;
         match self.writer.flush() {
             Ok(_) => Ok(()),
             Err(e) => Err(RecordWriteError {
@@ -204,6 +487,7 @@ impl<'a, W: Write> RecordWriter for RecordWrite<'a, W> {
         }
     }
     fn write(&mut self, mut data: Vec<u8>) -> crate::result::RecordWriteResult<()> {
+        self.write_header()?;
         data.push(b'\n');
         match self.writer.write_all(&data) {
             Ok(_) => (),
@@ -224,6 +508,89 @@ impl<'a, W: Write> RecordWriter for RecordWrite<'a, W> {
     }
 }
 
+/// Csv writer appending to an existing file.
+/// The header is written only when the file is empty,
+/// otherwise the existing header must match the written columns
+pub struct CsvAppendWriter<'a> {
+    inner: RecordWrite<'a, File>,
+}
+
+impl<'a> CsvAppendWriter<'a> {
+    /// Opens or creates the file at the path for appending.
+    /// Fails if the file has a header with other columns or delimiter
+    pub fn open(
+        path: &Path,
+        fields: &'a [&'a str],
+        delimiter: u8,
+    ) -> RecordWriteResult<CsvAppendWriter<'a>> {
+        let fields = fields.iter().map(|&f| Cow::Borrowed(f)).collect();
+        CsvAppendWriter::open_columns(path, fields, delimiter)
+    }
+
+    pub(crate) fn open_columns(
+        path: &Path,
+        fields: Vec<Cow<'a, str>>,
+        delimiter: u8,
+    ) -> RecordWriteResult<CsvAppendWriter<'a>> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| RecordWriteError {
+                text: format!("failed to open {}", path.display()),
+                source: Some(Box::new(e)),
+            })?;
+        let mut header = String::new();
+        BufReader::new(&mut file)
+            .read_line(&mut header)
+            .map_err(|e| RecordWriteError {
+                text: format!("failed to read header of {}", path.display()),
+                source: Some(Box::new(e)),
+            })?;
+        let has_header = !header.is_empty();
+        if has_header {
+            check_header(header.trim_end_matches(['\r', '\n']), &fields, delimiter)?;
+        }
+        let mut inner = RecordWrite::new(file, fields, delimiter);
+        inner.header_written = has_header;
+        Ok(CsvAppendWriter { inner })
+    }
+}
+
+fn check_header(header: &str, fields: &[Cow<'_, str>], delimiter: u8) -> RecordWriteResult<()> {
+    let columns = header.split(delimiter as char).collect::<Vec<_>>();
+    if columns.len() == 1 && fields.len() > 1 {
+        return Err(RecordWriteError {
+            text: format!(
+                "existing header {} is not delimited by {:?}",
+                header, delimiter as char
+            ),
+            source: None,
+        });
+    }
+    if columns != fields {
+        return Err(RecordWriteError {
+            text: format!(
+                "existing columns {} don't match appended columns {}",
+                columns.join(","),
+                fields.join(",")
+            ),
+            source: None,
+        });
+    }
+    Ok(())
+}
+
+impl<'a> RecordWriter for CsvAppendWriter<'a> {
+    fn write_header(&mut self) -> RecordWriteResult<()> {
+        self.inner.write_header()
+    }
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.inner.write(data)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
@@ -254,6 +621,53 @@ hewston,Australia,2
         }
     }
 
+    #[test]
+    fn test_detect_separator() {
+        let fields = FieldName::ALL.map(|name| name.as_str());
+        for separator in [",", ";", "\t", "|"] {
+            let header = fields.join(separator);
+            let expected = Some(separator.as_bytes()[0]);
+            assert_eq!(detect_separator(&header), expected);
+            let sample = format!("{}\n1{}\"a, b; c\"\n", header, separator);
+            assert_eq!(detect_separator(&sample), expected);
+        }
+        // too few separators for a header
+        assert_eq!(detect_separator("TX_ID;TX_TYPE;AMOUNT"), None);
+        // a tie
+        assert_eq!(detect_separator("A,B,C,D,E,F,G,H;I;J;K;L;M;N;O"), None);
+        assert_eq!(detect_separator(""), None);
+        // separators quoted or past the sample aren't counted
+        let quoted = format!("\"{}\",{}", ",".repeat(20), fields.join(";"));
+        assert_eq!(detect_separator(&quoted), Some(b';'));
+        let late = format!("{}{}", " ".repeat(SAMPLE_LENGTH), fields.join(","));
+        assert_eq!(detect_separator(&late), None);
+    }
+
+    #[test]
+    fn test_auto_separator_reader() {
+        let expected = crate::record::RecordIterator::new(
+            CsvReader::new(Cursor::new(get_good_input()), b',').unwrap(),
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        for separator in [";", "\t", "|", ","] {
+            let input = get_good_input().replace(',', separator);
+            let reader = crate::builder::build_reader(Cursor::new(input), "csv-auto").unwrap();
+            let records = crate::record::RecordIterator::new(reader)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(records, expected, "{:?}", separator);
+        }
+        // an ambiguous header falls back to commas
+        let input = "TX_ID,AMOUNT\n1,2\n";
+        let mut reader = crate::builder::build_reader(Cursor::new(input), "csv-auto").unwrap();
+        assert!(
+            reader.produce_record().unwrap().is_err_and(|e| {
+                matches!(e.kind(), RecordProduceErrorKind::MissingField { .. })
+            })
+        );
+    }
+
     #[test]
     fn test_read() {
         let input = Cursor::new(get_good_input().into_bytes());
@@ -307,4 +721,405 @@ hewston,Australia,2
         assert_eq!(result.tx_id, 1000000000000001);
         assert!(reader.read().is_none());
     }
+
+    #[test]
+    fn test_produce_partial_record() {
+        let input = Cursor::new("TX_ID,STATUS,DESCRIPTION\n1000000000000001,SUCCESS,fixed\n");
+        let mut reader = CsvReader::new(input, b',').unwrap();
+        let patch = reader.produce_partial_record().unwrap().unwrap();
+        assert_eq!(patch.tx_id, 1000000000000001);
+        assert_eq!(patch.status, Some(crate::record::Status::Success));
+        assert_eq!(patch.description.as_deref(), Some("fixed"));
+        assert_eq!(patch.from_user, None);
+        assert!(reader.produce_partial_record().is_none());
+    }
+
+    #[test]
+    fn test_major_amount_unit() {
+        let input = Cursor::new(
+            "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,2,12.34,1633036860000,SUCCESS,fee\n1,DEPOSIT,0,2,12.345,1633036860000,SUCCESS,fee\n",
+        );
+        let options = ParseOptions {
+            amount_unit: AmountUnit::Major { scale: 2 },
+            ..Default::default()
+        };
+        let mut reader = CsvReader::with_options(input, b',', &options).unwrap();
+        let record = reader.produce_record().unwrap().unwrap();
+        assert_eq!(record.amount, 1234);
+        assert!(reader.produce_record().unwrap().is_err());
+
+        let fields = [FieldName::TxId.as_str(), FieldName::Amount.as_str()];
+        let ser = CsvSerialize::with_options(
+            columns(&fields),
+            ",",
+            &SerializeOptions {
+                amount_unit: AmountUnit::Major { scale: 2 },
+                ..Default::default()
+            },
+        );
+        assert_eq!(ser.serialize(&record).unwrap(), b"1,12.34");
+    }
+
+    const APPEND_FIELDS: &[&str] = &[FieldName::TxId.as_str(), FieldName::Amount.as_str()];
+
+    fn columns(fields: &[&'static str]) -> Vec<Cow<'static, str>> {
+        fields.iter().map(|&f| Cow::Borrowed(f)).collect()
+    }
+
+    fn append(
+        path: &Path,
+        fields: &'static [&'static str],
+        rows: &[&str],
+    ) -> RecordWriteResult<()> {
+        let mut writer = CsvAppendWriter::open(path, fields, b',')?;
+        writer.write_header()?;
+        for row in rows {
+            writer.write(row.as_bytes().to_vec())?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_writes_header_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.csv");
+        append(&path, APPEND_FIELDS, &["1,100"]).unwrap();
+        append(&path, APPEND_FIELDS, &["2,200"]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "TX_ID,AMOUNT\n1,100\n2,200\n"
+        );
+    }
+
+    #[test]
+    fn test_append_to_empty_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.csv");
+        File::create(&path).unwrap();
+        append(&path, APPEND_FIELDS, &["1,100"]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "TX_ID,AMOUNT\n1,100\n"
+        );
+    }
+
+    #[test]
+    fn test_append_with_mismatched_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.csv");
+        std::fs::write(&path, "TX_ID,STATUS\n1,SUCCESS\n").unwrap();
+        let err = append(&path, APPEND_FIELDS, &["1,100"]).unwrap_err();
+        assert!(err.to_string().contains("TX_ID,STATUS"));
+        assert!(err.to_string().contains("TX_ID,AMOUNT"));
+        std::fs::write(&path, "TX_ID;AMOUNT\n1;100\n").unwrap();
+        let err = append(&path, APPEND_FIELDS, &["1,100"]).unwrap_err();
+        assert!(err.to_string().contains("not delimited"));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "TX_ID;AMOUNT\n1;100\n"
+        );
+    }
+
+    #[test]
+    fn test_built_writer_header() {
+        use crate::builder::{build_serializer, build_writer};
+
+        let dir = tempfile::tempdir().unwrap();
+        for (format, header) in [
+            (
+                "csv",
+                "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n",
+            ),
+            (
+                "tsv",
+                "TX_ID\tTX_TYPE\tFROM_USER_ID\tTO_USER_ID\tAMOUNT\tTIMESTAMP\tSTATUS\tDESCRIPTION\n",
+            ),
+        ] {
+            let path = dir.path().join(format);
+            let mut writer = build_writer(std::fs::File::create(&path).unwrap(), format).unwrap();
+            let record = Record::default();
+            writer.write_header().unwrap();
+            writer
+                .write_record(
+                    &record,
+                    build_serializer(format)
+                        .unwrap()
+                        .serialize(&record)
+                        .unwrap(),
+                )
+                .unwrap();
+            writer.finish().unwrap();
+            drop(writer);
+            let output = std::fs::read_to_string(&path).unwrap();
+            assert!(output.starts_with(header), "{}", output);
+            assert_eq!(output.lines().count(), 2, "{}", output);
+        }
+    }
+
+    #[test]
+    fn test_header_only_and_empty_input() {
+        let header = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+        let allow_empty = ParseOptions {
+            allow_empty: true,
+            ..Default::default()
+        };
+        for options in [ParseOptions::default(), allow_empty.clone()] {
+            let mut reader = CsvReader::with_options(Cursor::new(header), b',', &options).unwrap();
+            assert!(reader.produce_record().is_none());
+        }
+
+        let e = CsvReader::new(Cursor::new(""), b',').err().unwrap();
+        assert_eq!(e.to_string(), "empty input, no header found");
+        let mut reader = CsvReader::with_options(Cursor::new(""), b',', &allow_empty).unwrap();
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_empty_description() {
+        let input = format!(
+            "{}\n1,DEPOSIT,0,2,5,9,SUCCESS,\n",
+            get_good_input().lines().next().unwrap()
+        );
+        let mut reader = CsvReader::new(Cursor::new(input.clone()), b',').unwrap();
+        let record = reader.produce_record().unwrap().unwrap();
+        assert_eq!(record.description, "");
+        let header = input.lines().next().unwrap();
+        let fields = header
+            .split(',')
+            .map(|f| Cow::Owned(f.to_string()))
+            .collect();
+        let line = CsvSerialize::with_options(fields, ",", &SerializeOptions::default())
+            .serialize(&record)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(line).unwrap(),
+            input.lines().nth(1).unwrap()
+        );
+
+        let no_column = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS\n1,DEPOSIT,0,2,5,9,SUCCESS\n";
+        let mut reader = CsvReader::new(Cursor::new(no_column), b',').unwrap();
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+    }
+
+    #[test]
+    fn test_quoted_round_trip() {
+        let header = get_good_input().lines().next().unwrap().to_string();
+        let fields = header
+            .split(',')
+            .map(|f| Cow::Owned(f.to_string()))
+            .collect::<Vec<_>>();
+        let serializer = CsvSerialize::with_options(fields, ",", &SerializeOptions::default());
+        for description in ["a,b", "two\nlines", "say \"hi\"", "\",\n\"", "plain"] {
+            let record = Record::new(
+                1,
+                crate::record::TxType::Deposit,
+                0,
+                2,
+                100,
+                5,
+                crate::record::Status::Success,
+                description.into(),
+            );
+            let line = String::from_utf8(serializer.serialize(&record).unwrap()).unwrap();
+            assert_eq!(
+                line.starts_with("1,DEPOSIT,0,2,100,5,SUCCESS,\""),
+                description != "plain",
+                "{}",
+                line
+            );
+            let input = format!("{}\n{}\n", header, line);
+            let mut reader = CsvReader::new(Cursor::new(input), b',').unwrap();
+            assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+            assert!(reader.produce_record().is_none());
+        }
+    }
+
+    #[test]
+    fn test_tsv_round_trip() {
+        use crate::builder::{build_reader, build_serializer, build_writer};
+
+        let record = Record::new(
+            1,
+            crate::record::TxType::Deposit,
+            0,
+            2,
+            100,
+            5,
+            crate::record::Status::Success,
+            "a\tb\nc".into(),
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.tsv");
+        let mut writer = build_writer(std::fs::File::create(&path).unwrap(), "tsv").unwrap();
+        writer
+            .write(build_serializer("tsv").unwrap().serialize(&record).unwrap())
+            .unwrap();
+        drop(writer);
+        let output = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            output,
+            "TX_ID\tTX_TYPE\tFROM_USER_ID\tTO_USER_ID\tAMOUNT\tTIMESTAMP\tSTATUS\tDESCRIPTION\n1\tDEPOSIT\t0\t2\t100\t5\tSUCCESS\t\"a\tb\nc\"\n"
+        );
+        let mut reader = build_reader(Cursor::new(output), "tsv").unwrap();
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_sanitized_description() {
+        let record = Record::new(
+            1,
+            crate::record::TxType::Deposit,
+            0,
+            2,
+            100,
+            5,
+            crate::record::Status::Success,
+            "a\tb\rc\0d".into(),
+        );
+        let fields = [FieldName::TxId.as_str(), FieldName::Description.as_str()];
+        let serialize = |sanitize| {
+            let options = SerializeOptions {
+                sanitize,
+                ..Default::default()
+            };
+            CsvSerialize::with_options(columns(&fields), ",", &options)
+                .serialize(&record)
+                .map(|b| String::from_utf8(b).unwrap())
+        };
+        assert_eq!(
+            serialize(SanitizePolicy::Preserve).unwrap(),
+            "1,\"a\tb\rc\0d\""
+        );
+        assert_eq!(
+            serialize(SanitizePolicy::Replace('_')).unwrap(),
+            "1,a_b_c_d"
+        );
+        assert_eq!(serialize(SanitizePolicy::Strip).unwrap(), "1,abcd");
+        assert!(serialize(SanitizePolicy::Error).is_err());
+    }
+
+    #[test]
+    fn test_header_written_once() {
+        let header = "TX_ID,AMOUNT\n";
+        let mut out = Vec::new();
+        let mut writer = RecordWrite::new(&mut out, columns(APPEND_FIELDS), b',');
+        writer.write(b"1,100".to_vec()).unwrap();
+        writer.write_header().unwrap();
+        writer.write(b"2,200".to_vec()).unwrap();
+        assert_eq!(out, format!("{}1,100\n2,200\n", header).as_bytes());
+
+        let mut out = Vec::new();
+        let mut writer = RecordWrite::new(&mut out, columns(APPEND_FIELDS), b',');
+        writer.write_header().unwrap();
+        writer.write_header().unwrap();
+        writer.write(b"1,100".to_vec()).unwrap();
+        assert_eq!(out, format!("{}1,100\n", header).as_bytes());
+
+        let mut out = Vec::new();
+        let mut writer = RecordWrite::new(&mut out, columns(APPEND_FIELDS), b',');
+        writer.write_header().unwrap();
+        writer.finish().unwrap();
+        assert_eq!(out, header.as_bytes());
+    }
+
+    #[test]
+    fn test_derived_columns() {
+        let record = Record::new(
+            7,
+            crate::record::TxType::Withdrawal,
+            2,
+            0,
+            100,
+            1633036860000,
+            crate::record::Status::Success,
+            "fee".into(),
+        );
+        let fields = [
+            FieldName::TxId.as_str(),
+            "DATE",
+            FieldName::Amount.as_str(),
+            "DIRECTION",
+        ];
+        let serialize = |options: &SerializeOptions| {
+            CsvSerialize::with_options(columns(&fields), ",", options).serialize(&record)
+        };
+        assert_eq!(
+            serialize(&SerializeOptions::default()).unwrap(),
+            b"7,2021-09-30,100,OUT"
+        );
+
+        let registered = SerializeOptions {
+            derived_columns: vec![DerivedColumn {
+                name: "DIRECTION".into(),
+                derive: |r| format!("to {}", r.to_user),
+            }],
+            ..Default::default()
+        };
+        assert_eq!(serialize(&registered).unwrap(), b"7,2021-09-30,100,to 0");
+
+        let unknown = CsvSerialize::with_options(columns(&["WEEKDAY"]), ",", &registered);
+        assert!(unknown.serialize(&record).is_err());
+    }
+
+    #[test]
+    fn test_unknown_columns() {
+        let input = "TX_ID,BATCH_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION,SOURCE_SYSTEM\n\
+                     1,b7,DEPOSIT,0,2,100,5,SUCCESS,fee,crm\n\
+                     2,b7,DEPOSIT,0,2,100\n";
+        let reader = |unknown_fields| {
+            let options = ParseOptions {
+                unknown_fields,
+                ..Default::default()
+            };
+            CsvReader::with_options(Cursor::new(input), b',', &options)
+        };
+        let Err(e) = reader(UnknownFields::Error) else {
+            panic!("unknown columns accepted");
+        };
+        assert_eq!(e.to_string(), "unknown columns BATCH_ID,SOURCE_SYSTEM");
+
+        let mut reader = reader(UnknownFields::Ignore).unwrap();
+        let record = reader.produce_record().unwrap().unwrap();
+        assert_eq!(
+            record,
+            Record::new(
+                1,
+                crate::record::TxType::Deposit,
+                0,
+                2,
+                100,
+                5,
+                crate::record::Status::Success,
+                "fee".into(),
+            )
+        );
+        assert!(reader.produce_record().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_aliases() {
+        let canonical = include_str!("../../examples/source/records_example.csv");
+        let (_, rows) = canonical.split_once('\n').unwrap();
+        let aliased = format!(
+            "ID,TYPE,FROM_USER,TO_USER,AMOUNT,TIMESTAMP,STATUS,DESC\n{}",
+            rows
+        );
+        let mut options = ParseOptions::default();
+        assert!(CsvReader::with_options(Cursor::new(aliased.clone()), b',', &options).is_err());
+
+        options.allow_aliases = true;
+        let mut expected = CsvReader::new(Cursor::new(canonical), b',').unwrap();
+        let mut reader = CsvReader::with_options(Cursor::new(aliased), b',', &options).unwrap();
+        while let Some(record) = expected.produce_record() {
+            assert_eq!(reader.produce_record().unwrap().unwrap(), record.unwrap());
+        }
+        assert!(reader.produce_record().is_none());
+
+        let conflict = Cursor::new("TX_ID,ID,TYPE\n1,1,DEPOSIT\n");
+        let Err(e) = CsvReader::with_options(conflict, b',', &options) else {
+            panic!("conflicting columns accepted");
+        };
+        assert_eq!(e.to_string(), "duplicate column ID");
+    }
 }
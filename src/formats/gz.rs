@@ -0,0 +1,135 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use crate::error::RecordWriteError;
+use crate::record::{Record, RecordWriter};
+use crate::result::RecordWriteResult;
+
+/// Gzip encoder shared by the format writer writing into it
+/// and the [`GzWrite`] finishing it
+pub(crate) struct SharedEncoder<W: Write>(Rc<RefCell<GzEncoder<W>>>);
+
+impl<W: Write> Write for SharedEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+/// Writer of a format compressing its output with gzip.
+/// The gzip trailer is written on finish
+pub(crate) struct GzWrite<W: Write> {
+    inner: Box<dyn RecordWriter>,
+    encoder: Rc<RefCell<GzEncoder<W>>>,
+}
+
+impl<W: Write> GzWrite<W> {
+    /// Creates the format writer with the given function over the compressing sink
+    pub(crate) fn new<F>(writer: W, build: F) -> Result<GzWrite<W>, Box<dyn Error>>
+    where
+        F: FnOnce(SharedEncoder<W>) -> Result<Box<dyn RecordWriter>, Box<dyn Error>>,
+    {
+        let encoder = Rc::new(RefCell::new(GzEncoder::new(writer, Compression::default())));
+        Ok(GzWrite {
+            inner: build(SharedEncoder(encoder.clone()))?,
+            encoder,
+        })
+    }
+}
+
+impl<W: Write> RecordWriter for GzWrite<W> {
+    fn write_header(&mut self) -> RecordWriteResult<()> {
+        self.inner.write_header()
+    }
+
+    fn write_comment(&mut self, text: &str) -> RecordWriteResult<()> {
+        self.inner.write_comment(text)
+    }
+
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.inner.write(data)
+    }
+
+    fn write_record(&mut self, record: &Record, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.inner.write_record(record, data)
+    }
+
+    fn finish(&mut self) -> RecordWriteResult<()> {
+        self.inner.finish()?;
+        self.encoder
+            .borrow_mut()
+            .try_finish()
+            .map_err(|e| RecordWriteError {
+                text: "failed to finish gzip stream".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::builder::{build_reader, build_serializer, build_writer};
+    use crate::record::{Record, RecordIterator, Status, TxType};
+
+    /// Sink keeping the written bytes readable after the writer is dropped
+    #[derive(Clone, Default)]
+    struct Shared(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl std::io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let records = (0..50)
+            .map(|i| {
+                Record::new(
+                    i,
+                    TxType::Transfer,
+                    i + 1,
+                    i + 2,
+                    100 * i,
+                    1633036860000 + i,
+                    Status::Success,
+                    format!("Record number {}", i),
+                )
+            })
+            .collect::<Vec<_>>();
+        for format in ["bin", "csv", "txt"] {
+            let sink = Shared::default();
+            let serializer = build_serializer(format).unwrap();
+            let mut writer = build_writer(sink.clone(), &format!("{}.gz", format)).unwrap();
+            for record in &records {
+                writer
+                    .write_record(record, serializer.serialize(record).unwrap())
+                    .unwrap();
+            }
+            writer.finish().unwrap();
+            drop(writer);
+            let compressed = sink.0.borrow().clone();
+            assert_eq!(&compressed[..2], [0x1f, 0x8b], "{}", format);
+
+            let reader = build_reader(Cursor::new(compressed), &format!("{}.gz", format)).unwrap();
+            let read = RecordIterator::new(reader)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(read, records, "{}", format);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::bin::{
+    CHECKSUM_LENGTH, DESCRIPTION_LENGTH, FIXED_BODY_LENGTH, FIXED_FIELDS, RecordBytes,
+};
+use crate::error::RecordSerializeError;
+use crate::record::{Field, FieldName, Record, RecordSerialize};
+use crate::result::RecordSerializeResult;
+
+/// Bytes shown on one line of the dump
+const LINE_WIDTH: usize = 16;
+
+/// Serializes a record as an annotated hex dump of its YPBN frame.
+/// Every region of the frame is labeled with the field it holds,
+/// its offset within the frame and its decoded value
+#[derive(Default)]
+pub(crate) struct HexdumpSerialize;
+
+impl HexdumpSerialize {
+    fn region(dump: &mut String, offset: usize, bytes: &[u8], label: &str, value: &str) {
+        let mut chunks = bytes.chunks(LINE_WIDTH);
+        let first = chunks.next().unwrap_or_default();
+        dump.push_str(&format!(
+            "{:08x}  {:<width$}  {:<12}  {}\n",
+            offset,
+            hex(first),
+            label,
+            value,
+            width = LINE_WIDTH * 3 - 1
+        ));
+        for (i, chunk) in chunks.enumerate() {
+            dump.push_str(&format!(
+                "{:08x}  {}\n",
+                offset + (i + 1) * LINE_WIDTH,
+                hex(chunk)
+            ));
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl RecordSerialize for HexdumpSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let frame = RecordBytes::default().serialize(record)?;
+        let (head, body) = frame.split_at(8);
+        let mut dump = String::new();
+        let header = format!(
+            "{}, {} byte body",
+            String::from_utf8_lossy(&head[..4]),
+            body.len()
+        );
+        HexdumpSerialize::region(&mut dump, 0, head, "header", &header);
+        let decode = |name: FieldName, bytes: &[u8]| {
+            Field::new(name, bytes)
+                .parse()
+                .map(|value| value.as_string())
+                .map_err(|e| RecordSerializeError {
+                    text: format!("failed to decode {} of record {}", name, record.tx_id),
+                    source: Some(Box::new(e)),
+                })
+        };
+        for (name, range) in FIXED_FIELDS {
+            let value = decode(name, &body[range.clone()])?;
+            HexdumpSerialize::region(
+                &mut dump,
+                head.len() + range.start,
+                &body[range],
+                name.as_str(),
+                &value,
+            );
+        }
+        let length = &body[DESCRIPTION_LENGTH];
+        HexdumpSerialize::region(
+            &mut dump,
+            head.len() + DESCRIPTION_LENGTH.start,
+            length,
+            "length",
+            &record.description.len().to_string(),
+        );
+        let end = body.len() - CHECKSUM_LENGTH;
+        let description = &body[FIXED_BODY_LENGTH..end];
+        let value = format!("{:?}", decode(FieldName::Description, description)?);
+        HexdumpSerialize::region(
+            &mut dump,
+            head.len() + FIXED_BODY_LENGTH,
+            description,
+            FieldName::Description.as_str(),
+            &value,
+        );
+        let checksum = &body[end..];
+        HexdumpSerialize::region(
+            &mut dump,
+            head.len() + end,
+            checksum,
+            "checksum",
+            &format!("crc32 {:08x}", record.checksum()),
+        );
+        Ok(dump.into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::{Status, TxType};
+
+    #[test]
+    fn test_dump() {
+        let record = Record::new(
+            1,
+            TxType::Transfer,
+            2,
+            3,
+            400,
+            5,
+            Status::Pending,
+            "Record number 1, \"quoted\"".into(),
+        );
+        let dump = String::from_utf8(HexdumpSerialize.serialize(&record).unwrap()).unwrap();
+        assert_eq!(
+            dump,
+            r#"00000000  59 50 42 4e 00 00 00 4b                          header        YPBN, 75 byte body
+00000008  00 00 00 00 00 00 00 01                          TX_ID         1
+00000010  01                                               TX_TYPE       TRANSFER
+00000011  00 00 00 00 00 00 00 02                          FROM_USER_ID  2
+00000019  00 00 00 00 00 00 00 03                          TO_USER_ID    3
+00000021  00 00 00 00 00 00 01 90                          AMOUNT        400
+00000029  00 00 00 00 00 00 00 05                          TIMESTAMP     5
+00000031  02                                               STATUS        PENDING
+00000032  00 00 00 19                                      length        25
+00000036  52 65 63 6f 72 64 20 6e 75 6d 62 65 72 20 31 2c  DESCRIPTION   "Record number 1, \"quoted\""
+00000046  20 22 71 75 6f 74 65 64 22
+0000004f  ca f6 14 5e                                      checksum      crc32 caf6145e
+"#
+        );
+        assert_eq!(record.checksum(), 0xcaf6145e);
+    }
+}
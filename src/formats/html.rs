@@ -0,0 +1,155 @@
+use std::borrow::Cow;
+use std::io::Write;
+
+use quick_xml::escape::escape;
+
+use crate::csv::CsvSerialize;
+use crate::error::RecordWriteError;
+use crate::options::SerializeOptions;
+use crate::record::{Record, RecordSerialize, RecordWriter};
+use crate::result::{RecordSerializeResult, RecordWriteResult};
+
+/// Serializes a record as a row of an html table
+/// with the columns of csv output
+pub(crate) struct HtmlSerialize {
+    columns: CsvSerialize<'static>,
+}
+
+impl HtmlSerialize {
+    pub(crate) fn with_options(
+        fields: Vec<Cow<'static, str>>,
+        options: &SerializeOptions,
+    ) -> HtmlSerialize {
+        HtmlSerialize {
+            columns: CsvSerialize::with_options(fields, ",", options),
+        }
+    }
+}
+
+impl RecordSerialize for HtmlSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let cells = self
+            .columns
+            .values(record)?
+            .iter()
+            .map(|v| format!("<td>{}</td>", escape(v.as_str())))
+            .collect::<String>();
+        Ok(format!("<tr>{}</tr>", cells).into_bytes())
+    }
+}
+
+/// Writes rows into a table opened by the header
+/// and closed by [`RecordWriter::finish`]
+pub(crate) struct RecordWrite<W: Write> {
+    fields: Vec<Cow<'static, str>>,
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W, fields: Vec<Cow<'static, str>>) -> RecordWrite<W> {
+        RecordWrite {
+            fields,
+            writer,
+            header_written: false,
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(data)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write_header(&mut self) -> RecordWriteResult<()> {
+        if self.header_written {
+            return Ok(());
+        }
+        let cells = self
+            .fields
+            .iter()
+            .map(|f| format!("<th>{}</th>", escape(f.as_ref())))
+            .collect::<String>();
+        let header = format!("<table>\n<thead>\n<tr>{}</tr>\n</thead>\n<tbody>\n", cells);
+        self.write_all(header.as_bytes())?;
+        self.header_written = true;
+        Ok(())
+    }
+
+    fn write_comment(&mut self, text: &str) -> RecordWriteResult<()> {
+        self.write_header()?;
+        // `--` can't appear inside a comment
+        let comment = format!("<!-- {} -->\n", text.replace("--", "- -"));
+        self.write_all(comment.as_bytes())
+    }
+
+    fn write(&mut self, mut data: Vec<u8>) -> RecordWriteResult<()> {
+        self.write_header()?;
+        data.push(b'\n');
+        self.write_all(&data)
+    }
+
+    fn finish(&mut self) -> RecordWriteResult<()> {
+        self.write_header()?;
+        self.write_all(b"</tbody>\n</table>\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::{Status, TxType};
+
+    fn write(records: &[Record]) -> String {
+        let fields = vec![
+            Cow::Borrowed("TX_ID"),
+            Cow::Borrowed("STATUS"),
+            Cow::Borrowed("DESCRIPTION"),
+        ];
+        let serializer = HtmlSerialize::with_options(fields.clone(), &SerializeOptions::default());
+        let mut output = Vec::new();
+        let mut writer = RecordWrite::new(&mut output, fields);
+        for record in records {
+            writer.write(serializer.serialize(record).unwrap()).unwrap();
+        }
+        writer.finish().unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_escaped_output() {
+        let record = Record::new(
+            1,
+            TxType::Deposit,
+            0,
+            2,
+            100,
+            5,
+            Status::Success,
+            "<script>alert(\"a & b\")</script>".into(),
+        );
+        assert_eq!(
+            write(&[record]),
+            "<table>\n\
+            <thead>\n\
+            <tr><th>TX_ID</th><th>STATUS</th><th>DESCRIPTION</th></tr>\n\
+            </thead>\n\
+            <tbody>\n\
+            <tr><td>1</td><td>SUCCESS</td>\
+            <td>&lt;script&gt;alert(&quot;a &amp; b&quot;)&lt;/script&gt;</td></tr>\n\
+            </tbody>\n\
+            </table>\n"
+        );
+    }
+
+    #[test]
+    fn test_empty_table() {
+        assert!(write(&[]).ends_with("</thead>\n<tbody>\n</tbody>\n</table>\n"));
+    }
+}
@@ -0,0 +1,340 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::error::{ReaderCreateError, RecordProduceError, RecordReadError, RecordWriteError};
+use crate::ndjson::{NdjsonReader, NdjsonSerialize};
+use crate::options::{ParseOptions, SerializeOptions};
+use crate::record::{DataConsumer, DataProducer, Position, Record, RecordSerialize, RecordWriter};
+use crate::result::{
+    ReaderCreateResult, RecordProduceResult, RecordReadResult, RecordSerializeResult,
+    RecordWriteResult,
+};
+
+/// Part of the array the reader is in
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArrayState {
+    /// Before the opening bracket
+    Start,
+    /// After the opening bracket
+    First,
+    /// After an element
+    Next,
+    /// After the closing bracket or an error
+    End,
+}
+
+/// Reader of the json format, an array of objects.
+/// Objects are parsed the same way as ndjson lines
+pub(crate) struct JsonReader<T: Read> {
+    reader: BufReader<T>,
+    parser: NdjsonReader<io::Empty>,
+    state: ArrayState,
+    current_line: u64,
+    offset: u64,
+    records: u64,
+}
+
+impl<T: Read> JsonReader<T> {
+    pub(crate) fn with_options(
+        reader: T,
+        options: &ParseOptions,
+    ) -> ReaderCreateResult<JsonReader<T>> {
+        Ok(JsonReader {
+            reader: BufReader::new(reader),
+            parser: NdjsonReader::with_options(io::empty(), options).map_err(|e| {
+                ReaderCreateError {
+                    text: "failed to create json reader".into(),
+                    source: Some(Box::new(e)),
+                }
+            })?,
+            state: ArrayState::Start,
+            current_line: 1,
+            offset: 0,
+            records: 0,
+        })
+    }
+
+    fn next_byte(&mut self) -> RecordReadResult<Option<u8>> {
+        let buf = self.reader.fill_buf().map_err(|e| RecordReadError {
+            text: "couldn't read data".into(),
+            source: Some(Box::new(e)),
+        })?;
+        let Some(&byte) = buf.first() else {
+            return Ok(None);
+        };
+        self.reader.consume(1);
+        self.offset += 1;
+        if byte == b'\n' {
+            self.current_line += 1;
+        }
+        Ok(Some(byte))
+    }
+
+    /// Next byte which isn't whitespace
+    fn next_token(&mut self) -> RecordReadResult<Option<u8>> {
+        loop {
+            match self.next_byte()? {
+                Some(byte) if byte.is_ascii_whitespace() => continue,
+                token => return Ok(token),
+            }
+        }
+    }
+
+    /// Reads the rest of an object after its opening brace
+    fn read_object(&mut self) -> RecordReadResult<String> {
+        let mut object = vec![b'{'];
+        let mut depth = 1;
+        let mut in_string = false;
+        let mut escaped = false;
+        while depth > 0 {
+            let byte = self
+                .next_byte()?
+                .ok_or_else(|| self.error("unterminated object"))?;
+            object.push(byte);
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' if in_string => escaped = true,
+                b'"' => in_string = !in_string,
+                b'{' | b'[' if !in_string => depth += 1,
+                b'}' | b']' if !in_string => depth -= 1,
+                _ => (),
+            }
+        }
+        String::from_utf8(object).map_err(|e| RecordReadError {
+            text: format!("invalid utf-8 in object. near line {}", self.current_line),
+            source: Some(Box::new(e)),
+        })
+    }
+
+    fn error(&self, text: &str) -> RecordReadError {
+        RecordReadError {
+            text: format!("{}. near line {}", text, self.current_line),
+            source: None,
+        }
+    }
+
+    fn unexpected(&self, expected: &str, found: Option<u8>) -> RecordReadError {
+        match found {
+            Some(byte) => self.error(&format!(
+                "expected {} but found {}",
+                expected,
+                char::from(byte)
+            )),
+            None => self.error(&format!("expected {} but found the end of input", expected)),
+        }
+    }
+
+    fn read_element(&mut self) -> RecordReadResult<Option<String>> {
+        if self.state == ArrayState::Start {
+            match self.next_token()? {
+                Some(b'[') => self.state = ArrayState::First,
+                token => return Err(self.unexpected("[", token)),
+            }
+        }
+        let token = match (self.state, self.next_token()?) {
+            (ArrayState::First | ArrayState::Next, Some(b']')) => {
+                self.state = ArrayState::End;
+                return match self.next_token()? {
+                    None => Ok(None),
+                    Some(byte) => {
+                        Err(self.error(&format!("unexpected {} after the array", char::from(byte))))
+                    }
+                };
+            }
+            (ArrayState::Next, Some(b',')) => self.next_token()?,
+            (ArrayState::Next, token) => return Err(self.unexpected(", or ]", token)),
+            (_, token) => token,
+        };
+        if token != Some(b'{') {
+            return Err(self.unexpected("an object", token));
+        }
+        let object = self.read_object()?;
+        self.state = ArrayState::Next;
+        Ok(Some(object))
+    }
+}
+
+impl<T: Read> DataConsumer for JsonReader<T> {
+    type Item = String;
+    fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
+        if self.state == ArrayState::End {
+            return None;
+        }
+        match self.read_element() {
+            Ok(object) => object.map(Ok),
+            Err(e) => {
+                // the rest of the input can't be aligned to elements
+                self.state = ArrayState::End;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<T: Read> DataProducer for JsonReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let object = match self.read()? {
+            Ok(object) => object,
+            Err(e) => {
+                return Some(Err(RecordProduceError {
+                    kind: e.kind(),
+                    text: format!("failed to read record {}", self.records + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        self.records += 1;
+        Some(self.parser.parse_object(&object, self.current_line))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: self.current_line,
+            record: self.records,
+        })
+    }
+}
+
+/// Serializes a record as a JSON object, the same as a ndjson line
+pub(crate) struct JsonSerialize(NdjsonSerialize);
+
+impl JsonSerialize {
+    pub(crate) fn with_options(options: &SerializeOptions) -> JsonSerialize {
+        JsonSerialize(NdjsonSerialize::with_options(options))
+    }
+}
+
+impl RecordSerialize for JsonSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        self.0.serialize(record)
+    }
+}
+
+/// Writes serialized records as elements of an array, one per line.
+/// The array is closed on finish
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+    header_written: bool,
+    records: u64,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W) -> RecordWrite<W> {
+        RecordWrite {
+            writer,
+            header_written: false,
+            records: 0,
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(data)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write_header(&mut self) -> RecordWriteResult<()> {
+        if self.header_written {
+            return Ok(());
+        }
+        self.write_all(b"[")?;
+        self.header_written = true;
+        Ok(())
+    }
+
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.write_header()?;
+        let separator: &[u8] = if self.records == 0 { b"\n" } else { b",\n" };
+        self.write_all(&[separator, &data].concat())?;
+        self.records += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> RecordWriteResult<()> {
+        self.write_header()?;
+        self.write_all(b"\n]\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::error::RecordProduceErrorKind;
+    use crate::record::{RecordIterator, Status, TxType};
+
+    fn reader(input: &str) -> JsonReader<Cursor<String>> {
+        JsonReader::with_options(Cursor::new(input.to_string()), &ParseOptions::default()).unwrap()
+    }
+
+    fn records() -> Vec<Record> {
+        (0..3)
+            .map(|i| {
+                Record::new(
+                    i,
+                    TxType::Deposit,
+                    0,
+                    i + 1,
+                    100 * i,
+                    1633036860000 + i,
+                    Status::Success,
+                    format!("Record {{\"number\": [{}]}}", i),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let serializer = JsonSerialize::with_options(&SerializeOptions::default());
+        let mut output = Vec::new();
+        let mut writer = RecordWrite::new(&mut output);
+        for record in records() {
+            writer
+                .write(serializer.serialize(&record).unwrap())
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("[\n{\"TX_ID\":0,"), "{}", output);
+        assert!(output.contains("},\n{"), "{}", output);
+        assert!(output.ends_with("}\n]\n"), "{}", output);
+
+        let read = RecordIterator::new(reader(&output))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, records());
+
+        let mut empty = Vec::new();
+        RecordWrite::new(&mut empty).finish().unwrap();
+        assert_eq!(empty, b"[\n]\n");
+        assert!(reader(" [ ] ").produce_record().is_none());
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        for input in ["", "{}", "[{\"TX_ID\":1", "[1]", "[,]", "[] x"] {
+            let mut reader = reader(input);
+            let err = reader.produce_record().unwrap().unwrap_err();
+            assert_eq!(
+                err.kind(),
+                RecordProduceErrorKind::MalformedRecord,
+                "{}",
+                input
+            );
+            assert!(reader.produce_record().is_none(), "{}", input);
+        }
+
+        let mut reader = reader("[\n{\"TX_ID\":1,\n\"NAME\":2}\n]");
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert!(err.to_string().contains("unknown field NAME"), "{}", err);
+        assert!(err.to_string().contains("line 3"), "{}", err);
+    }
+}
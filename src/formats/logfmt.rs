@@ -0,0 +1,403 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::error::{
+    ReaderCreateError, RecordProduceError, RecordProduceErrorKind, RecordReadError,
+    RecordWriteError,
+};
+use crate::options::{
+    AmountUnit, ParseOptions, SanitizePolicy, SerializeOptions, TimestampUnit, UnknownFields,
+};
+use crate::record::{
+    DataConsumer, DataProducer, Field, FieldName, FieldValue, Position, Record, RecordSerialize,
+    RecordWriter, resolve_field, with_defaults,
+};
+use crate::result::{
+    ReaderCreateResult, RecordProduceResult, RecordReadResult, RecordSerializeResult,
+    RecordWriteResult,
+};
+
+/// Splits a logfmt line into its key value pairs.
+/// Values are bare or double quoted with `\"`, `\\`, `\n`, `\r` and `\t` escapes
+fn parse_pairs(line: &str) -> Result<Vec<(String, String)>, String> {
+    let mut pairs = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(pairs);
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|&c| c != '=' && !c.is_ascii_whitespace()) {
+            key.push(c);
+        }
+        if chars.next() != Some('=') {
+            return Err(format!("missing value of {}", key));
+        }
+        if key.is_empty() {
+            return Err("missing key".into());
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => value.push(match chars.next() {
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some(c @ ('"' | '\\')) => c,
+                        Some(c) => return Err(format!("invalid escape \\{} in {}", c, key)),
+                        None => return Err(format!("unterminated value of {}", key)),
+                    }),
+                    Some(c) => value.push(c),
+                    None => return Err(format!("unterminated value of {}", key)),
+                }
+            }
+            if chars.peek().is_some_and(|c| !c.is_ascii_whitespace()) {
+                return Err(format!("missing space after the value of {}", key));
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_ascii_whitespace()) {
+                if c == '"' {
+                    return Err(format!("unexpected quote in the value of {}", key));
+                }
+                value.push(c);
+            }
+        }
+        pairs.push((key, value));
+    }
+}
+
+/// Quotes the value when it can't be written bare
+fn quote(value: &str) -> String {
+    let bare = !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '=' | '"' | '\\'));
+    if bare {
+        return value.to_string();
+    }
+    let mut r = String::with_capacity(value.len() + 2);
+    r.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => r.push_str("\\\""),
+            '\\' => r.push_str("\\\\"),
+            '\n' => r.push_str("\\n"),
+            '\r' => r.push_str("\\r"),
+            '\t' => r.push_str("\\t"),
+            c => r.push(c),
+        }
+    }
+    r.push('"');
+    r
+}
+
+/// Reader of the logfmt format, one record of `KEY=value` pairs per line.
+/// Blank lines are skipped
+pub(crate) struct LogfmtReader<T: Read> {
+    reader: BufReader<T>,
+    current_line: u64,
+    offset: u64,
+    records: u64,
+    is_exhausted: bool,
+    amount_unit: AmountUnit,
+    timestamp_unit: TimestampUnit,
+    unknown_fields: UnknownFields,
+    defaults: Vec<FieldValue>,
+    aliases: Vec<(String, FieldName)>,
+}
+
+impl<T: Read> LogfmtReader<T> {
+    pub(crate) fn with_options(
+        reader: T,
+        options: &ParseOptions,
+    ) -> ReaderCreateResult<LogfmtReader<T>> {
+        let defaults = options
+            .defaults
+            .parse(options.amount_unit, options.timestamp_unit)
+            .map_err(|e| ReaderCreateError {
+                text: "failed to create logfmt reader".into(),
+                source: Some(Box::new(e)),
+            })?;
+        Ok(LogfmtReader {
+            reader: BufReader::new(reader),
+            current_line: 0,
+            offset: 0,
+            records: 0,
+            is_exhausted: false,
+            amount_unit: options.amount_unit,
+            timestamp_unit: options.timestamp_unit,
+            unknown_fields: options.unknown_fields,
+            defaults,
+            aliases: options.alias_table(),
+        })
+    }
+
+    fn parse_line(&self, line: &str) -> RecordProduceResult<Vec<FieldValue>> {
+        let error = |text: String| RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!("{}. near line {}", text, self.current_line),
+            source: None,
+        };
+        let pairs = parse_pairs(line).map_err(|e| error(format!("invalid logfmt: {}", e)))?;
+        let mut values: Vec<FieldValue> = Vec::with_capacity(pairs.len());
+        for (key, text) in pairs {
+            let Some(name) = resolve_field(&key, &self.aliases) else {
+                match self.unknown_fields {
+                    UnknownFields::Error => return Err(error(format!("unknown field {}", key))),
+                    UnknownFields::Ignore => continue,
+                }
+            };
+            if values.iter().any(|v| v.field() == name) {
+                return Err(error(format!("duplicate field {}", name.as_str())));
+            }
+            let value = Field::new(name, text.as_str())
+                .parse_with_units(self.amount_unit, self.timestamp_unit)
+                .map_err(|e| RecordProduceError {
+                    kind: RecordProduceErrorKind::ParseField {
+                        field_name: name.as_str().into(),
+                    },
+                    text: format!("failed to parse field. near line {}", self.current_line),
+                    source: Some(Box::new(e)),
+                })?;
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    fn parse_record(&self, line: &str) -> RecordProduceResult<Record> {
+        let values = self.parse_line(line)?;
+        Record::try_from(with_defaults(&self.defaults, values)).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!("failed to parse record. near line {}", self.current_line),
+            source: Some(e.into()),
+        })
+    }
+}
+
+impl<T: Read> DataConsumer for LogfmtReader<T> {
+    type Item = String;
+    fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
+        if self.is_exhausted {
+            return None;
+        }
+        loop {
+            let mut buf = String::new();
+            let bytes_read = match self.reader.read_line(&mut buf) {
+                Ok(count) => count,
+                Err(e) => {
+                    return Some(Err(RecordReadError {
+                        text: "couldn't read data".to_string(),
+                        source: Some(Box::new(e)),
+                    }));
+                }
+            };
+            if bytes_read == 0 {
+                self.is_exhausted = true;
+                return None;
+            }
+            self.current_line += 1;
+            self.offset += bytes_read as u64;
+            let line = buf.trim();
+            if !line.is_empty() {
+                return Some(Ok(line.to_string()));
+            }
+        }
+    }
+}
+
+impl<T: Read> DataProducer for LogfmtReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let line = match self.read()? {
+            Ok(line) => line,
+            Err(e) => {
+                return Some(Err(RecordProduceError {
+                    kind: e.kind(),
+                    text: format!("failed to read line {}", self.current_line + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        self.records += 1;
+        Some(self.parse_record(&line))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: self.current_line,
+            record: self.records,
+        })
+    }
+}
+
+pub(crate) struct LogfmtSerialize {
+    amount_unit: AmountUnit,
+    sanitize: SanitizePolicy,
+    timestamp_unit: TimestampUnit,
+}
+
+impl LogfmtSerialize {
+    pub(crate) fn with_options(options: &SerializeOptions) -> LogfmtSerialize {
+        LogfmtSerialize {
+            amount_unit: options.amount_unit,
+            sanitize: options.sanitize,
+            timestamp_unit: options.timestamp_unit,
+        }
+    }
+}
+
+impl RecordSerialize for LogfmtSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let fields = [
+            (FieldName::TxId, record.tx_id.to_string()),
+            (FieldName::TxType, record.tx_type.to_string()),
+            (FieldName::FromUser, record.from_user.to_string()),
+            (FieldName::ToUser, record.to_user.to_string()),
+            (FieldName::Amount, self.amount_unit.format(record.amount)),
+            (
+                FieldName::Timestamp,
+                self.timestamp_unit.timestamp(record)?.to_string(),
+            ),
+            (FieldName::Status, record.status.to_string()),
+            (FieldName::Description, self.sanitize.description(record)?),
+        ];
+        let pairs = fields
+            .iter()
+            .map(|(name, value)| format!("{}={}", name.as_str(), quote(value)))
+            .collect::<Vec<_>>();
+        Ok(pairs.join(" ").into_bytes())
+    }
+}
+
+/// Writes one serialized record per line
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W) -> RecordWrite<W> {
+        RecordWrite { writer }
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(&data)
+            .and_then(|_| self.writer.write_all(b"\n"))
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::record::{Status, TxType};
+
+    fn reader(input: &str) -> LogfmtReader<Cursor<String>> {
+        LogfmtReader::with_options(Cursor::new(input.to_string()), &ParseOptions::default())
+            .unwrap()
+    }
+
+    fn record(description: &str) -> Record {
+        Record::new(
+            7,
+            TxType::Transfer,
+            1,
+            2,
+            300,
+            1633036860000,
+            Status::Pending,
+            description.into(),
+        )
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let record = record("say \"hi\" to a=b\\c\nbye");
+        let data = LogfmtSerialize::with_options(&SerializeOptions::default())
+            .serialize(&record)
+            .unwrap();
+        let line = String::from_utf8(data).unwrap();
+        assert_eq!(
+            line,
+            r#"TX_ID=7 TX_TYPE=TRANSFER FROM_USER_ID=1 TO_USER_ID=2 AMOUNT=300 TIMESTAMP=1633036860000 STATUS=PENDING DESCRIPTION="say \"hi\" to a=b\\c\nbye""#
+        );
+        let mut reader = reader(&format!("\n{}\n\n{}\n", line, line));
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+        assert!(reader.produce_record().is_none());
+        assert_eq!(reader.position().unwrap().line, 4);
+    }
+
+    #[test]
+    fn test_bare_and_empty_descriptions() {
+        let serializer = LogfmtSerialize::with_options(&SerializeOptions::default());
+        for (description, expected) in [("plain", "DESCRIPTION=plain"), ("", "DESCRIPTION=\"\"")] {
+            let line =
+                String::from_utf8(serializer.serialize(&record(description)).unwrap()).unwrap();
+            assert!(line.ends_with(expected), "{}", line);
+            assert_eq!(
+                reader(&line).produce_record().unwrap().unwrap(),
+                record(description)
+            );
+        }
+    }
+
+    #[test]
+    fn test_key_order() {
+        let mut reader = reader(
+            "DESCRIPTION=\"hello world\" STATUS=PENDING TIMESTAMP=1633036860000 AMOUNT=300 \
+            TO_USER_ID=2 FROM_USER_ID=1 TX_TYPE=TRANSFER TX_ID=7",
+        );
+        assert_eq!(
+            reader.produce_record().unwrap().unwrap(),
+            record("hello world")
+        );
+    }
+
+    #[test]
+    fn test_missing_field() {
+        let mut reader = reader(
+            "\nTX_ID=1 TX_TYPE=DEPOSIT FROM_USER_ID=0 TO_USER_ID=2 TIMESTAMP=9 STATUS=SUCCESS DESCRIPTION=a\n",
+        );
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert_eq!(
+            err.kind(),
+            RecordProduceErrorKind::MissingField {
+                field_name: "AMOUNT".into()
+            }
+        );
+        assert!(err.to_string().contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        for line in [
+            "TX_ID=1 TX_ID=2",
+            "NAME=1",
+            "TX_ID",
+            "=1",
+            "TX_ID=x",
+            "DESCRIPTION=\"open",
+            "DESCRIPTION=\"a\"b",
+            "DESCRIPTION=a\"b",
+            "DESCRIPTION=\"\\x\"",
+        ] {
+            let err = reader(line).produce_record().unwrap().unwrap_err();
+            assert!(
+                !matches!(err.kind(), RecordProduceErrorKind::MissingField { .. }),
+                "{}",
+                line
+            );
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::borrow::Cow;
+use std::io::Write;
+
+use crate::csv::CsvSerialize;
+use crate::error::RecordWriteError;
+use crate::options::SerializeOptions;
+use crate::record::{Record, RecordSerialize, RecordWriter};
+use crate::result::{RecordSerializeResult, RecordWriteResult};
+
+/// Escapes a cell so it stays within its table row
+fn escape(value: &str) -> String {
+    value
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\n', '\r'], "<br>")
+}
+
+/// Row of cells, like `| 1 | DEPOSIT |`
+fn row<'a>(cells: impl Iterator<Item = Cow<'a, str>>) -> Vec<u8> {
+    let mut row = String::from("|");
+    for cell in cells {
+        row.push(' ');
+        row.push_str(&cell);
+        row.push_str(" |");
+    }
+    row.into_bytes()
+}
+
+/// Serializes a record as a row of a markdown table
+/// with the columns of csv output
+pub(crate) struct MarkdownSerialize {
+    columns: CsvSerialize<'static>,
+}
+
+impl MarkdownSerialize {
+    pub(crate) fn with_options(
+        fields: Vec<Cow<'static, str>>,
+        options: &SerializeOptions,
+    ) -> MarkdownSerialize {
+        MarkdownSerialize {
+            columns: CsvSerialize::with_options(fields, "|", options),
+        }
+    }
+}
+
+impl RecordSerialize for MarkdownSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let values = self.columns.values(record)?;
+        Ok(row(values.iter().map(|v| Cow::Owned(escape(v)))))
+    }
+}
+
+/// Writes the header and separator rows of the table before the first row
+pub(crate) struct RecordWrite<W: Write> {
+    fields: Vec<Cow<'static, str>>,
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W, fields: Vec<Cow<'static, str>>) -> RecordWrite<W> {
+        RecordWrite {
+            fields,
+            writer,
+            header_written: false,
+        }
+    }
+
+    fn write_line(&mut self, mut line: Vec<u8>) -> RecordWriteResult<()> {
+        line.push(b'\n');
+        self.writer
+            .write_all(&line)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write_header(&mut self) -> RecordWriteResult<()> {
+        if self.header_written {
+            return Ok(());
+        }
+        let header = row(self.fields.iter().map(|f| Cow::Owned(escape(f))));
+        let separator = row(self.fields.iter().map(|_| Cow::Borrowed("---")));
+        self.write_line(header)?;
+        self.write_line(separator)?;
+        self.header_written = true;
+        Ok(())
+    }
+
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.write_header()?;
+        self.write_line(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::DEFAULT_CSV_FIELDS;
+    use crate::record::{Status, TxType};
+
+    fn columns() -> Vec<Cow<'static, str>> {
+        DEFAULT_CSV_FIELDS
+            .iter()
+            .map(|&f| Cow::Borrowed(f))
+            .collect()
+    }
+
+    #[test]
+    fn test_golden_output() {
+        let records = [
+            Record::new(
+                1,
+                TxType::Deposit,
+                0,
+                2,
+                100,
+                1633036860000,
+                Status::Success,
+                "Initial deposit".into(),
+            ),
+            Record::new(
+                2,
+                TxType::Transfer,
+                2,
+                3,
+                50,
+                1633036920000,
+                Status::Pending,
+                "rent | march\nsecond line".into(),
+            ),
+            Record::new(
+                3,
+                TxType::Withdrawal,
+                3,
+                0,
+                7,
+                1633036980000,
+                Status::Failure,
+                String::new(),
+            ),
+        ];
+        let serializer = MarkdownSerialize::with_options(columns(), &SerializeOptions::default());
+        let mut output = Vec::new();
+        let mut writer = RecordWrite::new(&mut output, columns());
+        for record in &records {
+            writer.write(serializer.serialize(record).unwrap()).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\
+| TX_ID | TX_TYPE | FROM_USER_ID | TO_USER_ID | AMOUNT | TIMESTAMP | STATUS | DESCRIPTION |
+| --- | --- | --- | --- | --- | --- | --- | --- |
+| 1 | DEPOSIT | 0 | 2 | 100 | 1633036860000 | SUCCESS | Initial deposit |
+| 2 | TRANSFER | 2 | 3 | 50 | 1633036920000 | PENDING | rent \\| march<br>second line |
+| 3 | WITHDRAWAL | 3 | 0 | 7 | 1633036980000 | FAILURE |  |
+"
+        );
+    }
+
+    #[test]
+    fn test_columns() {
+        let fields = vec![Cow::Borrowed("AMOUNT"), Cow::Borrowed("TX_ID")];
+        let serializer =
+            MarkdownSerialize::with_options(fields.clone(), &SerializeOptions::default());
+        let mut output = Vec::new();
+        let mut writer = RecordWrite::new(&mut output, fields);
+        writer
+            .write(serializer.serialize(&Record::default()).unwrap())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "| AMOUNT | TX_ID |\n| --- | --- |\n| 0 | 0 |\n"
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use rmpv::Value;
+
+use crate::error::{
+    RecordProduceError, RecordProduceErrorKind, RecordReadError, RecordSerializeError,
+    RecordWriteError,
+};
+use crate::record::{
+    DataConsumer, DataProducer, Field, FieldName, FieldValue, Position, Record, RecordSerialize,
+    RecordWriter,
+};
+use crate::result::{
+    RecordProduceResult, RecordReadResult, RecordSerializeResult, RecordWriteResult,
+};
+
+/// Reader of the msgpack format, a stream of maps keyed by the field names.
+/// The stream ends cleanly only between two maps
+pub(crate) struct MsgpackReader<T: Read> {
+    reader: BufReader<T>,
+    offset: u64,
+    records: u64,
+    is_exhausted: bool,
+}
+
+impl<T: Read> MsgpackReader<T> {
+    pub(crate) fn new(reader: T) -> MsgpackReader<T> {
+        MsgpackReader {
+            reader: BufReader::new(reader),
+            offset: 0,
+            records: 0,
+            is_exhausted: false,
+        }
+    }
+
+    fn error(&self, text: String) -> RecordProduceError {
+        RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!("{}. record {}", text, self.records),
+            source: None,
+        }
+    }
+
+    fn parse_value(&self, value: Value) -> RecordProduceResult<Vec<FieldValue>> {
+        let Value::Map(entries) = value else {
+            return Err(self.error("expected a map".into()));
+        };
+        let mut values: Vec<FieldValue> = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let key = key
+                .as_str()
+                .ok_or_else(|| self.error("keys must be field names".into()))?;
+            let name = key
+                .parse::<FieldName>()
+                .map_err(|_| self.error(format!("unknown field {}", key)))?;
+            if values.iter().any(|v| v.field() == name) {
+                return Err(self.error(format!("duplicate field {}", name.as_str())));
+            }
+            let text = match value {
+                Value::Nil => continue,
+                Value::String(text) => text.into_str(),
+                Value::Integer(number) => number.as_u64().map(|number| number.to_string()),
+                _ => None,
+            };
+            let Some(text) = text else {
+                return Err(self.error(format!("invalid value of {}", name.as_str())));
+            };
+            let value =
+                Field::new(name, text.as_str())
+                    .parse()
+                    .map_err(|e| RecordProduceError {
+                        kind: RecordProduceErrorKind::ParseField {
+                            field_name: name.as_str().into(),
+                        },
+                        text: format!("failed to parse field. record {}", self.records),
+                        source: Some(Box::new(e)),
+                    })?;
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+impl<T: Read> DataConsumer for MsgpackReader<T> {
+    type Item = Value;
+    fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
+        if self.is_exhausted {
+            return None;
+        }
+        match self.reader.fill_buf() {
+            Ok([]) => {
+                self.is_exhausted = true;
+                return None;
+            }
+            Ok(_) => (),
+            Err(e) => {
+                self.is_exhausted = true;
+                return Some(Err(RecordReadError {
+                    text: "couldn't read data".into(),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        }
+        let mut counted = Counted {
+            reader: &mut self.reader,
+            count: 0,
+        };
+        let value = rmpv::decode::read_value(&mut counted);
+        self.offset += counted.count;
+        Some(value.map_err(|e| {
+            // the rest of the stream can't be aligned to maps
+            self.is_exhausted = true;
+            RecordReadError {
+                text: format!("truncated or invalid map at byte {}", self.offset),
+                source: Some(Box::new(e)),
+            }
+        }))
+    }
+}
+
+impl<T: Read> DataProducer for MsgpackReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let value = match self.read()? {
+            Ok(value) => value,
+            Err(e) => {
+                return Some(Err(RecordProduceError {
+                    kind: e.kind(),
+                    text: format!("failed to read record {}", self.records + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        self.records += 1;
+        Some(self.parse_value(value).and_then(|values| {
+            Record::try_from(values).map_err(|e| RecordProduceError {
+                kind: RecordProduceErrorKind::of_record(&e),
+                text: format!("failed to parse record {}", self.records),
+                source: Some(e.into()),
+            })
+        }))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: 0,
+            record: self.records,
+        })
+    }
+}
+
+/// Reader counting the bytes read through it
+struct Counted<'a, R: Read> {
+    reader: &'a mut R,
+    count: u64,
+}
+
+impl<R: Read> Read for Counted<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.reader.read(buf)?;
+        self.count += count as u64;
+        Ok(count)
+    }
+}
+
+/// Serializes a record as a map keyed by the field names
+#[derive(Default)]
+pub(crate) struct MsgpackSerialize;
+
+impl RecordSerialize for MsgpackSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let fields = [
+            (FieldName::TxId, Value::from(record.tx_id)),
+            (FieldName::TxType, Value::from(record.tx_type.to_string())),
+            (FieldName::FromUser, Value::from(record.from_user)),
+            (FieldName::ToUser, Value::from(record.to_user)),
+            (FieldName::Amount, Value::from(record.amount)),
+            (FieldName::Timestamp, Value::from(record.timestamp)),
+            (FieldName::Status, Value::from(record.status.to_string())),
+            (
+                FieldName::Description,
+                Value::from(record.description.as_str()),
+            ),
+        ];
+        let map = Value::Map(
+            fields
+                .into_iter()
+                .map(|(name, value)| (Value::from(name.as_str()), value))
+                .collect(),
+        );
+        let mut data = Vec::new();
+        rmpv::encode::write_value(&mut data, &map).map_err(|e| RecordSerializeError {
+            text: format!("failed to serialize record {}", record.tx_id),
+            source: Some(Box::new(e)),
+        })?;
+        Ok(data)
+    }
+}
+
+/// Writes serialized records one after another
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W) -> RecordWrite<W> {
+        RecordWrite { writer }
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(&data)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::formats::testing::{Codec, records};
+    use crate::record::RecordIterator;
+
+    fn codec() -> Codec<MsgpackReader<Cursor<Vec<u8>>>> {
+        Codec {
+            serializer: &MsgpackSerialize,
+            writer: |output| Box::new(RecordWrite::new(output)),
+            reader: MsgpackReader::new,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        codec().assert_round_trip();
+    }
+
+    #[test]
+    fn test_bin_round_trip() {
+        let bin_ser = crate::bin::RecordBytes::default();
+        let mut bin = Vec::new();
+        for record in records() {
+            bin.extend(bin_ser.serialize(&record).unwrap());
+        }
+        let from_bin = RecordIterator::new(crate::bin::BinReader::new(Cursor::new(bin)).unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let from_msgpack = RecordIterator::new(codec().read(codec().encode(&from_bin)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(from_msgpack, from_bin);
+        assert_eq!(from_msgpack, records());
+    }
+
+    #[test]
+    fn test_invalid_maps() {
+        let map = |entries: Vec<(&str, Value)>| {
+            Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (Value::from(key), value))
+                    .collect(),
+            )
+        };
+        for value in [
+            Value::from(1),
+            map(vec![("NAME", Value::from(1))]),
+            map(vec![("TX_ID", Value::from(-1))]),
+            map(vec![("TX_ID", Value::from(1)), ("TX_ID", Value::from(2))]),
+            map(vec![("TX_ID", Value::from(1.5))]),
+            map(vec![("TX_ID", Value::from(1))]),
+        ] {
+            let mut data = Vec::new();
+            rmpv::encode::write_value(&mut data, &value).unwrap();
+            // a map of a wrong layout doesn't break the stream
+            codec().assert_rejected_then_recovers(&data);
+        }
+    }
+}
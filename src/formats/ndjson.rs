@@ -0,0 +1,351 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::error::{
+    ReaderCreateError, RecordProduceError, RecordProduceErrorKind, RecordReadError,
+    RecordWriteError,
+};
+use crate::json::{JsonValue, json_string, parse_object};
+use crate::options::{
+    AmountUnit, ParseOptions, SanitizePolicy, SerializeOptions, TimestampUnit, UnknownFields,
+};
+use crate::record::{
+    DataConsumer, DataProducer, Field, FieldName, FieldValue, Position, Record, RecordSerialize,
+    RecordWriter, resolve_field, with_defaults,
+};
+use crate::result::{
+    ReaderCreateResult, RecordProduceResult, RecordReadResult, RecordSerializeResult,
+    RecordWriteResult,
+};
+
+/// Reader of the ndjson format, one JSON object per line.
+/// Blank lines are skipped
+pub(crate) struct NdjsonReader<T: Read> {
+    reader: BufReader<T>,
+    current_line: u64,
+    offset: u64,
+    records: u64,
+    is_exhausted: bool,
+    amount_unit: AmountUnit,
+    timestamp_unit: TimestampUnit,
+    unknown_fields: UnknownFields,
+    defaults: Vec<FieldValue>,
+    aliases: Vec<(String, FieldName)>,
+}
+
+impl<T: Read> NdjsonReader<T> {
+    pub(crate) fn with_options(
+        reader: T,
+        options: &ParseOptions,
+    ) -> ReaderCreateResult<NdjsonReader<T>> {
+        let defaults = options
+            .defaults
+            .parse(options.amount_unit, options.timestamp_unit)
+            .map_err(|e| ReaderCreateError {
+                text: "failed to create ndjson reader".into(),
+                source: Some(Box::new(e)),
+            })?;
+        Ok(NdjsonReader {
+            reader: BufReader::new(reader),
+            current_line: 0,
+            offset: 0,
+            records: 0,
+            is_exhausted: false,
+            amount_unit: options.amount_unit,
+            timestamp_unit: options.timestamp_unit,
+            unknown_fields: options.unknown_fields,
+            defaults,
+            aliases: options.alias_table(),
+        })
+    }
+
+    fn parse_line(&self, line: &str) -> RecordProduceResult<Vec<FieldValue>> {
+        let error = |text: String| RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!("{}. near line {}", text, self.current_line),
+            source: None,
+        };
+        let entries = parse_object(line).map_err(|e| error(format!("invalid json: {}", e)))?;
+        let mut values: Vec<FieldValue> = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let Some(name) = resolve_field(&key, &self.aliases) else {
+                match self.unknown_fields {
+                    UnknownFields::Error => return Err(error(format!("unknown field {}", key))),
+                    UnknownFields::Ignore => continue,
+                }
+            };
+            if values.iter().any(|v| v.field() == name) {
+                return Err(error(format!("duplicate field {}", name.as_str())));
+            }
+            let text = match value {
+                JsonValue::String(text) | JsonValue::Number(text) => text,
+                JsonValue::Null => continue,
+                JsonValue::Bool(_) => {
+                    return Err(error(format!("invalid value of {}", name.as_str())));
+                }
+            };
+            let value = Field::new(name, text.as_str())
+                .parse_with_units(self.amount_unit, self.timestamp_unit)
+                .map_err(|e| RecordProduceError {
+                    kind: RecordProduceErrorKind::ParseField {
+                        field_name: name.as_str().into(),
+                    },
+                    text: format!("failed to parse field. near line {}", self.current_line),
+                    source: Some(Box::new(e)),
+                })?;
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+impl<T: Read> NdjsonReader<T> {
+    fn parse_record(&self, line: &str) -> RecordProduceResult<Record> {
+        let values = self.parse_line(line)?;
+        Record::try_from(with_defaults(&self.defaults, values)).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!("failed to parse record. near line {}", self.current_line),
+            source: Some(e.into()),
+        })
+    }
+
+    /// Parses an object read outside of the reader ending at the line
+    pub(crate) fn parse_object(&mut self, object: &str, line: u64) -> RecordProduceResult<Record> {
+        self.current_line = line;
+        self.records += 1;
+        self.parse_record(object)
+    }
+}
+
+impl<T: Read> DataConsumer for NdjsonReader<T> {
+    type Item = String;
+    fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
+        if self.is_exhausted {
+            return None;
+        }
+        loop {
+            let mut buf = String::new();
+            let bytes_read = match self.reader.read_line(&mut buf) {
+                Ok(count) => count,
+                Err(e) => {
+                    return Some(Err(RecordReadError {
+                        text: "couldn't read data".to_string(),
+                        source: Some(Box::new(e)),
+                    }));
+                }
+            };
+            if bytes_read == 0 {
+                self.is_exhausted = true;
+                return None;
+            }
+            self.current_line += 1;
+            self.offset += bytes_read as u64;
+            let line = buf.trim();
+            if !line.is_empty() {
+                return Some(Ok(line.to_string()));
+            }
+        }
+    }
+}
+
+impl<T: Read> DataProducer for NdjsonReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let line = match self.read()? {
+            Ok(line) => line,
+            Err(e) => {
+                return Some(Err(RecordProduceError {
+                    kind: e.kind(),
+                    text: format!("failed to read line {}", self.current_line + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        self.records += 1;
+        Some(self.parse_record(&line))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: self.current_line,
+            record: self.records,
+        })
+    }
+}
+
+pub(crate) struct NdjsonSerialize {
+    amount_unit: AmountUnit,
+    sanitize: SanitizePolicy,
+    timestamp_unit: TimestampUnit,
+}
+
+impl NdjsonSerialize {
+    pub(crate) fn with_options(options: &SerializeOptions) -> NdjsonSerialize {
+        NdjsonSerialize {
+            amount_unit: options.amount_unit,
+            sanitize: options.sanitize,
+            timestamp_unit: options.timestamp_unit,
+        }
+    }
+}
+
+impl RecordSerialize for NdjsonSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let fields = [
+            (FieldName::TxId.as_str(), record.tx_id.to_string()),
+            (
+                FieldName::TxType.as_str(),
+                json_string(&record.tx_type.to_string()),
+            ),
+            (FieldName::FromUser.as_str(), record.from_user.to_string()),
+            (FieldName::ToUser.as_str(), record.to_user.to_string()),
+            (
+                FieldName::Amount.as_str(),
+                self.amount_unit.format(record.amount),
+            ),
+            (
+                FieldName::Timestamp.as_str(),
+                self.timestamp_unit.timestamp(record)?.to_string(),
+            ),
+            (
+                FieldName::Status.as_str(),
+                json_string(&record.status.to_string()),
+            ),
+            (
+                FieldName::Description.as_str(),
+                json_string(&self.sanitize.description(record)?),
+            ),
+        ];
+        let entries = fields
+            .iter()
+            .map(|(name, value)| format!("{}:{}", json_string(name), value))
+            .collect::<Vec<_>>();
+        Ok(format!("{{{}}}", entries.join(",")).into_bytes())
+    }
+}
+
+/// Writes one serialized record per line
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W) -> RecordWrite<W> {
+        RecordWrite { writer }
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(&data)
+            .and_then(|_| self.writer.write_all(b"\n"))
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::record::{Status, TxType};
+
+    fn reader(input: &str) -> NdjsonReader<Cursor<String>> {
+        NdjsonReader::with_options(Cursor::new(input.to_string()), &ParseOptions::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let record = Record::new(
+            7,
+            TxType::Transfer,
+            1,
+            2,
+            300,
+            1633036860000,
+            Status::Pending,
+            "say \"hi\"\nbye".into(),
+        );
+        let data = NdjsonSerialize::with_options(&SerializeOptions::default())
+            .serialize(&record)
+            .unwrap();
+        let line = String::from_utf8(data).unwrap();
+        assert_eq!(
+            line,
+            r#"{"TX_ID":7,"TX_TYPE":"TRANSFER","FROM_USER_ID":1,"TO_USER_ID":2,"AMOUNT":300,"TIMESTAMP":1633036860000,"STATUS":"PENDING","DESCRIPTION":"say \"hi\"\nbye"}"#
+        );
+        let mut reader = reader(&format!("\n{}\n\n{}\n", line, line));
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+        assert!(reader.produce_record().is_none());
+        assert_eq!(reader.position().unwrap().line, 4);
+    }
+
+    #[test]
+    fn test_missing_field() {
+        let mut reader = reader(
+            "\n{\"TX_ID\":1,\"TX_TYPE\":\"DEPOSIT\",\"FROM_USER_ID\":0,\"TO_USER_ID\":2,\"AMOUNT\":5,\"TIMESTAMP\":9}\n",
+        );
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        for line in [
+            "{\"TX_ID\":1",
+            "{\"TX_ID\":1,\"TX_ID\":2}",
+            "{\"NAME\":1}",
+            "{\"TX_ID\":true}",
+            "{\"TX_ID\":\"x\"}",
+        ] {
+            assert!(reader(line).produce_record().unwrap().is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_csv_fixture_round_trip() {
+        let csv = include_str!("../../examples/source/records_example.csv");
+        let records = crate::record::RecordIterator::new(
+            crate::builder::build_reader(Cursor::new(csv), "csv").unwrap(),
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        let serializer = NdjsonSerialize::with_options(&SerializeOptions::default());
+        let mut output = Vec::new();
+        let mut writer = RecordWrite::new(&mut output);
+        for record in &records {
+            writer.write(serializer.serialize(record).unwrap()).unwrap();
+        }
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), records.len());
+
+        let mut reader = reader(&output);
+        for record in &records {
+            assert_eq!(&reader.produce_record().unwrap().unwrap(), record);
+        }
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_malformed_amount() {
+        let mut reader = reader("\n\n{\"TX_ID\":1,\"AMOUNT\":\"ten\"}\n");
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert!(err.to_string().contains("line 3"), "{}", err);
+        assert_eq!(
+            err.kind(),
+            RecordProduceErrorKind::ParseField {
+                field_name: "AMOUNT".into()
+            }
+        );
+        assert!(
+            std::error::Error::source(&err)
+                .is_some_and(|e| e.is::<crate::error::FieldParseError>())
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use memmap2::Mmap;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::bin::{BinReader, body_size, is_self_contained};
+use crate::error::{RecordProduceError, RecordProduceErrorKind};
+use crate::record::{DataProducer, Record};
+use crate::result::RecordProduceResult;
+
+/// Memory map shared by the reader and its sequential fallback
+struct SharedMap(Arc<Mmap>);
+
+impl AsRef<[u8]> for SharedMap {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// What is found at the start of a frame
+enum FrameScan {
+    /// A frame parsing on its own ending at the offset
+    SelfContained(usize),
+    /// A frame of a dictionary encoded input depending on the frames before it
+    Shared,
+    /// A truncated or oversized frame, the input can't be split after it
+    Invalid,
+}
+
+fn bin_reader<T: Read>(input: T, checksum: bool) -> Result<BinReader<T>, Box<dyn Error>> {
+    if checksum {
+        BinReader::new(input)
+    } else {
+        BinReader::new_no_checksum(input)
+    }
+}
+
+fn scan_frame(data: &[u8], start: usize) -> FrameScan {
+    let Some(head) = data.get(start..start + 8) else {
+        return FrameScan::Invalid;
+    };
+    let mut header = [0u8; 8];
+    header.copy_from_slice(head);
+    let Ok(size) = body_size(&header) else {
+        return FrameScan::Invalid;
+    };
+    let end = start + 8 + size;
+    match data.get(start + 8..end) {
+        Some(body) if is_self_contained(&header[..4], body) => FrameScan::SelfContained(end),
+        Some(_) => FrameScan::Shared,
+        None => FrameScan::Invalid,
+    }
+}
+
+/// Reader of a memory mapped bin file parsing chunks of frames on the rayon
+/// thread pool. Every frame has a header with its size, so the input is split
+/// without parsing it. Records are produced in input order. A dictionary
+/// encoded input is read sequentially from its first dictionary frame
+pub(crate) struct ParallelBinReader {
+    map: Arc<Mmap>,
+    /// Start of the first frame not split into chunks yet
+    offset: usize,
+    chunk_size: usize,
+    checksum: bool,
+    ready: VecDeque<RecordProduceResult<Record>>,
+    sequential: Option<BinReader<Cursor<SharedMap>>>,
+}
+
+impl ParallelBinReader {
+    /// Maps the file checking that it starts with a record magic.
+    /// YPBN frames must end with the CRC32 of the body unless the checksum is off
+    pub(crate) fn open(
+        path: &Path,
+        chunk_size: usize,
+        checksum: bool,
+    ) -> Result<ParallelBinReader, Box<dyn Error>> {
+        if chunk_size == 0 {
+            return Err("chunk size must be positive".into());
+        }
+        let file = File::open(path)?;
+        // SAFETY: the map is read only. Like any memory mapped reader,
+        // this one expects the file not to be truncated while it is read
+        let map = unsafe { Mmap::map(&file)? };
+        let reader = ParallelBinReader {
+            map: Arc::new(map),
+            offset: 0,
+            chunk_size,
+            checksum,
+            ready: VecDeque::new(),
+            sequential: None,
+        };
+        bin_reader(&reader.map[..], checksum)?;
+        Ok(reader)
+    }
+
+    /// Splits the next chunks, one per thread, stopping at a frame
+    /// needing the frames before it. Returns whether one was met
+    fn split(&mut self) -> (Vec<Range<usize>>, bool) {
+        let threads = rayon::current_num_threads();
+        let mut chunks = Vec::with_capacity(threads);
+        let mut shared = false;
+        while chunks.len() < threads && self.offset < self.map.len() && !shared {
+            let start = self.offset;
+            for _ in 0..self.chunk_size {
+                if self.offset == self.map.len() {
+                    break;
+                }
+                match scan_frame(&self.map, self.offset) {
+                    FrameScan::SelfContained(end) => self.offset = end,
+                    FrameScan::Shared => {
+                        shared = true;
+                        break;
+                    }
+                    FrameScan::Invalid => {
+                        // the chunk reader reports the error
+                        self.offset = self.map.len();
+                        break;
+                    }
+                }
+            }
+            if self.offset > start {
+                chunks.push(start..self.offset);
+            }
+        }
+        (chunks, shared)
+    }
+
+    /// Parses the chunks in parallel. A chunk is parsed up to its first error,
+    /// the rest of it is parsed here since errors can't be sent between threads
+    fn read_chunks(&mut self) {
+        let (chunks, shared) = self.split();
+        let (map, checksum) = (&self.map, self.checksum);
+        let parsed = chunks
+            .clone()
+            .into_par_iter()
+            .map(|chunk| {
+                let Ok(mut reader) = bin_reader(&map[chunk], checksum) else {
+                    return (Vec::new(), Some(0));
+                };
+                let mut records = Vec::new();
+                loop {
+                    let before = reader.position().map_or(0, |p| p.byte as usize);
+                    match reader.produce_record() {
+                        Some(Ok(record)) => records.push(record),
+                        Some(Err(_)) => return (records, Some(before)),
+                        None => return (records, None),
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        for (chunk, (records, failed)) in chunks.into_iter().zip(parsed) {
+            self.ready.extend(records.into_iter().map(Ok));
+            let Some(failed) = failed else {
+                continue;
+            };
+            match bin_reader(&self.map[chunk.start + failed..chunk.end], self.checksum) {
+                Ok(mut reader) => {
+                    while let Some(record) = reader.produce_record() {
+                        self.ready.push_back(record);
+                    }
+                }
+                Err(e) => self.ready.push_back(Err(RecordProduceError {
+                    kind: RecordProduceErrorKind::MalformedRecord,
+                    text: "failed to read record".into(),
+                    source: Some(e.to_string().into()),
+                })),
+            }
+        }
+        if shared {
+            let mut input = Cursor::new(SharedMap(self.map.clone()));
+            input.set_position(self.offset as u64);
+            self.offset = self.map.len();
+            // the input starts with the magic of the shared frame
+            self.sequential = bin_reader(input, self.checksum).ok();
+        }
+    }
+}
+
+impl DataProducer for ParallelBinReader {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        loop {
+            if let Some(record) = self.ready.pop_front() {
+                return Some(record);
+            }
+            if let Some(reader) = self.sequential.as_mut() {
+                return reader.produce_record();
+            }
+            if self.offset == self.map.len() {
+                return None;
+            }
+            self.read_chunks();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::bin::{BinEncoding, DictionaryRecordBytes, RecordBytes};
+    use crate::builder::build_reader;
+    use crate::record::{RecordIterator, RecordSerialize, Status, TxType};
+
+    fn records() -> Vec<Record> {
+        (0..100)
+            .map(|i| {
+                Record::new(
+                    i,
+                    TxType::Transfer,
+                    i + 1,
+                    i + 2,
+                    100 * i,
+                    1633036860000 + i,
+                    Status::Success,
+                    format!("Record number {}", i % 7),
+                )
+            })
+            .collect()
+    }
+
+    fn read_all(reader: impl DataProducer) -> Vec<Result<Record, String>> {
+        RecordIterator::new(reader)
+            .map(|r| r.map_err(|e| format!("{:?}", e)))
+            .collect()
+    }
+
+    #[test]
+    fn test_same_records_as_sequential() {
+        let dictionary = DictionaryRecordBytes::default();
+        let mut corrupted = Vec::new();
+        let mut dictionary_tail = Vec::new();
+        let mut compact = Vec::new();
+        for (i, record) in records().iter().enumerate() {
+            let mut frame = RecordBytes::default().serialize(record).unwrap();
+            if i == 41 {
+                let last = frame.len() - 1;
+                frame[last] ^= 1;
+            }
+            corrupted.extend(frame);
+            let frame = RecordBytes::default().serialize(record).unwrap();
+            if i < 30 {
+                dictionary_tail.extend(frame);
+            } else {
+                dictionary_tail.extend(dictionary.serialize(record).unwrap());
+            }
+            compact.extend(
+                RecordBytes::new(BinEncoding::Compact)
+                    .serialize(record)
+                    .unwrap(),
+            );
+        }
+        let mut truncated = corrupted.clone();
+        truncated.truncate(truncated.len() - 3);
+
+        for input in [corrupted, dictionary_tail, compact, truncated, Vec::new()] {
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            file.write_all(&input).unwrap();
+            let expected = read_all(build_reader(Cursor::new(input), "bin").unwrap());
+            for chunk_size in [1, 3, 1000] {
+                let reader = ParallelBinReader::open(file.path(), chunk_size, true).unwrap();
+                assert_eq!(read_all(reader), expected, "{}", chunk_size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_open_checks_input() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"TX_ID: 1\n").unwrap();
+        assert!(ParallelBinReader::open(file.path(), 10, true).is_err());
+        assert!(ParallelBinReader::open(file.path(), 0, true).is_err());
+    }
+}
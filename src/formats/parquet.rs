@@ -0,0 +1,240 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::Arc;
+
+use ::parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+use ::parquet::errors::ParquetError;
+use ::parquet::file::properties::WriterProperties;
+use ::parquet::file::writer::SerializedFileWriter;
+use ::parquet::schema::parser::parse_message_type;
+
+use crate::error::RecordWriteError;
+use crate::record::{FieldName, Record, RecordWriter};
+use crate::result::RecordWriteResult;
+
+/// Schema of the written files with a column per field in the order of [`Record`] fields.
+/// Numbers are INT64 annotated as unsigned, the enums and the description are UTF8
+const SCHEMA: &str = "message record {
+    REQUIRED INT64 TX_ID (INTEGER(64, false));
+    REQUIRED BYTE_ARRAY TX_TYPE (UTF8);
+    REQUIRED INT64 FROM_USER_ID (INTEGER(64, false));
+    REQUIRED INT64 TO_USER_ID (INTEGER(64, false));
+    REQUIRED INT64 AMOUNT (INTEGER(64, false));
+    REQUIRED INT64 TIMESTAMP (INTEGER(64, false));
+    REQUIRED BYTE_ARRAY STATUS (UTF8);
+    REQUIRED BYTE_ARRAY DESCRIPTION (UTF8);
+}";
+
+/// Rows of a row group unless given otherwise
+const DEFAULT_ROW_GROUP_SIZE: usize = 10_000;
+
+/// Writer of records to a parquet file. Records are buffered into row groups
+/// of ten thousand rows by default, the last row group and the file footer
+/// are written by [`RecordWriter::finish`]. Text columns are dictionary encoded.
+/// Only [`RecordWriter::write_record`] writes rows since the columns are built
+/// from the record rather than its serialized form
+pub struct ParquetWriter<W: Write + Send> {
+    writer: SerializedFileWriter<W>,
+    row_group_size: usize,
+    pending: Vec<Record>,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    /// Creates a writer with the default row group size
+    pub fn new(writer: W) -> Result<ParquetWriter<W>, Box<dyn Error>> {
+        ParquetWriter::with_row_group_size(
+            writer,
+            NonZeroUsize::new(DEFAULT_ROW_GROUP_SIZE).unwrap(),
+        )
+    }
+
+    /// Creates a writer putting the given number of rows in a row group
+    pub fn with_row_group_size(
+        writer: W,
+        row_group_size: NonZeroUsize,
+    ) -> Result<ParquetWriter<W>, Box<dyn Error>> {
+        let schema = Arc::new(parse_message_type(SCHEMA)?);
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(row_group_size.get())
+            .build();
+        Ok(ParquetWriter {
+            writer: SerializedFileWriter::new(writer, schema, Arc::new(properties))?,
+            row_group_size: row_group_size.get(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// The underlying writer. The file is complete only
+    /// after [`RecordWriter::finish`]
+    pub fn get_ref(&self) -> &W {
+        self.writer.inner()
+    }
+
+    fn error(text: &str, e: ParquetError) -> RecordWriteError {
+        RecordWriteError {
+            text: text.into(),
+            source: Some(Box::new(e)),
+        }
+    }
+
+    fn write_row_group(&mut self) -> Result<(), ParquetError> {
+        let mut row_group = self.writer.next_row_group()?;
+        for name in FieldName::ALL {
+            let mut column = row_group
+                .next_column()?
+                .ok_or_else(|| ParquetError::General(format!("no column for {}", name)))?;
+            match name {
+                FieldName::TxType | FieldName::Status | FieldName::Description => {
+                    let values = self
+                        .pending
+                        .iter()
+                        .map(|r| match name {
+                            FieldName::TxType => ByteArray::from(r.tx_type.to_string().as_str()),
+                            FieldName::Status => ByteArray::from(r.status.to_string().as_str()),
+                            _ => ByteArray::from(r.description.as_str()),
+                        })
+                        .collect::<Vec<_>>();
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, None, None)?;
+                }
+                _ => {
+                    // unsigned values are stored in the bits of signed ones
+                    let values = self
+                        .pending
+                        .iter()
+                        .map(|r| match name {
+                            FieldName::TxId => r.tx_id as i64,
+                            FieldName::FromUser => r.from_user as i64,
+                            FieldName::ToUser => r.to_user as i64,
+                            FieldName::Amount => r.amount as i64,
+                            _ => r.timestamp as i64,
+                        })
+                        .collect::<Vec<_>>();
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, None, None)?;
+                }
+            }
+            column.close()?;
+        }
+        row_group.close()?;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl ParquetWriter<File> {
+    /// Creates a writer to the file at the path, truncating an existing file
+    pub fn create(path: &Path) -> Result<ParquetWriter<File>, Box<dyn Error>> {
+        ParquetWriter::new(File::create(path)?)
+    }
+}
+
+impl<W: Write + Send> RecordWriter for ParquetWriter<W> {
+    fn write_comment(&mut self, _text: &str) -> RecordWriteResult<()> {
+        Ok(())
+    }
+
+    fn write(&mut self, _data: Vec<u8>) -> RecordWriteResult<()> {
+        Err(RecordWriteError {
+            text: "parquet rows are written from records, not serialized data".into(),
+            source: None,
+        })
+    }
+
+    fn write_record(&mut self, record: &Record, _data: Vec<u8>) -> RecordWriteResult<()> {
+        self.pending.push(record.clone());
+        if self.pending.len() == self.row_group_size {
+            self.write_row_group()
+                .map_err(|e| ParquetWriter::<W>::error("failed to write a row group", e))?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> RecordWriteResult<()> {
+        if !self.pending.is_empty() {
+            self.write_row_group()
+                .map_err(|e| ParquetWriter::<W>::error("failed to write a row group", e))?;
+        }
+        self.writer
+            .finish()
+            .map(|_| ())
+            .map_err(|e| ParquetWriter::<W>::error("failed to write the file footer", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::parquet::file::reader::{FileReader, SerializedFileReader};
+    use ::parquet::record::Field;
+
+    use super::*;
+    use crate::record::{Status, TxType};
+
+    fn records() -> Vec<Record> {
+        (0..5)
+            .map(|i| {
+                Record::new(
+                    u64::MAX - i,
+                    TxType::Transfer,
+                    i,
+                    i + 1,
+                    100 * i,
+                    1633036860000 + i,
+                    [Status::Success, Status::Failure][i as usize % 2],
+                    format!("Record number {}, \"quoted\"", i),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("records.parquet");
+        let file = File::create(&path).unwrap();
+        let mut writer =
+            ParquetWriter::with_row_group_size(file, NonZeroUsize::new(2).unwrap()).unwrap();
+        for record in records() {
+            writer.write_record(&record, Vec::new()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 3);
+        let rows = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 5);
+        for (row, record) in rows.iter().zip(records()) {
+            let columns = row.get_column_iter().collect::<Vec<_>>();
+            let names = columns.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>();
+            assert_eq!(names, FieldName::ALL.map(|n| n.as_str()));
+            assert_eq!(columns[0].1, &Field::ULong(record.tx_id));
+            assert_eq!(columns[1].1, &Field::Str("TRANSFER".into()));
+            assert_eq!(columns[4].1, &Field::ULong(record.amount));
+            assert_eq!(columns[6].1, &Field::Str(record.status.to_string()));
+            assert_eq!(columns[7].1, &Field::Str(record.description.clone()));
+        }
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let mut writer = ParquetWriter::new(Vec::new()).unwrap();
+        writer.finish().unwrap();
+        let data = writer.get_ref();
+        assert!(data.starts_with(b"PAR1") && data.ends_with(b"PAR1"));
+    }
+
+    #[test]
+    fn test_serialized_data() {
+        let mut writer = ParquetWriter::new(Vec::new()).unwrap();
+        assert!(writer.write(b"data".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::io::Write;
+
+use crate::error::RecordWriteError;
+use crate::record::{FieldName, Record, RecordSerialize, RecordWriter, sql_identifier};
+use crate::result::{RecordSerializeResult, RecordWriteResult};
+
+/// Null of the COPY text format, written for an empty description
+const NULL: &str = "\\N";
+
+/// End of data marker following the rows of a `COPY ... FROM stdin` statement
+const END_OF_DATA: &[u8] = b"\\.";
+
+/// Escapes the characters with a meaning in the COPY text format
+fn escape(value: &str) -> String {
+    let mut r = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => r.push_str("\\\\"),
+            '\t' => r.push_str("\\t"),
+            '\n' => r.push_str("\\n"),
+            '\r' => r.push_str("\\r"),
+            c => r.push(c),
+        }
+    }
+    r
+}
+
+/// Serializes a record as a row of the PostgreSQL COPY text format,
+/// the fields tab separated in the order of [`FieldName::ALL`].
+/// Values are stored as in [`Record::to_sql_insert`]
+#[derive(Default)]
+pub(crate) struct PgcopySerialize;
+
+impl RecordSerialize for PgcopySerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let values = FieldName::ALL.map(|name| match name {
+            FieldName::TxId => record.tx_id.to_string(),
+            FieldName::TxType => record.tx_type.to_string(),
+            FieldName::FromUser => record.from_user.to_string(),
+            FieldName::ToUser => record.to_user.to_string(),
+            FieldName::Amount => record.amount.to_string(),
+            FieldName::Timestamp => record.timestamp.to_string(),
+            FieldName::Status => record.status.to_string(),
+            FieldName::Description if record.description.is_empty() => NULL.to_string(),
+            FieldName::Description => escape(&record.description),
+        });
+        Ok(values.join("\t").into_bytes())
+    }
+}
+
+/// Writes one row per line. Given a table the rows are preceded
+/// by a `COPY table (columns) FROM stdin;` statement and followed
+/// by the end of data marker, so the output can be run by psql
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+    table: Option<String>,
+    header_written: bool,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W, table: Option<String>) -> RecordWrite<W> {
+        RecordWrite {
+            writer,
+            table,
+            header_written: false,
+        }
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(line)
+            .and_then(|_| self.writer.write_all(b"\n"))
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write_header(&mut self) -> RecordWriteResult<()> {
+        let Some(table) = &self.table else {
+            return Ok(());
+        };
+        if self.header_written {
+            return Ok(());
+        }
+        let statement = format!(
+            "COPY {} ({}) FROM stdin;",
+            sql_identifier(table),
+            FieldName::ALL.map(|name| name.as_str()).join(", ")
+        );
+        self.write_line(statement.as_bytes())?;
+        self.header_written = true;
+        Ok(())
+    }
+
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.write_header()?;
+        self.write_line(&data)
+    }
+
+    fn finish(&mut self) -> RecordWriteResult<()> {
+        if self.header_written {
+            self.write_line(END_OF_DATA)?;
+            self.header_written = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::{Status, TxType};
+
+    fn records() -> [Record; 3] {
+        [
+            Record::new(
+                1,
+                TxType::Deposit,
+                0,
+                2,
+                100,
+                1633036860000,
+                Status::Success,
+                "Initial deposit".into(),
+            ),
+            Record::new(
+                2,
+                TxType::Transfer,
+                2,
+                3,
+                50,
+                1633036920000,
+                Status::Pending,
+                "rent\tmarch\nC:\\rent\r".into(),
+            ),
+            Record::new(
+                3,
+                TxType::Withdrawal,
+                3,
+                0,
+                7,
+                1633036980000,
+                Status::Failure,
+                String::new(),
+            ),
+        ]
+    }
+
+    fn write(table: Option<&str>) -> String {
+        let mut output = Vec::new();
+        let mut writer = RecordWrite::new(&mut output, table.map(str::to_string));
+        writer.write_header().unwrap();
+        for record in &records() {
+            writer
+                .write(PgcopySerialize.serialize(record).unwrap())
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a\tb"), "a\\tb");
+        assert_eq!(escape("a\nb\r\n"), "a\\nb\\r\\n");
+        assert_eq!(escape("C:\\temp\\\\"), "C:\\\\temp\\\\\\\\");
+        assert_eq!(escape("\\N"), "\\\\N");
+        assert_eq!(escape("café, \"quoted\""), "café, \"quoted\"");
+    }
+
+    #[test]
+    fn test_golden_output() {
+        assert_eq!(
+            write(None),
+            "\
+1\tDEPOSIT\t0\t2\t100\t1633036860000\tSUCCESS\tInitial deposit
+2\tTRANSFER\t2\t3\t50\t1633036920000\tPENDING\trent\\tmarch\\nC:\\\\rent\\r
+3\tWITHDRAWAL\t3\t0\t7\t1633036980000\tFAILURE\t\\N
+"
+        );
+    }
+
+    #[test]
+    fn test_copy_statement() {
+        let output = write(Some("tx\"log"));
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "COPY \"tx\"\"log\" (TX_ID, TX_TYPE, FROM_USER_ID, TO_USER_ID, AMOUNT, TIMESTAMP, STATUS, DESCRIPTION) FROM stdin;"
+        );
+        assert_eq!(lines[1..4], write(None).lines().collect::<Vec<_>>());
+        assert_eq!(lines[4..], ["\\."]);
+    }
+}
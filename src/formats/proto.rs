@@ -0,0 +1,469 @@
+//! Length-delimited protobuf messages of the schema
+//!
+//! ```proto
+//! message Transaction {
+//!   uint64 tx_id = 1;
+//!   TxType tx_type = 2;      // DEPOSIT = 0, TRANSFER = 1, WITHDRAWAL = 2
+//!   uint64 from_user_id = 3;
+//!   uint64 to_user_id = 4;
+//!   uint64 amount = 5;
+//!   uint64 timestamp = 6;
+//!   Status status = 7;       // SUCCESS = 0, FAILURE = 1, PENDING = 2
+//!   string description = 8;
+//! }
+//! ```
+//!
+//! Every field is written, also the zero ones, and is required on read.
+//! Each message is prefixed with its varint length
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::bin::{MAX_VARINT_LENGTH, get_varint, put_varint};
+use crate::error::{RecordProduceError, RecordProduceErrorKind, RecordReadError, RecordWriteError};
+use crate::record::{
+    DataConsumer, DataProducer, FieldName, FieldValue, Position, Record, RecordSerialize,
+    RecordWriter, Status, TxType,
+};
+use crate::result::{
+    RecordProduceResult, RecordReadResult, RecordSerializeResult, RecordWriteResult,
+};
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LENGTH_DELIMITED: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+/// Max length of a message. The description with room for the other fields
+const MAX_MESSAGE_LENGTH: u64 = 1048576 + 128;
+
+/// Fields in the order of their numbers starting at 1
+const FIELDS: [FieldName; 8] = [
+    FieldName::TxId,
+    FieldName::TxType,
+    FieldName::FromUser,
+    FieldName::ToUser,
+    FieldName::Amount,
+    FieldName::Timestamp,
+    FieldName::Status,
+    FieldName::Description,
+];
+
+/// Reader of length-delimited protobuf messages.
+/// The stream ends cleanly only between two messages
+pub(crate) struct ProtoReader<T: Read> {
+    reader: BufReader<T>,
+    offset: u64,
+    records: u64,
+    is_exhausted: bool,
+}
+
+impl<T: Read> ProtoReader<T> {
+    pub(crate) fn new(reader: T) -> ProtoReader<T> {
+        ProtoReader {
+            reader: BufReader::new(reader),
+            offset: 0,
+            records: 0,
+            is_exhausted: false,
+        }
+    }
+
+    fn error(&self, text: String) -> RecordProduceError {
+        RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!("{}. record {}", text, self.records),
+            source: None,
+        }
+    }
+
+    fn read_message(&mut self) -> Result<Vec<u8>, String> {
+        let start = self.offset;
+        let mut prefix = Vec::with_capacity(MAX_VARINT_LENGTH);
+        let mut byte = [0u8; 1];
+        loop {
+            if let Err(e) = self.reader.read_exact(&mut byte) {
+                return Err(match e.kind() {
+                    io::ErrorKind::UnexpectedEof => {
+                        format!("truncated message length at byte {}", start)
+                    }
+                    _ => format!("couldn't read data at byte {}: {}", start, e),
+                });
+            }
+            prefix.push(byte[0]);
+            if byte[0] & 0x80 == 0 || prefix.len() == MAX_VARINT_LENGTH {
+                break;
+            }
+        }
+        self.offset += prefix.len() as u64;
+        let (length, _) = get_varint(&prefix)
+            .map_err(|e| format!("invalid message length at byte {}: {}", start, e))?;
+        if length > MAX_MESSAGE_LENGTH {
+            return Err(format!(
+                "message of {} bytes is greater than the max limit {}",
+                length, MAX_MESSAGE_LENGTH
+            ));
+        }
+        let mut message = vec![0u8; length as usize];
+        self.reader.read_exact(&mut message).map_err(|e| {
+            format!(
+                "truncated message at byte {}. expected {} bytes: {}",
+                self.offset, length, e
+            )
+        })?;
+        self.offset += length;
+        Ok(message)
+    }
+
+    fn parse_message(&self, mut message: &[u8]) -> RecordProduceResult<Record> {
+        let varint = |message: &mut &[u8]| {
+            let (value, count) =
+                get_varint(message).map_err(|e| self.error(format!("invalid varint: {}", e)))?;
+            *message = &message[count..];
+            Ok::<u64, RecordProduceError>(value)
+        };
+        let mut values: Vec<FieldValue> = Vec::with_capacity(FIELDS.len());
+        while !message.is_empty() {
+            let key = varint(&mut message)?;
+            let (number, wire_type) = (key >> 3, key & 0x7);
+            let field = number
+                .checked_sub(1)
+                .and_then(|i| FIELDS.get(i as usize).copied());
+            let payload = match wire_type {
+                WIRE_VARINT => None,
+                WIRE_FIXED64 | WIRE_FIXED32 => {
+                    let size = if wire_type == WIRE_FIXED64 { 8 } else { 4 };
+                    let Some(rest) = message.get(size..) else {
+                        return Err(self.error(format!("truncated field {}", number)));
+                    };
+                    message = rest;
+                    if field.is_none() {
+                        continue;
+                    }
+                    return Err(self.error(format!("invalid wire type of field {}", number)));
+                }
+                WIRE_LENGTH_DELIMITED => {
+                    let length = varint(&mut message)?;
+                    let Some(payload) = message.get(..length as usize) else {
+                        return Err(self.error(format!("truncated field {}", number)));
+                    };
+                    message = &message[length as usize..];
+                    Some(payload)
+                }
+                _ => {
+                    return Err(self.error(format!(
+                        "unsupported wire type {} of field {}",
+                        wire_type, number
+                    )));
+                }
+            };
+            let value = match payload {
+                None => Some(varint(&mut message)?),
+                Some(_) => None,
+            };
+            // unknown fields are skipped
+            let Some(name) = field else {
+                continue;
+            };
+            if values.iter().any(|v| v.field() == name) {
+                return Err(self.error(format!("duplicate field {}", name.as_str())));
+            }
+            let invalid = || self.error(format!("invalid wire type of field {}", number));
+            let value = match (name, value, payload) {
+                (FieldName::Description, None, Some(text)) => FieldValue::Description(
+                    String::from_utf8(text.to_vec())
+                        .map_err(|_| self.error("description isn't utf-8".into()))?,
+                ),
+                (FieldName::TxType | FieldName::Status, Some(code), None) => {
+                    let parsed = u8::try_from(code)
+                        .map_err(|e| e.to_string())
+                        .and_then(|code| match name {
+                            FieldName::TxType => TxType::try_from(&code).map(FieldValue::TxType),
+                            _ => Status::try_from(&code).map(FieldValue::Status),
+                        });
+                    parsed.map_err(|e| RecordProduceError {
+                        kind: RecordProduceErrorKind::ParseField {
+                            field_name: name.as_str().into(),
+                        },
+                        text: format!("failed to parse field. record {}", self.records),
+                        source: Some(e.into()),
+                    })?
+                }
+                (FieldName::TxId, Some(n), None) => FieldValue::TxId(n),
+                (FieldName::FromUser, Some(n), None) => FieldValue::FromUser(n),
+                (FieldName::ToUser, Some(n), None) => FieldValue::ToUser(n),
+                (FieldName::Amount, Some(n), None) => FieldValue::Amount(n),
+                (FieldName::Timestamp, Some(n), None) => FieldValue::Timestamp(n),
+                _ => return Err(invalid()),
+            };
+            values.push(value);
+        }
+        Record::try_from(values).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!("failed to parse record {}", self.records),
+            source: Some(e.into()),
+        })
+    }
+}
+
+impl<T: Read> DataConsumer for ProtoReader<T> {
+    type Item = Vec<u8>;
+    fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
+        if self.is_exhausted {
+            return None;
+        }
+        match self.reader.fill_buf() {
+            Ok([]) => {
+                self.is_exhausted = true;
+                return None;
+            }
+            Ok(_) => (),
+            Err(e) => {
+                self.is_exhausted = true;
+                return Some(Err(RecordReadError {
+                    text: "couldn't read data".into(),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        }
+        Some(self.read_message().map_err(|text| {
+            // the rest of the stream can't be aligned to messages
+            self.is_exhausted = true;
+            RecordReadError { text, source: None }
+        }))
+    }
+}
+
+impl<T: Read> DataProducer for ProtoReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let message = match self.read()? {
+            Ok(message) => message,
+            Err(e) => {
+                return Some(Err(RecordProduceError {
+                    kind: e.kind(),
+                    text: format!("failed to read record {}", self.records + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        self.records += 1;
+        Some(self.parse_message(&message))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: 0,
+            record: self.records,
+        })
+    }
+}
+
+/// Serializes a record as a length-delimited message of the schema
+#[derive(Default)]
+pub(crate) struct ProtoSerialize;
+
+impl RecordSerialize for ProtoSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let numbers = [
+            record.tx_id,
+            u8::from(&record.tx_type) as u64,
+            record.from_user,
+            record.to_user,
+            record.amount,
+            record.timestamp,
+            u8::from(&record.status) as u64,
+        ];
+        let mut message = Vec::with_capacity(64 + record.description.len());
+        for (number, value) in (1..).zip(numbers) {
+            put_varint(&mut message, number << 3 | WIRE_VARINT);
+            put_varint(&mut message, value);
+        }
+        put_varint(&mut message, 8 << 3 | WIRE_LENGTH_DELIMITED);
+        put_varint(&mut message, record.description.len() as u64);
+        message.extend_from_slice(record.description.as_bytes());
+
+        let mut data = Vec::with_capacity(MAX_VARINT_LENGTH + message.len());
+        put_varint(&mut data, message.len() as u64);
+        data.extend(message);
+        Ok(data)
+    }
+}
+
+/// Writes serialized records one after another
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W) -> RecordWrite<W> {
+        RecordWrite { writer }
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(&data)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::formats::testing::Codec;
+
+    fn codec() -> Codec<ProtoReader<Cursor<Vec<u8>>>> {
+        Codec {
+            serializer: &ProtoSerialize,
+            writer: |output| Box::new(RecordWrite::new(output)),
+            reader: ProtoReader::new,
+        }
+    }
+
+    /// Message of a record with tx_id 150, without its length prefix
+    const MESSAGE: &[u8] = &[
+        0x08, 0x96, 0x01, // tx_id 150
+        0x10, 0x00, // tx_type DEPOSIT
+        0x18, 0x00, // from_user_id 0
+        0x20, 0x01, // to_user_id 1
+        0x28, 0x01, // amount 1
+        0x30, 0x00, // timestamp 0
+        0x38, 0x00, // status SUCCESS
+        0x42, 0x00, // description ""
+    ];
+
+    fn record() -> Record {
+        Record::new(
+            150,
+            TxType::Deposit,
+            0,
+            1,
+            1,
+            0,
+            Status::Success,
+            String::new(),
+        )
+    }
+
+    /// The message with its length prefix
+    fn delimited(message: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        put_varint(&mut data, message.len() as u64);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn test_round_trip() {
+        codec().assert_round_trip();
+    }
+
+    #[test]
+    fn test_fixture() {
+        assert_eq!(
+            ProtoSerialize.serialize(&record()).unwrap(),
+            delimited(MESSAGE)
+        );
+        let mut reader = codec().read(delimited(MESSAGE));
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record());
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let mut message = vec![0x48, 0x05]; // 9: varint
+        message.extend(&MESSAGE[..3]);
+        message.extend([0x51, 1, 2, 3, 4, 5, 6, 7, 8]); // 10: fixed64
+        message.extend(&MESSAGE[3..]);
+        message.extend([0x5a, 0x02, b'h', b'i']); // 11: bytes
+        message.extend([0x65, 1, 2, 3, 4]); // 12: fixed32
+        message.extend([0x80, 0x01, 0x00]); // 16: two byte key
+        message.extend([0xf8, 0xff, 0xff, 0xff, 0x0f, 0x07]); // the max field number
+        let mut reader = codec().read(delimited(&message));
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record());
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_repeated_fields() {
+        // unknown repeated fields are skipped unpacked and packed
+        let mut message = MESSAGE.to_vec();
+        message.extend([0x48, 0x01, 0x48, 0x02, 0x48, 0x03]);
+        message.extend([0x4a, 0x03, 0x01, 0x02, 0x03]);
+        let mut reader = codec().read(delimited(&message));
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record());
+
+        // the fields of the schema are singular, a repeated one is
+        // rejected instead of the last value winning
+        let mut unpacked = MESSAGE.to_vec();
+        unpacked.extend([0x08, 0x97, 0x01]);
+        let mut packed = MESSAGE[3..].to_vec();
+        packed.extend([0x0a, 0x02, 0x96, 0x01]);
+        for message in [unpacked, packed] {
+            codec().assert_rejected_then_recovers(&delimited(&message));
+        }
+    }
+
+    #[test]
+    fn test_wire_type_mismatch() {
+        let replace = |field: usize, encoded: &[u8]| {
+            // offsets of the fields in MESSAGE
+            let bounds = [0, 3, 5, 7, 9, 11, 13, 15, 17];
+            let mut message = MESSAGE[..bounds[field - 1]].to_vec();
+            message.extend_from_slice(encoded);
+            message.extend_from_slice(&MESSAGE[bounds[field]..]);
+            delimited(&message)
+        };
+        for message in [
+            replace(1, &[0x09, 0x96, 0, 0, 0, 0, 0, 0, 0]), // tx_id as fixed64
+            replace(5, &[0x2d, 0x01, 0, 0, 0]),             // amount as fixed32
+            replace(2, &[0x12, 0x01, 0x00]),                // tx_type as bytes
+            replace(8, &[0x40, 0x00]),                      // description as varint
+            replace(8, &[0x45, 0, 0, 0, 0]),                // description as fixed32
+        ] {
+            let mut reader = codec().read(message.clone());
+            let err = reader.produce_record().unwrap().unwrap_err();
+            assert_eq!(err.kind(), RecordProduceErrorKind::MalformedRecord);
+            assert!(
+                err.to_string().contains("invalid wire type of field"),
+                "{}",
+                err
+            );
+            codec().assert_rejected_then_recovers(&message);
+        }
+    }
+
+    #[test]
+    fn test_missing_field() {
+        // the message without the status
+        let message = [&MESSAGE[..13], &MESSAGE[15..]].concat();
+        let err = codec()
+            .read(delimited(&message))
+            .produce_record()
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            RecordProduceErrorKind::MissingField { .. }
+        ));
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        for message in [
+            &[0x08][..],             // truncated varint
+            &[0x42, 0x05, b'a'][..], // truncated description
+            &[0x43][..],             // group wire type
+            &[0x10, 0x07][..],       // unknown tx_type
+        ] {
+            // a malformed message doesn't break the stream
+            codec().assert_rejected_then_recovers(&delimited(message));
+        }
+    }
+}
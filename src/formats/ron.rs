@@ -0,0 +1,610 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use crate::error::{
+    ReaderCreateError, RecordProduceError, RecordProduceErrorKind, RecordReadError,
+    RecordWriteError,
+};
+use crate::options::{
+    AmountUnit, ParseOptions, SanitizePolicy, SerializeOptions, TimestampUnit, UnknownFields,
+};
+use crate::record::{
+    DataConsumer, DataProducer, Field, FieldName, FieldValue, Position, Record, RecordSerialize,
+    RecordWriter, resolve_field, with_defaults,
+};
+use crate::result::{
+    ReaderCreateResult, RecordProduceResult, RecordReadResult, RecordSerializeResult,
+    RecordWriteResult,
+};
+
+/// Name of the struct every record is written as
+const STRUCT_NAME: &str = "Record";
+
+/// Value of a struct field
+#[derive(Debug, PartialEq)]
+pub(crate) enum RonValue {
+    Number(String),
+    Text(String),
+    /// Enum variant or any other bare identifier
+    Ident(String),
+}
+
+/// Reader of the ron format, a sequence of `Record(NAME: value, ...)` structs.
+/// The struct name may be omitted, `//` and `/* */` comments are skipped
+pub(crate) struct RonReader<T: Read> {
+    reader: BufReader<T>,
+    current_line: u64,
+    offset: u64,
+    records: u64,
+    is_exhausted: bool,
+    amount_unit: AmountUnit,
+    timestamp_unit: TimestampUnit,
+    unknown_fields: UnknownFields,
+    defaults: Vec<FieldValue>,
+    aliases: Vec<(String, FieldName)>,
+}
+
+impl<T: Read> RonReader<T> {
+    pub(crate) fn with_options(
+        reader: T,
+        options: &ParseOptions,
+    ) -> ReaderCreateResult<RonReader<T>> {
+        let defaults = options
+            .defaults
+            .parse(options.amount_unit, options.timestamp_unit)
+            .map_err(|e| ReaderCreateError {
+                text: "failed to create ron reader".into(),
+                source: Some(Box::new(e)),
+            })?;
+        Ok(RonReader {
+            reader: BufReader::new(reader),
+            current_line: 1,
+            offset: 0,
+            records: 0,
+            is_exhausted: false,
+            amount_unit: options.amount_unit,
+            timestamp_unit: options.timestamp_unit,
+            unknown_fields: options.unknown_fields,
+            defaults,
+            aliases: options.alias_table(),
+        })
+    }
+
+    fn peek_byte(&mut self) -> RecordReadResult<Option<u8>> {
+        let buf = self.reader.fill_buf().map_err(|e| RecordReadError {
+            text: "couldn't read data".into(),
+            source: Some(Box::new(e)),
+        })?;
+        Ok(buf.first().copied())
+    }
+
+    fn next_byte(&mut self) -> RecordReadResult<Option<u8>> {
+        let byte = self.peek_byte()?;
+        if let Some(byte) = byte {
+            self.reader.consume(1);
+            self.offset += 1;
+            if byte == b'\n' {
+                self.current_line += 1;
+            }
+        }
+        Ok(byte)
+    }
+
+    /// Next byte which isn't whitespace or in a comment
+    fn next_token(&mut self) -> RecordReadResult<Option<u8>> {
+        loop {
+            match self.next_byte()? {
+                Some(byte) if byte.is_ascii_whitespace() => continue,
+                Some(b'/') => self.skip_comment()?,
+                token => return Ok(token),
+            }
+        }
+    }
+
+    /// Skips a comment after its leading slash. Block comments nest
+    fn skip_comment(&mut self) -> RecordReadResult<()> {
+        match self.next_byte()? {
+            Some(b'/') => {
+                while !matches!(self.next_byte()?, Some(b'\n') | None) {}
+                Ok(())
+            }
+            Some(b'*') => {
+                let mut depth = 1;
+                let mut previous = 0;
+                while depth > 0 {
+                    let byte = self
+                        .next_byte()?
+                        .ok_or_else(|| self.error("unterminated comment"))?;
+                    match (previous, byte) {
+                        (b'/', b'*') => depth += 1,
+                        (b'*', b'/') => depth -= 1,
+                        _ => (),
+                    }
+                    // the closing slash can't start another comment
+                    previous = if previous == b'*' && byte == b'/' {
+                        0
+                    } else {
+                        byte
+                    };
+                }
+                Ok(())
+            }
+            _ => Err(self.error("expected a comment after /")),
+        }
+    }
+
+    fn error(&self, text: &str) -> RecordReadError {
+        RecordReadError {
+            text: format!("{}. near line {}", text, self.current_line),
+            source: None,
+        }
+    }
+
+    fn unexpected(&self, expected: &str, found: Option<u8>) -> RecordReadError {
+        match found {
+            Some(byte) => self.error(&format!(
+                "expected {} but found {}",
+                expected,
+                char::from(byte)
+            )),
+            None => self.error(&format!("expected {} but found the end of input", expected)),
+        }
+    }
+
+    /// Reads the rest of a word after its first byte
+    fn read_word(&mut self, first: u8) -> RecordReadResult<String> {
+        let mut word = String::from(char::from(first));
+        while let Some(byte) = self.peek_byte()?
+            && (byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'.')
+        {
+            self.next_byte()?;
+            word.push(char::from(byte));
+        }
+        Ok(word)
+    }
+
+    /// Reads the rest of a string after its opening quote
+    fn read_string(&mut self) -> RecordReadResult<String> {
+        let mut text = Vec::new();
+        loop {
+            let byte = self
+                .next_byte()?
+                .ok_or_else(|| self.error("unterminated string"))?;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = match self.next_byte()? {
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'0') => '\0',
+                        Some(byte @ (b'\\' | b'"' | b'\'')) => char::from(byte),
+                        Some(b'u') => self.read_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape in string")),
+                    };
+                    text.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                byte => text.push(byte),
+            }
+        }
+        String::from_utf8(text).map_err(|e| RecordReadError {
+            text: format!("invalid utf-8 in string. near line {}", self.current_line),
+            source: Some(Box::new(e)),
+        })
+    }
+
+    /// Reads the `{XXXX}` of a `\u{XXXX}` escape
+    fn read_unicode_escape(&mut self) -> RecordReadResult<char> {
+        if self.next_byte()? != Some(b'{') {
+            return Err(self.error("expected { in unicode escape"));
+        }
+        let mut code = String::new();
+        loop {
+            match self.next_byte()? {
+                Some(b'}') => break,
+                Some(byte) if byte.is_ascii_hexdigit() && code.len() < 6 => {
+                    code.push(char::from(byte))
+                }
+                _ => return Err(self.error("invalid unicode escape")),
+            }
+        }
+        u32::from_str_radix(&code, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn read_struct(&mut self) -> RecordReadResult<Option<Vec<(String, RonValue)>>> {
+        let token = match self.next_token()? {
+            None => return Ok(None),
+            Some(b'(') => Some(b'('),
+            Some(byte) if byte.is_ascii_alphabetic() => {
+                let name = self.read_word(byte)?;
+                if name != STRUCT_NAME {
+                    return Err(self.error(&format!("expected {} but found {}", STRUCT_NAME, name)));
+                }
+                self.next_token()?
+            }
+            token => return Err(self.unexpected(STRUCT_NAME, token)),
+        };
+        if token != Some(b'(') {
+            return Err(self.unexpected("(", token));
+        }
+        let mut entries = Vec::new();
+        loop {
+            let name = match self.next_token()? {
+                Some(b')') => break,
+                Some(byte) if byte.is_ascii_alphabetic() || byte == b'_' => self.read_word(byte)?,
+                token => return Err(self.unexpected("a field name or )", token)),
+            };
+            match self.next_token()? {
+                Some(b':') => (),
+                token => return Err(self.unexpected(":", token)),
+            }
+            let value = match self.next_token()? {
+                Some(b'"') => RonValue::Text(self.read_string()?),
+                Some(byte) if byte.is_ascii_digit() => RonValue::Number(self.read_word(byte)?),
+                Some(byte) if byte.is_ascii_alphabetic() => RonValue::Ident(self.read_word(byte)?),
+                token => return Err(self.unexpected("a value", token)),
+            };
+            entries.push((name, value));
+            match self.next_token()? {
+                Some(b',') => (),
+                Some(b')') => break,
+                token => return Err(self.unexpected(", or )", token)),
+            }
+        }
+        Ok(Some(entries))
+    }
+
+    fn parse_entries(&self, entries: Vec<(String, RonValue)>) -> RecordProduceResult<Record> {
+        let error = |text: String| RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!("{}. near line {}", text, self.current_line),
+            source: None,
+        };
+        let mut values: Vec<FieldValue> = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let Some(name) = resolve_field(&key, &self.aliases) else {
+                match self.unknown_fields {
+                    UnknownFields::Error => return Err(error(format!("unknown field {}", key))),
+                    UnknownFields::Ignore => continue,
+                }
+            };
+            if values.iter().any(|v| v.field() == name) {
+                return Err(error(format!("duplicate field {}", name.as_str())));
+            }
+            let text = match (name, value) {
+                (FieldName::Description, RonValue::Text(text)) => text,
+                (FieldName::TxType | FieldName::Status, RonValue::Ident(text)) => text,
+                (
+                    FieldName::TxId
+                    | FieldName::FromUser
+                    | FieldName::ToUser
+                    | FieldName::Amount
+                    | FieldName::Timestamp,
+                    RonValue::Number(text),
+                ) => text,
+                _ => return Err(error(format!("invalid value of {}", name.as_str()))),
+            };
+            let value = Field::new(name, text.as_str())
+                .parse_with_units(self.amount_unit, self.timestamp_unit)
+                .map_err(|e| RecordProduceError {
+                    kind: RecordProduceErrorKind::ParseField {
+                        field_name: name.as_str().into(),
+                    },
+                    text: format!("failed to parse field. near line {}", self.current_line),
+                    source: Some(Box::new(e)),
+                })?;
+            values.push(value);
+        }
+        Record::try_from(with_defaults(&self.defaults, values)).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!("failed to parse record. near line {}", self.current_line),
+            source: Some(e.into()),
+        })
+    }
+}
+
+impl<T: Read> DataConsumer for RonReader<T> {
+    type Item = Vec<(String, RonValue)>;
+    fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
+        if self.is_exhausted {
+            return None;
+        }
+        match self.read_struct() {
+            Ok(Some(entries)) => Some(Ok(entries)),
+            Ok(None) => {
+                self.is_exhausted = true;
+                None
+            }
+            Err(e) => {
+                // the rest of the input can't be aligned to structs
+                self.is_exhausted = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<T: Read> DataProducer for RonReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let entries = match self.read()? {
+            Ok(entries) => entries,
+            Err(e) => {
+                return Some(Err(RecordProduceError {
+                    kind: e.kind(),
+                    text: format!("failed to read record {}", self.records + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        self.records += 1;
+        Some(self.parse_entries(entries))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: self.current_line,
+            record: self.records,
+        })
+    }
+}
+
+/// String literal of the text with the ron escapes
+fn ron_string(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => literal.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+pub(crate) struct RonSerialize {
+    amount_unit: AmountUnit,
+    sanitize: SanitizePolicy,
+    timestamp_unit: TimestampUnit,
+    pretty: bool,
+}
+
+impl RonSerialize {
+    pub(crate) fn with_options(options: &SerializeOptions) -> RonSerialize {
+        RonSerialize {
+            amount_unit: options.amount_unit,
+            sanitize: options.sanitize,
+            timestamp_unit: options.timestamp_unit,
+            pretty: options.ron_pretty,
+        }
+    }
+}
+
+impl RecordSerialize for RonSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let fields = [
+            (FieldName::TxId, record.tx_id.to_string()),
+            (FieldName::TxType, record.tx_type.to_string()),
+            (FieldName::FromUser, record.from_user.to_string()),
+            (FieldName::ToUser, record.to_user.to_string()),
+            (FieldName::Amount, self.amount_unit.format(record.amount)),
+            (
+                FieldName::Timestamp,
+                self.timestamp_unit.timestamp(record)?.to_string(),
+            ),
+            (FieldName::Status, record.status.to_string()),
+            (
+                FieldName::Description,
+                ron_string(&self.sanitize.description(record)?),
+            ),
+        ];
+        let entries = fields
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name.as_str(), value));
+        let data = if self.pretty {
+            let entries = entries
+                .map(|entry| format!("    {},\n", entry))
+                .collect::<String>();
+            format!("{}(\n{})", STRUCT_NAME, entries)
+        } else {
+            format!(
+                "{}({})",
+                STRUCT_NAME,
+                entries.collect::<Vec<_>>().join(", ")
+            )
+        };
+        Ok(data.into_bytes())
+    }
+}
+
+/// Writes one serialized record per line, or per lines when pretty
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W) -> RecordWrite<W> {
+        RecordWrite { writer }
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(&data)
+            .and_then(|_| self.writer.write_all(b"\n"))
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+
+    fn write_comment(&mut self, text: &str) -> RecordWriteResult<()> {
+        let comment = text
+            .lines()
+            .map(|line| format!("// {}\n", line))
+            .collect::<String>();
+        self.writer
+            .write_all(comment.as_bytes())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write comment".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::record::{RecordIterator, Status, TxType};
+
+    fn reader(input: &str) -> RonReader<Cursor<String>> {
+        RonReader::with_options(Cursor::new(input.to_string()), &ParseOptions::default()).unwrap()
+    }
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record::new(
+                u64::MAX,
+                TxType::Withdrawal,
+                1,
+                2,
+                300,
+                1633036860000,
+                Status::Pending,
+                "say \"hi\"\n\tbye \\ Café 😀 \u{1}".into(),
+            ),
+            Record::new(
+                0,
+                TxType::Deposit,
+                0,
+                1,
+                1,
+                0,
+                Status::Success,
+                String::new(),
+            ),
+        ]
+    }
+
+    fn write(records: &[Record], pretty: bool) -> String {
+        let serializer = RonSerialize::with_options(&SerializeOptions {
+            ron_pretty: pretty,
+            ..Default::default()
+        });
+        let mut output = Vec::new();
+        let mut writer = RecordWrite::new(&mut output);
+        for record in records {
+            writer.write(serializer.serialize(record).unwrap()).unwrap();
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for pretty in [false, true] {
+            let output = write(&records(), pretty);
+            let read = RecordIterator::new(reader(&output))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(read, records(), "{}", output);
+        }
+        assert_eq!(
+            write(&records()[1..], false),
+            "Record(TX_ID: 0, TX_TYPE: DEPOSIT, FROM_USER_ID: 0, TO_USER_ID: 1, AMOUNT: 1, \
+             TIMESTAMP: 0, STATUS: SUCCESS, DESCRIPTION: \"\")\n"
+        );
+        assert!(write(&records()[1..], true).starts_with("Record(\n    TX_ID: 0,\n"));
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "// fixtures\n/* a /* nested */ block */ Record(\n    TX_ID: 7, // id\n    \
+                     TX_TYPE: TRANSFER,\n    FROM_USER_ID: 1,\n    TO_USER_ID: 2,\n    \
+                     AMOUNT: 300,\n    TIMESTAMP: 9,\n    STATUS: FAILURE,\n    \
+                     DESCRIPTION: \"// not a comment\",\n)\n(TX_ID: 8, TX_TYPE: DEPOSIT, \
+                     FROM_USER_ID: 0, TO_USER_ID: 2, AMOUNT: 1, TIMESTAMP: 9, STATUS: SUCCESS)\n";
+        let mut reader = reader(input);
+        let record = reader.produce_record().unwrap().unwrap();
+        assert_eq!(record.tx_id, 7);
+        assert_eq!(record.description, "// not a comment");
+        assert_eq!(reader.produce_record().unwrap().unwrap().tx_id, 8);
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        for (input, kind) in [
+            (
+                "(TX_TYPE: Deposit)",
+                RecordProduceErrorKind::ParseField {
+                    field_name: "TX_TYPE".into(),
+                },
+            ),
+            (
+                "(TX_TYPE: \"DEPOSIT\")",
+                RecordProduceErrorKind::MalformedRecord,
+            ),
+            (
+                "(TX_ID: 1, TX_ID: 2)",
+                RecordProduceErrorKind::MalformedRecord,
+            ),
+            ("(NAME: 1)", RecordProduceErrorKind::MalformedRecord),
+            (
+                "(TX_ID: 1x)",
+                RecordProduceErrorKind::ParseField {
+                    field_name: "TX_ID".into(),
+                },
+            ),
+            (
+                "(TX_ID: 1)",
+                RecordProduceErrorKind::MissingField {
+                    field_name: "TX_TYPE".into(),
+                },
+            ),
+        ] {
+            let input = format!("{}\n{}", input, write(&records()[1..], false));
+            let mut reader = reader(&input);
+            let err = reader.produce_record().unwrap().unwrap_err();
+            assert_eq!(err.kind(), kind, "{}", input);
+            assert!(err.to_string().contains("near line 1"), "{}", err);
+            // a struct with invalid values doesn't break the stream
+            assert_eq!(reader.produce_record().unwrap().unwrap(), records()[1]);
+        }
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        for input in [
+            "Transaction(TX_ID: 1)",
+            "Record[TX_ID: 1]",
+            "Record(TX_ID 1)",
+            "Record(TX_ID: 1",
+            "Record(TX_ID: \"1)",
+            "Record(DESCRIPTION: \"\\q\")",
+            "/* unterminated",
+            "/ x",
+        ] {
+            let mut reader = reader(input);
+            let err = reader.produce_record().unwrap().unwrap_err();
+            assert_eq!(
+                err.kind(),
+                RecordProduceErrorKind::MalformedRecord,
+                "{}",
+                input
+            );
+            assert!(reader.produce_record().is_none(), "{}", input);
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::path::Path;
+
+use rusqlite::Connection;
+use rusqlite::types::Value;
+
+use crate::error::{RecordProduceError, RecordProduceErrorKind, RecordWriteError};
+use crate::record::{DataProducer, Field, FieldName, FieldValue, Position, Record, RecordWriter};
+use crate::result::{RecordProduceResult, RecordWriteResult};
+
+/// Columns of a record table in the order of [`Record`] fields
+const COLUMNS: [(FieldName, &str); 8] = [
+    (FieldName::TxId, "INTEGER"),
+    (FieldName::TxType, "TEXT"),
+    (FieldName::FromUser, "INTEGER"),
+    (FieldName::ToUser, "INTEGER"),
+    (FieldName::Amount, "INTEGER"),
+    (FieldName::Timestamp, "INTEGER"),
+    (FieldName::Status, "TEXT"),
+    (FieldName::Description, "TEXT"),
+];
+
+/// Rows fetched from the table at once
+const PAGE_SIZE: usize = 512;
+
+/// Rows inserted in one transaction
+const BATCH_SIZE: usize = 1000;
+
+/// Quotes a table name for use in a statement
+fn quote(table: &str) -> String {
+    format!("\"{}\"", table.replace('"', "\"\""))
+}
+
+fn column_list() -> String {
+    COLUMNS
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Reader of the records of a sqlite table with columns named after the fields.
+/// Rows are read in rowid order, a page at a time
+///
+/// ```
+/// use parserde::{DataProducer, Record, RecordWriter, SqliteReader, SqliteWriter};
+/// let connection = rusqlite::Connection::open_in_memory().unwrap();
+/// let mut writer = SqliteWriter::new(connection, "records");
+/// writer.write_header().unwrap();
+/// writer.write_record(&Record::default(), Vec::new()).unwrap();
+/// writer.finish().unwrap();
+/// let mut reader = SqliteReader::new(writer.into_connection(), "records").unwrap();
+/// assert_eq!(reader.produce_record().unwrap().unwrap(), Record::default());
+/// ```
+pub struct SqliteReader {
+    connection: Connection,
+    query: String,
+    last_rowid: i64,
+    rows: VecDeque<(i64, Vec<Value>)>,
+    records: u64,
+    is_exhausted: bool,
+}
+
+impl SqliteReader {
+    /// Creates a reader of the table of the connection
+    pub fn new(connection: Connection, table: &str) -> Result<SqliteReader, Box<dyn Error>> {
+        let query = format!(
+            "SELECT rowid, {} FROM {} WHERE rowid > ?1 ORDER BY rowid LIMIT {}",
+            column_list(),
+            quote(table),
+            PAGE_SIZE
+        );
+        // fails early on a missing table or column
+        connection.prepare(&query)?;
+        Ok(SqliteReader {
+            connection,
+            query,
+            last_rowid: i64::MIN,
+            rows: VecDeque::new(),
+            records: 0,
+            is_exhausted: false,
+        })
+    }
+
+    /// Creates a reader of the table of the database file
+    pub fn open(path: &Path, table: &str) -> Result<SqliteReader, Box<dyn Error>> {
+        SqliteReader::new(Connection::open(path)?, table)
+    }
+
+    fn fetch_page(&mut self) -> rusqlite::Result<()> {
+        let mut statement = self.connection.prepare_cached(&self.query)?;
+        let rows = statement.query_map([self.last_rowid], |row| {
+            let values = (1..=COLUMNS.len())
+                .map(|i| row.get::<_, Value>(i))
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok((row.get::<_, i64>(0)?, values))
+        })?;
+        for row in rows {
+            self.rows.push_back(row?);
+        }
+        Ok(())
+    }
+
+    fn parse_row(&self, row: Vec<Value>) -> RecordProduceResult<Record> {
+        let mut values: Vec<FieldValue> = Vec::with_capacity(COLUMNS.len());
+        for ((name, _), value) in COLUMNS.iter().zip(row) {
+            let text = match value {
+                Value::Null => continue,
+                Value::Integer(number) => number.to_string(),
+                Value::Text(text) => text,
+                _ => {
+                    return Err(RecordProduceError {
+                        kind: RecordProduceErrorKind::ParseField {
+                            field_name: name.as_str().into(),
+                        },
+                        text: format!(
+                            "invalid value of {}. record {}",
+                            name.as_str(),
+                            self.records
+                        ),
+                        source: None,
+                    });
+                }
+            };
+            let value =
+                Field::new(*name, text.as_str())
+                    .parse()
+                    .map_err(|e| RecordProduceError {
+                        kind: RecordProduceErrorKind::ParseField {
+                            field_name: name.as_str().into(),
+                        },
+                        text: format!("failed to parse field. record {}", self.records),
+                        source: Some(Box::new(e)),
+                    })?;
+            values.push(value);
+        }
+        Record::try_from(values).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!("failed to parse record {}", self.records),
+            source: Some(e.into()),
+        })
+    }
+}
+
+impl DataProducer for SqliteReader {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        if self.is_exhausted {
+            return None;
+        }
+        if self.rows.is_empty() {
+            if let Err(e) = self.fetch_page() {
+                self.is_exhausted = true;
+                return Some(Err(RecordProduceError {
+                    kind: RecordProduceErrorKind::Io,
+                    text: format!("failed to read record {}", self.records + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+            if self.rows.is_empty() {
+                self.is_exhausted = true;
+                return None;
+            }
+        }
+        let (rowid, row) = self.rows.pop_front()?;
+        self.last_rowid = rowid;
+        self.records += 1;
+        Some(self.parse_row(row))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: 0,
+            line: 0,
+            record: self.records,
+        })
+    }
+}
+
+/// Writer of records to a sqlite table with columns named after the fields.
+/// Rows are inserted in transactions of a thousand records, the last one
+/// is committed by [`RecordWriter::finish`]. Only
+/// [`RecordWriter::write_record`] writes rows since a row is built
+/// from the record rather than its serialized form
+pub struct SqliteWriter {
+    connection: Connection,
+    table: String,
+    insert: String,
+    in_transaction: bool,
+    pending: usize,
+}
+
+impl SqliteWriter {
+    /// Creates a writer to the table of the connection
+    pub fn new(connection: Connection, table: &str) -> SqliteWriter {
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            quote(table),
+            column_list()
+        );
+        SqliteWriter {
+            connection,
+            table: table.to_string(),
+            insert,
+            in_transaction: false,
+            pending: 0,
+        }
+    }
+
+    /// Creates a writer to the table of the database file,
+    /// creating the file if it doesn't exist
+    pub fn open(path: &Path, table: &str) -> Result<SqliteWriter, Box<dyn Error>> {
+        Ok(SqliteWriter::new(Connection::open(path)?, table))
+    }
+
+    /// The connection of the writer. Rows not committed by
+    /// [`RecordWriter::finish`] are rolled back
+    pub fn into_connection(self) -> Connection {
+        self.connection
+    }
+
+    fn error(text: &str, e: rusqlite::Error) -> RecordWriteError {
+        RecordWriteError {
+            text: text.into(),
+            source: Some(Box::new(e)),
+        }
+    }
+
+    fn insert(&mut self, record: &Record) -> RecordWriteResult<()> {
+        let number = |name: FieldName, value: u64| {
+            i64::try_from(value).map_err(|_| RecordWriteError {
+                text: format!(
+                    "{} {} of record {} doesn't fit a sqlite integer",
+                    name.as_str(),
+                    value,
+                    record.tx_id
+                ),
+                source: None,
+            })
+        };
+        let params = (
+            number(FieldName::TxId, record.tx_id)?,
+            record.tx_type.to_string(),
+            number(FieldName::FromUser, record.from_user)?,
+            number(FieldName::ToUser, record.to_user)?,
+            number(FieldName::Amount, record.amount)?,
+            number(FieldName::Timestamp, record.timestamp)?,
+            record.status.to_string(),
+            record.description.as_str(),
+        );
+        if !self.in_transaction {
+            self.connection
+                .execute_batch("BEGIN")
+                .map_err(|e| SqliteWriter::error("failed to begin a transaction", e))?;
+            self.in_transaction = true;
+        }
+        self.connection
+            .prepare_cached(&self.insert)
+            .and_then(|mut statement| statement.execute(params))
+            .map_err(|e| SqliteWriter::error("failed to insert record", e))?;
+        self.pending += 1;
+        if self.pending == BATCH_SIZE {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self) -> RecordWriteResult<()> {
+        if !self.in_transaction {
+            return Ok(());
+        }
+        self.in_transaction = false;
+        self.pending = 0;
+        self.connection
+            .execute_batch("COMMIT")
+            .map_err(|e| SqliteWriter::error("failed to commit records", e))
+    }
+}
+
+impl RecordWriter for SqliteWriter {
+    fn write_header(&mut self) -> RecordWriteResult<()> {
+        let columns = COLUMNS
+            .iter()
+            .map(|(name, kind)| format!("{} {}", name.as_str(), kind))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.connection
+            .execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {} ({})",
+                quote(&self.table),
+                columns
+            ))
+            .map_err(|e| SqliteWriter::error("failed to create table", e))
+    }
+
+    fn write_comment(&mut self, _text: &str) -> RecordWriteResult<()> {
+        Ok(())
+    }
+
+    fn write(&mut self, _data: Vec<u8>) -> RecordWriteResult<()> {
+        Err(RecordWriteError {
+            text: "sqlite rows are written from records, not serialized data".into(),
+            source: None,
+        })
+    }
+
+    fn write_record(&mut self, record: &Record, _data: Vec<u8>) -> RecordWriteResult<()> {
+        self.insert(record)
+    }
+
+    fn finish(&mut self) -> RecordWriteResult<()> {
+        self.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::{RecordIterator, Status, TxType};
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record::new(
+                1000000000000000,
+                TxType::Withdrawal,
+                i64::MAX as u64,
+                0,
+                100,
+                1633036860000,
+                Status::Failure,
+                "Überweisung für \"Café\" — 送金 😀".into(),
+            ),
+            Record::new(
+                0,
+                TxType::Deposit,
+                0,
+                1,
+                1,
+                0,
+                Status::Success,
+                String::new(),
+            ),
+        ]
+    }
+
+    fn write(records: &[Record]) -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        let mut writer = SqliteWriter::new(connection, "my \"records\"");
+        writer.write_header().unwrap();
+        for record in records {
+            writer.write_record(record, Vec::new()).unwrap();
+        }
+        writer.finish().unwrap();
+        writer.into_connection()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let read =
+            RecordIterator::new(SqliteReader::new(write(&records()), "my \"records\"").unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        assert_eq!(read, records());
+    }
+
+    #[test]
+    fn test_batches_and_pages() {
+        let records = (0..BATCH_SIZE as u64 * 2 + 1)
+            .map(|i| Record {
+                tx_id: i,
+                ..Record::default()
+            })
+            .collect::<Vec<_>>();
+        let connection = write(&records);
+        let count: u64 = connection
+            .query_row("SELECT COUNT(*) FROM \"my \"\"records\"\"\"", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count, records.len() as u64);
+        let mut iter =
+            RecordIterator::new(SqliteReader::new(connection, "my \"records\"").unwrap());
+        let read = iter.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(read, records);
+        let position = iter.into_inner().position().unwrap();
+        assert_eq!(position.record, records.len() as u64);
+    }
+
+    #[test]
+    fn test_existing_table() {
+        let connection = write(&records()[..1]);
+        let mut writer = SqliteWriter::new(connection, "my \"records\"");
+        writer.write_header().unwrap();
+        writer.write_record(&records()[1], Vec::new()).unwrap();
+        writer.finish().unwrap();
+        let read = RecordIterator::new(
+            SqliteReader::new(writer.into_connection(), "my \"records\"").unwrap(),
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        assert_eq!(read, records());
+    }
+
+    #[test]
+    fn test_invalid_rows() {
+        let connection = write(&[]);
+        let insert = |values: &str| {
+            connection
+                .execute_batch(&format!(
+                    "INSERT INTO \"my \"\"records\"\"\" ({}) VALUES ({})",
+                    column_list(),
+                    values
+                ))
+                .unwrap();
+        };
+        insert("-1, 'DEPOSIT', 1, 2, 3, 4, 'SUCCESS', ''");
+        insert("1, 'GIFT', 1, 2, 3, 4, 'SUCCESS', ''");
+        insert("1, 'DEPOSIT', 1.5, 2, 3, 4, 'SUCCESS', ''");
+        insert("1, 'DEPOSIT', 1, 2, NULL, 4, 'SUCCESS', ''");
+        insert("1, 'DEPOSIT', 1, 2, 3, 4, 'SUCCESS', 'ok'");
+        let mut reader = SqliteReader::new(connection, "my \"records\"").unwrap();
+        for field in ["TX_ID", "TX_TYPE", "FROM_USER_ID"] {
+            assert_eq!(
+                reader.produce_record().unwrap().unwrap_err().kind(),
+                RecordProduceErrorKind::ParseField {
+                    field_name: field.into()
+                }
+            );
+        }
+        assert!(matches!(
+            reader.produce_record().unwrap().unwrap_err().kind(),
+            RecordProduceErrorKind::MissingField { .. }
+        ));
+        // an invalid row doesn't break the table
+        assert_eq!(reader.produce_record().unwrap().unwrap().description, "ok");
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(SqliteReader::new(Connection::open_in_memory().unwrap(), "records").is_err());
+        let mut writer = SqliteWriter::new(Connection::open_in_memory().unwrap(), "records");
+        assert!(writer.write_record(&records()[0], Vec::new()).is_err());
+        writer.write_header().unwrap();
+        assert!(writer.write(Vec::new()).is_err());
+        let too_large = Record {
+            amount: u64::MAX,
+            ..Record::default()
+        };
+        assert!(writer.write_record(&too_large, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! Checks shared by the tests of the binary formats
+
+use std::io::Cursor;
+
+use crate::error::{RecordProduceError, RecordProduceErrorKind};
+use crate::record::{
+    DataProducer, Record, RecordIterator, RecordSerialize, RecordWriter, Status, TxType,
+};
+
+/// Records with the extreme values of the fields,
+/// a multibyte description and an empty one
+pub(crate) fn records() -> Vec<Record> {
+    vec![
+        Record::new(
+            u64::MAX,
+            TxType::Withdrawal,
+            u64::MAX - 1,
+            0,
+            u64::MAX,
+            1633036860000,
+            Status::Failure,
+            "Überweisung für Café — 送金 😀".into(),
+        ),
+        Record::new(
+            0,
+            TxType::Deposit,
+            0,
+            1,
+            1,
+            0,
+            Status::Success,
+            String::new(),
+        ),
+    ]
+}
+
+/// Serializer, writer and reader of a format under test
+pub(crate) struct Codec<P: DataProducer> {
+    pub(crate) serializer: &'static dyn RecordSerialize,
+    pub(crate) writer: for<'a> fn(&'a mut Vec<u8>) -> Box<dyn RecordWriter + 'a>,
+    pub(crate) reader: fn(Cursor<Vec<u8>>) -> P,
+}
+
+impl<P: DataProducer> Codec<P> {
+    /// Serializes the records and writes them one after another
+    pub(crate) fn encode(&self, records: &[Record]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut writer = (self.writer)(&mut output);
+        for record in records {
+            writer
+                .write_record(record, self.serializer.serialize(record).unwrap())
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+        output
+    }
+
+    pub(crate) fn read(&self, data: Vec<u8>) -> P {
+        (self.reader)(Cursor::new(data))
+    }
+
+    /// Checks the fixture records are read back as written, an empty input
+    /// holds no records and an input cut within the second record fails
+    /// after the first one. Returns the error of the cut record
+    pub(crate) fn assert_round_trip(&self) -> RecordProduceError {
+        let data = self.encode(&records());
+        let read = RecordIterator::new(self.read(data.clone()))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, records());
+        assert!(self.read(Vec::new()).produce_record().is_none());
+
+        let first = self.serializer.serialize(&records()[0]).unwrap().len();
+        let mut reader = self.read(data[..first + 5].to_vec());
+        assert_eq!(reader.produce_record().unwrap().unwrap(), records()[0]);
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert_eq!(err.kind(), RecordProduceErrorKind::MalformedRecord);
+        assert!(reader.produce_record().is_none());
+        err
+    }
+
+    /// Checks the reader rejects the data and then reads
+    /// the first fixture record following it
+    pub(crate) fn assert_rejected_then_recovers(&self, invalid: &[u8]) {
+        let mut data = invalid.to_vec();
+        data.extend(self.encode(&records()[..1]));
+        let mut reader = self.read(data);
+        assert!(reader.produce_record().unwrap().is_err(), "{:x?}", invalid);
+        assert_eq!(
+            reader.produce_record().unwrap().unwrap(),
+            records()[0],
+            "{:x?}",
+            invalid
+        );
+    }
+}
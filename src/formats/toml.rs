@@ -0,0 +1,489 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use toml::{Table, Value};
+
+use crate::error::{
+    ReaderCreateError, RecordProduceError, RecordProduceErrorKind, RecordReadError,
+    RecordWriteError,
+};
+use crate::json::json_string;
+use crate::options::{
+    AmountUnit, ParseOptions, SanitizePolicy, SerializeOptions, TimestampUnit, UnknownFields,
+};
+use crate::record::{
+    DataConsumer, DataProducer, Field, FieldName, FieldValue, Position, Record, RecordSerialize,
+    RecordWriter, resolve_field, with_defaults,
+};
+use crate::result::{
+    ReaderCreateResult, RecordProduceResult, RecordReadResult, RecordSerializeResult,
+    RecordWriteResult,
+};
+
+const HEADER: &str = "[[transaction]]";
+
+/// Whether the line opens a transaction table
+fn is_header(line: &str) -> bool {
+    line.trim()
+        .strip_prefix(HEADER)
+        .is_some_and(|rest| rest.trim_start().is_empty() || rest.trim_start().starts_with('#'))
+}
+
+/// Whether the line has nothing but whitespace or a comment
+fn is_blank(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#')
+}
+
+/// Reader of the toml format, a `[[transaction]]` table per record.
+/// Keys are field names in any case
+pub(crate) struct TomlReader<T: Read> {
+    reader: BufReader<T>,
+    /// Header line read at the end of the previous table
+    has_header: bool,
+    current_line: u64,
+    /// Line of the header of the last read table
+    table_line: u64,
+    offset: u64,
+    records: u64,
+    is_exhausted: bool,
+    amount_unit: AmountUnit,
+    timestamp_unit: TimestampUnit,
+    unknown_fields: UnknownFields,
+    defaults: Vec<FieldValue>,
+    aliases: Vec<(String, FieldName)>,
+}
+
+impl<T: Read> TomlReader<T> {
+    pub(crate) fn with_options(
+        reader: T,
+        options: &ParseOptions,
+    ) -> ReaderCreateResult<TomlReader<T>> {
+        let defaults = options
+            .defaults
+            .parse(options.amount_unit, options.timestamp_unit)
+            .map_err(|e| ReaderCreateError {
+                text: "failed to create toml reader".into(),
+                source: Some(Box::new(e)),
+            })?;
+        Ok(TomlReader {
+            reader: BufReader::new(reader),
+            has_header: false,
+            current_line: 0,
+            table_line: 0,
+            offset: 0,
+            records: 0,
+            is_exhausted: false,
+            amount_unit: options.amount_unit,
+            timestamp_unit: options.timestamp_unit,
+            unknown_fields: options.unknown_fields,
+            defaults,
+            aliases: options.alias_table(),
+        })
+    }
+
+    fn read_line(&mut self) -> RecordReadResult<Option<String>> {
+        let mut line = String::new();
+        let bytes_read = self
+            .reader
+            .read_line(&mut line)
+            .map_err(|e| RecordReadError {
+                text: "couldn't read data".to_string(),
+                source: Some(Box::new(e)),
+            })?;
+        if bytes_read == 0 {
+            return Ok(None);
+        }
+        self.current_line += 1;
+        self.offset += bytes_read as u64;
+        Ok(Some(line))
+    }
+
+    /// Reads the body of the next table up to the header of the following one
+    fn read_table(&mut self) -> RecordReadResult<Option<String>> {
+        while !self.has_header {
+            match self.read_line()? {
+                None => return Ok(None),
+                Some(line) if is_header(&line) => self.has_header = true,
+                Some(line) if is_blank(&line) => continue,
+                Some(_) => {
+                    return Err(RecordReadError {
+                        text: format!(
+                            "expected {} but found a key. near line {}",
+                            HEADER, self.current_line
+                        ),
+                        source: None,
+                    });
+                }
+            }
+        }
+        self.table_line = self.current_line;
+        let mut body = String::new();
+        loop {
+            match self.read_line()? {
+                None => {
+                    self.has_header = false;
+                    return Ok(Some(body));
+                }
+                Some(line) if is_header(&line) => return Ok(Some(body)),
+                Some(line) => body.push_str(&line),
+            }
+        }
+    }
+
+    fn error(&self, text: String) -> RecordProduceError {
+        RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!(
+                "{}. table {} near line {}",
+                text, self.records, self.table_line
+            ),
+            source: None,
+        }
+    }
+
+    fn resolve(&self, key: &str) -> Option<FieldName> {
+        resolve_field(&key.to_ascii_uppercase(), &[]).or_else(|| resolve_field(key, &self.aliases))
+    }
+
+    fn parse_table(&self, body: &str) -> RecordProduceResult<Vec<FieldValue>> {
+        let table = body.parse::<Table>().map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!(
+                "invalid toml. table {} near line {}",
+                self.records, self.table_line
+            ),
+            source: Some(Box::new(e)),
+        })?;
+        let mut values: Vec<FieldValue> = Vec::with_capacity(table.len());
+        for (key, value) in table {
+            let Some(name) = self.resolve(&key) else {
+                match self.unknown_fields {
+                    UnknownFields::Error => {
+                        return Err(self.error(format!("unknown field {}", key)));
+                    }
+                    UnknownFields::Ignore => continue,
+                }
+            };
+            if values.iter().any(|v| v.field() == name) {
+                return Err(self.error(format!("duplicate field {}", name.as_str())));
+            }
+            let text = match value {
+                Value::String(text) => text,
+                Value::Integer(number) if number >= 0 => number.to_string(),
+                value => {
+                    return Err(RecordProduceError {
+                        kind: RecordProduceErrorKind::ParseField {
+                            field_name: name.as_str().into(),
+                        },
+                        text: format!(
+                            "{} of {} has the wrong type. table {} near line {}",
+                            value.type_str(),
+                            name.as_str(),
+                            self.records,
+                            self.table_line
+                        ),
+                        source: None,
+                    });
+                }
+            };
+            let value = Field::new(name, text.as_str())
+                .parse_with_units(self.amount_unit, self.timestamp_unit)
+                .map_err(|e| RecordProduceError {
+                    kind: RecordProduceErrorKind::ParseField {
+                        field_name: name.as_str().into(),
+                    },
+                    text: format!(
+                        "failed to parse {}. table {} near line {}",
+                        name.as_str(),
+                        self.records,
+                        self.table_line
+                    ),
+                    source: Some(Box::new(e)),
+                })?;
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    fn parse_record(&self, body: &str) -> RecordProduceResult<Record> {
+        let values = self.parse_table(body)?;
+        Record::try_from(with_defaults(&self.defaults, values)).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!(
+                "failed to parse record. table {} near line {}",
+                self.records, self.table_line
+            ),
+            source: Some(e.into()),
+        })
+    }
+}
+
+impl<T: Read> DataConsumer for TomlReader<T> {
+    type Item = String;
+    fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
+        if self.is_exhausted {
+            return None;
+        }
+        let table = self.read_table();
+        if matches!(table, Ok(None) | Err(_)) {
+            self.is_exhausted = true;
+        }
+        table.transpose()
+    }
+}
+
+impl<T: Read> DataProducer for TomlReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let body = match self.read()? {
+            Ok(body) => body,
+            Err(e) => {
+                return Some(Err(RecordProduceError {
+                    kind: e.kind(),
+                    text: format!("failed to read table {}", self.records + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        self.records += 1;
+        Some(self.parse_record(&body))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: self.current_line,
+            record: self.records,
+        })
+    }
+}
+
+pub(crate) struct TomlSerialize {
+    amount_unit: AmountUnit,
+    sanitize: SanitizePolicy,
+    timestamp_unit: TimestampUnit,
+}
+
+impl TomlSerialize {
+    pub(crate) fn with_options(options: &SerializeOptions) -> TomlSerialize {
+        TomlSerialize {
+            amount_unit: options.amount_unit,
+            sanitize: options.sanitize,
+            timestamp_unit: options.timestamp_unit,
+        }
+    }
+}
+
+/// Number as a toml integer, or as a string when it is out of the integer range
+/// or has a fraction
+fn toml_number(text: String) -> String {
+    match text.parse::<i64>() {
+        Ok(_) => text,
+        Err(_) => json_string(&text),
+    }
+}
+
+impl RecordSerialize for TomlSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let fields = [
+            (FieldName::TxId, toml_number(record.tx_id.to_string())),
+            (FieldName::TxType, json_string(&record.tx_type.to_string())),
+            (
+                FieldName::FromUser,
+                toml_number(record.from_user.to_string()),
+            ),
+            (FieldName::ToUser, toml_number(record.to_user.to_string())),
+            (
+                FieldName::Amount,
+                toml_number(self.amount_unit.format(record.amount)),
+            ),
+            (
+                FieldName::Timestamp,
+                toml_number(self.timestamp_unit.timestamp(record)?.to_string()),
+            ),
+            (FieldName::Status, json_string(&record.status.to_string())),
+            (
+                FieldName::Description,
+                json_string(&self.sanitize.description(record)?),
+            ),
+        ];
+        let mut table = format!("{}\n", HEADER);
+        for (name, value) in fields {
+            table.push_str(&format!("{} = {}\n", name.as_str(), value));
+        }
+        Ok(table.into_bytes())
+    }
+}
+
+/// Writes serialized tables separated by blank lines
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+    records: u64,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W) -> RecordWrite<W> {
+        RecordWrite { writer, records: 0 }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(data)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write_comment(&mut self, text: &str) -> RecordWriteResult<()> {
+        let comment = text
+            .lines()
+            .map(|line| format!("# {}\n", line))
+            .collect::<String>();
+        self.write_all(comment.as_bytes())
+    }
+
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        if self.records > 0 {
+            self.write_all(b"\n")?;
+        }
+        self.write_all(&data)?;
+        self.records += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::record::{RecordIterator, Status, TxType};
+
+    fn reader(input: &str) -> TomlReader<Cursor<String>> {
+        TomlReader::with_options(Cursor::new(input.to_string()), &ParseOptions::default()).unwrap()
+    }
+
+    fn record(tx_id: u64, description: &str) -> Record {
+        Record::new(
+            tx_id,
+            TxType::Withdrawal,
+            3,
+            0,
+            250,
+            1633036860000,
+            Status::Success,
+            description.into(),
+        )
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let records = [
+            record(1, "plain"),
+            record(2, "say \"hi\" \\ 'bye'\n[[transaction]]\tnext"),
+            record(u64::MAX, "# not a comment = ''' \u{7f}"),
+        ];
+        let serializer = TomlSerialize::with_options(&SerializeOptions::default());
+        let mut output = Vec::new();
+        let mut writer = RecordWrite::new(&mut output);
+        writer.write_comment("nightly export").unwrap();
+        for record in &records {
+            writer.write(serializer.serialize(record).unwrap()).unwrap();
+        }
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.starts_with("# nightly export\n[[transaction]]\nTX_ID = 1\n"),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains("TX_ID = \"18446744073709551615\""),
+            "{}",
+            output
+        );
+
+        let read = RecordIterator::new(reader(&output))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, records);
+        assert!(reader("# nothing\n\n").produce_record().is_none());
+    }
+
+    #[test]
+    fn test_config_style() {
+        let input = r#"
+            [[transaction]] # first
+            tx_id = 1
+            tx_type = "WITHDRAWAL"
+            from_user_id = 3
+            to_user_id = 0
+            amount = 250
+            timestamp = 1633036860000
+            status = "SUCCESS"
+            description = 'literal "string"'
+        "#;
+        let mut reader = reader(input);
+        assert_eq!(
+            reader.produce_record().unwrap().unwrap(),
+            record(1, "literal \"string\"")
+        );
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_invalid_tables() {
+        let table = |amount: &str| {
+            format!(
+                "[[transaction]]\nTX_ID = 1\nTX_TYPE = \"WITHDRAWAL\"\nFROM_USER_ID = 3\nTO_USER_ID = 0\nAMOUNT = {}\nTIMESTAMP = 1\nSTATUS = \"SUCCESS\"\nDESCRIPTION = \"\"\n",
+                amount
+            )
+        };
+        let input = [table("1"), table("\"abc\""), table("1.5"), table("-1")].concat();
+        let mut tables = reader(&input);
+        assert!(tables.produce_record().unwrap().is_ok());
+        for index in 2..=4 {
+            let err = tables.produce_record().unwrap().unwrap_err();
+            assert_eq!(
+                err.kind(),
+                RecordProduceErrorKind::ParseField {
+                    field_name: "AMOUNT".into()
+                }
+            );
+            assert!(
+                err.to_string().contains(&format!("table {}", index)),
+                "{}",
+                err
+            );
+        }
+        assert!(tables.produce_record().is_none());
+
+        let mut reader = reader("[[transaction]]\nTX_ID = 1\n\n[[transaction]]\nTX_ID = 2\n");
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert!(
+            matches!(err.kind(), RecordProduceErrorKind::MissingField { .. }),
+            "{:?}",
+            err.kind()
+        );
+        assert!(err.to_string().contains("table 1 near line 1"), "{}", err);
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert!(err.to_string().contains("table 2 near line 4"), "{}", err);
+
+        for input in [
+            "TX_ID = 1\n",
+            "[[transaction]]\nTX_ID = \n",
+            "[[transaction]]\nNAME = 1\n",
+        ] {
+            let mut reader = self::reader(input);
+            let err = reader.produce_record().unwrap().unwrap_err();
+            assert_eq!(
+                err.kind(),
+                RecordProduceErrorKind::MalformedRecord,
+                "{}",
+                input
+            );
+        }
+    }
+}
@@ -1,25 +1,179 @@
-use std::io::{self, BufRead, BufReader, Read, Write};
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
 
 use crate::record::{
-    Data, DataConsumer, DataProducer, FieldValue, Record, RecordSerialize, RecordWriter, fields,
+    DataConsumer, DataProducer, Field, FieldName, FieldValue, PartialDataProducer, PartialRecord,
+    Position, RawDataProducer, RawRecord, RawRepr, Record, RecordSerialize, RecordWriter,
+    resolve_field, with_defaults,
 };
 
-use crate::error::{RecordProduceError, RecordReadError, RecordSerializeError, RecordWriteError};
-use crate::result::{ReaderCreateResult, RecordProduceResult, RecordReadResult};
+use crate::error::{
+    FieldParseError, ReaderCreateError, RecordProduceError, RecordProduceErrorKind,
+    RecordReadError, RecordSerializeError, RecordWriteError,
+};
+use crate::options::{
+    ParseOptions, SanitizePolicy, SerializeOptions, TimestampUnit, TxtDelimiter, TxtStyle,
+};
+use crate::result::{FieldParseResult, ReaderCreateResult, RecordProduceResult, RecordReadResult};
+
+/// Which comment lines a [`TxtReader`] keeps
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CommentCapture {
+    /// Comments are skipped
+    #[default]
+    None,
+    /// Comments before the first record are kept
+    Leading,
+    /// Every comment is kept along with its line number
+    All,
+}
 
-pub(crate) struct TxtReader<T: Read> {
+/// Reader of the txt format
+pub struct TxtReader<T: Read> {
     pub(crate) reader: BufReader<T>,
     current_line: u64,
+    offset: u64,
+    records: u64,
     is_exhausted: bool,
+    capture: CommentCapture,
+    delimiter: TxtDelimiter,
+    timestamp_unit: TimestampUnit,
+    defaults: Vec<FieldValue>,
+    aliases: Vec<(String, FieldName)>,
+    in_leading_comments: bool,
+    leading_comments: Vec<String>,
+    comments: Vec<(u64, String)>,
+    lenient: bool,
+    /// Errors of the skipped records
+    errors: Vec<RecordProduceError>,
 }
 
 impl<T: Read> TxtReader<T> {
-    pub(crate) fn new(reader: T) -> ReaderCreateResult<TxtReader<T>> {
-        Ok(TxtReader {
+    pub(crate) fn new(reader: T) -> Result<TxtReader<T>, Box<dyn Error>> {
+        TxtReader::with_options(reader, &ParseOptions::default())
+    }
+
+    /// Creates a reader configured with the given parse options.
+    /// A lenient reader skips the records that fail to parse,
+    /// keeping their errors, and still returns read errors
+    ///
+    /// ```
+    /// use parserde::{DataProducer, ParseOptions, TxtReader};
+    /// let input = "TX_ID: x\n\nTX_ID: 1\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 2\n\
+    ///     AMOUNT: 5\nTIMESTAMP: 9\nSTATUS: SUCCESS\nDESCRIPTION: fee\n";
+    /// let options = ParseOptions {
+    ///     lenient: true,
+    ///     ..Default::default()
+    /// };
+    /// let mut reader = TxtReader::with_options(input.as_bytes(), &options).unwrap();
+    /// assert_eq!(reader.produce_record().unwrap().unwrap().tx_id(), 1);
+    /// assert!(reader.produce_record().is_none());
+    /// assert_eq!(reader.collected_errors().len(), 1);
+    /// ```
+    ///
+    pub fn with_options(reader: T, options: &ParseOptions) -> Result<TxtReader<T>, Box<dyn Error>> {
+        Ok(TxtReader::configured(reader, options)?)
+    }
+
+    /// Creates a reader configured with the given parse options
+    pub(crate) fn configured(
+        reader: T,
+        options: &ParseOptions,
+    ) -> ReaderCreateResult<TxtReader<T>> {
+        let mut reader = TxtReader::with_comments(reader, CommentCapture::None);
+        reader.delimiter = options.txt_delimiter;
+        reader.timestamp_unit = options.timestamp_unit;
+        reader.aliases = options.alias_table();
+        reader.lenient = options.lenient;
+        reader.defaults = options
+            .defaults
+            .parse(options.amount_unit, options.timestamp_unit)
+            .map_err(|e| ReaderCreateError {
+                text: "failed to create txt reader".into(),
+                source: Some(Box::new(e)),
+            })?;
+        Ok(reader)
+    }
+
+    /// Creates a reader of lines whose names and values are separated with the delimiter
+    ///
+    /// ```
+    /// use parserde::{DataProducer, TxtDelimiter, TxtReader};
+    /// let input = "TX_ID = 1\nTX_TYPE = DEPOSIT\nFROM_USER_ID = 0\nTO_USER_ID = 2\n\
+    ///     AMOUNT = 5\nTIMESTAMP = 9\nSTATUS = SUCCESS\nDESCRIPTION = fee\n";
+    /// let mut reader = TxtReader::new_with_delimiter(input.as_bytes(), TxtDelimiter::Equals);
+    /// assert_eq!(reader.produce_record().unwrap().unwrap().description(), "fee");
+    /// ```
+    ///
+    pub fn new_with_delimiter(reader: T, delimiter: TxtDelimiter) -> TxtReader<T> {
+        let mut reader = TxtReader::with_comments(reader, CommentCapture::None);
+        reader.delimiter = delimiter;
+        reader
+    }
+
+    /// Creates a reader keeping the comments selected by the capture.
+    /// Records are produced the same way regardless of it
+    pub fn with_comments(reader: T, capture: CommentCapture) -> TxtReader<T> {
+        TxtReader {
             reader: BufReader::new(reader),
             current_line: 0,
+            offset: 0,
+            records: 0,
             is_exhausted: false,
-        })
+            capture,
+            delimiter: TxtDelimiter::default(),
+            timestamp_unit: TimestampUnit::default(),
+            defaults: Vec::new(),
+            aliases: Vec::new(),
+            in_leading_comments: true,
+            leading_comments: Vec::new(),
+            comments: Vec::new(),
+            lenient: false,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Errors of the records skipped so far by a lenient reader
+    pub fn collected_errors(&self) -> &[RecordProduceError] {
+        &self.errors
+    }
+
+    /// Comments met before the first record, without the leading `# `
+    pub fn leading_comments(&self) -> &[String] {
+        &self.leading_comments
+    }
+
+    /// Comments met so far with their line numbers.
+    /// Empty unless all comments are captured
+    pub fn comments(&self) -> &[(u64, String)] {
+        &self.comments
+    }
+
+    /// Reads the comments and blank lines at the start of the input
+    /// without reading the first record
+    pub fn read_leading_comments(&mut self) -> io::Result<&[String]> {
+        while self.in_leading_comments
+            && matches!(self.reader.fill_buf()?.first(), Some(b'#' | b'\n' | b'\r'))
+        {
+            let mut buf = String::new();
+            self.offset += self.reader.read_line(&mut buf)? as u64;
+            self.current_line += 1;
+            let line = buf.trim_end_matches(['\n', '\r']);
+            if !line.is_empty() {
+                self.keep_comment(line);
+            }
+        }
+        Ok(&self.leading_comments)
+    }
+
+    fn keep_comment(&mut self, line: &str) {
+        let text = line.strip_prefix("# ").unwrap_or(&line[1..]);
+        if self.in_leading_comments && self.capture != CommentCapture::None {
+            self.leading_comments.push(text.to_string());
+        }
+        if self.capture == CommentCapture::All {
+            self.comments.push((self.current_line, text.to_string()));
+        }
     }
 
     fn read_payload(&mut self) -> Option<Result<String, io::Error>> {
@@ -34,12 +188,17 @@ impl<T: Read> TxtReader<T> {
                 return None;
             }
             self.current_line += 1;
+            self.offset += bytes_read as u64;
             if buf.ends_with('\n') {
                 let _ = buf.pop();
             }
             if buf.starts_with('#') {
+                self.keep_comment(&buf);
                 continue;
             }
+            if !buf.is_empty() {
+                self.in_leading_comments = false;
+            }
             break buf;
         };
         Some(Ok(payload))
@@ -63,72 +222,322 @@ impl<T: Read> DataConsumer for TxtReader<T> {
     }
 }
 
-impl<T: Read> DataProducer for TxtReader<T> {
-    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
-        let mut fields = Vec::new();
+impl<T: Read> TxtReader<T> {
+    /// Reads the lines of the next record
+    fn read_block(&mut self) -> Option<RecordProduceResult<Vec<String>>> {
+        let mut lines = Vec::new();
         while let Some(read_result) = self.read() {
-            if let Err(e) = read_result {
-                return Some(Err(RecordProduceError {
-                    text: format!("failed to read line {}", self.current_line),
-                    source: Some(Box::new(e)),
-                }));
-            }
-            let line = read_result.unwrap();
+            let line = match read_result {
+                Ok(line) => line,
+                Err(e) => {
+                    return Some(Err(RecordProduceError {
+                        kind: e.kind(),
+                        text: format!("failed to read line {}", self.current_line),
+                        source: Some(Box::new(e)),
+                    }));
+                }
+            };
+            // a run of blank lines is a single separator
             if line.is_empty() {
+                if lines.is_empty() {
+                    continue;
+                }
                 break;
             }
-            match FieldValue::try_from(Data::new(line)) {
+            lines.push(line);
+        }
+        if lines.is_empty() {
+            return None;
+        }
+        self.records += 1;
+        Some(Ok(lines))
+    }
+
+    fn parse_block(&self, lines: &[String]) -> RecordProduceResult<Vec<FieldValue>> {
+        let mut fields: Vec<FieldValue> = Vec::with_capacity(lines.len());
+        for line in lines {
+            let value = self
+                .parse_line(line)
+                .and_then(|value| value.with_timestamp_unit(self.timestamp_unit));
+            match value {
+                Ok(val)
+                    if !self.aliases.is_empty()
+                        && fields.iter().any(|f| f.field() == val.field()) =>
+                {
+                    return Err(RecordProduceError {
+                        kind: RecordProduceErrorKind::MalformedRecord,
+                        text: format!(
+                            "duplicate field {}. near line {}",
+                            val.field().as_str(),
+                            self.current_line
+                        ),
+                        source: None,
+                    });
+                }
                 Ok(val) => fields.push(val),
                 Err(e) => {
-                    return Some(Err(RecordProduceError {
-                        text: format!("failed to parse field. line {}", self.current_line),
+                    return Err(RecordProduceError {
+                        kind: self.field_error_kind(line),
+                        text: format!("failed to parse field. near line {}", self.current_line),
                         source: Some(Box::new(e)),
-                    }));
+                    });
                 }
             }
         }
-        if fields.is_empty() {
-            return None;
+        Ok(fields)
+    }
+
+    /// Kind of the error of parsing the field line
+    fn field_error_kind(&self, line: &str) -> RecordProduceErrorKind {
+        match self
+            .delimiter
+            .split(line)
+            .and_then(|(name, _)| resolve_field(name, &self.aliases))
+        {
+            Some(field) => RecordProduceErrorKind::ParseField {
+                field_name: field.as_str().into(),
+            },
+            None => RecordProduceErrorKind::MalformedRecord,
+        }
+    }
+
+    /// Parses a field line split with the delimiter, resolving an aliased name
+    fn parse_line(&self, line: &str) -> FieldParseResult<FieldValue> {
+        let (name, value) = self.delimiter.split(line).ok_or_else(|| FieldParseError {
+            text: "no delimiter found".into(),
+            source: None,
+        })?;
+        let field = resolve_field(name, &self.aliases).ok_or_else(|| FieldParseError {
+            text: format!("unknown field: {}", name),
+            source: None,
+        })?;
+        Field::new(field, value).parse()
+    }
+
+    fn read_fields(&mut self) -> Option<RecordProduceResult<Vec<FieldValue>>> {
+        let lines = match self.read_block()? {
+            Ok(lines) => lines,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(self.parse_block(&lines))
+    }
+
+    fn record_from_fields(&self, fields: Vec<FieldValue>) -> RecordProduceResult<Record> {
+        Record::try_from(with_defaults(&self.defaults, fields)).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!("failed to parse record. near line {}", self.current_line),
+            source: Some(e.into()),
+        })
+    }
+
+    /// Parses the lines of a record read outside of the reader ending at the line
+    #[cfg(feature = "tokio")]
+    pub(crate) fn parse_lines(
+        &mut self,
+        lines: &[String],
+        line: u64,
+    ) -> RecordProduceResult<Record> {
+        self.current_line = line;
+        self.records += 1;
+        self.record_from_fields(self.parse_block(lines)?)
+    }
+}
+
+impl<T: Read + Seek> TxtReader<T> {
+    /// Moves the reader to a position previously returned by `position`
+    pub(crate) fn seek(&mut self, position: Position) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(position.byte))?;
+        self.offset = position.byte;
+        self.current_line = position.line;
+        self.records = position.record;
+        self.is_exhausted = false;
+        Ok(())
+    }
+}
+
+impl<T: Read> DataProducer for TxtReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        loop {
+            let record = self
+                .read_fields()?
+                .and_then(|fields| self.record_from_fields(fields));
+            match record {
+                Err(e) if self.lenient && e.kind != RecordProduceErrorKind::Io => {
+                    self.errors.push(e);
+                }
+                record => return Some(record),
+            }
         }
-        match Record::try_from(fields) {
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: self.current_line,
+            record: self.records,
+        })
+    }
+}
+
+impl<T: Read> RawDataProducer for TxtReader<T> {
+    fn produce_raw(&mut self) -> Option<RecordProduceResult<RawRecord>> {
+        match self.read_block()? {
+            Ok(lines) => Some(Ok(RawRecord(RawRepr::Txt(lines)))),
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn parse_raw(&self, raw: &RawRecord) -> RecordProduceResult<Record> {
+        match &raw.0 {
+            RawRepr::Txt(lines) => self.record_from_fields(self.parse_block(lines)?),
+            _ => Err(RecordProduceError {
+                kind: RecordProduceErrorKind::Unknown,
+                text: "raw record is not a txt block".into(),
+                source: None,
+            }),
+        }
+    }
+}
+
+impl<T: Read> PartialDataProducer for TxtReader<T> {
+    fn produce_partial_record(&mut self) -> Option<RecordProduceResult<PartialRecord>> {
+        let fields = match self.read_fields()? {
+            Ok(fields) => fields,
+            Err(e) => return Some(Err(e)),
+        };
+        match PartialRecord::try_from(with_defaults(&self.defaults, fields)) {
             Ok(r) => Some(Ok(r)),
             Err(e) => Some(Err(RecordProduceError {
-                text: format!("failed to parse record. near line {}", self.current_line),
+                kind: RecordProduceErrorKind::of_record(&e),
+                text: format!(
+                    "failed to parse partial record. near line {}",
+                    self.current_line
+                ),
                 source: Some(e.into()),
             })),
         }
     }
 }
 
-pub(crate) struct TxtSerialize;
+pub(crate) struct TxtSerialize {
+    style: TxtStyle,
+    right_align_numbers: bool,
+    sanitize: SanitizePolicy,
+    timestamp_unit: TimestampUnit,
+}
+
+impl TxtSerialize {
+    pub(crate) fn with_options(options: &SerializeOptions) -> TxtSerialize {
+        TxtSerialize {
+            style: options.txt_style,
+            right_align_numbers: options.right_align_numbers,
+            sanitize: options.sanitize,
+            timestamp_unit: options.timestamp_unit,
+        }
+    }
+}
 
 impl RecordSerialize for TxtSerialize {
     fn serialize(&self, record: &Record) -> Result<Vec<u8>, RecordSerializeError> {
         let fields = [
-            format!("{}: {}", fields::str::TX_ID, &record.tx_id),
-            format!("{}: {}", fields::str::AMOUNT, &record.amount),
-            format!("{}: {}", fields::str::TIMESTAMP, &record.timestamp),
-            format!("{}: {}", fields::str::DESCRIPTION, &record.description),
-            format!("{}: {}", fields::str::TX_TYPE, &record.tx_type),
-            format!("{}: {}", fields::str::FROM_USER, &record.from_user),
-            format!("{}: {}", fields::str::TO_USER, &record.to_user),
-            format!("{}: {}\n", fields::str::STATUS, &record.status),
+            (FieldName::TxId.as_str(), record.tx_id.to_string(), true),
+            (FieldName::Amount.as_str(), record.amount.to_string(), true),
+            (
+                FieldName::Timestamp.as_str(),
+                self.timestamp_unit.timestamp(record)?.to_string(),
+                true,
+            ),
+            (
+                FieldName::Description.as_str(),
+                self.sanitize.description(record)?,
+                false,
+            ),
+            (
+                FieldName::TxType.as_str(),
+                record.tx_type.to_string(),
+                false,
+            ),
+            (
+                FieldName::FromUser.as_str(),
+                record.from_user.to_string(),
+                true,
+            ),
+            (FieldName::ToUser.as_str(), record.to_user.to_string(), true),
+            (FieldName::Status.as_str(), record.status.to_string(), false),
         ];
-        Ok(fields.join("\n").into_bytes())
+        let lines = match self.style {
+            TxtStyle::Plain => fields
+                .iter()
+                .map(|(name, value, _)| format!("{}: {}", name, value))
+                .collect::<Vec<_>>(),
+            TxtStyle::Aligned => {
+                let name_width = fields.iter().map(|f| f.0.len()).max().unwrap_or(0);
+                let number_width = fields
+                    .iter()
+                    .filter(|f| f.2)
+                    .map(|f| f.1.len())
+                    .max()
+                    .unwrap_or(0);
+                fields
+                    .iter()
+                    .map(|(name, value, is_number)| {
+                        if *is_number && self.right_align_numbers {
+                            format!("{:name_width$}: {:>number_width$}", name, value)
+                        } else {
+                            format!("{:name_width$}: {}", name, value)
+                        }
+                    })
+                    .collect()
+            }
+        };
+        let mut r = lines.join("\n");
+        r.push('\n');
+        Ok(r.into_bytes())
     }
 }
 
 pub(crate) struct RecordWrite<W: Write> {
     writer: W,
+    section_comments: bool,
+    records: u64,
 }
 
 impl<W: Write> RecordWrite<W> {
-    pub(crate) fn new(writer: W) -> RecordWrite<W> {
-        RecordWrite { writer }
+    pub(crate) fn with_options(writer: W, options: &SerializeOptions) -> RecordWrite<W> {
+        RecordWrite {
+            writer,
+            section_comments: options.txt_section_comments,
+            records: 0,
+        }
     }
 }
 
 impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write_record(
+        &mut self,
+        record: &Record,
+        data: Vec<u8>,
+    ) -> crate::result::RecordWriteResult<()> {
+        self.records += 1;
+        if self.section_comments {
+            let mut annotated =
+                format!("# Record {} ({})\n", self.records, record.tx_type).into_bytes();
+            annotated.extend(data);
+            return self.write(annotated);
+        }
+        self.write(data)
+    }
+
+    fn write_comment(&mut self, text: &str) -> crate::result::RecordWriteResult<()> {
+        if text.contains('\n') {
+            return Err(RecordWriteError {
+                text: "comment must be a single line".into(),
+                source: None,
+            });
+        }
+        self.write(format!("# {}", text).into_bytes())
+    }
+
     fn write(&mut self, mut data: Vec<u8>) -> crate::result::RecordWriteResult<()> {
         data.push(b'\n');
         match self.writer.write_all(&data) {
@@ -252,4 +661,385 @@ TX_ID: 1000000000000006"
         assert_eq!(record2.tx_id, 1000000000000006);
         assert!(tx.produce_record().is_none());
     }
+
+    #[test]
+    fn test_partial_record_produce() {
+        let input =
+            "TX_ID: 1000000000000001\nSTATUS: SUCCESS\nDESCRIPTION: fixed\n\nSTATUS: FAILURE\n";
+        let mut tx = TxtReader::new(Cursor::new(input)).unwrap();
+        let patch = tx.produce_partial_record().unwrap().unwrap();
+        assert_eq!(patch.tx_id, 1000000000000001);
+        assert_eq!(patch.status, Some(crate::record::Status::Success));
+        assert_eq!(patch.description.as_deref(), Some("fixed"));
+        assert_eq!(patch.amount, None);
+        // tx_id is still required
+        assert!(tx.produce_partial_record().unwrap().is_err());
+    }
+
+    fn get_record() -> Record {
+        Record::new(
+            1000000000000001,
+            crate::record::TxType::Transfer,
+            42,
+            9223372036854775807,
+            200,
+            1633036920000,
+            crate::record::Status::Pending,
+            " Record number 2".into(),
+        )
+    }
+
+    #[test]
+    fn test_aligned_serialize() {
+        let ser = TxtSerialize::with_options(&SerializeOptions {
+            txt_style: TxtStyle::Aligned,
+            right_align_numbers: true,
+            ..Default::default()
+        });
+        let output = String::from_utf8(ser.serialize(&get_record()).unwrap()).unwrap();
+        assert_eq!(
+            output,
+            "\
+TX_ID       :    1000000000000001
+AMOUNT      :                 200
+TIMESTAMP   :       1633036920000
+DESCRIPTION :  Record number 2
+TX_TYPE     : TRANSFER
+FROM_USER_ID:                  42
+TO_USER_ID  : 9223372036854775807
+STATUS      : PENDING
+"
+        );
+    }
+
+    #[test]
+    fn test_aligned_round_trip() {
+        let records = [get_record(), Record::default()];
+        for right_align_numbers in [false, true] {
+            let ser = TxtSerialize::with_options(&SerializeOptions {
+                txt_style: TxtStyle::Aligned,
+                right_align_numbers,
+                ..Default::default()
+            });
+            let mut out = Vec::new();
+            let mut writer = RecordWrite::with_options(&mut out, &SerializeOptions::default());
+            for record in &records {
+                writer.write(ser.serialize(record).unwrap()).unwrap();
+            }
+            let mut reader = TxtReader::new(Cursor::new(out)).unwrap();
+            for record in &records {
+                assert_eq!(&reader.produce_record().unwrap().unwrap(), record);
+            }
+            assert!(reader.produce_record().is_none());
+        }
+    }
+
+    #[test]
+    fn test_success_round_trip() {
+        use crate::record::Status;
+        let mut record = get_record();
+        record.status = Status::Success;
+        assert_eq!(<&str>::from(Status::Success), "SUCCESS");
+        assert_eq!(
+            Status::try_from(<&str>::from(record.status)),
+            Ok(Status::Success)
+        );
+        let ser = TxtSerialize::with_options(&SerializeOptions::default());
+        let mut out = Vec::new();
+        let mut writer = RecordWrite::with_options(&mut out, &SerializeOptions::default());
+        writer.write(ser.serialize(&record).unwrap()).unwrap();
+        let mut reader = TxtReader::new(Cursor::new(out)).unwrap();
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_leading_comments() {
+        let input = format!(
+            "# exported 2024-05-01 by batch 118\n#raw\n{}",
+            get_good_input()
+        );
+        let mut tx = TxtReader::with_comments(Cursor::new(input.clone()), CommentCapture::Leading);
+        assert_eq!(
+            tx.read_leading_comments().unwrap(),
+            [
+                "exported 2024-05-01 by batch 118",
+                "raw",
+                "Record 2 (TRANSFER)"
+            ]
+        );
+        assert_eq!(
+            tx.produce_record().unwrap().unwrap().tx_id,
+            1000000000000001
+        );
+        assert_eq!(
+            tx.produce_record().unwrap().unwrap().tx_id,
+            1000000000000006
+        );
+        assert_eq!(tx.leading_comments().len(), 3);
+        assert!(tx.comments().is_empty());
+
+        let mut tx = TxtReader::with_comments(Cursor::new(input), CommentCapture::All);
+        while tx.produce_record().is_some() {}
+        assert_eq!(tx.records, 2);
+        assert_eq!(tx.leading_comments().len(), 3);
+        assert_eq!(
+            tx.comments().last().unwrap(),
+            &(13, "Record 7 (DEPOSIT)".to_string())
+        );
+        assert_eq!(tx.comments().len(), 4);
+    }
+
+    #[test]
+    fn test_write_comment() {
+        let mut out = Vec::new();
+        let mut writer = RecordWrite::with_options(&mut out, &SerializeOptions::default());
+        writer.write_comment("exported by batch 118").unwrap();
+        assert!(writer.write_comment("two\nlines").is_err());
+        let ser = TxtSerialize::with_options(&SerializeOptions::default());
+        writer.write(ser.serialize(&get_record()).unwrap()).unwrap();
+
+        let mut tx = TxtReader::with_comments(Cursor::new(out), CommentCapture::Leading);
+        assert_eq!(tx.produce_record().unwrap().unwrap(), get_record());
+        assert_eq!(tx.leading_comments(), ["exported by batch 118"]);
+    }
+
+    #[test]
+    fn test_blank_line_runs() {
+        let good = get_good_input();
+        let (first, second) = good.split_once("\n\n").unwrap();
+        let inputs = [
+            format!("{}\n\n\n{}", first, second),
+            format!("\n{}\n\n{}", first, second),
+            format!("{}\n\n{}\n\n\n\n", first, second),
+            format!("\n\n# banner\n\n{}\n\n\n# note\n\n{}\n", first, second),
+        ];
+        for input in inputs {
+            let mut tx = TxtReader::new(Cursor::new(input.clone())).unwrap();
+            assert_eq!(
+                tx.produce_record().unwrap().unwrap().tx_id,
+                1000000000000001
+            );
+            assert_eq!(
+                tx.produce_record().unwrap().unwrap().tx_id,
+                1000000000000006
+            );
+            assert!(tx.produce_record().is_none(), "{:?}", input);
+        }
+
+        let mut tx = TxtReader::new(Cursor::new("# only\n\n\n# comments\n\n")).unwrap();
+        assert!(tx.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_sanitized_round_trip() {
+        let mut record = get_record();
+        record.description = "a\tb\rc\0d".into();
+        let ser = TxtSerialize::with_options(&SerializeOptions {
+            sanitize: SanitizePolicy::Replace(' '),
+            ..Default::default()
+        });
+        let mut out = Vec::new();
+        RecordWrite::with_options(&mut out, &SerializeOptions::default())
+            .write(ser.serialize(&record).unwrap())
+            .unwrap();
+        let parsed = TxtReader::new(Cursor::new(out))
+            .unwrap()
+            .produce_record()
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.description, "a b c d");
+
+        let ser = TxtSerialize::with_options(&SerializeOptions {
+            sanitize: SanitizePolicy::Error,
+            ..Default::default()
+        });
+        assert!(ser.serialize(&record).is_err());
+    }
+
+    #[test]
+    fn test_section_comments() {
+        let options = SerializeOptions {
+            txt_section_comments: true,
+            ..Default::default()
+        };
+        let records = [get_record(), Record::default()];
+        let ser = TxtSerialize::with_options(&options);
+        let mut out = Vec::new();
+        let mut writer = RecordWrite::with_options(&mut out, &options);
+        for record in &records {
+            writer
+                .write_record(record, ser.serialize(record).unwrap())
+                .unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(out.clone()).unwrap(),
+            "\
+# Record 1 (TRANSFER)
+TX_ID: 1000000000000001
+AMOUNT: 200
+TIMESTAMP: 1633036920000
+DESCRIPTION:  Record number 2
+TX_TYPE: TRANSFER
+FROM_USER_ID: 42
+TO_USER_ID: 9223372036854775807
+STATUS: PENDING
+
+# Record 2 (DEPOSIT)
+TX_ID: 0
+AMOUNT: 0
+TIMESTAMP: 0
+DESCRIPTION: 
+TX_TYPE: DEPOSIT
+FROM_USER_ID: 0
+TO_USER_ID: 0
+STATUS: PENDING
+
+"
+        );
+        let mut reader = TxtReader::new(Cursor::new(out)).unwrap();
+        for record in &records {
+            assert_eq!(&reader.produce_record().unwrap().unwrap(), record);
+        }
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_empty_description() {
+        let input = "TX_ID: 1\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 2\nAMOUNT: 5\nTIMESTAMP: 9\nSTATUS: SUCCESS\n";
+        let record = TxtReader::new(Cursor::new(input))
+            .unwrap()
+            .produce_record()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.description, "");
+        let out = TxtSerialize::with_options(&SerializeOptions::default())
+            .serialize(&record)
+            .unwrap();
+        let mut reader = TxtReader::new(Cursor::new(out)).unwrap();
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+    }
+
+    #[test]
+    fn test_lenient() {
+        let record = |id: &str, amount: &str| {
+            format!(
+                "ID: {}\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 2\nAMOUNT: {}\nTIMESTAMP: 9\nSTATUS: SUCCESS\n",
+                id, amount
+            )
+        };
+        let input = [record("1", "5"), record("2", "five"), record("3", "7")].join("\n");
+        let mut options = ParseOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        options.alias("ID", FieldName::TxId);
+
+        let mut reader = TxtReader::with_options(Cursor::new(input.clone()), &options).unwrap();
+        let ids = std::iter::from_fn(|| reader.produce_record())
+            .map(|r| r.unwrap().tx_id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [1, 3]);
+        let errors = reader.collected_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind(),
+            RecordProduceErrorKind::ParseField {
+                field_name: "AMOUNT".into()
+            }
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "failed to parse field. near line 16. source failed to parse amount"
+        );
+        // the source is kept as it was
+        let source = Error::source(&errors[0]).unwrap();
+        assert!(source.is::<FieldParseError>());
+        assert!(source.source().is_some());
+
+        options.lenient = false;
+        let mut reader = TxtReader::with_options(Cursor::new(input), &options).unwrap();
+        assert!(reader.produce_record().unwrap().is_ok());
+        let e = reader.produce_record().unwrap().unwrap_err();
+        assert_eq!(e.to_string(), errors[0].to_string());
+        assert!(reader.produce_record().unwrap().is_ok());
+        assert!(reader.collected_errors().is_empty());
+    }
+
+    #[test]
+    fn test_field_defaults() {
+        let input = "TX_ID: 1\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nAMOUNT: 5\nTIMESTAMP: 9\nSTATUS: SUCCESS\nDESCRIPTION: a\n\n\
+                     TX_ID: 2\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 3\nAMOUNT: 5\nTIMESTAMP: 9\n";
+        let mut options = ParseOptions::default();
+        options.defaults.set(crate::record::FieldName::ToUser, "0");
+        let mut reader = TxtReader::with_options(Cursor::new(input), &options).unwrap();
+        let record = reader.produce_record().unwrap().unwrap();
+        assert_eq!(record.to_user, 0);
+        assert_eq!(record.description, "a");
+        let e = reader.produce_record().unwrap().unwrap_err();
+        assert!(e.to_string().contains("missing field status"), "{}", e);
+
+        options
+            .defaults
+            .set(crate::record::FieldName::Amount, "five");
+        assert!(TxtReader::with_options(Cursor::new(input), &options).is_err());
+    }
+
+    #[test]
+    fn test_aliases() {
+        let canonical = include_str!("../../examples/source/records_example.txt");
+        let aliased = canonical
+            .replace("TX_ID:", "ID:")
+            .replace("TX_TYPE:", "TYPE:")
+            .replace("FROM_USER_ID:", "FROM_USER:")
+            .replace("DESCRIPTION:", "KIND:");
+        let mut options = ParseOptions::default();
+        options.alias("KIND", crate::record::FieldName::Description);
+        let mut expected = TxtReader::new(Cursor::new(canonical)).unwrap();
+        let mut reader = TxtReader::with_options(Cursor::new(aliased), &options).unwrap();
+        while let Some(record) = expected.produce_record() {
+            assert_eq!(reader.produce_record().unwrap().unwrap(), record.unwrap());
+        }
+        assert!(reader.produce_record().is_none());
+
+        let conflict = "TX_ID: 1\nID: 1\n";
+        let mut reader = TxtReader::with_options(Cursor::new(conflict), &options).unwrap();
+        let e = reader.produce_record().unwrap().unwrap_err();
+        assert!(e.to_string().contains("duplicate field TX_ID"), "{}", e);
+    }
+
+    #[test]
+    fn test_delimiters() {
+        use crate::record::RecordIterator;
+
+        let colon = include_str!("../../examples/source/records_example.txt");
+        let equals = colon
+            .lines()
+            .map(|line| match line.split_once(": ") {
+                Some((name, value)) if !line.starts_with('#') => format!("{} = {}", name, value),
+                _ => line.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mixed = equals.replacen("TX_ID = ", "TX_ID: ", 3);
+        let read = |input: &str, delimiter| {
+            let reader = TxtReader::new_with_delimiter(Cursor::new(input.to_string()), delimiter);
+            RecordIterator::new(reader).collect::<Result<Vec<_>, _>>()
+        };
+        let expected = read(colon, TxtDelimiter::ColonSpace).unwrap();
+        assert!(!expected.is_empty());
+        assert_eq!(read(&equals, TxtDelimiter::Equals).unwrap(), expected);
+        assert_eq!(read(&mixed, TxtDelimiter::Auto).unwrap(), expected);
+        assert_eq!(read(colon, TxtDelimiter::Auto).unwrap(), expected);
+        assert!(read(&equals, TxtDelimiter::ColonSpace).is_err());
+        assert!(read(&mixed, TxtDelimiter::Equals).is_err());
+
+        let options = ParseOptions {
+            txt_delimiter: TxtDelimiter::Equals,
+            ..Default::default()
+        };
+        let mut reader = TxtReader::with_options(Cursor::new("TX_ID:1\n"), &options).unwrap();
+        let e = reader.produce_record().unwrap().unwrap_err();
+        assert!(e.to_string().contains("near line 1"), "{}", e);
+    }
 }
@@ -0,0 +1,343 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+
+use calamine::{Data, Range, Reader, Xlsx};
+
+use crate::error::{ReaderCreateError, RecordProduceError, RecordProduceErrorKind};
+use crate::record::{DataProducer, Field, FieldName, FieldValue, Position, Record};
+use crate::result::RecordProduceResult;
+
+/// Reader of the records of an xlsx worksheet. The first row is the header
+/// naming the column of every field, the other rows are records.
+/// Numeric cells are accepted for the numeric fields and date cells for
+/// TIMESTAMP, which are read as UTC. Empty rows are skipped
+///
+/// ```no_run
+/// use parserde::{DataProducer, XlsxReader};
+/// let mut reader = XlsxReader::open("export.xlsx".as_ref(), Some("May")).unwrap();
+/// while let Some(record) = reader.produce_record() {
+///     println!("{}", record.unwrap().tx_id());
+/// }
+/// ```
+pub struct XlsxReader {
+    columns: Vec<Option<FieldName>>,
+    rows: std::vec::IntoIter<Vec<Data>>,
+    /// Spreadsheet number of the last row read, 1 based
+    current_row: u64,
+    records: u64,
+}
+
+impl XlsxReader {
+    /// Creates a reader of the named sheet of the workbook, the first sheet if none is given
+    pub fn new<R: Read + Seek>(
+        reader: R,
+        sheet: Option<&str>,
+    ) -> Result<XlsxReader, Box<dyn Error>> {
+        let mut workbook = Xlsx::new(reader)?;
+        let name = match sheet {
+            Some(name) => name.to_string(),
+            None => workbook
+                .sheet_names()
+                .first()
+                .cloned()
+                .ok_or_else(|| ReaderCreateError {
+                    text: "the workbook has no sheets".into(),
+                    source: None,
+                })?,
+        };
+        let range = workbook.worksheet_range(&name)?;
+        XlsxReader::from_range(range)
+    }
+
+    /// Creates a reader of the named sheet of the workbook file, the first sheet if none is given
+    pub fn open(path: &Path, sheet: Option<&str>) -> Result<XlsxReader, Box<dyn Error>> {
+        XlsxReader::new(BufReader::new(File::open(path)?), sheet)
+    }
+
+    fn from_range(range: Range<Data>) -> Result<XlsxReader, Box<dyn Error>> {
+        let first_row = range.start().map_or(0, |(row, _)| row as u64);
+        let mut rows = range
+            .rows()
+            .map(|row| row.to_vec())
+            .collect::<Vec<_>>()
+            .into_iter();
+        let header = rows.next().unwrap_or_default();
+        let mut columns = Vec::new();
+        for cell in header {
+            let name = match cell {
+                Data::Empty => None,
+                Data::String(text) if text.trim().is_empty() => None,
+                Data::String(text) => {
+                    Some(
+                        text.trim()
+                            .parse::<FieldName>()
+                            .map_err(|e| ReaderCreateError {
+                                text: format!("invalid header. {}", e),
+                                source: None,
+                            })?,
+                    )
+                }
+                cell => {
+                    return Err(ReaderCreateError {
+                        text: format!("invalid header. expected a field name, found {}", cell),
+                        source: None,
+                    }
+                    .into());
+                }
+            };
+            if let Some(name) = name
+                && columns.contains(&Some(name))
+            {
+                return Err(ReaderCreateError {
+                    text: format!("invalid header. duplicate column {}", name),
+                    source: None,
+                }
+                .into());
+            }
+            columns.push(name);
+        }
+        Ok(XlsxReader {
+            columns,
+            rows,
+            current_row: first_row + 1,
+            records: 0,
+        })
+    }
+
+    fn cell_text(&self, name: FieldName, cell: &Data) -> RecordProduceResult<Option<String>> {
+        let error = |text: String| RecordProduceError {
+            kind: RecordProduceErrorKind::ParseField {
+                field_name: name.as_str().into(),
+            },
+            text: format!("{}. row {}", text, self.current_row),
+            source: None,
+        };
+        let is_number = !matches!(
+            name,
+            FieldName::TxType | FieldName::Status | FieldName::Description
+        );
+        Ok(Some(match cell {
+            Data::Empty => return Ok(None),
+            Data::String(text) => text.clone(),
+            Data::Int(number) => number.to_string(),
+            Data::Float(number) if !is_number || number.fract() == 0.0 => number.to_string(),
+            Data::DateTime(date) if name == FieldName::Timestamp => {
+                let millis = date
+                    .as_datetime()
+                    .map(|d| d.and_utc().timestamp_millis())
+                    .filter(|&millis| millis >= 0)
+                    .ok_or_else(|| error(format!("{} is not a date after 1970", date)))?;
+                millis.to_string()
+            }
+            cell => return Err(error(format!("invalid value {} of {}", cell, name))),
+        }))
+    }
+
+    fn parse_row(&self, row: &[Data]) -> RecordProduceResult<Record> {
+        let mut values: Vec<FieldValue> = Vec::with_capacity(self.columns.len());
+        for (name, cell) in self.columns.iter().zip(row) {
+            let Some(name) = *name else {
+                continue;
+            };
+            let Some(text) = self.cell_text(name, cell)? else {
+                continue;
+            };
+            let value =
+                Field::new(name, text.as_str())
+                    .parse()
+                    .map_err(|e| RecordProduceError {
+                        kind: RecordProduceErrorKind::ParseField {
+                            field_name: name.as_str().into(),
+                        },
+                        text: format!("failed to parse field. row {}", self.current_row),
+                        source: Some(Box::new(e)),
+                    })?;
+            values.push(value);
+        }
+        Record::try_from(values).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!("failed to parse record. row {}", self.current_row),
+            source: Some(e.into()),
+        })
+    }
+}
+
+impl DataProducer for XlsxReader {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        loop {
+            let row = self.rows.next()?;
+            self.current_row += 1;
+            if row.iter().all(|cell| *cell == Data::Empty) {
+                continue;
+            }
+            self.records += 1;
+            return Some(self.parse_row(&row));
+        }
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: 0,
+            line: self.current_row,
+            record: self.records,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+    use super::*;
+    use crate::record::{RecordIterator, Status, TxType};
+
+    fn range(rows: &[Vec<Data>]) -> Range<Data> {
+        let width = rows.iter().map(Vec::len).max().unwrap() as u32;
+        let mut range = Range::new((0, 0), (rows.len() as u32 - 1, width - 1));
+        for (i, row) in rows.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                range.set_value((i as u32, j as u32), cell.clone());
+            }
+        }
+        range
+    }
+
+    fn text(s: &str) -> Data {
+        Data::String(s.into())
+    }
+
+    fn header() -> Vec<Data> {
+        [
+            "DESCRIPTION",
+            "TX_ID",
+            "TX_TYPE",
+            "FROM_USER_ID",
+            "TO_USER_ID",
+            "AMOUNT",
+            "TIMESTAMP",
+            "STATUS",
+        ]
+        .map(text)
+        .to_vec()
+    }
+
+    #[test]
+    fn test_cell_types() {
+        // 2021-10-01 00:01:00 UTC
+        let date = ExcelDateTime::new(44470.0 + 1.0 / 1440.0, ExcelDateTimeType::DateTime, false);
+        let rows = [
+            header(),
+            vec![
+                text("Record number 1"),
+                Data::Float(1.0),
+                text("DEPOSIT"),
+                Data::Int(0),
+                text("2"),
+                Data::Float(100.0),
+                Data::DateTime(date),
+                text("SUCCESS"),
+            ],
+            vec![Data::Empty; 8],
+            vec![
+                Data::Float(2.5),
+                Data::Int(2),
+                text("TRANSFER"),
+                Data::Int(2),
+                Data::Int(3),
+                Data::Int(50),
+                Data::Float(1633036860000.0),
+                text("PENDING"),
+            ],
+        ];
+        let records = RecordIterator::new(XlsxReader::from_range(range(&rows)).unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            records,
+            [
+                Record::new(
+                    1,
+                    TxType::Deposit,
+                    0,
+                    2,
+                    100,
+                    1633046460000,
+                    Status::Success,
+                    "Record number 1".into()
+                ),
+                Record::new(
+                    2,
+                    TxType::Transfer,
+                    2,
+                    3,
+                    50,
+                    1633036860000,
+                    Status::Pending,
+                    "2.5".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_errors_name_the_row() {
+        let mut row = vec![
+            text("a"),
+            Data::Int(1),
+            text("DEPOSIT"),
+            Data::Int(0),
+            Data::Int(2),
+            Data::Int(100),
+            Data::Int(5),
+            text("SUCCESS"),
+        ];
+        let valid = row.clone();
+        let mut rows = vec![header(), valid.clone()];
+        row[5] = Data::Float(1.5);
+        rows.push(row);
+        let mut row = valid.clone();
+        row[3] = Data::Int(-1);
+        rows.push(row);
+        let mut row = valid.clone();
+        row[0] = Data::Empty;
+        row[1] = Data::Empty;
+        rows.push(row);
+        let mut row = valid;
+        row[2] = Data::Bool(true);
+        rows.push(row);
+        let mut reader = XlsxReader::from_range(range(&rows)).unwrap();
+        assert!(reader.produce_record().unwrap().is_ok());
+        for (row, field) in [
+            (3, "AMOUNT"),
+            (4, "FROM_USER_ID"),
+            (5, "TX_ID"),
+            (6, "TX_TYPE"),
+        ] {
+            let err = reader.produce_record().unwrap().unwrap_err();
+            assert!(err.to_string().contains(&format!("row {}", row)), "{}", err);
+            match err.kind() {
+                RecordProduceErrorKind::ParseField { field_name }
+                | RecordProduceErrorKind::MissingField { field_name } => {
+                    assert_eq!(field_name, field)
+                }
+                kind => panic!("unexpected {:?}", kind),
+            }
+        }
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let mut unknown = header();
+        unknown[0] = text("NOTE");
+        let mut duplicate = header();
+        duplicate[0] = text("TX_ID");
+        let mut number = header();
+        number[0] = Data::Int(1);
+        for header in [unknown, duplicate, number] {
+            assert!(XlsxReader::from_range(range(&[header])).is_err());
+        }
+    }
+}
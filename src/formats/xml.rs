@@ -0,0 +1,591 @@
+use std::io::{BufReader, Read, Write};
+
+use quick_xml::Reader;
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+
+use crate::error::{
+    ReaderCreateError, RecordProduceError, RecordProduceErrorKind, RecordReadError,
+    RecordWriteError,
+};
+use crate::options::{
+    AmountUnit, ParseOptions, SanitizePolicy, SerializeOptions, TimestampUnit, UnknownFields,
+};
+use crate::record::{
+    DataConsumer, DataProducer, Field, FieldName, FieldValue, Position, Record, RecordSerialize,
+    RecordWriter, resolve_field, with_defaults,
+};
+use crate::result::{
+    ReaderCreateResult, RecordProduceResult, RecordReadResult, RecordSerializeResult,
+    RecordWriteResult,
+};
+
+const ROOT: &str = "transactions";
+const ELEMENT: &str = "transaction";
+
+/// Part of the document the reader is in
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DocumentState {
+    /// Before the root element
+    Start,
+    /// Inside the root element
+    Root,
+    /// After the root element or an error
+    End,
+}
+
+/// Element of a transaction, a child element or an attribute, with its text
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct XmlField {
+    name: String,
+    text: String,
+}
+
+/// Reader of the xml format, `transaction` elements inside a `transactions` root.
+/// Fields are child elements or attributes of a transaction,
+/// comments and whitespace between elements are skipped
+pub(crate) struct XmlReader<T: Read> {
+    reader: Reader<BufReader<T>>,
+    buf: Vec<u8>,
+    state: DocumentState,
+    records: u64,
+    amount_unit: AmountUnit,
+    timestamp_unit: TimestampUnit,
+    unknown_fields: UnknownFields,
+    defaults: Vec<FieldValue>,
+    aliases: Vec<(String, FieldName)>,
+}
+
+impl<T: Read> XmlReader<T> {
+    pub(crate) fn with_options(
+        reader: T,
+        options: &ParseOptions,
+    ) -> ReaderCreateResult<XmlReader<T>> {
+        let defaults = options
+            .defaults
+            .parse(options.amount_unit, options.timestamp_unit)
+            .map_err(|e| ReaderCreateError {
+                text: "failed to create xml reader".into(),
+                source: Some(Box::new(e)),
+            })?;
+        Ok(XmlReader {
+            reader: Reader::from_reader(BufReader::new(reader)),
+            buf: Vec::new(),
+            state: DocumentState::Start,
+            records: 0,
+            amount_unit: options.amount_unit,
+            timestamp_unit: options.timestamp_unit,
+            unknown_fields: options.unknown_fields,
+            defaults,
+            aliases: options.alias_table(),
+        })
+    }
+
+    fn error(&self, text: &str) -> RecordReadError {
+        RecordReadError {
+            text: format!("{}. near byte {}", text, self.reader.buffer_position()),
+            source: None,
+        }
+    }
+
+    /// Next event which isn't markup without data like a comment
+    fn read_event(&mut self) -> RecordReadResult<Event<'static>> {
+        loop {
+            self.buf.clear();
+            let event =
+                self.reader
+                    .read_event_into(&mut self.buf)
+                    .map_err(|e| RecordReadError {
+                        text: format!("invalid xml. near byte {}", self.reader.error_position()),
+                        source: Some(Box::new(e)),
+                    })?;
+            match event {
+                Event::Comment(_) | Event::Decl(_) | Event::PI(_) | Event::DocType(_) => continue,
+                event => return Ok(event.into_owned()),
+            }
+        }
+    }
+
+    /// Next event which isn't markup without data or whitespace between elements
+    fn next_event(&mut self) -> RecordReadResult<Event<'static>> {
+        loop {
+            match self.read_event()? {
+                Event::Text(text) if text.iter().all(u8::is_ascii_whitespace) => continue,
+                event => return Ok(event),
+            }
+        }
+    }
+
+    fn name(element: &BytesStart<'_>) -> String {
+        String::from_utf8_lossy(element.name().as_ref()).into_owned()
+    }
+
+    fn unexpected(&self, expected: &str, event: &Event<'_>) -> RecordReadError {
+        let found = match event {
+            Event::Start(element) | Event::Empty(element) => {
+                format!("element {}", Self::name(element))
+            }
+            Event::End(element) => {
+                format!(
+                    "end of {}",
+                    String::from_utf8_lossy(element.name().as_ref())
+                )
+            }
+            Event::Eof => "the end of input".into(),
+            _ => "text".into(),
+        };
+        self.error(&format!("expected {} but found {}", expected, found))
+    }
+
+    fn attributes(&self, element: &BytesStart<'_>) -> RecordReadResult<Vec<XmlField>> {
+        let mut fields = Vec::new();
+        for attribute in element.attributes() {
+            let attribute = attribute.map_err(|e| RecordReadError {
+                text: format!("invalid attribute of {}", Self::name(element)),
+                source: Some(Box::new(e)),
+            })?;
+            let name = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+            let text = attribute.unescape_value().map_err(|e| RecordReadError {
+                text: format!("invalid value of attribute {}", name),
+                source: Some(Box::new(e)),
+            })?;
+            fields.push(XmlField {
+                name,
+                text: text.into_owned(),
+            });
+        }
+        Ok(fields)
+    }
+
+    /// Reads the text of a field element up to its end
+    fn read_text(&mut self, name: &str) -> RecordReadResult<String> {
+        let mut text = String::new();
+        loop {
+            match self.read_event()? {
+                Event::Text(part) => {
+                    text.push_str(&part.unescape().map_err(|e| RecordReadError {
+                        text: format!("invalid text of element {}", name),
+                        source: Some(Box::new(e)),
+                    })?)
+                }
+                Event::CData(part) => {
+                    text.push_str(&part.decode().map_err(|e| RecordReadError {
+                        text: format!("invalid text of element {}", name),
+                        source: Some(Box::new(e)),
+                    })?)
+                }
+                Event::End(_) => return Ok(text),
+                event => {
+                    return Err(self.unexpected(&format!("the text of element {}", name), &event));
+                }
+            }
+        }
+    }
+
+    /// Reads the fields of a transaction up to its end
+    fn read_fields(&mut self, mut fields: Vec<XmlField>) -> RecordReadResult<Vec<XmlField>> {
+        loop {
+            match self.next_event()? {
+                Event::Start(element) => {
+                    let name = Self::name(&element);
+                    let text = self.read_text(&name)?;
+                    fields.push(XmlField { name, text });
+                }
+                Event::Empty(element) => fields.push(XmlField {
+                    name: Self::name(&element),
+                    text: String::new(),
+                }),
+                Event::End(element) if element.name().as_ref() == ELEMENT.as_bytes() => {
+                    return Ok(fields);
+                }
+                event => return Err(self.unexpected("a field element", &event)),
+            }
+        }
+    }
+
+    fn read_element(&mut self) -> RecordReadResult<Option<Vec<XmlField>>> {
+        if self.state == DocumentState::Start {
+            match self.next_event()? {
+                Event::Start(element) if element.name().as_ref() == ROOT.as_bytes() => {
+                    self.state = DocumentState::Root;
+                }
+                Event::Empty(element) if element.name().as_ref() == ROOT.as_bytes() => {
+                    self.state = DocumentState::End;
+                    return self.read_end();
+                }
+                event => return Err(self.unexpected(&format!("element {}", ROOT), &event)),
+            }
+        }
+        match self.next_event()? {
+            Event::Start(element) if element.name().as_ref() == ELEMENT.as_bytes() => {
+                let fields = self.attributes(&element)?;
+                self.read_fields(fields).map(Some)
+            }
+            Event::Empty(element) if element.name().as_ref() == ELEMENT.as_bytes() => {
+                self.attributes(&element).map(Some)
+            }
+            Event::End(element) if element.name().as_ref() == ROOT.as_bytes() => {
+                self.state = DocumentState::End;
+                self.read_end()
+            }
+            event => Err(self.unexpected(&format!("element {}", ELEMENT), &event)),
+        }
+    }
+
+    /// Checks nothing but comments follows the root element
+    fn read_end(&mut self) -> RecordReadResult<Option<Vec<XmlField>>> {
+        match self.next_event()? {
+            Event::Eof => Ok(None),
+            event => Err(self.unexpected("the end of input", &event)),
+        }
+    }
+
+    fn parse_fields(&self, fields: Vec<XmlField>) -> RecordProduceResult<Vec<FieldValue>> {
+        let position = self.reader.buffer_position();
+        let error = |text: String| RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!("{}. near byte {}", text, position),
+            source: None,
+        };
+        let mut values: Vec<FieldValue> = Vec::with_capacity(fields.len());
+        for XmlField {
+            name: element,
+            text,
+        } in fields
+        {
+            let Some(name) = resolve_field(&element, &self.aliases) else {
+                match self.unknown_fields {
+                    UnknownFields::Error => {
+                        return Err(error(format!("unknown element {}", element)));
+                    }
+                    UnknownFields::Ignore => continue,
+                }
+            };
+            if values.iter().any(|v| v.field() == name) {
+                return Err(error(format!("duplicate element {}", element)));
+            }
+            let text = if name == FieldName::Description {
+                text.as_str()
+            } else {
+                text.trim()
+            };
+            let value = Field::new(name, text)
+                .parse_with_units(self.amount_unit, self.timestamp_unit)
+                .map_err(|e| RecordProduceError {
+                    kind: RecordProduceErrorKind::ParseField {
+                        field_name: name.as_str().into(),
+                    },
+                    text: format!(
+                        "failed to parse element {}. near byte {}",
+                        element, position
+                    ),
+                    source: Some(Box::new(e)),
+                })?;
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    fn parse_record(&self, fields: Vec<XmlField>) -> RecordProduceResult<Record> {
+        let values = self.parse_fields(fields)?;
+        Record::try_from(with_defaults(&self.defaults, values)).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!(
+                "failed to parse element {}. near byte {}",
+                ELEMENT,
+                self.reader.buffer_position()
+            ),
+            source: Some(e.into()),
+        })
+    }
+}
+
+impl<T: Read> DataConsumer for XmlReader<T> {
+    type Item = Vec<XmlField>;
+    fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
+        if self.state == DocumentState::End {
+            return None;
+        }
+        match self.read_element() {
+            Ok(fields) => fields.map(Ok),
+            Err(e) => {
+                // the rest of the document can't be aligned to elements
+                self.state = DocumentState::End;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<T: Read> DataProducer for XmlReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let fields = match self.read()? {
+            Ok(fields) => fields,
+            Err(e) => {
+                return Some(Err(RecordProduceError {
+                    kind: e.kind(),
+                    text: format!("failed to read record {}", self.records + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        self.records += 1;
+        Some(self.parse_record(fields))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.reader.buffer_position(),
+            line: 0,
+            record: self.records,
+        })
+    }
+}
+
+/// Serializes a record as a `transaction` element with a child element per field
+pub(crate) struct XmlSerialize {
+    amount_unit: AmountUnit,
+    sanitize: SanitizePolicy,
+    timestamp_unit: TimestampUnit,
+}
+
+impl XmlSerialize {
+    pub(crate) fn with_options(options: &SerializeOptions) -> XmlSerialize {
+        XmlSerialize {
+            amount_unit: options.amount_unit,
+            sanitize: options.sanitize,
+            timestamp_unit: options.timestamp_unit,
+        }
+    }
+}
+
+impl RecordSerialize for XmlSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        let fields = [
+            (FieldName::TxId, record.tx_id.to_string()),
+            (FieldName::TxType, record.tx_type.to_string()),
+            (FieldName::FromUser, record.from_user.to_string()),
+            (FieldName::ToUser, record.to_user.to_string()),
+            (FieldName::Amount, self.amount_unit.format(record.amount)),
+            (
+                FieldName::Timestamp,
+                self.timestamp_unit.timestamp(record)?.to_string(),
+            ),
+            (FieldName::Status, record.status.to_string()),
+            (FieldName::Description, self.sanitize.description(record)?),
+        ];
+        let elements = fields
+            .iter()
+            .map(|(name, value)| format!("<{0}>{1}</{0}>", name.as_str(), escape(value.as_str())))
+            .collect::<String>();
+        Ok(format!("<{0}>{1}</{0}>", ELEMENT, elements).into_bytes())
+    }
+}
+
+/// Writes serialized records as children of the root element, one per line.
+/// The declaration and the root are opened by the header and closed on finish
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W) -> RecordWrite<W> {
+        RecordWrite {
+            writer,
+            header_written: false,
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(data)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write_header(&mut self) -> RecordWriteResult<()> {
+        if self.header_written {
+            return Ok(());
+        }
+        self.write_all(
+            format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<{}>\n", ROOT).as_bytes(),
+        )?;
+        self.header_written = true;
+        Ok(())
+    }
+
+    fn write_comment(&mut self, text: &str) -> RecordWriteResult<()> {
+        self.write_header()?;
+        // a comment can't contain two hyphens in a row
+        let text = text.replace("--", "- -");
+        self.write_all(format!("  <!-- {} -->\n", text).as_bytes())
+    }
+
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.write_header()?;
+        self.write_all(&[b"  ", data.as_slice(), b"\n"].concat())
+    }
+
+    fn finish(&mut self) -> RecordWriteResult<()> {
+        self.write_header()?;
+        self.write_all(format!("</{}>\n", ROOT).as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::record::{RecordIterator, Status, TxType};
+
+    fn reader(input: &str) -> XmlReader<Cursor<String>> {
+        XmlReader::with_options(Cursor::new(input.to_string()), &ParseOptions::default()).unwrap()
+    }
+
+    fn records() -> Vec<Record> {
+        (0..3)
+            .map(|i| {
+                Record::new(
+                    i,
+                    TxType::Transfer,
+                    i + 1,
+                    i + 2,
+                    100 * i,
+                    1633036860000 + i,
+                    Status::Failure,
+                    format!(" <Record> & \"number\" {} ", i),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let serializer = XmlSerialize::with_options(&SerializeOptions::default());
+        let mut output = Vec::new();
+        let mut writer = RecordWrite::new(&mut output);
+        writer.write_header().unwrap();
+        writer.write_comment("exported -- today").unwrap();
+        for record in records() {
+            writer
+                .write(serializer.serialize(&record).unwrap())
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<transactions>\n"),
+            "{}",
+            output
+        );
+        assert!(output.contains("<TX_ID>1</TX_ID>"), "{}", output);
+        assert!(
+            output.ends_with("</transaction>\n</transactions>\n"),
+            "{}",
+            output
+        );
+
+        let read = RecordIterator::new(reader(&output))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, records());
+
+        let mut empty = Vec::new();
+        RecordWrite::new(&mut empty).finish().unwrap();
+        let empty = String::from_utf8(empty).unwrap();
+        assert!(reader(&empty).produce_record().is_none());
+        assert!(reader("<transactions/>").produce_record().is_none());
+    }
+
+    #[test]
+    fn test_layout() {
+        let input = r#"<?xml version="1.0"?>
+            <!-- settlement export -->
+            <transactions>
+              <transaction TX_ID = " 7 " TX_TYPE="DEPOSIT">
+                <!-- sender -->
+                <FROM_USER_ID> 0 </FROM_USER_ID>
+                <TO_USER_ID>2</TO_USER_ID>
+                <AMOUNT>
+                  300
+                </AMOUNT>
+                <TIMESTAMP>1633036860000</TIMESTAMP>
+                <STATUS>SUCCESS</STATUS>
+                <DESCRIPTION><![CDATA[a <raw> text]]></DESCRIPTION>
+              </transaction>
+              <transaction TX_ID="8" TX_TYPE="DEPOSIT" FROM_USER_ID="0" TO_USER_ID="2"
+                AMOUNT="1" TIMESTAMP="1" STATUS="PENDING" DESCRIPTION=""/>
+            </transactions>
+            <!-- end -->"#;
+        let read = RecordIterator::new(reader(input))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            read,
+            [
+                Record::new(
+                    7,
+                    TxType::Deposit,
+                    0,
+                    2,
+                    300,
+                    1633036860000,
+                    Status::Success,
+                    "a <raw> text".into(),
+                ),
+                Record::new(
+                    8,
+                    TxType::Deposit,
+                    0,
+                    2,
+                    1,
+                    1,
+                    Status::Pending,
+                    String::new()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        for input in [
+            "",
+            "<records/>",
+            "<transactions><transaction><TX_ID>1</TX_ID>",
+            "<transactions><transaction><TX_ID><A/></TX_ID></transaction></transactions>",
+            "<transactions>text</transactions>",
+            "<transactions></transactions><transactions/>",
+            "<transactions><transaction></other></transactions>",
+        ] {
+            let mut reader = reader(input);
+            let err = reader.produce_record().unwrap().unwrap_err();
+            assert_eq!(
+                err.kind(),
+                RecordProduceErrorKind::MalformedRecord,
+                "{}",
+                input
+            );
+            assert!(reader.produce_record().is_none(), "{}", input);
+        }
+
+        let mut reader =
+            reader("<transactions><transaction><AMOUNT>ten</AMOUNT></transaction></transactions>");
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert!(err.to_string().contains("element AMOUNT"), "{}", err);
+        assert_eq!(
+            err.kind(),
+            RecordProduceErrorKind::ParseField {
+                field_name: "AMOUNT".into()
+            }
+        );
+        assert!(reader.produce_record().is_none());
+    }
+}
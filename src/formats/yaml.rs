@@ -0,0 +1,433 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use serde_yaml::{Mapping, Value};
+
+use crate::error::{
+    ReaderCreateError, RecordProduceError, RecordProduceErrorKind, RecordReadError,
+    RecordSerializeError, RecordWriteError,
+};
+use crate::options::{
+    AmountUnit, ParseOptions, SanitizePolicy, SerializeOptions, TimestampUnit, UnknownFields,
+};
+use crate::record::{
+    DataConsumer, DataProducer, Field, FieldName, FieldValue, Position, Record, RecordSerialize,
+    RecordWriter, resolve_field, with_defaults,
+};
+use crate::result::{
+    ReaderCreateResult, RecordProduceResult, RecordReadResult, RecordSerializeResult,
+    RecordWriteResult,
+};
+
+/// Whether the line starts a new document
+fn is_separator(line: &str) -> bool {
+    line.strip_prefix("---")
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t', '#']))
+}
+
+/// Reader of the yaml format, a mapping of fields per document.
+/// Documents are separated by `---` lines, empty documents are skipped
+pub(crate) struct YamlReader<T: Read> {
+    reader: BufReader<T>,
+    /// Separator line read at the end of the previous document
+    pending: Option<String>,
+    current_line: u64,
+    /// Line the last read document starts at
+    document_line: u64,
+    offset: u64,
+    records: u64,
+    is_exhausted: bool,
+    amount_unit: AmountUnit,
+    timestamp_unit: TimestampUnit,
+    unknown_fields: UnknownFields,
+    defaults: Vec<FieldValue>,
+    aliases: Vec<(String, FieldName)>,
+}
+
+impl<T: Read> YamlReader<T> {
+    pub(crate) fn with_options(
+        reader: T,
+        options: &ParseOptions,
+    ) -> ReaderCreateResult<YamlReader<T>> {
+        let defaults = options
+            .defaults
+            .parse(options.amount_unit, options.timestamp_unit)
+            .map_err(|e| ReaderCreateError {
+                text: "failed to create yaml reader".into(),
+                source: Some(Box::new(e)),
+            })?;
+        Ok(YamlReader {
+            reader: BufReader::new(reader),
+            pending: None,
+            current_line: 0,
+            document_line: 0,
+            offset: 0,
+            records: 0,
+            is_exhausted: false,
+            amount_unit: options.amount_unit,
+            timestamp_unit: options.timestamp_unit,
+            unknown_fields: options.unknown_fields,
+            defaults,
+            aliases: options.alias_table(),
+        })
+    }
+
+    /// Reads the lines of the next document without its separator
+    fn read_document(&mut self) -> RecordReadResult<Option<String>> {
+        let mut document = String::new();
+        let mut has_content = false;
+        loop {
+            let mut line = String::new();
+            let bytes_read = match self.pending.take() {
+                Some(pending) => {
+                    line = pending;
+                    0
+                }
+                None => self
+                    .reader
+                    .read_line(&mut line)
+                    .map_err(|e| RecordReadError {
+                        text: "couldn't read data".to_string(),
+                        source: Some(Box::new(e)),
+                    })?,
+            };
+            if bytes_read == 0 && line.is_empty() {
+                self.is_exhausted = true;
+                return Ok(has_content.then_some(document));
+            }
+            if bytes_read > 0 {
+                self.current_line += 1;
+                self.offset += bytes_read as u64;
+            }
+            if is_separator(line.trim_end()) {
+                if has_content {
+                    self.pending = Some(line);
+                    return Ok(Some(document));
+                }
+                // the separator may carry the start of the document
+                document.clear();
+                document.push_str(&line[3..]);
+                self.document_line = self.current_line;
+                has_content = !is_blank(&line[3..]);
+                continue;
+            }
+            if line.trim_end() == "..." {
+                if has_content {
+                    return Ok(Some(document));
+                }
+                continue;
+            }
+            if !has_content && document.is_empty() {
+                self.document_line = self.current_line;
+            }
+            has_content |= !is_blank(&line);
+            document.push_str(&line);
+        }
+    }
+
+    fn error(&self, text: String) -> RecordProduceError {
+        RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: format!("{}. near line {}", text, self.document_line),
+            source: None,
+        }
+    }
+
+    fn parse_document(&self, document: &str) -> RecordProduceResult<Vec<FieldValue>> {
+        let mapping = match serde_yaml::from_str::<Value>(document) {
+            Ok(Value::Mapping(mapping)) => mapping,
+            Ok(Value::Null) => Mapping::new(),
+            Ok(_) => return Err(self.error("document isn't a mapping".into())),
+            Err(e) => {
+                return Err(RecordProduceError {
+                    kind: RecordProduceErrorKind::MalformedRecord,
+                    text: format!("invalid yaml. near line {}", self.document_line),
+                    source: Some(Box::new(e)),
+                });
+            }
+        };
+        let mut values: Vec<FieldValue> = Vec::with_capacity(mapping.len());
+        for (key, value) in mapping {
+            let Value::String(key) = key else {
+                return Err(self.error("keys must be field names".into()));
+            };
+            let Some(name) = resolve_field(&key, &self.aliases) else {
+                match self.unknown_fields {
+                    UnknownFields::Error => {
+                        return Err(self.error(format!("unknown field {}", key)));
+                    }
+                    UnknownFields::Ignore => continue,
+                }
+            };
+            if values.iter().any(|v| v.field() == name) {
+                return Err(self.error(format!("duplicate field {}", name.as_str())));
+            }
+            let text = match value {
+                Value::String(text) => text,
+                Value::Number(number) => number.to_string(),
+                Value::Bool(value) => value.to_string(),
+                Value::Null => continue,
+                _ => return Err(self.error(format!("invalid value of {}", name.as_str()))),
+            };
+            let value = Field::new(name, text.as_str())
+                .parse_with_units(self.amount_unit, self.timestamp_unit)
+                .map_err(|e| RecordProduceError {
+                    kind: RecordProduceErrorKind::ParseField {
+                        field_name: name.as_str().into(),
+                    },
+                    text: format!("failed to parse field. near line {}", self.document_line),
+                    source: Some(Box::new(e)),
+                })?;
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    fn parse_record(&self, document: &str) -> RecordProduceResult<Record> {
+        let values = self.parse_document(document)?;
+        Record::try_from(with_defaults(&self.defaults, values)).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: format!("failed to parse record. near line {}", self.document_line),
+            source: Some(e.into()),
+        })
+    }
+}
+
+/// Whether the text has nothing but whitespace and comments
+fn is_blank(text: &str) -> bool {
+    text.lines().all(|line| {
+        let line = line.trim();
+        line.is_empty() || line.starts_with('#')
+    })
+}
+
+impl<T: Read> DataConsumer for YamlReader<T> {
+    type Item = String;
+    fn read(&mut self) -> Option<RecordReadResult<Self::Item>> {
+        if self.is_exhausted {
+            return None;
+        }
+        self.read_document().transpose()
+    }
+}
+
+impl<T: Read> DataProducer for YamlReader<T> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        let document = match self.read()? {
+            Ok(document) => document,
+            Err(e) => {
+                return Some(Err(RecordProduceError {
+                    kind: e.kind(),
+                    text: format!("failed to read line {}", self.current_line + 1),
+                    source: Some(Box::new(e)),
+                }));
+            }
+        };
+        self.records += 1;
+        Some(self.parse_record(&document))
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(Position {
+            byte: self.offset,
+            line: self.current_line,
+            record: self.records,
+        })
+    }
+}
+
+pub(crate) struct YamlSerialize {
+    amount_unit: AmountUnit,
+    sanitize: SanitizePolicy,
+    timestamp_unit: TimestampUnit,
+}
+
+impl YamlSerialize {
+    pub(crate) fn with_options(options: &SerializeOptions) -> YamlSerialize {
+        YamlSerialize {
+            amount_unit: options.amount_unit,
+            sanitize: options.sanitize,
+            timestamp_unit: options.timestamp_unit,
+        }
+    }
+}
+
+impl RecordSerialize for YamlSerialize {
+    fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>> {
+        // a scaled amount is quoted so its trailing zeros are kept
+        let amount = self.amount_unit.format(record.amount);
+        let amount = match amount.parse::<u64>() {
+            Ok(amount) => Value::from(amount),
+            Err(_) => Value::from(amount),
+        };
+        let fields = [
+            (FieldName::TxId, Value::from(record.tx_id)),
+            (FieldName::TxType, Value::from(record.tx_type.to_string())),
+            (FieldName::FromUser, Value::from(record.from_user)),
+            (FieldName::ToUser, Value::from(record.to_user)),
+            (FieldName::Amount, amount),
+            (
+                FieldName::Timestamp,
+                Value::from(self.timestamp_unit.timestamp(record)?),
+            ),
+            (FieldName::Status, Value::from(record.status.to_string())),
+            (
+                FieldName::Description,
+                Value::from(self.sanitize.description(record)?),
+            ),
+        ];
+        let mapping = fields
+            .into_iter()
+            .map(|(name, value)| (Value::from(name.as_str()), value))
+            .collect::<Mapping>();
+        serde_yaml::to_string(&mapping)
+            .map(String::into_bytes)
+            .map_err(|e| RecordSerializeError {
+                text: format!("failed to serialize record {}", record.tx_id),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+/// Writes serialized records as documents each starting with a separator
+pub(crate) struct RecordWrite<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWrite<W> {
+    pub(crate) fn new(writer: W) -> RecordWrite<W> {
+        RecordWrite { writer }
+    }
+}
+
+impl<W: Write> RecordWriter for RecordWrite<W> {
+    fn write_comment(&mut self, text: &str) -> RecordWriteResult<()> {
+        self.write(format!("# {}\n", text.replace('\n', " ")).into_bytes())
+    }
+
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.writer
+            .write_all(b"---\n")
+            .and_then(|_| self.writer.write_all(&data))
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| RecordWriteError {
+                text: "failed to write data".into(),
+                source: Some(Box::new(e)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::record::{RecordIterator, Status, TxType};
+
+    fn reader(input: &str) -> YamlReader<Cursor<String>> {
+        YamlReader::with_options(Cursor::new(input.to_string()), &ParseOptions::default()).unwrap()
+    }
+
+    fn record(description: &str) -> Record {
+        Record::new(
+            7,
+            TxType::Transfer,
+            1,
+            2,
+            300,
+            1633036860000,
+            Status::Pending,
+            description.into(),
+        )
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let records = [
+            "plain words",
+            "key: value # not a comment",
+            "#hashtag",
+            "'single' and \"double\" quotes",
+            "123",
+            "true",
+            "",
+            " padded ",
+            "two\nlines",
+        ]
+        .map(record);
+        let serializer = YamlSerialize::with_options(&SerializeOptions::default());
+        let mut output = Vec::new();
+        let mut writer = RecordWrite::new(&mut output);
+        writer.write_comment("fixtures").unwrap();
+        for record in &records {
+            writer.write(serializer.serialize(record).unwrap()).unwrap();
+        }
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("---\nTX_ID: 7\n"), "{}", output);
+
+        let read = RecordIterator::new(reader(&output))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, records);
+
+        let options = SerializeOptions {
+            amount_unit: AmountUnit::Major { scale: 2 },
+            ..Default::default()
+        };
+        let data = YamlSerialize::with_options(&options)
+            .serialize(&record("fee"))
+            .unwrap();
+        assert!(
+            String::from_utf8(data).unwrap().contains("AMOUNT: '3.00'"),
+            "amount"
+        );
+    }
+
+    #[test]
+    fn test_hand_written() {
+        let input = "# golden fixtures\n--- # first\nTX_ID: 7\nTX_TYPE: TRANSFER\nFROM_USER_ID: 1\nTO_USER_ID: 2\nAMOUNT: 300\nTIMESTAMP: 1633036860000\nSTATUS: PENDING\nDESCRIPTION: \"quoted: yes\"\n...\n---\n---\nTX_ID: 7\nTX_TYPE: TRANSFER\nFROM_USER_ID: 1\nTO_USER_ID: 2\nAMOUNT: 300\nTIMESTAMP: 1633036860000\nSTATUS: PENDING\nDESCRIPTION: unquoted # comment\n";
+        let mut reader = reader(input);
+        assert_eq!(
+            reader.produce_record().unwrap().unwrap(),
+            record("quoted: yes")
+        );
+        assert_eq!(
+            reader.produce_record().unwrap().unwrap(),
+            record("unquoted")
+        );
+        assert!(reader.produce_record().is_none());
+        assert_eq!(reader.position().unwrap().line, 21);
+    }
+
+    #[test]
+    fn test_missing_field() {
+        let mut reader = reader("---\nTX_ID: 1\n---\nTX_ID: 2\nTX_TYPE: DEPOSIT\n");
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert!(err.to_string().contains("line 1"), "{}", err);
+        assert!(
+            matches!(err.kind(), RecordProduceErrorKind::MissingField { .. }),
+            "{:?}",
+            err.kind()
+        );
+        let err = reader.produce_record().unwrap().unwrap_err();
+        assert!(err.to_string().contains("line 3"), "{}", err);
+        assert!(reader.produce_record().is_none());
+    }
+
+    #[test]
+    fn test_invalid_documents() {
+        for document in [
+            "TX_ID: [1]",
+            "- 1",
+            "TX_ID: 1\nTX_ID: 2",
+            "NAME: 1",
+            "TX_ID: x",
+            "TX_ID: {",
+        ] {
+            assert!(
+                reader(document).produce_record().unwrap().is_err(),
+                "{}",
+                document
+            );
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+use crate::bin::BinReader;
+use crate::error::{RecordProduceError, RecordProduceErrorKind};
+use crate::record::{DataConsumer, DataProducer, Record};
+use crate::result::RecordProduceResult;
+
+/// Index of the records of a seekable bin input by TX_ID.
+/// The input is scanned once when the index is built,
+/// then every lookup seeks to the record and parses only it
+///
+/// ```
+/// use parserde::{Record, RecordIndex, Status, TxType, build_serializer};
+/// let serializer = build_serializer("bin").unwrap();
+/// let mut data = Vec::new();
+/// for tx_id in [5, 3, 9] {
+///     let record = Record::new(tx_id, TxType::Deposit, 0, 1, 100, 0, Status::Success, "".into());
+///     data.extend(serializer.serialize(&record).unwrap());
+/// }
+/// let mut index = RecordIndex::build_from_bin(std::io::Cursor::new(data)).unwrap();
+/// assert_eq!(index.get(3).unwrap().unwrap().tx_id(), 3);
+/// assert!(index.get(4).is_none());
+/// ```
+pub struct RecordIndex<T: Read + Seek> {
+    reader: BinReader<T>,
+    offsets: HashMap<u64, u64>,
+}
+
+impl<T: Read + Seek> RecordIndex<T> {
+    /// Scans the bin input recording the offset of every record.
+    /// Fails on the first record that can't be read or parsed.
+    /// Of the records sharing a TX_ID the first one is indexed
+    pub fn build_from_bin(source: T) -> Result<RecordIndex<T>, RecordProduceError> {
+        let mut reader = BinReader::new(source).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::MalformedRecord,
+            text: "failed to create bin reader".into(),
+            source: Some(e.to_string().into()),
+        })?;
+        let mut offsets = HashMap::new();
+        loop {
+            let offset = reader.position().map_or(0, |p| p.byte);
+            let frame = match reader.read() {
+                Some(Ok(frame)) => frame,
+                Some(Err(e)) => {
+                    return Err(RecordProduceError {
+                        kind: e.kind(),
+                        text: format!("failed to index record at byte {}", offset),
+                        source: Some(Box::new(e)),
+                    });
+                }
+                None => break,
+            };
+            // string table entries are stored by the reader, so the offset
+            // of a record is the one of its own frame
+            let Some(frame) = reader.take_frame(frame) else {
+                continue;
+            };
+            match frame.and_then(|frame| reader.parse_frame(&frame)) {
+                Ok(record) => {
+                    offsets.entry(record.tx_id).or_insert(offset);
+                }
+                Err(e) => {
+                    return Err(RecordProduceError {
+                        kind: e.kind(),
+                        text: format!("failed to index record at byte {}", offset),
+                        source: Some(Box::new(e)),
+                    });
+                }
+            }
+        }
+        Ok(RecordIndex { reader, offsets })
+    }
+
+    /// Reads the record with the TX_ID, none if the input has no such record
+    pub fn get(&mut self, tx_id: u64) -> Option<RecordProduceResult<Record>> {
+        let offset = *self.offsets.get(&tx_id)?;
+        // the string table of a dictionary input is kept from the scan
+        if let Err(e) = self.reader.seek_frame(offset) {
+            return Some(Err(RecordProduceError {
+                kind: RecordProduceErrorKind::Io,
+                text: format!("failed to seek to record {}", tx_id),
+                source: Some(Box::new(e)),
+            }));
+        }
+        self.reader.produce_record()
+    }
+
+    /// Whether the input has a record with the TX_ID
+    pub fn contains(&self, tx_id: u64) -> bool {
+        self.offsets.contains_key(&tx_id)
+    }
+
+    /// Number of indexed records
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Whether the input has no records
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::bin::{DictionaryRecordBytes, RecordBytes};
+    use crate::record::{RecordSerialize, Status, TxType};
+
+    fn record(tx_id: u64) -> Record {
+        Record::new(
+            tx_id,
+            TxType::Transfer,
+            tx_id % 7,
+            tx_id % 11,
+            tx_id * 100,
+            1633036860000 + tx_id,
+            Status::Success,
+            format!("Record number {}", tx_id % 3),
+        )
+    }
+
+    #[test]
+    fn test_random_lookups() {
+        let serializer = RecordBytes::default();
+        let mut data = Vec::new();
+        for tx_id in 0..100 {
+            data.extend(serializer.serialize(&record(tx_id)).unwrap());
+        }
+        let mut index = RecordIndex::build_from_bin(Cursor::new(data)).unwrap();
+        assert_eq!(index.len(), 100);
+        for i in 0..100 {
+            let tx_id = i * 37 % 100;
+            assert_eq!(index.get(tx_id).unwrap().unwrap(), record(tx_id));
+        }
+        assert!(index.get(100).is_none());
+        assert!(!index.contains(100));
+    }
+
+    #[test]
+    fn test_dictionary_input() {
+        let serializer = DictionaryRecordBytes::default();
+        let mut data = Vec::new();
+        for tx_id in 0..10 {
+            data.extend(serializer.serialize(&record(tx_id)).unwrap());
+        }
+        let mut index = RecordIndex::build_from_bin(Cursor::new(data)).unwrap();
+        assert_eq!(index.get(9).unwrap().unwrap(), record(9));
+        assert_eq!(index.get(1).unwrap().unwrap(), record(1));
+        // the first records using each table entry follow its definition
+        for tx_id in [3, 4, 5] {
+            assert_eq!(index.get(tx_id).unwrap().unwrap(), record(tx_id));
+        }
+        for tx_id in 0..10 {
+            assert_eq!(index.get(tx_id).unwrap().unwrap(), record(tx_id));
+        }
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let mut data = RecordBytes::default().serialize(&record(1)).unwrap();
+        data.truncate(data.len() - 1);
+        assert!(RecordIndex::build_from_bin(Cursor::new(data)).is_err());
+        assert!(RecordIndex::build_from_bin(Cursor::new(b"hello".to_vec())).is_err());
+        let index = RecordIndex::build_from_bin(Cursor::new(Vec::new())).unwrap();
+        assert!(index.is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    error::Error,
+    fmt::Display,
+    io::{self, Read},
+    rc::Rc,
+};
+
+use crate::bin::BinReader;
+use crate::builder::build_reader;
+use crate::json::json_string;
+use crate::record::{DataProducer, Record};
+
+/// Max count of collected structural errors. The scan stops when it is reached
+const MAX_ERRORS: usize = 100;
+
+/// How deep the file is inspected
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum InspectDepth {
+    /// Counts records parsing as little as the format allows.
+    /// For bin only the first and the last records are parsed
+    #[default]
+    Quick,
+    /// Parses every record and collects per TxType and Status counts
+    Full,
+}
+
+/// Metadata of a file gathered in a single streaming pass
+#[derive(Debug, Default, PartialEq)]
+pub struct FileInfo {
+    /// Format the file was read as
+    pub format: String,
+    /// Count of records including the ones that failed to parse
+    pub record_count: u64,
+    /// Count of bytes read from the input
+    pub total_bytes: u64,
+    /// tx_id and timestamp of the first parsed record
+    pub first: Option<(u64, u64)>,
+    /// tx_id and timestamp of the last parsed record
+    pub last: Option<(u64, u64)>,
+    /// Count of records per TxType. Empty unless the depth is full
+    pub tx_type_counts: BTreeMap<String, u64>,
+    /// Count of records per Status. Empty unless the depth is full
+    pub status_counts: BTreeMap<String, u64>,
+    /// Structural errors encountered while scanning
+    pub errors: Vec<String>,
+}
+
+impl FileInfo {
+    fn add_record(&mut self, record: &Record, depth: InspectDepth) {
+        let key = (record.tx_id, record.timestamp);
+        if self.first.is_none() {
+            self.first = Some(key);
+        }
+        self.last = Some(key);
+        if depth == InspectDepth::Full {
+            *self
+                .tx_type_counts
+                .entry(record.tx_type.to_string())
+                .or_default() += 1;
+            *self
+                .status_counts
+                .entry(record.status.to_string())
+                .or_default() += 1;
+        }
+    }
+
+    /// Renders the info as a json object
+    pub fn to_json(&self) -> String {
+        let key = |k: &Option<(u64, u64)>| match k {
+            Some((tx_id, timestamp)) => {
+                format!("{{\"tx_id\":{},\"timestamp\":{}}}", tx_id, timestamp)
+            }
+            None => "null".to_string(),
+        };
+        let counts = |c: &BTreeMap<String, u64>| {
+            let entries = c
+                .iter()
+                .map(|(k, v)| format!("{}:{}", json_string(k), v))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", entries.join(","))
+        };
+        let errors = self
+            .errors
+            .iter()
+            .map(|e| json_string(e))
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"format\":{},\"record_count\":{},\"total_bytes\":{},\"first\":{},\"last\":{},\"tx_type_counts\":{},\"status_counts\":{},\"errors\":[{}]}}",
+            json_string(&self.format),
+            self.record_count,
+            self.total_bytes,
+            key(&self.first),
+            key(&self.last),
+            counts(&self.tx_type_counts),
+            counts(&self.status_counts),
+            errors.join(",")
+        )
+    }
+}
+
+impl Display for FileInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let key = |k: &Option<(u64, u64)>| match k {
+            Some((tx_id, timestamp)) => format!("tx_id {} timestamp {}", tx_id, timestamp),
+            None => "-".to_string(),
+        };
+        writeln!(f, "{:<14}{}", "format", self.format)?;
+        writeln!(f, "{:<14}{}", "records", self.record_count)?;
+        writeln!(f, "{:<14}{}", "bytes", self.total_bytes)?;
+        writeln!(f, "{:<14}{}", "first", key(&self.first))?;
+        writeln!(f, "{:<14}{}", "last", key(&self.last))?;
+        for (name, count) in self.tx_type_counts.iter().chain(&self.status_counts) {
+            writeln!(f, "{:<14}{}", name, count)?;
+        }
+        writeln!(f, "{:<14}{}", "errors", self.errors.len())?;
+        for e in &self.errors {
+            writeln!(f, "  {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+struct CountingReader<R: Read> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.count.set(self.count.get() + count as u64);
+        Ok(count)
+    }
+}
+
+/// Scans the input in a single pass and reports its metadata.
+/// Errors in the records are collected into the info rather than returned
+///
+/// ```
+/// use parserde::{inspect, InspectDepth};
+/// let cursor = std::io::Cursor::new("TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,2,100,1633036860000,SUCCESS,fee\n");
+/// let info = inspect(cursor, "csv", InspectDepth::Full).unwrap();
+/// assert_eq!(info.record_count, 1);
+/// ```
+///
+pub fn inspect<T: Read + 'static>(
+    reader: T,
+    format: &str,
+    depth: InspectDepth,
+) -> Result<FileInfo, Box<dyn Error>> {
+    let count = Rc::new(Cell::new(0));
+    let reader = CountingReader {
+        inner: reader,
+        count: count.clone(),
+    };
+    let mut info = FileInfo {
+        format: format.to_string(),
+        ..Default::default()
+    };
+    if format == "bin" && depth == InspectDepth::Quick {
+        inspect_bin_frames(BinReader::new(reader)?, &mut info);
+    } else {
+        inspect_records(build_reader(reader, format)?, &mut info, depth);
+    }
+    info.total_bytes = count.get();
+    Ok(info)
+}
+
+fn inspect_bin_frames<T: Read>(mut reader: BinReader<T>, info: &mut FileInfo) {
+    let mut last_frame = None;
+    while let Some(result) = reader.next_record_frame() {
+        info.record_count += 1;
+        let frame = match result {
+            Ok(frame) => frame,
+            Err(e) => {
+                info.errors
+                    .push(format!("record {}. {}", info.record_count, e));
+                break;
+            }
+        };
+        if info.record_count == 1 {
+            match reader.parse_frame(&frame) {
+                Ok(record) => info.add_record(&record, InspectDepth::Quick),
+                Err(e) => info.errors.push(format!("record 1. {}", e)),
+            }
+        } else {
+            last_frame = Some(frame);
+        }
+    }
+    if let Some(frame) = last_frame {
+        match reader.parse_frame(&frame) {
+            Ok(record) => info.last = Some((record.tx_id, record.timestamp)),
+            Err(e) => info.errors.push(format!("last record. {}", e)),
+        }
+    }
+}
+
+fn inspect_records(mut reader: Box<dyn DataProducer>, info: &mut FileInfo, depth: InspectDepth) {
+    while let Some(result) = reader.produce_record() {
+        info.record_count += 1;
+        match result {
+            Ok(record) => info.add_record(&record, depth),
+            Err(e) => {
+                info.errors
+                    .push(format!("record {}. {}", info.record_count, e));
+                if info.errors.len() >= MAX_ERRORS {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::bin::RecordBytes;
+    use crate::record::{RecordSerialize, Status, TxType};
+
+    fn get_bin_input() -> Vec<u8> {
+        let ser = RecordBytes::default();
+        (0..10u64)
+            .map(|i| {
+                let record = Record::new(
+                    100 + i,
+                    if i % 3 == 0 {
+                        TxType::Deposit
+                    } else {
+                        TxType::Transfer
+                    },
+                    1,
+                    2,
+                    10 * i,
+                    1633036860000 + i,
+                    if i % 2 == 0 {
+                        Status::Success
+                    } else {
+                        Status::Pending
+                    },
+                    format!("Record number {}", i),
+                );
+                ser.serialize(&record).unwrap()
+            })
+            .collect::<Vec<_>>()
+            .concat()
+    }
+
+    #[test]
+    fn test_inspect_bin() {
+        let input = get_bin_input();
+        let total_bytes = input.len() as u64;
+        let quick = inspect(Cursor::new(input.clone()), "bin", InspectDepth::Quick).unwrap();
+        assert_eq!(quick.record_count, 10);
+        assert_eq!(quick.total_bytes, total_bytes);
+        assert_eq!(quick.first, Some((100, 1633036860000)));
+        assert_eq!(quick.last, Some((109, 1633036860009)));
+        assert!(quick.tx_type_counts.is_empty());
+        assert!(quick.errors.is_empty());
+
+        let full = inspect(Cursor::new(input), "bin", InspectDepth::Full).unwrap();
+        assert_eq!(full.first, quick.first);
+        assert_eq!(full.last, quick.last);
+        assert_eq!(full.tx_type_counts["DEPOSIT"], 4);
+        assert_eq!(full.tx_type_counts["TRANSFER"], 6);
+        assert_eq!(full.status_counts["SUCCESS"], 5);
+        assert_eq!(full.status_counts["PENDING"], 5);
+    }
+
+    #[test]
+    fn test_inspect_truncated_bin() {
+        let mut input = get_bin_input();
+        input.truncate(input.len() - 5);
+        let info = inspect(Cursor::new(input), "bin", InspectDepth::Quick).unwrap();
+        assert_eq!(info.record_count, 10);
+        assert_eq!(info.last, Some((108, 1633036860008)));
+        assert_eq!(info.errors.len(), 1);
+        assert!(info.errors[0].contains("record 10"));
+    }
+
+    #[test]
+    fn test_inspect_json() {
+        let input = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,2,100,5,SUCCESS,fee\n";
+        let info = inspect(Cursor::new(input), "csv", InspectDepth::Full).unwrap();
+        assert_eq!(
+            info.to_json(),
+            format!(
+                "{{\"format\":\"csv\",\"record_count\":1,\"total_bytes\":{},\"first\":{{\"tx_id\":1,\"timestamp\":5}},\"last\":{{\"tx_id\":1,\"timestamp\":5}},\"tx_type_counts\":{{\"DEPOSIT\":1}},\"status_counts\":{{\"SUCCESS\":1}},\"errors\":[]}}",
+                input.len()
+            )
+        );
+    }
+}
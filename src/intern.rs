@@ -0,0 +1,376 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use crate::record::{DataProducer, Record, Status, TxType};
+use crate::result::RecordProduceResult;
+
+/// Shared pool of descriptions. Clones are handles to the same pool,
+/// so several readers can intern into one
+///
+/// ```
+/// use parserde::StringInterner;
+/// let interner = StringInterner::new();
+/// let fee = interner.intern("Monthly fee");
+/// assert!(std::sync::Arc::ptr_eq(&fee, &interner.clone().intern("Monthly fee")));
+/// assert_eq!(interner.len(), 1);
+/// ```
+///
+#[derive(Debug, Default, Clone)]
+pub struct StringInterner {
+    strings: Arc<Mutex<HashSet<Arc<str>>>>,
+}
+
+impl StringInterner {
+    /// Creates an empty pool
+    pub fn new() -> StringInterner {
+        StringInterner::default()
+    }
+
+    /// Returns the pooled copy of the string, adding it on the first call
+    pub fn intern(&self, value: &str) -> Arc<str> {
+        let mut strings = self.strings.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(interned) = strings.get(value) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = Arc::from(value);
+        strings.insert(interned.clone());
+        interned
+    }
+
+    /// Count of distinct strings in the pool
+    pub fn len(&self) -> usize {
+        self.strings.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Whether the pool has no strings
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads every record of the producer interning their descriptions.
+    /// The owned description of a record is dropped as soon as it's interned
+    pub fn read_all(
+        &self,
+        producer: &mut dyn DataProducer,
+    ) -> RecordProduceResult<Vec<CompactRecord>> {
+        let mut records = Vec::new();
+        while let Some(record) = producer.produce_record() {
+            records.push(CompactRecord::from_record(record?, self));
+        }
+        Ok(records)
+    }
+}
+
+/// Reader of records holding their descriptions in a [`StringInterner`].
+/// Readers are usually created by
+/// [`build_compact_reader_with_options`](crate::build_compact_reader_with_options),
+/// sharing the pool of the parse options
+///
+/// ```
+/// use parserde::{ParseOptions, StringInterner, build_compact_reader_with_options};
+/// let input = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+///     1,DEPOSIT,0,2,5,9,SUCCESS,fee\n2,DEPOSIT,0,2,5,9,SUCCESS,fee\n";
+/// let options = ParseOptions {
+///     interner: Some(StringInterner::new()),
+///     ..Default::default()
+/// };
+/// let mut reader = build_compact_reader_with_options(std::io::Cursor::new(input), "csv", &options).unwrap();
+/// let records = reader.read_all().unwrap();
+/// assert!(std::sync::Arc::ptr_eq(records[0].description(), records[1].description()));
+/// assert_eq!(options.interner.unwrap().len(), 1);
+/// ```
+///
+pub struct CompactReader {
+    producer: Box<dyn DataProducer>,
+    interner: StringInterner,
+}
+
+impl CompactReader {
+    /// Wraps the producer interning descriptions into the pool
+    pub fn new(producer: Box<dyn DataProducer>, interner: StringInterner) -> CompactReader {
+        CompactReader { producer, interner }
+    }
+
+    /// The pool descriptions are interned into
+    pub fn interner(&self) -> &StringInterner {
+        &self.interner
+    }
+
+    /// Reads the next record. None once the input is exhausted
+    pub fn produce_record(&mut self) -> Option<RecordProduceResult<CompactRecord>> {
+        let record = self.producer.produce_record()?;
+        Some(record.map(|record| CompactRecord::from_record(record, &self.interner)))
+    }
+
+    /// Reads every remaining record
+    pub fn read_all(&mut self) -> RecordProduceResult<Vec<CompactRecord>> {
+        self.interner.read_all(self.producer.as_mut())
+    }
+}
+
+impl Iterator for CompactReader {
+    type Item = RecordProduceResult<CompactRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.produce_record()
+    }
+}
+
+/// Record holding its description in a [`StringInterner`].
+/// Identical descriptions share one allocation, which matters for bulk loads
+/// dominated by a few repeated descriptions. The savings are measured
+/// by the `intern_memory` test
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct CompactRecord {
+    tx_id: u64,
+    tx_type: TxType,
+    from_user: u64,
+    to_user: u64,
+    amount: u64,
+    timestamp: u64,
+    status: Status,
+    description: Arc<str>,
+}
+
+impl CompactRecord {
+    /// Converts the record interning its description
+    pub fn from_record(record: Record, interner: &StringInterner) -> CompactRecord {
+        CompactRecord {
+            tx_id: record.tx_id,
+            tx_type: record.tx_type,
+            from_user: record.from_user,
+            to_user: record.to_user,
+            amount: record.amount,
+            timestamp: record.timestamp,
+            status: record.status,
+            description: interner.intern(&record.description),
+        }
+    }
+
+    /// ID of the transaction
+    pub fn tx_id(&self) -> u64 {
+        self.tx_id
+    }
+
+    /// Type of the transaction
+    pub fn tx_type(&self) -> TxType {
+        self.tx_type
+    }
+
+    /// User sending the amount, 0 for deposits
+    pub fn from_user(&self) -> u64 {
+        self.from_user
+    }
+
+    /// User receiving the amount, 0 for withdrawals
+    pub fn to_user(&self) -> u64 {
+        self.to_user
+    }
+
+    /// Amount in minor units
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    /// Time of the transaction in milliseconds since the Unix epoch
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Status of the transaction
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// The interned description
+    pub fn description(&self) -> &Arc<str> {
+        &self.description
+    }
+
+    /// Copies the record into an owned [`Record`], e.g. to serialize it
+    pub fn to_record(&self) -> Record {
+        Record {
+            tx_id: self.tx_id,
+            tx_type: self.tx_type,
+            from_user: self.from_user,
+            to_user: self.to_user,
+            amount: self.amount,
+            timestamp: self.timestamp,
+            status: self.status,
+            description: self.description.to_string(),
+        }
+    }
+}
+
+impl From<CompactRecord> for Record {
+    fn from(value: CompactRecord) -> Self {
+        value.to_record()
+    }
+}
+
+impl PartialEq<Record> for CompactRecord {
+    fn eq(&self, other: &Record) -> bool {
+        self.tx_id == other.tx_id
+            && self.tx_type == other.tx_type
+            && self.from_user == other.from_user
+            && self.to_user == other.to_user
+            && self.amount == other.amount
+            && self.timestamp == other.timestamp
+            && self.status == other.status
+            && *self.description == *other.description
+    }
+}
+
+impl PartialEq<CompactRecord> for Record {
+    fn eq(&self, other: &CompactRecord) -> bool {
+        other == self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::builder::{build_compact_reader_with_options, build_reader, build_serializer};
+    use crate::options::ParseOptions;
+
+    const INPUT: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/examples/source/records_example.csv"
+    );
+
+    fn records() -> Vec<Record> {
+        let input = std::fs::read(INPUT).unwrap();
+        let mut reader = build_reader(Cursor::new(input), "csv").unwrap();
+        (0..20)
+            .map(|i| {
+                let mut record = reader.produce_record().unwrap().unwrap();
+                record.description = ["Monthly fee", "Refund"][i % 2].into();
+                record
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_shared_descriptions() {
+        let interner = StringInterner::new();
+        let compact = records()
+            .into_iter()
+            .map(|r| CompactRecord::from_record(r, &interner))
+            .collect::<Vec<_>>();
+        assert_eq!(interner.len(), 2);
+        assert!(Arc::ptr_eq(
+            compact[0].description(),
+            compact[2].description()
+        ));
+        assert!(!Arc::ptr_eq(
+            compact[0].description(),
+            compact[1].description()
+        ));
+        for (compact, record) in compact.iter().zip(records()) {
+            assert_eq!(*compact, record);
+            assert_eq!(record, *compact);
+        }
+    }
+
+    #[test]
+    fn test_equality_and_hashing() {
+        let interner = StringInterner::new();
+        let other = StringInterner::new();
+        let record = records().remove(0);
+        let compact = CompactRecord::from_record(record.clone(), &interner);
+        // equal descriptions from different pools compare and hash equal
+        let copy = CompactRecord::from_record(record.clone(), &other);
+        assert!(!Arc::ptr_eq(compact.description(), copy.description()));
+        assert_eq!(compact, copy);
+        let set = [compact.clone(), copy].into_iter().collect::<HashSet<_>>();
+        assert_eq!(set.len(), 1);
+
+        for changed in [
+            Record {
+                amount: record.amount + 1,
+                ..record.clone()
+            },
+            Record {
+                description: "Other".into(),
+                ..record.clone()
+            },
+        ] {
+            assert_ne!(compact, changed);
+            let changed = CompactRecord::from_record(changed, &interner);
+            assert_ne!(compact, changed);
+            assert!(!set.contains(&changed));
+        }
+    }
+
+    #[test]
+    fn test_getters() {
+        let record = records().remove(0);
+        let compact = CompactRecord::from_record(record.clone(), &StringInterner::new());
+        assert_eq!(compact.tx_id(), record.tx_id());
+        assert_eq!(compact.tx_type(), record.tx_type());
+        assert_eq!(compact.from_user(), record.from_user());
+        assert_eq!(compact.to_user(), record.to_user());
+        assert_eq!(compact.amount(), record.amount());
+        assert_eq!(compact.timestamp(), record.timestamp());
+        assert_eq!(compact.status(), record.status());
+        assert_eq!(&**compact.description(), record.description());
+    }
+
+    #[test]
+    fn test_readers_share_the_pool_of_options() {
+        let options = ParseOptions {
+            interner: Some(StringInterner::new()),
+            ..Default::default()
+        };
+        let input = std::fs::read(INPUT).unwrap();
+        let mut first =
+            build_compact_reader_with_options(Cursor::new(input.clone()), "csv", &options).unwrap();
+        let mut second =
+            build_compact_reader_with_options(Cursor::new(input), "csv", &options).unwrap();
+        let first = first.next().unwrap().unwrap();
+        let second = second.next().unwrap().unwrap();
+        assert!(Arc::ptr_eq(first.description(), second.description()));
+        assert_eq!(options.interner.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_serialized_bytes_unchanged() {
+        let options = ParseOptions {
+            interner: Some(StringInterner::new()),
+            ..Default::default()
+        };
+        let header = b"TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION";
+        for format in ["csv", "txt", "bin", "bin-compact", "bin-dict"] {
+            let serializer = build_serializer(format).unwrap();
+            let expected = records()
+                .iter()
+                .map(|r| serializer.serialize(r).unwrap())
+                .collect::<Vec<_>>();
+            let (input_format, input) = match format {
+                "csv" => (
+                    "csv",
+                    [&header[..], b"\n", &expected.join(&b'\n'), b"\n"].concat(),
+                ),
+                "txt" => ("txt", expected.join(&b'\n')),
+                _ => ("bin", expected.concat()),
+            };
+            let compact =
+                build_compact_reader_with_options(Cursor::new(input), input_format, &options)
+                    .unwrap()
+                    .read_all()
+                    .unwrap();
+            // serialized again from the interned descriptions
+            let serializer = build_serializer(format).unwrap();
+            let written = compact
+                .iter()
+                .map(|r| serializer.serialize(&r.to_record()).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(written, expected, "{}", format);
+        }
+        assert_eq!(options.interner.unwrap().len(), 2);
+    }
+}
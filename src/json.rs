@@ -0,0 +1,191 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Scalar value of a flat JSON object
+#[derive(Debug, PartialEq)]
+pub(crate) enum JsonValue {
+    String(String),
+    /// Number as written in the input
+    Number(String),
+    Bool(bool),
+    Null,
+}
+
+/// Quotes and escapes the value as a JSON string
+pub(crate) fn json_string(value: &str) -> String {
+    let mut r = String::with_capacity(value.len() + 2);
+    r.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => r.push_str("\\\""),
+            '\\' => r.push_str("\\\\"),
+            '\n' => r.push_str("\\n"),
+            '\r' => r.push_str("\\r"),
+            '\t' => r.push_str("\\t"),
+            c if c.is_control() => r.push_str(&format!("\\u{:04x}", c as u32)),
+            c => r.push(c),
+        }
+    }
+    r.push('"');
+    r
+}
+
+/// Parses a JSON object whose values are scalars.
+/// Keys are returned in input order, nested objects and arrays are an error
+pub(crate) fn parse_object(input: &str) -> Result<Vec<(String, JsonValue)>, String> {
+    let mut chars = input.chars().peekable();
+    let mut entries = Vec::new();
+    expect(&mut chars, '{')?;
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_whitespace(&mut chars);
+            expect(&mut chars, '"')?;
+            let key = parse_string(&mut chars)?;
+            expect(&mut chars, ':')?;
+            skip_whitespace(&mut chars);
+            let value = parse_value(&mut chars)?;
+            entries.push((key, value));
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                Some(c) => return Err(format!("expected , or }} but found {}", c)),
+                None => return Err("unterminated object".into()),
+            }
+        }
+    }
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(entries),
+        Some(c) => Err(format!("unexpected {} after the object", c)),
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars<'_>>) {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+}
+
+fn expect(chars: &mut Peekable<Chars<'_>>, expected: char) -> Result<(), String> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        Some(c) => Err(format!("expected {} but found {}", expected, c)),
+        None => Err(format!("expected {} but found the end of line", expected)),
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars<'_>>) -> Result<JsonValue, String> {
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            Ok(JsonValue::String(parse_string(chars)?))
+        }
+        Some('{' | '[') => Err("nested values are not supported".into()),
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) =
+                chars.next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+            {
+                number.push(c);
+            }
+            Ok(JsonValue::Number(number))
+        }
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "true" => Ok(JsonValue::Bool(true)),
+                "false" => Ok(JsonValue::Bool(false)),
+                "null" => Ok(JsonValue::Null),
+                _ => Err(format!("invalid value {}", word)),
+            }
+        }
+        None => Err("expected a value but found the end of line".into()),
+    }
+}
+
+/// Parses the rest of a string after the opening quote
+fn parse_string(chars: &mut Peekable<Chars<'_>>) -> Result<String, String> {
+    let mut r = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(r),
+            Some('\\') => match chars.next() {
+                Some('"') => r.push('"'),
+                Some('\\') => r.push('\\'),
+                Some('/') => r.push('/'),
+                Some('b') => r.push('\u{8}'),
+                Some('f') => r.push('\u{c}'),
+                Some('n') => r.push('\n'),
+                Some('r') => r.push('\r'),
+                Some('t') => r.push('\t'),
+                Some('u') => {
+                    let high = parse_hex(chars)?;
+                    let code = if (0xD800..0xDC00).contains(&high) {
+                        if chars.next() != Some('\\') || chars.next() != Some('u') {
+                            return Err("unpaired surrogate".into());
+                        }
+                        let low = parse_hex(chars)?;
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return Err("unpaired surrogate".into());
+                        }
+                        0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                    } else {
+                        high
+                    };
+                    r.push(char::from_u32(code).ok_or("invalid unicode escape")?);
+                }
+                Some(c) => return Err(format!("invalid escape \\{}", c)),
+                None => return Err("unterminated string".into()),
+            },
+            Some(c) => r.push(c),
+            None => return Err("unterminated string".into()),
+        }
+    }
+}
+
+fn parse_hex(chars: &mut Peekable<Chars<'_>>) -> Result<u32, String> {
+    let digits = chars.take(4).collect::<String>();
+    if digits.len() != 4 {
+        return Err("invalid unicode escape".into());
+    }
+    u32::from_str_radix(&digits, 16).map_err(|_| "invalid unicode escape".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_object() {
+        let entries =
+            parse_object(r#" {"A": 1, "B":"x\"é😀", "C": -2.5e3, "D": true, "E": null} "#).unwrap();
+        assert_eq!(
+            entries,
+            [
+                ("A".to_string(), JsonValue::Number("1".into())),
+                ("B".to_string(), JsonValue::String("x\"é😀".into())),
+                ("C".to_string(), JsonValue::Number("-2.5e3".into())),
+                ("D".to_string(), JsonValue::Bool(true)),
+                ("E".to_string(), JsonValue::Null),
+            ]
+        );
+        assert!(parse_object("{}").unwrap().is_empty());
+        assert!(parse_object(r#"{"A": [1]}"#).is_err());
+        assert!(parse_object(r#"{"A": 1"#).is_err());
+        assert!(parse_object(r#"{"A": 1} x"#).is_err());
+        assert!(parse_object(r#"{"A": nope}"#).is_err());
+    }
+
+    #[test]
+    fn test_json_string_round_trip() {
+        let value = "a \"quoted\"\tline\nwith \\ and \u{1}";
+        let entries = parse_object(&format!("{{\"K\":{}}}", json_string(value))).unwrap();
+        assert_eq!(entries[0].1, JsonValue::String(value.into()));
+    }
+}
@@ -3,19 +3,123 @@
 
 //! A simple library with utilities for
 //! parsing, serializing, converting and comparing data
-//! in csv, bin and txt formats.
+//! in csv, tsv, bin, txt, ndjson, logfmt, json, toml and xml formats,
+//! cbor, msgpack, proto and ron with the features of the same names
+//! and yaml with the `yaml` feature.
+//! Records are read from and written to sqlite tables with the `sqlite` feature
+//! and written to parquet files with the `parquet` feature.
+//! Records of xlsx worksheets are read with the `xlsx` feature.
 
+mod batch;
 mod builder;
+mod checkpoint;
+mod compare;
+mod derived;
+mod encoding;
 mod error;
+#[cfg(feature = "fallible-iterator")]
+mod fallible;
+mod files;
 mod formats;
+mod index;
+mod inspect;
+mod intern;
+mod json;
+mod options;
 mod record;
 mod result;
+mod sink;
+mod sort;
+mod splitter;
+mod validate;
 
-pub use record::{Record, fields};
+pub use record::{
+    AndFilter, ByAmountRange, ByStatus, ByTimestampRange, ByTxType, ChainedProducer, DataProducer,
+    FieldDiff, FieldName, FieldValue, Filtered, PartialDataProducer, PartialRecord, Position,
+    RawDataProducer, RawRecord, Record, RecordBuilder, RecordDiff, RecordFilter, RecordIterator,
+    RecordSampler, RecordSerialize, RecordWriter, RejectedRecord, SkipProducer, Status,
+    TakeProducer, TeeWriter, TxType, VecProducer, fields,
+};
 
-use formats::{bin, csv, txt};
+#[cfg(feature = "tokio")]
+pub use record::AsyncDataProducer;
 
-pub use builder::{build_reader, build_serializer, build_writer};
+pub use formats::bin::BinReader;
+pub use formats::csv::{CsvAppendWriter, detect_separator};
+pub use formats::txt::{CommentCapture, TxtReader};
 
-pub use error::{RecordProduceError, RecordSerializeError};
-pub use result::{RecordProduceResult, RecordSerializeResult};
+#[cfg(feature = "parquet")]
+pub use formats::parquet::ParquetWriter;
+#[cfg(feature = "sqlite")]
+pub use formats::sqlite::{SqliteReader, SqliteWriter};
+#[cfg(feature = "xlsx")]
+pub use formats::xlsx::XlsxReader;
+
+#[cfg(feature = "cbor")]
+use formats::cbor;
+#[cfg(feature = "msgpack")]
+use formats::msgpack;
+#[cfg(feature = "proto")]
+use formats::proto;
+#[cfg(feature = "ron")]
+use formats::ron;
+#[cfg(feature = "yaml")]
+use formats::yaml;
+#[cfg(feature = "tokio")]
+use formats::{async_bin, async_csv, async_txt};
+use formats::{bin, csv, gz, ndjson, txt, xml};
+
+pub use builder::{
+    build_append_writer, build_append_writer_with_options, build_compact_reader,
+    build_compact_reader_with_options, build_partial_reader, build_raw_reader,
+    build_raw_reader_with_options, build_reader, build_reader_at, build_reader_from_path,
+    build_reader_from_path_with_options, build_reader_with_options, build_serializer,
+    build_serializer_with_fields, build_serializer_with_options, build_tee_writer, build_writer,
+    build_writer_with_options, detect_format,
+};
+
+#[cfg(feature = "tokio")]
+pub use builder::{build_async_reader, build_async_reader_with_options};
+
+#[cfg(feature = "rayon")]
+pub use builder::{
+    build_parallel_reader, build_sync_serializer, build_sync_serializer_with_options,
+};
+
+pub use batch::RecordBatch;
+
+pub use checkpoint::Checkpoint;
+
+pub use compare::{CompareSummary, RecordDifference};
+
+pub use derived::DerivedColumn;
+
+pub use files::{FileSequence, expand_input};
+
+#[cfg(feature = "fallible-iterator")]
+pub use fallible::FallibleRecords;
+
+pub use index::RecordIndex;
+
+pub use inspect::{FileInfo, InspectDepth, inspect};
+
+pub use intern::{CompactReader, CompactRecord, StringInterner};
+
+pub use sort::{KeyPart, KeyValue, SortDirection, SortKey, SortKeyValue};
+
+pub use sink::{SinkFailure, TeeSink};
+
+pub use splitter::{RecordSplitter, SplitRoute};
+
+pub use validate::{ValidationError, ValidationRules};
+
+pub use options::{
+    AmountUnit, FieldDefaults, InputEncoding, ParseOptions, SanitizePolicy, SerializeOptions,
+    TimestampUnit, TxtDelimiter, TxtStyle, UnknownFields,
+};
+
+pub use error::{
+    EnumParseError, RecordProduceError, RecordProduceErrorKind, RecordSerializeError,
+    RecordWriteError,
+};
+pub use result::{RecordProduceResult, RecordSerializeResult, RecordWriteResult};
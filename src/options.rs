@@ -0,0 +1,689 @@
+use std::str::FromStr;
+
+use crate::derived::DerivedColumn;
+use crate::error::{
+    FieldParseError, RecordProduceError, RecordProduceErrorKind, RecordSerializeError,
+};
+use crate::intern::StringInterner;
+use crate::record::{Field, FieldName, FieldValue, Record, fields};
+use crate::result::{FieldParseResult, RecordProduceResult, RecordSerializeResult};
+
+/// Unit of the AMOUNT field on the text boundary.
+/// Internally amounts are always integer minor units
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum AmountUnit {
+    /// Integer minor units, e.g. cents
+    #[default]
+    Minor,
+    /// Decimal major units with `scale` digits after the point
+    Major {
+        /// Count of minor unit digits
+        scale: u32,
+    },
+}
+
+impl AmountUnit {
+    /// Converts a textual amount into minor units.
+    /// Rounding is never performed, a value with more decimals than the scale is an error
+    pub(crate) fn to_minor(self, value: &str) -> FieldParseResult<u64> {
+        let scale = match self {
+            AmountUnit::Minor => {
+                return value.parse().map_err(|err| FieldParseError {
+                    text: "failed to parse amount".into(),
+                    source: Some(Box::new(err)),
+                });
+            }
+            AmountUnit::Major { scale } => scale,
+        };
+        let (int_part, frac_part) = match value.split_once('.') {
+            Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+            None => (value, None),
+        };
+        let frac_part = frac_part.unwrap_or_default();
+        if frac_part.len() > scale as usize {
+            return Err(FieldParseError {
+                text: format!("amount {} has more than {} decimal digits", value, scale),
+                source: None,
+            });
+        }
+        let digits = |part: &str| {
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(FieldParseError {
+                    text: format!("invalid amount {}", value),
+                    source: None,
+                });
+            }
+            part.parse::<u64>().map_err(|err| FieldParseError {
+                text: "failed to parse amount".into(),
+                source: Some(Box::new(err)),
+            })
+        };
+        let overflow = || FieldParseError {
+            text: format!("amount {} overflows u64 minor units", value),
+            source: None,
+        };
+        let multiplier = 10u64.checked_pow(scale).ok_or_else(overflow)?;
+        let mut minor = digits(int_part)?
+            .checked_mul(multiplier)
+            .ok_or_else(overflow)?;
+        if value.contains('.') {
+            let frac_multiplier = 10u64.pow(scale - frac_part.len() as u32);
+            minor = minor
+                .checked_add(digits(frac_part)? * frac_multiplier)
+                .ok_or_else(overflow)?;
+        }
+        Ok(minor)
+    }
+
+    /// Renders an amount given in minor units
+    pub(crate) fn format(self, minor: u64) -> String {
+        match self {
+            AmountUnit::Minor | AmountUnit::Major { scale: 0 } => minor.to_string(),
+            AmountUnit::Major { scale } => match 10u64.checked_pow(scale) {
+                Some(divisor) => format!(
+                    "{}.{:0width$}",
+                    minor / divisor,
+                    minor % divisor,
+                    width = scale as usize
+                ),
+                None => format!("0.{:0width$}", minor, width = scale as usize),
+            },
+        }
+    }
+}
+
+impl FromStr for AmountUnit {
+    type Err = String;
+    /// Parses `minor`, `major` (scale 2) or `major:<scale>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "minor" => Ok(AmountUnit::Minor),
+            None if s == "major" => Ok(AmountUnit::Major { scale: 2 }),
+            Some(("major", scale)) => scale
+                .parse()
+                .map(|scale| AmountUnit::Major { scale })
+                .map_err(|_| format!("invalid amount scale {}", scale)),
+            _ => Err(format!(
+                "unknown amount unit {}. expected minor, major or major:<scale>",
+                s
+            )),
+        }
+    }
+}
+
+/// Unit of the TIMESTAMP field on the text boundary.
+/// Internally and in bin timestamps are always epoch milliseconds.
+/// Conversions to a coarser unit truncate toward zero, e.g. 1999 ms is 1 s,
+/// conversions to a finer unit fail when they overflow u64.
+/// Timestamps are never guessed from their magnitude, the unit is always explicit
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TimestampUnit {
+    /// Epoch seconds
+    Seconds,
+    /// Epoch milliseconds
+    #[default]
+    Millis,
+    /// Epoch microseconds
+    Micros,
+}
+
+impl TimestampUnit {
+    /// Converts a timestamp in this unit into milliseconds
+    pub(crate) fn to_millis(self, value: u64) -> FieldParseResult<u64> {
+        match self {
+            TimestampUnit::Seconds => value.checked_mul(1000).ok_or_else(|| FieldParseError {
+                text: format!("timestamp {} s overflows u64 milliseconds", value),
+                source: None,
+            }),
+            TimestampUnit::Millis => Ok(value),
+            TimestampUnit::Micros => Ok(value / 1000),
+        }
+    }
+
+    /// Timestamp of the record converted into this unit
+    pub(crate) fn timestamp(self, record: &Record) -> RecordSerializeResult<u64> {
+        match self {
+            TimestampUnit::Seconds => Ok(record.timestamp / 1000),
+            TimestampUnit::Millis => Ok(record.timestamp),
+            TimestampUnit::Micros => {
+                record
+                    .timestamp
+                    .checked_mul(1000)
+                    .ok_or_else(|| RecordSerializeError {
+                        text: format!(
+                            "timestamp {} ms of record {} overflows u64 microseconds",
+                            record.timestamp, record.tx_id
+                        ),
+                        source: None,
+                    })
+            }
+        }
+    }
+}
+
+impl FromStr for TimestampUnit {
+    type Err = String;
+    /// Parses `s`, `ms` or `us`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "s" => Ok(TimestampUnit::Seconds),
+            "ms" => Ok(TimestampUnit::Millis),
+            "us" => Ok(TimestampUnit::Micros),
+            _ => Err(format!(
+                "unknown timestamp unit {}. expected s, ms or us",
+                s
+            )),
+        }
+    }
+}
+
+/// Encoding of csv and txt input. Input in another encoding than UTF-8
+/// is decoded while read, so readers and outputs always see UTF-8.
+/// Raw records and positions then refer to the decoded bytes
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum InputEncoding {
+    /// UTF-8, read as is
+    #[default]
+    Utf8,
+    /// ISO-8859-1, every byte is the code point of the same value
+    Latin1,
+    /// Windows-1252, Latin-1 with printable characters in 0x80-0x9F
+    Windows1252,
+}
+
+/// Characters of Windows-1252 bytes 0x80-0x9F.
+/// Unassigned bytes keep their Latin-1 control code points
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+impl InputEncoding {
+    /// Character of the byte in a single-byte encoding
+    pub(crate) fn decode(self, byte: u8) -> char {
+        match (self, byte) {
+            (InputEncoding::Windows1252, 0x80..=0x9F) => {
+                WINDOWS_1252_HIGH[usize::from(byte - 0x80)]
+            }
+            _ => char::from(byte),
+        }
+    }
+}
+
+impl FromStr for InputEncoding {
+    type Err = String;
+    /// Parses `utf-8`, `latin-1` or `windows-1252`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(InputEncoding::Utf8),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(InputEncoding::Latin1),
+            "windows-1252" | "cp1252" => Ok(InputEncoding::Windows1252),
+            _ => Err(format!(
+                "unknown encoding {}. expected utf-8, latin-1 or windows-1252",
+                s
+            )),
+        }
+    }
+}
+
+/// Layout of the txt output
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TxtStyle {
+    /// `NAME: value` lines
+    #[default]
+    Plain,
+    /// Field names are padded to a common width so the values line up
+    Aligned,
+}
+
+/// Separator of field names and values in txt input
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TxtDelimiter {
+    /// `NAME: value` lines
+    ColonSpace,
+    /// `NAME=value` lines. Whitespace around the name and the value is trimmed
+    Equals,
+    /// Whichever of `:` and `=` comes first in the line, so both styles may be mixed
+    #[default]
+    Auto,
+}
+
+impl TxtDelimiter {
+    /// Splits the line into the field name and the value
+    pub(crate) fn split(self, line: &str) -> Option<(&str, &str)> {
+        let position = match self {
+            TxtDelimiter::ColonSpace => line.find(':'),
+            TxtDelimiter::Equals => line.find('='),
+            TxtDelimiter::Auto => line.find([':', '=']),
+        }?;
+        let name = line[..position].trim();
+        let value = &line[position + 1..];
+        if line.as_bytes()[position] == b'=' {
+            return Some((name, value.trim()));
+        }
+        // padding of aligned output is tolerated around names and non-text values
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        if name == FieldName::Description.as_str() {
+            Some((name, value))
+        } else {
+            Some((name, value.trim()))
+        }
+    }
+}
+
+/// Handling of control characters in descriptions of text output
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SanitizePolicy {
+    /// Descriptions are written as they are
+    #[default]
+    Preserve,
+    /// Every control character is replaced with the given one
+    Replace(char),
+    /// Control characters are removed
+    Strip,
+    /// A control character is a serialization error
+    Error,
+}
+
+impl SanitizePolicy {
+    /// Applies the policy to the description of the record
+    pub(crate) fn description(self, record: &Record) -> RecordSerializeResult<String> {
+        let text = &record.description;
+        Ok(match self {
+            SanitizePolicy::Preserve => text.clone(),
+            SanitizePolicy::Replace(r) => text
+                .chars()
+                .map(|c| if c.is_control() { r } else { c })
+                .collect(),
+            SanitizePolicy::Strip => text.chars().filter(|c| !c.is_control()).collect(),
+            SanitizePolicy::Error => match text.chars().find(|c| c.is_control()) {
+                Some(c) => {
+                    return Err(RecordSerializeError {
+                        text: format!(
+                            "description of record {} contains control character U+{:04X}",
+                            record.tx_id, c as u32
+                        ),
+                        source: None,
+                    });
+                }
+                None => text.clone(),
+            },
+        })
+    }
+}
+
+impl FromStr for SanitizePolicy {
+    type Err = String;
+    /// Parses `preserve`, `strip`, `error`, `replace` (with a space) or `replace:<char>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(SanitizePolicy::Preserve),
+            "strip" => Ok(SanitizePolicy::Strip),
+            "error" => Ok(SanitizePolicy::Error),
+            "replace" => Ok(SanitizePolicy::Replace(' ')),
+            _ => {
+                let mut replacement = s.strip_prefix("replace:").map(|r| r.chars());
+                match replacement.as_mut().map(|r| (r.next(), r.next())) {
+                    Some((Some(c), None)) if !c.is_control() => Ok(SanitizePolicy::Replace(c)),
+                    _ => Err(format!(
+                        "unknown sanitize policy {}. expected preserve, strip, error, replace or replace:<char>",
+                        s
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Handling of field names that aren't record fields
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum UnknownFields {
+    /// An unknown field is an error
+    #[default]
+    Error,
+    /// Unknown fields are skipped
+    Ignore,
+}
+
+/// Values of fields absent from the input, given as text the way csv cells are.
+/// Only the listed fields are filled, any other missing field is still an error
+///
+/// ```
+/// use parserde::{FieldDefaults, FieldName, ParseOptions, build_reader_with_options};
+/// let mut defaults = FieldDefaults::new();
+/// defaults.set(FieldName::Description, "");
+/// let options = ParseOptions {
+///     defaults,
+///     ..Default::default()
+/// };
+/// let input = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS\n1,DEPOSIT,0,2,5,9,SUCCESS\n";
+/// let mut reader = build_reader_with_options(std::io::Cursor::new(input), "csv", &options).unwrap();
+/// assert!(reader.produce_record().unwrap().is_ok());
+/// ```
+///
+#[derive(Debug, Default, Clone)]
+pub struct FieldDefaults {
+    values: Vec<(FieldName, String)>,
+}
+
+impl FieldDefaults {
+    /// Creates defaults without any field
+    pub fn new() -> FieldDefaults {
+        FieldDefaults::default()
+    }
+
+    /// Sets the default of the field replacing an earlier one
+    pub fn set(&mut self, field: FieldName, value: impl Into<String>) {
+        self.values.retain(|(name, _)| *name != field);
+        self.values.push((field, value.into()));
+    }
+
+    /// Default of the field if it's set
+    pub fn get(&self, field: FieldName) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether no field has a default
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Parses the defaults the way readers parse the fields
+    pub(crate) fn parse(
+        &self,
+        amount_unit: AmountUnit,
+        timestamp_unit: TimestampUnit,
+    ) -> RecordProduceResult<Vec<FieldValue>> {
+        self.values
+            .iter()
+            .map(|(name, value)| {
+                Field::new(*name, value.as_str())
+                    .parse_with_units(amount_unit, timestamp_unit)
+                    .map_err(|e| RecordProduceError {
+                        kind: RecordProduceErrorKind::ParseField {
+                            field_name: name.as_str().into(),
+                        },
+                        text: format!("invalid default of {}", name.as_str()),
+                        source: Some(Box::new(e)),
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Options applied by readers while parsing records
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    /// Unit of AMOUNT in csv input
+    pub amount_unit: AmountUnit,
+    /// Handling of unknown keys when a record is built from a map
+    /// and of unknown columns in a csv header
+    pub unknown_fields: UnknownFields,
+    /// Empty csv input without a header gives no records instead of an error
+    pub allow_empty: bool,
+    /// Unit of TIMESTAMP in csv and txt input
+    pub timestamp_unit: TimestampUnit,
+    /// Values of fields missing from csv and txt input
+    pub defaults: FieldDefaults,
+    /// Accept alias names of fields in csv headers and txt keys,
+    /// the ones of [`fields::ALIASES`] and the registered ones
+    pub allow_aliases: bool,
+    /// Aliases registered with [`ParseOptions::alias`]
+    pub aliases: Vec<(String, FieldName)>,
+    /// Encoding of csv and txt input
+    pub encoding: InputEncoding,
+    /// Separator of names and values in txt input
+    pub txt_delimiter: TxtDelimiter,
+    /// Skip the records of txt input that fail to parse. Their errors are
+    /// kept by the reader, see [`TxtReader::collected_errors`](crate::TxtReader::collected_errors)
+    pub lenient: bool,
+    /// Pool the descriptions of readers built with
+    /// [`build_compact_reader_with_options`](crate::build_compact_reader_with_options)
+    /// are interned into. Readers given the same pool share their descriptions,
+    /// None gives every reader a pool of its own
+    pub interner: Option<StringInterner>,
+}
+
+impl ParseOptions {
+    /// Registers an alias of the field and enables aliases
+    ///
+    /// ```
+    /// use parserde::{FieldName, ParseOptions, build_reader_with_options};
+    /// let mut options = ParseOptions::default();
+    /// options.alias("KIND", FieldName::TxType);
+    /// let input = "ID,KIND,FROM_USER,TO_USER,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,2,5,9,SUCCESS,a\n";
+    /// let mut reader = build_reader_with_options(std::io::Cursor::new(input), "csv", &options).unwrap();
+    /// assert!(reader.produce_record().unwrap().is_ok());
+    /// ```
+    pub fn alias(&mut self, alias: impl Into<String>, field: FieldName) -> &mut ParseOptions {
+        self.allow_aliases = true;
+        self.aliases.push((alias.into(), field));
+        self
+    }
+
+    /// Aliases in effect, the registered ones first
+    pub(crate) fn alias_table(&self) -> Vec<(String, FieldName)> {
+        if !self.allow_aliases {
+            return Vec::new();
+        }
+        let builtin = fields::ALIASES
+            .iter()
+            .map(|&(alias, field)| (alias.to_string(), field));
+        self.aliases.iter().cloned().chain(builtin).collect()
+    }
+}
+
+/// Options applied by serializers while rendering records
+#[derive(Debug, Default, Clone)]
+pub struct SerializeOptions {
+    /// Unit of AMOUNT in csv output
+    pub amount_unit: AmountUnit,
+    /// Layout of the txt output
+    pub txt_style: TxtStyle,
+    /// Right-align numeric values in the aligned txt output
+    pub right_align_numbers: bool,
+    /// Precede every txt record with a `# Record N (TX_TYPE)` comment
+    pub txt_section_comments: bool,
+    /// Handling of control characters in descriptions of csv and txt output
+    pub sanitize: SanitizePolicy,
+    /// Unit of TIMESTAMP in csv and txt output
+    pub timestamp_unit: TimestampUnit,
+    /// Columns of csv output in order, record fields or derived columns.
+    /// Empty means all record fields
+    pub csv_columns: Vec<String>,
+    /// Derived columns available to `csv_columns` besides the built-in ones
+    pub derived_columns: Vec<DerivedColumn>,
+    /// Write every field of a ron record on its own line
+    pub ron_pretty: bool,
+    /// Table of the `COPY ... FROM stdin;` statement preceding pgcopy output.
+    /// None writes the rows only
+    pub pgcopy_table: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::{Status, TxType};
+
+    #[test]
+    fn test_major_to_minor() {
+        let unit = AmountUnit::Major { scale: 2 };
+        assert_eq!(unit.to_minor("12.34").unwrap(), 1234);
+        assert_eq!(unit.to_minor("12.3").unwrap(), 1230);
+        assert_eq!(unit.to_minor("12").unwrap(), 1200);
+        assert_eq!(unit.to_minor("0.05").unwrap(), 5);
+        assert!(unit.to_minor("12.345").is_err());
+        assert!(unit.to_minor("-1.00").is_err());
+        assert!(unit.to_minor("1.").is_err());
+        assert!(unit.to_minor("184467440737095516.16").is_err());
+        assert_eq!(AmountUnit::Minor.to_minor("1234").unwrap(), 1234);
+    }
+
+    #[test]
+    fn test_minor_to_major() {
+        let unit = AmountUnit::Major { scale: 2 };
+        assert_eq!(unit.format(1234), "12.34");
+        assert_eq!(unit.format(5), "0.05");
+        assert_eq!(unit.format(1200), "12.00");
+        assert_eq!(AmountUnit::Minor.format(1234), "1234");
+        assert_eq!(unit.to_minor(&unit.format(u64::MAX)).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn test_amount_unit_from_str() {
+        assert_eq!("minor".parse::<AmountUnit>(), Ok(AmountUnit::Minor));
+        assert_eq!(
+            "major".parse::<AmountUnit>(),
+            Ok(AmountUnit::Major { scale: 2 })
+        );
+        assert_eq!(
+            "major:3".parse::<AmountUnit>(),
+            Ok(AmountUnit::Major { scale: 3 })
+        );
+        assert!("major:x".parse::<AmountUnit>().is_err());
+    }
+
+    #[test]
+    fn test_amount_unit_ignored_by_bin_and_txt() {
+        let record = crate::Record::new(
+            1,
+            crate::TxType::Deposit,
+            0,
+            2,
+            1234,
+            1633036860000,
+            crate::Status::Success,
+            "fee".into(),
+        );
+        let options = SerializeOptions {
+            amount_unit: AmountUnit::Major { scale: 2 },
+            ..Default::default()
+        };
+        for format in ["bin", "txt"] {
+            let plain = crate::build_serializer(format).unwrap();
+            let major = crate::build_serializer_with_options(format, &options).unwrap();
+            assert_eq!(
+                plain.serialize(&record).unwrap(),
+                major.serialize(&record).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_sanitize_policy() {
+        let record = Record::new(
+            7,
+            TxType::Deposit,
+            0,
+            1,
+            10,
+            5,
+            Status::Success,
+            "a\tb\rc\0d".into(),
+        );
+        let apply = |policy: SanitizePolicy| policy.description(&record);
+        assert_eq!(apply(SanitizePolicy::Preserve).unwrap(), "a\tb\rc\0d");
+        assert_eq!(apply(SanitizePolicy::Replace('?')).unwrap(), "a?b?c?d");
+        assert_eq!(apply(SanitizePolicy::Strip).unwrap(), "abcd");
+        let e = apply(SanitizePolicy::Error).unwrap_err();
+        assert!(e.to_string().contains("record 7"));
+        assert!(e.to_string().contains("U+0009"));
+
+        assert_eq!("replace:_".parse(), Ok(SanitizePolicy::Replace('_')));
+        assert_eq!("strip".parse(), Ok(SanitizePolicy::Strip));
+        assert!("replace:ab".parse::<SanitizePolicy>().is_err());
+        assert!("drop".parse::<SanitizePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_timestamp_unit() {
+        let record = |timestamp| {
+            Record::new(
+                7,
+                TxType::Deposit,
+                0,
+                1,
+                10,
+                timestamp,
+                Status::Success,
+                "fee".into(),
+            )
+        };
+        assert_eq!(
+            TimestampUnit::Seconds.to_millis(1633036860).unwrap(),
+            1633036860000
+        );
+        assert!(
+            TimestampUnit::Seconds
+                .to_millis(u64::MAX / 1000 + 1)
+                .is_err()
+        );
+        // finer units are truncated toward zero
+        assert_eq!(
+            TimestampUnit::Micros.to_millis(1633036860000999).unwrap(),
+            1633036860000
+        );
+        assert_eq!(
+            TimestampUnit::Seconds
+                .timestamp(&record(1633036860999))
+                .unwrap(),
+            1633036860
+        );
+        assert_eq!(TimestampUnit::Seconds.timestamp(&record(999)).unwrap(), 0);
+        assert_eq!(TimestampUnit::Micros.timestamp(&record(5)).unwrap(), 5000);
+        let e = TimestampUnit::Micros
+            .timestamp(&record(u64::MAX - 1))
+            .unwrap_err();
+        assert!(e.to_string().contains("overflows u64 microseconds"));
+        assert_eq!("us".parse(), Ok(TimestampUnit::Micros));
+        assert!("sec".parse::<TimestampUnit>().is_err());
+    }
+
+    #[test]
+    fn test_timestamp_unit_round_trip() {
+        let seconds = "TX_ID,TIMESTAMP\n1,1633036860\n";
+        let parse = ParseOptions {
+            timestamp_unit: TimestampUnit::Seconds,
+            ..Default::default()
+        };
+        let mut reader = crate::build_partial_reader(std::io::Cursor::new(seconds), "csv").unwrap();
+        assert_eq!(
+            reader.produce_partial_record().unwrap().unwrap().timestamp,
+            Some(1633036860)
+        );
+        let input = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,1,10,1633036860,SUCCESS,fee\n";
+        let mut reader =
+            crate::build_reader_with_options(std::io::Cursor::new(input), "csv", &parse).unwrap();
+        let record = reader.produce_record().unwrap().unwrap();
+        assert_eq!(record.timestamp, 1633036860000);
+
+        let millis = crate::build_serializer("txt").unwrap();
+        assert!(
+            String::from_utf8(millis.serialize(&record).unwrap())
+                .unwrap()
+                .contains("TIMESTAMP: 1633036860000\n")
+        );
+        let options = SerializeOptions {
+            timestamp_unit: TimestampUnit::Seconds,
+            ..Default::default()
+        };
+        let seconds = crate::build_serializer_with_options("csv", &options).unwrap();
+        assert_eq!(
+            seconds.serialize(&record).unwrap(),
+            b"1,DEPOSIT,0,1,10,1633036860,SUCCESS,fee"
+        );
+        let bin = crate::build_serializer_with_options("bin", &options).unwrap();
+        assert_eq!(
+            bin.serialize(&record).unwrap(),
+            crate::build_serializer("bin")
+                .unwrap()
+                .serialize(&record)
+                .unwrap()
+        );
+    }
+}
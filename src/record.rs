@@ -1,80 +1,100 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::str::FromStr;
 
-use crate::error::FieldParseError;
+use csv::StringRecord;
+
+use crate::error::{
+    EnumParseError, FieldParseError, RecordProduceError, RecordProduceErrorKind, RecordWriteError,
+};
+use crate::formats::bin::Frame;
+use crate::formats::csv::csv_line;
+use crate::options::{AmountUnit, ParseOptions, TimestampUnit, TxtDelimiter, UnknownFields};
 use crate::result::{
     FieldParseResult, RecordProduceResult, RecordReadResult, RecordSerializeResult,
     RecordWriteResult,
 };
 
 #[derive(Debug)]
-pub(crate) struct Field<F, V> {
-    pub(crate) name: F,
+pub(crate) struct Field<V> {
+    pub(crate) name: FieldName,
     pub(crate) value: V,
 }
 
-impl Field<&str, &str> {
+impl Field<&str> {
     pub(crate) fn parse(&self) -> FieldParseResult<FieldValue> {
-        Ok(match self.name.as_bytes() {
-            fields::byte::TX_ID => {
+        Ok(match self.name {
+            FieldName::TxId => {
                 FieldValue::TxId(self.value.parse().map_err(|err| FieldParseError {
                     text: "failed to parse tx_id".into(),
                     source: Some(Box::new(err)),
                 })?)
             }
-            fields::byte::TX_TYPE => {
+            FieldName::TxType => {
                 FieldValue::TxType(TxType::try_from(self.value).map_err(|err| FieldParseError {
                     text: "failed to parse tx_type".into(),
                     source: Some(err.into()),
                 })?)
             }
-            fields::byte::STATUS => {
+            FieldName::Status => {
                 FieldValue::Status(Status::try_from(self.value).map_err(|err| FieldParseError {
                     text: "failed to parse status".into(),
                     source: Some(err.into()),
                 })?)
             }
-            fields::byte::FROM_USER => {
+            FieldName::FromUser => {
                 FieldValue::FromUser(self.value.parse().map_err(|err| FieldParseError {
                     text: "failed to parse from_user".into(),
                     source: Some(Box::new(err)),
                 })?)
             }
-            fields::byte::TO_USER => {
+            FieldName::ToUser => {
                 FieldValue::ToUser(self.value.parse().map_err(|err| FieldParseError {
                     text: "failed to parse to_user".into(),
                     source: Some(Box::new(err)),
                 })?)
             }
-            fields::byte::AMOUNT => {
+            FieldName::Amount => {
                 FieldValue::Amount(self.value.parse().map_err(|err| FieldParseError {
                     text: "failed to parse amount".into(),
                     source: Some(Box::new(err)),
                 })?)
             }
-            fields::byte::TIMESTAMP => {
+            FieldName::Timestamp => {
                 FieldValue::Timestamp(self.value.parse().map_err(|err| FieldParseError {
                     text: "failed to parse timestamp".into(),
                     source: Some(Box::new(err)),
                 })?)
             }
-            fields::byte::DESCRIPTION => FieldValue::Description(self.value.to_owned()),
-            _ => {
-                return Err(FieldParseError {
-                    text: format!("unknown field: {}", self.name),
-                    source: None,
-                });
-            }
+            FieldName::Description => FieldValue::Description(self.value.to_owned()),
         })
     }
 }
 
-impl<F, V> Field<F, V> {
-    pub(crate) fn new(name: F, value: V) -> Field<F, V> {
+impl Field<&str> {
+    /// Parses the field reading AMOUNT in the given unit
+    pub(crate) fn parse_with_units(
+        &self,
+        amount_unit: AmountUnit,
+        timestamp_unit: TimestampUnit,
+    ) -> FieldParseResult<FieldValue> {
+        if self.name == FieldName::Amount {
+            return Ok(FieldValue::Amount(amount_unit.to_minor(self.value)?));
+        }
+        self.parse()?.with_timestamp_unit(timestamp_unit)
+    }
+}
+
+impl<V> Field<V> {
+    pub(crate) fn new(name: FieldName, value: V) -> Field<V> {
         Field { name, value }
     }
 }
 
-impl<F: Display, V: Display> Display for Field<F, V> {
+impl<V: Display> Display for Field<V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}: {}", self.name, self.value)
     }
@@ -87,101 +107,854 @@ pub(crate) trait DataConsumer {
 
 /// Trait for types that return parsed Record structure
 pub trait DataProducer {
+    /// Produces the next record. Returns None when the input is exhausted
     fn produce_record(&mut self) -> Option<RecordProduceResult<Record>>;
+
+    /// Position right after the last produced record.
+    /// None if the reader doesn't track its position
+    fn position(&self) -> Option<Position> {
+        None
+    }
+
+    /// Adapts the producer to an [`Iterator`] of record results
+    ///
+    /// ```
+    /// use parserde::{build_reader, DataProducer};
+    /// let cursor = std::io::Cursor::new("TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,2,100,5,SUCCESS,fee\n");
+    /// let records = build_reader(cursor, "csv")
+    ///     .unwrap()
+    ///     .into_iter()
+    ///     .filter_map(Result::ok)
+    ///     .take(100)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(records.len(), 1);
+    /// ```
+    fn into_iter(self) -> RecordIterator<Self>
+    where
+        Self: Sized,
+    {
+        RecordIterator::new(self)
+    }
+
+    /// Skips the records the filter doesn't accept. Errors are passed through
+    ///
+    /// ```
+    /// use parserde::{build_reader, ByStatus, DataProducer, Status};
+    /// let cursor = std::io::Cursor::new("TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,2,100,5,SUCCESS,fee\n2,DEPOSIT,0,2,100,5,FAILURE,fee\n");
+    /// let mut failed = build_reader(cursor, "csv")
+    ///     .unwrap()
+    ///     .filter(ByStatus(Status::Failure));
+    /// assert_eq!(failed.produce_record().unwrap().unwrap().tx_id(), 2);
+    /// assert!(failed.produce_record().is_none());
+    /// ```
+    fn filter(self, filter: impl RecordFilter + 'static) -> Box<dyn DataProducer>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(Filtered::new(self, filter))
+    }
+
+    /// Produces the records of the other producer after the records of this one
+    ///
+    /// ```
+    /// use parserde::{build_reader, DataProducer};
+    /// let header = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+    /// let first = std::io::Cursor::new(format!("{}1,DEPOSIT,0,2,100,5,SUCCESS,fee\n", header));
+    /// let second = std::io::Cursor::new(format!("{}2,DEPOSIT,0,2,100,5,SUCCESS,fee\n", header));
+    /// let mut chained = build_reader(first, "csv")
+    ///     .unwrap()
+    ///     .chain(build_reader(second, "csv").unwrap());
+    /// assert_eq!(chained.produce_record().unwrap().unwrap().tx_id(), 1);
+    /// assert_eq!(chained.produce_record().unwrap().unwrap().tx_id(), 2);
+    /// assert!(chained.produce_record().is_none());
+    /// ```
+    fn chain(self, other: Box<dyn DataProducer>) -> ChainedProducer
+    where
+        Self: Sized + 'static,
+    {
+        ChainedProducer::new(vec![Box::new(self), other])
+    }
+
+    /// Produces at most `n` records. Errors count as records
+    ///
+    /// ```
+    /// use parserde::{build_reader, DataProducer};
+    /// let cursor = std::io::Cursor::new("TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,2,100,5,SUCCESS,fee\n2,DEPOSIT,0,2,100,5,SUCCESS,fee\n");
+    /// let mut first = build_reader(cursor, "csv").unwrap().take_records(1);
+    /// assert_eq!(first.produce_record().unwrap().unwrap().tx_id(), 1);
+    /// assert!(first.produce_record().is_none());
+    /// ```
+    fn take_records(self, n: usize) -> TakeProducer<Self>
+    where
+        Self: Sized,
+    {
+        TakeProducer {
+            producer: self,
+            remaining: n,
+        }
+    }
+
+    /// Discards the first `n` records before producing the rest.
+    /// Errors count as records
+    ///
+    /// ```
+    /// use parserde::{build_reader, DataProducer};
+    /// let cursor = std::io::Cursor::new("TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,2,100,5,SUCCESS,fee\n2,DEPOSIT,0,2,100,5,SUCCESS,fee\n");
+    /// let mut rest = build_reader(cursor, "csv").unwrap().skip_records(1);
+    /// assert_eq!(rest.produce_record().unwrap().unwrap().tx_id(), 2);
+    /// assert!(rest.produce_record().is_none());
+    /// ```
+    fn skip_records(self, n: usize) -> SkipProducer<Self>
+    where
+        Self: Sized,
+    {
+        SkipProducer {
+            producer: self,
+            remaining: n,
+        }
+    }
+
+    /// Produces every `step`-th record starting with the first one,
+    /// discarding the records in between. Errors count as records
+    ///
+    /// ```
+    /// use std::num::NonZeroUsize;
+    /// use parserde::{build_reader, DataProducer};
+    /// let cursor = std::io::Cursor::new("TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,2,100,5,SUCCESS,fee\n2,DEPOSIT,0,2,100,5,SUCCESS,fee\n3,DEPOSIT,0,2,100,5,SUCCESS,fee\n");
+    /// let mut sample = build_reader(cursor, "csv")
+    ///     .unwrap()
+    ///     .sample_every(NonZeroUsize::new(2).unwrap());
+    /// assert_eq!(sample.produce_record().unwrap().unwrap().tx_id(), 1);
+    /// assert_eq!(sample.produce_record().unwrap().unwrap().tx_id(), 3);
+    /// assert!(sample.produce_record().is_none());
+    /// ```
+    fn sample_every(self, step: NonZeroUsize) -> RecordSampler<Self>
+    where
+        Self: Sized,
+    {
+        RecordSampler {
+            producer: self,
+            step,
+            started: false,
+        }
+    }
+}
+
+/// Trait for types that return parsed Record structure read from async input
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+pub trait AsyncDataProducer: Send {
+    /// Produces the next record. Returns None when the input is exhausted
+    async fn produce_record(&mut self) -> Option<RecordProduceResult<Record>>;
+}
+
+/// Predicate deciding which records are kept
+pub trait RecordFilter {
+    /// Whether the record is kept
+    fn accept(&self, record: &Record) -> bool;
+}
+
+impl<F: RecordFilter + ?Sized> RecordFilter for Box<F> {
+    fn accept(&self, record: &Record) -> bool {
+        (**self).accept(record)
+    }
+}
+
+/// Keeps records with the status
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByStatus(pub Status);
+
+impl RecordFilter for ByStatus {
+    fn accept(&self, record: &Record) -> bool {
+        record.status == self.0
+    }
+}
+
+/// Keeps records of the transaction type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByTxType(pub TxType);
+
+impl RecordFilter for ByTxType {
+    fn accept(&self, record: &Record) -> bool {
+        record.tx_type == self.0
+    }
+}
+
+/// Keeps records with an amount within the inclusive range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByAmountRange {
+    /// Least kept amount
+    pub min: u64,
+    /// Greatest kept amount
+    pub max: u64,
+}
+
+impl RecordFilter for ByAmountRange {
+    fn accept(&self, record: &Record) -> bool {
+        (self.min..=self.max).contains(&record.amount)
+    }
+}
+
+/// Keeps records with a timestamp within the inclusive range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByTimestampRange {
+    /// Earliest kept timestamp
+    pub start: u64,
+    /// Latest kept timestamp
+    pub end: u64,
+}
+
+impl RecordFilter for ByTimestampRange {
+    fn accept(&self, record: &Record) -> bool {
+        (self.start..=self.end).contains(&record.timestamp)
+    }
+}
+
+/// Keeps records accepted by both filters
+pub struct AndFilter(pub Box<dyn RecordFilter>, pub Box<dyn RecordFilter>);
+
+impl RecordFilter for AndFilter {
+    fn accept(&self, record: &Record) -> bool {
+        self.0.accept(record) && self.1.accept(record)
+    }
+}
+
+/// Producer of the records of another producer accepted by a filter
+pub struct Filtered<P: DataProducer, F: RecordFilter> {
+    producer: P,
+    filter: F,
+}
+
+impl<P: DataProducer, F: RecordFilter> Filtered<P, F> {
+    /// Wraps the producer
+    pub fn new(producer: P, filter: F) -> Filtered<P, F> {
+        Filtered { producer, filter }
+    }
+}
+
+impl<P: DataProducer, F: RecordFilter> DataProducer for Filtered<P, F> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        loop {
+            match self.producer.produce_record()? {
+                Ok(record) if !self.filter.accept(&record) => continue,
+                result => return Some(result),
+            }
+        }
+    }
+
+    fn position(&self) -> Option<Position> {
+        self.producer.position()
+    }
+}
+
+/// Producer of the first records of another producer,
+/// created by [`DataProducer::take_records`]
+pub struct TakeProducer<P: DataProducer> {
+    producer: P,
+    remaining: usize,
+}
+
+impl<P: DataProducer> DataProducer for TakeProducer<P> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        self.producer.produce_record()
+    }
+
+    fn position(&self) -> Option<Position> {
+        self.producer.position()
+    }
+}
+
+/// Producer of the records of another producer after the first ones,
+/// created by [`DataProducer::skip_records`]
+pub struct SkipProducer<P: DataProducer> {
+    producer: P,
+    remaining: usize,
+}
+
+impl<P: DataProducer> DataProducer for SkipProducer<P> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        while self.remaining > 0 {
+            self.remaining -= 1;
+            // a skipped record is discarded whether it parsed or not
+            let _ = self.producer.produce_record()?;
+        }
+        self.producer.produce_record()
+    }
+
+    fn position(&self) -> Option<Position> {
+        self.producer.position()
+    }
+}
+
+/// Producer of every Nth record of another producer,
+/// created by [`DataProducer::sample_every`]
+pub struct RecordSampler<P: DataProducer> {
+    producer: P,
+    step: NonZeroUsize,
+    started: bool,
+}
+
+impl<P: DataProducer> DataProducer for RecordSampler<P> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        if self.started {
+            for _ in 1..self.step.get() {
+                let _ = self.producer.produce_record()?;
+            }
+        }
+        self.started = true;
+        self.producer.produce_record()
+    }
+
+    fn position(&self) -> Option<Position> {
+        self.producer.position()
+    }
+}
+
+/// Producer of the records of several producers one after another.
+/// A producer is dropped once it is exhausted, errors are passed through
+pub struct ChainedProducer {
+    producers: VecDeque<Box<dyn DataProducer>>,
+}
+
+impl ChainedProducer {
+    /// Chains the producers in the given order
+    pub fn new(producers: Vec<Box<dyn DataProducer>>) -> ChainedProducer {
+        ChainedProducer {
+            producers: producers.into(),
+        }
+    }
+}
+
+impl DataProducer for ChainedProducer {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        loop {
+            match self.producers.front_mut()?.produce_record() {
+                None => self.producers.pop_front(),
+                result => return result,
+            };
+        }
+    }
+
+    /// Positions within a chain of producers aren't tracked
+    fn position(&self) -> Option<Position> {
+        None
+    }
+
+    fn chain(mut self, other: Box<dyn DataProducer>) -> ChainedProducer {
+        self.producers.push_back(other);
+        self
+    }
+}
+
+/// Producer of records held in memory, each produced as is
+///
+/// ```
+/// use parserde::{DataProducer, Record, VecProducer};
+/// let mut producer = VecProducer::from_vec(vec![Record::default()]);
+/// assert_eq!(producer.produce_record().unwrap().unwrap(), Record::default());
+/// assert!(producer.produce_record().is_none());
+/// ```
+pub struct VecProducer {
+    records: std::vec::IntoIter<Record>,
+}
+
+impl VecProducer {
+    /// Creates a producer of the records in order
+    pub fn from_vec(records: Vec<Record>) -> VecProducer {
+        VecProducer {
+            records: records.into_iter(),
+        }
+    }
+}
+
+impl DataProducer for VecProducer {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        self.records.next().map(Ok)
+    }
+}
+
+/// Iterator over the records of a producer.
+/// A failed record is yielded as an error and the iteration goes on
+/// with the next one. Once the producer is exhausted the iterator is fused
+pub struct RecordIterator<P: DataProducer> {
+    producer: P,
+    is_exhausted: bool,
+}
+
+impl<P: DataProducer> RecordIterator<P> {
+    /// Wraps the producer
+    pub fn new(producer: P) -> RecordIterator<P> {
+        RecordIterator {
+            producer,
+            is_exhausted: false,
+        }
+    }
+
+    /// Returns the wrapped producer
+    pub fn into_inner(self) -> P {
+        self.producer
+    }
+}
+
+impl<P: DataProducer> From<P> for RecordIterator<P> {
+    fn from(producer: P) -> Self {
+        RecordIterator::new(producer)
+    }
+}
+
+impl<P: DataProducer> Iterator for RecordIterator<P> {
+    type Item = RecordProduceResult<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_exhausted {
+            return None;
+        }
+        let next = self.producer.produce_record();
+        self.is_exhausted = next.is_none();
+        next
+    }
+}
+
+impl<P: DataProducer> std::iter::FusedIterator for RecordIterator<P> {}
+
+impl<P: DataProducer + ?Sized> DataProducer for Box<P> {
+    fn produce_record(&mut self) -> Option<RecordProduceResult<Record>> {
+        (**self).produce_record()
+    }
+
+    fn position(&self) -> Option<Position> {
+        (**self).position()
+    }
+}
+
+/// Position of a reader in its input
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Byte offset the next record starts at
+    pub byte: u64,
+    /// Count of lines consumed. Always zero for bin input
+    pub line: u64,
+    /// Count of records produced
+    pub record: u64,
+}
+
+/// Record read from the input but not parsed yet
+#[derive(Debug)]
+pub struct RawRecord(pub(crate) RawRepr);
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum RawRepr {
+    Frame(Frame),
+    /// Csv row with the delimiter of its input
+    Csv(StringRecord, u8),
+    /// Lines of a txt record without the line breaks
+    Txt(Vec<String>),
+}
+
+impl RawRecord {
+    /// The record as it appears in its input. Concatenated records
+    /// of one input, preceded by its raw header, form a valid input.
+    /// A dictionary bin frame referencing a string table entry is returned
+    /// as is, so it can't be read without the table entry frames before it
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match &self.0 {
+            RawRepr::Frame(frame) => frame.to_bytes(),
+            RawRepr::Csv(row, delimiter) => csv_line(row.iter(), *delimiter),
+            RawRepr::Txt(lines) => {
+                let mut bytes = Vec::new();
+                for line in lines {
+                    bytes.extend_from_slice(line.as_bytes());
+                    bytes.push(b'\n');
+                }
+                bytes.push(b'\n');
+                bytes
+            }
+        }
+    }
+
+    /// Whether both records have the same content that parses the same
+    /// on its own. Frames referencing a string table never match this way
+    /// since the tables of the inputs may differ
+    pub fn same_bytes(&self, other: &RawRecord) -> bool {
+        match (&self.0, &other.0) {
+            (RawRepr::Frame(left), RawRepr::Frame(right)) => {
+                left.is_self_contained() && left == right
+            }
+            (left, right) => left == right,
+        }
+    }
+}
+
+/// Trait for producers able to hand out records before parsing them,
+/// so that equal inputs can be compared without parsing
+pub trait RawDataProducer: DataProducer {
+    /// Bytes preceding the records in the input, e.g. the csv header.
+    /// Raw records of inputs with different headers aren't comparable
+    fn raw_header(&self) -> Vec<u8> {
+        Vec::new()
+    }
+    /// Produces the next record without parsing it
+    fn produce_raw(&mut self) -> Option<RecordProduceResult<RawRecord>>;
+    /// Parses a raw record produced by this producer
+    fn parse_raw(&self, raw: &RawRecord) -> RecordProduceResult<Record>;
+    /// Produces the next record. A record failing to parse is handed back
+    /// with its raw bytes, which are empty if it couldn't even be read
+    fn produce_or_reject(&mut self) -> Option<Result<Record, RejectedRecord>> {
+        let position = self.position();
+        let raw = match self.produce_raw()? {
+            Ok(raw) => raw,
+            Err(error) => {
+                return Some(Err(RejectedRecord {
+                    raw: Vec::new(),
+                    error,
+                    position,
+                }));
+            }
+        };
+        Some(self.parse_raw(&raw).map_err(|error| RejectedRecord {
+            raw: raw.to_bytes(),
+            error,
+            position,
+        }))
+    }
+}
+
+/// Record that failed to be read or parsed
+#[derive(Debug)]
+pub struct RejectedRecord {
+    /// The record as it appears in the input, see [`RawRecord::to_bytes`]
+    pub raw: Vec<u8>,
+    /// Why the record was rejected
+    pub error: RecordProduceError,
+    /// Position of the reader before the record
+    pub position: Option<Position>,
+}
+
+/// Trait for types that return parsed PartialRecord structure.
+/// Missing fields are not an error, only tx_id is required
+pub trait PartialDataProducer {
+    /// Produces the next partial record
+    fn produce_partial_record(&mut self) -> Option<RecordProduceResult<PartialRecord>>;
 }
 
 /// Fields of data to match in input
 pub mod fields {
     /// a list of const fields as slice
     pub mod str {
+        use super::FieldName;
+
         /// ID
-        pub const TX_ID: &str = "TX_ID";
+        #[deprecated(note = "use FieldName::TxId")]
+        pub const TX_ID: &str = FieldName::TxId.as_str();
         /// Transaction type
-        pub const TX_TYPE: &str = "TX_TYPE";
+        #[deprecated(note = "use FieldName::TxType")]
+        pub const TX_TYPE: &str = FieldName::TxType.as_str();
         /// Status
-        pub const STATUS: &str = "STATUS";
+        #[deprecated(note = "use FieldName::Status")]
+        pub const STATUS: &str = FieldName::Status.as_str();
         /// From user
-        pub const FROM_USER: &str = "FROM_USER_ID";
+        #[deprecated(note = "use FieldName::FromUser")]
+        pub const FROM_USER: &str = FieldName::FromUser.as_str();
         /// To user
-        pub const TO_USER: &str = "TO_USER_ID";
+        #[deprecated(note = "use FieldName::ToUser")]
+        pub const TO_USER: &str = FieldName::ToUser.as_str();
         /// Timestamp
-        pub const TIMESTAMP: &str = "TIMESTAMP";
+        #[deprecated(note = "use FieldName::Timestamp")]
+        pub const TIMESTAMP: &str = FieldName::Timestamp.as_str();
         /// Amount
-        pub const AMOUNT: &str = "AMOUNT";
+        #[deprecated(note = "use FieldName::Amount")]
+        pub const AMOUNT: &str = FieldName::Amount.as_str();
         /// Descrption
-        pub const DESCRIPTION: &str = "DESCRIPTION";
+        #[deprecated(note = "use FieldName::Description")]
+        pub const DESCRIPTION: &str = FieldName::Description.as_str();
     }
     /// A list of const fields as bytes
     pub mod byte {
+        use super::FieldName;
+
         /// Id
-        pub const TX_ID: &[u8] = b"TX_ID";
+        #[deprecated(note = "use FieldName::TxId")]
+        pub const TX_ID: &[u8] = FieldName::TxId.as_bytes();
         /// Transaction type
-        pub const TX_TYPE: &[u8] = b"TX_TYPE";
+        #[deprecated(note = "use FieldName::TxType")]
+        pub const TX_TYPE: &[u8] = FieldName::TxType.as_bytes();
         /// Status
-        pub const STATUS: &[u8] = b"STATUS";
+        #[deprecated(note = "use FieldName::Status")]
+        pub const STATUS: &[u8] = FieldName::Status.as_bytes();
         /// From user
-        pub const FROM_USER: &[u8] = b"FROM_USER_ID";
+        #[deprecated(note = "use FieldName::FromUser")]
+        pub const FROM_USER: &[u8] = FieldName::FromUser.as_bytes();
         /// To user
-        pub const TO_USER: &[u8] = b"TO_USER_ID";
+        #[deprecated(note = "use FieldName::ToUser")]
+        pub const TO_USER: &[u8] = FieldName::ToUser.as_bytes();
         /// Timestamp
-        pub const TIMESTAMP: &[u8] = b"TIMESTAMP";
+        #[deprecated(note = "use FieldName::Timestamp")]
+        pub const TIMESTAMP: &[u8] = FieldName::Timestamp.as_bytes();
         /// Amount
-        pub const AMOUNT: &[u8] = b"AMOUNT";
+        #[deprecated(note = "use FieldName::Amount")]
+        pub const AMOUNT: &[u8] = FieldName::Amount.as_bytes();
         /// Descrption
-        pub const DESCRIPTION: &[u8] = b"DESCRIPTION";
+        #[deprecated(note = "use FieldName::Description")]
+        pub const DESCRIPTION: &[u8] = FieldName::Description.as_bytes();
+    }
+
+    use super::FieldName;
+
+    /// Common variants of field names accepted when aliases are allowed
+    pub const ALIASES: &[(&str, FieldName)] = &[
+        ("ID", FieldName::TxId),
+        ("TYPE", FieldName::TxType),
+        ("FROM_USER", FieldName::FromUser),
+        ("TO_USER", FieldName::ToUser),
+        ("DESC", FieldName::Description),
+    ];
+}
+
+/// Name of a record field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FieldName {
+    /// TX_ID
+    TxId,
+    /// TX_TYPE
+    TxType,
+    /// STATUS
+    Status,
+    /// FROM_USER_ID
+    FromUser,
+    /// TO_USER_ID
+    ToUser,
+    /// AMOUNT
+    Amount,
+    /// TIMESTAMP
+    Timestamp,
+    /// DESCRIPTION
+    Description,
+}
+
+impl FieldName {
+    /// All fields in the default column order
+    pub const ALL: [FieldName; 8] = [
+        FieldName::TxId,
+        FieldName::TxType,
+        FieldName::FromUser,
+        FieldName::ToUser,
+        FieldName::Amount,
+        FieldName::Timestamp,
+        FieldName::Status,
+        FieldName::Description,
+    ];
+
+    /// Name of the field as used in all formats
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            FieldName::TxId => "TX_ID",
+            FieldName::TxType => "TX_TYPE",
+            FieldName::Status => "STATUS",
+            FieldName::FromUser => "FROM_USER_ID",
+            FieldName::ToUser => "TO_USER_ID",
+            FieldName::Amount => "AMOUNT",
+            FieldName::Timestamp => "TIMESTAMP",
+            FieldName::Description => "DESCRIPTION",
+        }
+    }
+
+    /// Name of the field as bytes
+    pub const fn as_bytes(&self) -> &'static [u8] {
+        self.as_str().as_bytes()
+    }
+
+    /// Column type of the field in [`Record::to_sql_create_table`]
+    const fn sql_type(&self) -> &'static str {
+        match self {
+            FieldName::TxType | FieldName::Status => "VARCHAR(32)",
+            FieldName::Description => "TEXT",
+            _ => "BIGINT",
+        }
+    }
+}
+
+impl Display for FieldName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
+/// Canonical field of a name, looking the name up in the aliases
+/// when it isn't a canonical one
+pub(crate) fn resolve_field(name: &str, aliases: &[(String, FieldName)]) -> Option<FieldName> {
+    name.parse().ok().or_else(|| {
+        aliases
+            .iter()
+            .find(|(alias, _)| alias == name)
+            .map(|&(_, field)| field)
+    })
+}
+
+impl FromStr for FieldName {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FieldName::ALL
+            .into_iter()
+            .find(|field| field.as_str() == s)
+            .ok_or_else(|| format!("unknown field {}", s))
+    }
+}
+
+/// Value of a single record field
+#[derive(Debug, Clone, PartialEq)]
 pub enum FieldValue {
+    /// TX_ID
     TxId(u64),
+    /// TX_TYPE
     TxType(TxType),
+    /// STATUS
     Status(Status),
+    /// FROM_USER_ID
     FromUser(u64),
+    /// TO_USER_ID
     ToUser(u64),
+    /// TIMESTAMP
     Timestamp(u64),
+    /// AMOUNT
     Amount(u64),
+    /// DESCRIPTION
     Description(String),
 }
 
-#[derive(Debug, PartialEq)]
+/// Puts the defaults before the fields, so values read from the input override them
+pub(crate) fn with_defaults(
+    defaults: &[FieldValue],
+    mut fields: Vec<FieldValue>,
+) -> Vec<FieldValue> {
+    if !defaults.is_empty() {
+        fields.splice(0..0, defaults.iter().cloned());
+    }
+    fields
+}
+
+impl FieldValue {
+    /// Name of the field as in [`FieldName`]
+    pub fn name(&self) -> &'static str {
+        self.field().as_str()
+    }
+
+    /// Value of a numeric field
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            FieldValue::TxId(v)
+            | FieldValue::FromUser(v)
+            | FieldValue::ToUser(v)
+            | FieldValue::Timestamp(v)
+            | FieldValue::Amount(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Value of the description
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FieldValue::Description(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Value of the transaction type
+    pub fn as_tx_type(&self) -> Option<TxType> {
+        match self {
+            FieldValue::TxType(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Value of the status
+    pub fn as_status(&self) -> Option<Status> {
+        match self {
+            FieldValue::Status(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Value rendered the way the text serializers do
+    pub fn as_string(&self) -> String {
+        match self {
+            FieldValue::TxId(v)
+            | FieldValue::FromUser(v)
+            | FieldValue::ToUser(v)
+            | FieldValue::Timestamp(v)
+            | FieldValue::Amount(v) => v.to_string(),
+            FieldValue::TxType(v) => v.to_string(),
+            FieldValue::Status(v) => v.to_string(),
+            FieldValue::Description(v) => v.clone(),
+        }
+    }
+
+    pub(crate) fn field(&self) -> FieldName {
+        match self {
+            FieldValue::TxId(_) => FieldName::TxId,
+            FieldValue::TxType(_) => FieldName::TxType,
+            FieldValue::Status(_) => FieldName::Status,
+            FieldValue::FromUser(_) => FieldName::FromUser,
+            FieldValue::ToUser(_) => FieldName::ToUser,
+            FieldValue::Timestamp(_) => FieldName::Timestamp,
+            FieldValue::Amount(_) => FieldName::Amount,
+            FieldValue::Description(_) => FieldName::Description,
+        }
+    }
+
+    /// Converts a timestamp given in the unit into milliseconds
+    pub(crate) fn with_timestamp_unit(self, unit: TimestampUnit) -> FieldParseResult<FieldValue> {
+        match self {
+            FieldValue::Timestamp(value) => Ok(FieldValue::Timestamp(unit.to_millis(value)?)),
+            value => Ok(value),
+        }
+    }
+}
+
+/// Transaction type
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "UPPERCASE")
+)]
 pub enum TxType {
+    /// DEPOSIT
     Deposit,
+    /// TRANSFER
     Transfer,
+    /// WITHDRAWAL
     Withdrawal,
 }
 
 impl Display for TxType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                TxType::Deposit => "DEPOSIT",
-                TxType::Transfer => "TRANSFER",
-                TxType::Withdrawal => "WITHDRAWAL",
-            }
-        )
+        f.write_str((*self).into())
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// Transaction status
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "UPPERCASE")
+)]
 pub enum Status {
+    /// SUCCESS
     Success,
+    /// FAILURE
     Failure,
+    /// PENDING
     Pending,
 }
 
 impl Display for Status {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Status::Failure => "FAILURE",
-                Status::Pending => "PENDING",
-                Status::Success => "SUCCESS",
-            }
-        )
+        f.write_str((*self).into())
     }
 }
 
@@ -207,14 +980,48 @@ impl From<&TxType> for u8 {
     }
 }
 
-impl TryFrom<&str> for TxType {
-    type Error = String;
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        Ok(match value {
+impl FromStr for TxType {
+    type Err = EnumParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
             "DEPOSIT" => TxType::Deposit,
             "TRANSFER" => TxType::Transfer,
             "WITHDRAWAL" => TxType::Withdrawal,
-            _ => return Err("invalid TxType".to_string()),
+            _ => {
+                return Err(EnumParseError {
+                    kind: "tx_type",
+                    value: s.to_string(),
+                });
+            }
+        })
+    }
+}
+
+impl TryFrom<&str> for TxType {
+    type Error = EnumParseError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl TxType {
+    /// All transaction types in the order of their byte codes
+    pub fn all() -> &'static [TxType] {
+        &[TxType::Deposit, TxType::Transfer, TxType::Withdrawal]
+    }
+
+    /// Parses the transaction type ignoring the case of the text
+    ///
+    /// ```
+    /// use parserde::TxType;
+    /// assert_eq!(TxType::parse_lenient("Deposit"), Ok(TxType::Deposit));
+    /// assert!("Deposit".parse::<TxType>().is_err());
+    /// ```
+    ///
+    pub fn parse_lenient(s: &str) -> Result<TxType, EnumParseError> {
+        s.to_ascii_uppercase().parse().map_err(|_| EnumParseError {
+            kind: "tx_type",
+            value: s.to_string(),
         })
     }
 }
@@ -239,23 +1046,50 @@ impl From<&Status> for u8 {
     }
 }
 
-impl TryFrom<&str> for Status {
-    type Error = String;
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        Ok(match value {
+impl FromStr for Status {
+    type Err = EnumParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
             "SUCCESS" => Status::Success,
             "FAILURE" => Status::Failure,
             "PENDING" => Status::Pending,
-            _ => return Err("unknown status".to_string()),
+            _ => {
+                return Err(EnumParseError {
+                    kind: "status",
+                    value: s.to_string(),
+                });
+            }
         })
     }
 }
 
-impl From<Status> for &str {
-    fn from(value: Status) -> Self {
-        match value {
+impl TryFrom<&str> for Status {
+    type Error = EnumParseError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Status {
+    /// All statuses in the order of their byte codes
+    pub fn all() -> &'static [Status] {
+        &[Status::Success, Status::Failure, Status::Pending]
+    }
+
+    /// Parses the status ignoring the case of the text
+    pub fn parse_lenient(s: &str) -> Result<Status, EnumParseError> {
+        s.to_ascii_uppercase().parse().map_err(|_| EnumParseError {
+            kind: "status",
+            value: s.to_string(),
+        })
+    }
+}
+
+impl From<Status> for &str {
+    fn from(value: Status) -> Self {
+        match value {
             Status::Failure => "FAILURE",
-            Status::Success => "STATUS",
+            Status::Success => "SUCCESS",
             Status::Pending => "PENDING",
         }
     }
@@ -273,19 +1107,54 @@ impl TryFrom<&u8> for Status {
     }
 }
 
-/// Centralized data struct
-#[derive(Debug, PartialEq)]
+/// Centralized data struct.
+/// Records are ordered by TX_ID, then TIMESTAMP, then the remaining fields
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Record {
+    #[cfg_attr(feature = "serde", serde(rename = "TX_ID"))]
     pub(crate) tx_id: u64,
+    #[cfg_attr(feature = "serde", serde(rename = "TX_TYPE"))]
     pub(crate) tx_type: TxType,
+    #[cfg_attr(feature = "serde", serde(rename = "FROM_USER_ID"))]
     pub(crate) from_user: u64,
+    #[cfg_attr(feature = "serde", serde(rename = "TO_USER_ID"))]
     pub(crate) to_user: u64,
+    #[cfg_attr(feature = "serde", serde(rename = "AMOUNT"))]
     pub(crate) amount: u64,
+    #[cfg_attr(feature = "serde", serde(rename = "TIMESTAMP"))]
     pub(crate) timestamp: u64,
+    #[cfg_attr(feature = "serde", serde(rename = "STATUS"))]
     pub(crate) status: Status,
+    #[cfg_attr(feature = "serde", serde(rename = "DESCRIPTION", default))]
     pub(crate) description: String,
 }
 
+impl Ord for Record {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.tx_id, self.timestamp)
+            .cmp(&(other.tx_id, other.timestamp))
+            .then_with(|| self.tx_type.cmp(&other.tx_type))
+            .then_with(|| self.from_user.cmp(&other.from_user))
+            .then_with(|| self.to_user.cmp(&other.to_user))
+            .then_with(|| self.amount.cmp(&other.amount))
+            .then_with(|| self.status.cmp(&other.status))
+            .then_with(|| self.description.cmp(&other.description))
+    }
+}
+
+impl Hash for Record {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u32(self.checksum());
+    }
+}
+
+impl PartialOrd for Record {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Record {
     /// Create new record instance
     #[allow(clippy::too_many_arguments)]
@@ -310,6 +1179,192 @@ impl Record {
             timestamp,
         }
     }
+
+    /// ID of the transaction
+    ///
+    /// ```
+    /// use parserde::{Record, Status, TxType};
+    /// let record = Record::new(1, TxType::Deposit, 0, 2, 100, 5, Status::Success, "fee".into());
+    /// assert_eq!(record.tx_id(), 1);
+    /// assert_eq!(record.tx_type(), TxType::Deposit);
+    /// assert_eq!((record.from_user(), record.to_user()), (0, 2));
+    /// assert_eq!((record.amount(), record.timestamp()), (100, 5));
+    /// assert_eq!(record.status(), Status::Success);
+    /// assert_eq!(record.description(), "fee");
+    /// ```
+    pub fn tx_id(&self) -> u64 {
+        self.tx_id
+    }
+
+    /// Type of the transaction
+    pub fn tx_type(&self) -> TxType {
+        self.tx_type
+    }
+
+    /// User sending the amount, 0 for deposits
+    pub fn from_user(&self) -> u64 {
+        self.from_user
+    }
+
+    /// User receiving the amount, 0 for withdrawals
+    pub fn to_user(&self) -> u64 {
+        self.to_user
+    }
+
+    /// Amount in minor units
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    /// Time of the transaction in milliseconds since the Unix epoch
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Status of the transaction
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Free-form description
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Values of all fields: TX_ID, TX_TYPE, STATUS, FROM_USER_ID, TO_USER_ID,
+    /// TIMESTAMP, AMOUNT and DESCRIPTION.
+    /// `Record::try_from` builds the record back from them
+    ///
+    /// ```
+    /// use parserde::Record;
+    /// let record = Record::default();
+    /// let values = record.to_field_values();
+    /// assert_eq!(values[0].name(), "TX_ID");
+    /// assert_eq!(Record::try_from(values).unwrap(), record);
+    /// ```
+    pub fn to_field_values(&self) -> Vec<FieldValue> {
+        vec![
+            FieldValue::TxId(self.tx_id),
+            FieldValue::TxType(self.tx_type),
+            FieldValue::Status(self.status),
+            FieldValue::FromUser(self.from_user),
+            FieldValue::ToUser(self.to_user),
+            FieldValue::Timestamp(self.timestamp),
+            FieldValue::Amount(self.amount),
+            FieldValue::Description(self.description.clone()),
+        ]
+    }
+
+    /// Values of all fields in the order of [`Record::to_field_values`],
+    /// moving the description out of the record
+    pub fn into_field_values(self) -> Vec<FieldValue> {
+        vec![
+            FieldValue::TxId(self.tx_id),
+            FieldValue::TxType(self.tx_type),
+            FieldValue::Status(self.status),
+            FieldValue::FromUser(self.from_user),
+            FieldValue::ToUser(self.to_user),
+            FieldValue::Timestamp(self.timestamp),
+            FieldValue::Amount(self.amount),
+            FieldValue::Description(self.description),
+        ]
+    }
+}
+
+impl Record {
+    /// Fields whose values differ between the records, in the default column order
+    pub fn differing_fields(&self, other: &Record) -> Vec<FieldName> {
+        FieldName::ALL
+            .into_iter()
+            .filter(|name| match name {
+                FieldName::TxId => self.tx_id != other.tx_id,
+                FieldName::TxType => self.tx_type != other.tx_type,
+                FieldName::FromUser => self.from_user != other.from_user,
+                FieldName::ToUser => self.to_user != other.to_user,
+                FieldName::Amount => self.amount != other.amount,
+                FieldName::Timestamp => self.timestamp != other.timestamp,
+                FieldName::Status => self.status != other.status,
+                FieldName::Description => self.description != other.description,
+            })
+            .collect()
+    }
+
+    /// Fields whose values differ between the records rendered the way
+    /// the serializers do, in the default column order. Empty when the records are equal
+    ///
+    /// ```
+    /// use parserde::{FieldName, Record};
+    /// let left = Record::default();
+    /// let mut right = left.clone();
+    /// right.set_amount(5);
+    /// let diff = left.diff(&right);
+    /// assert_eq!(diff.len(), 1);
+    /// assert_eq!((diff[0].name, diff[0].right.as_str()), (FieldName::Amount.as_str(), "5"));
+    /// ```
+    pub fn diff(&self, other: &Record) -> Vec<FieldDiff> {
+        self.diff_ignoring(other, &[])
+    }
+
+    /// Like [`Record::diff`] skipping the fields with the given [`FieldName`] names
+    pub fn diff_ignoring(&self, other: &Record, ignored: &[&str]) -> Vec<FieldDiff> {
+        self.to_pairs()
+            .into_iter()
+            .zip(other.to_pairs())
+            .filter(|((name, left), (_, right))| left != right && !ignored.contains(name))
+            .map(|((name, left), (_, right))| FieldDiff { name, left, right })
+            .collect()
+    }
+}
+
+/// Field whose value differs between two records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    /// Name of the field, one of the [`FieldName`] names
+    pub name: &'static str,
+    /// Value in the left record
+    pub left: String,
+    /// Value in the right record
+    pub right: String,
+}
+
+/// Field level differences between two records
+///
+/// ```
+/// use parserde::{Record, RecordDiff};
+/// let left = Record::default();
+/// let mut right = left.clone();
+/// right.set_amount(5);
+/// let diff = RecordDiff::compute(&left, &right);
+/// assert_eq!(diff.to_string(), "AMOUNT: 0 != 5");
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordDiff {
+    /// Differing fields in the default column order
+    pub fields: Vec<FieldDiff>,
+}
+
+impl RecordDiff {
+    /// Compares the records field by field
+    pub fn compute(a: &Record, b: &Record) -> RecordDiff {
+        RecordDiff { fields: a.diff(b) }
+    }
+
+    /// Whether the records are equal
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+impl Display for RecordDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = self
+            .fields
+            .iter()
+            .map(|field| format!("{}: {} != {}", field.name, field.left, field.right))
+            .collect::<Vec<_>>();
+        f.write_str(&fields.join(", "))
+    }
 }
 
 impl Default for Record {
@@ -348,18 +1403,18 @@ pub struct Data<T>(T);
 
 impl TryFrom<Data<String>> for FieldValue {
     type Error = FieldParseError;
-    fn try_from(mut field: Data<String>) -> FieldParseResult<FieldValue> {
-        let del_pos = field.0.find(':');
-        if del_pos.is_none() {
-            return Err(FieldParseError {
+    /// Parses a `NAME: value` or `NAME=value` line
+    fn try_from(field: Data<String>) -> FieldParseResult<FieldValue> {
+        let (name, value) = TxtDelimiter::Auto
+            .split(&field.0)
+            .ok_or_else(|| FieldParseError {
                 text: "no delimiter found".into(),
                 source: None,
-            });
-        }
-        let del_pos = del_pos.unwrap();
-        let value = field.0.split_off(del_pos);
-        let f = Field::new(field.0.as_str(), &value[2..]);
-        f.parse()
+            })?;
+        let field = name
+            .parse()
+            .map_err(|text| FieldParseError { text, source: None })?;
+        Field::new(field, value).parse()
     }
 }
 
@@ -400,18 +1455,1366 @@ impl TryFrom<Vec<FieldValue>> for Record {
             amount: amount.ok_or("missing field amount")?,
             timestamp: timestamp.ok_or("missing field timestamp")?,
             status: status.ok_or("missing field status")?,
-            description: description.ok_or("missing field description")?,
+            description: description.unwrap_or_default(),
         })
     }
 }
 
+impl Record {
+    /// Builds a record from field name and value pairs parsing the values
+    /// the way the text readers do. Every field but DESCRIPTION is required
+    ///
+    /// ```
+    /// use parserde::{ParseOptions, Record, UnknownFields};
+    /// let entries = [
+    ///     ("TX_ID", "1"), ("TX_TYPE", "DEPOSIT"), ("FROM_USER_ID", "0"),
+    ///     ("TO_USER_ID", "2"), ("AMOUNT", "100"), ("TIMESTAMP", "5"),
+    ///     ("STATUS", "SUCCESS"), ("DESCRIPTION", "fee"), ("BATCH", "7"),
+    /// ];
+    /// let options = ParseOptions {
+    ///     unknown_fields: UnknownFields::Ignore,
+    ///     ..Default::default()
+    /// };
+    /// assert!(Record::from_map_with_options(entries, &options).is_ok());
+    /// ```
+    pub fn from_map_with_options<K, V, I>(
+        entries: I,
+        options: &ParseOptions,
+    ) -> RecordProduceResult<Record>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut values = options
+            .defaults
+            .parse(options.amount_unit, options.timestamp_unit)?;
+        for (name, value) in entries {
+            let name = name.as_ref();
+            let Ok(field) = name.parse::<FieldName>() else {
+                match options.unknown_fields {
+                    UnknownFields::Error => {
+                        return Err(RecordProduceError {
+                            kind: RecordProduceErrorKind::MalformedRecord,
+                            text: format!("unknown field {}", name),
+                            source: Some(Box::new(FieldParseError {
+                                text: format!("unknown field: {}", name),
+                                source: None,
+                            })),
+                        });
+                    }
+                    UnknownFields::Ignore => continue,
+                }
+            };
+            let value = Field::new(field, value.as_ref())
+                .parse_with_units(options.amount_unit, options.timestamp_unit)
+                .map_err(|e| RecordProduceError {
+                    kind: RecordProduceErrorKind::ParseField {
+                        field_name: name.into(),
+                    },
+                    text: format!("failed to parse field {}", name),
+                    source: Some(Box::new(e)),
+                })?;
+            values.push(value);
+        }
+        Record::try_from(values).map_err(|e| RecordProduceError {
+            kind: RecordProduceErrorKind::of_record(&e),
+            text: e,
+            source: None,
+        })
+    }
+}
+
+impl Record {
+    /// Builds a record from a map of field names to values
+    /// with the default parse options
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use parserde::Record;
+    /// let map = HashMap::from([
+    ///     ("TX_ID", "1"), ("TX_TYPE", "DEPOSIT"), ("FROM_USER_ID", "0"),
+    ///     ("TO_USER_ID", "2"), ("AMOUNT", "100"), ("TIMESTAMP", "5"),
+    ///     ("STATUS", "SUCCESS"),
+    /// ]);
+    /// assert_eq!(Record::from_field_map(&map).unwrap().amount(), 100);
+    /// ```
+    pub fn from_field_map(map: &HashMap<&str, &str>) -> RecordProduceResult<Record> {
+        Record::from_map_with_options(map, &ParseOptions::default())
+    }
+}
+
+impl Record {
+    /// Fields rendered the way the serializers do, in the default column order
+    pub fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        FieldName::ALL
+            .into_iter()
+            .map(|name| {
+                let value = match name {
+                    FieldName::TxId => self.tx_id.to_string(),
+                    FieldName::TxType => self.tx_type.to_string(),
+                    FieldName::FromUser => self.from_user.to_string(),
+                    FieldName::ToUser => self.to_user.to_string(),
+                    FieldName::Amount => self.amount.to_string(),
+                    FieldName::Timestamp => self.timestamp.to_string(),
+                    FieldName::Status => self.status.to_string(),
+                    FieldName::Description => self.description.clone(),
+                };
+                (name.as_str(), value)
+            })
+            .collect()
+    }
+
+    /// Fields rendered the way the serializers do keyed by the [`FieldName`] names.
+    /// The map is ordered by name, use [`Record::to_pairs`] for the column order.
+    /// Building a record from the map gives back the same record
+    pub fn to_map(&self) -> BTreeMap<&'static str, String> {
+        self.to_pairs().into_iter().collect()
+    }
+
+    /// `INSERT` statement adding the record to the table, in the default column order.
+    /// Text values are quoted with embedded single quotes doubled
+    ///
+    /// ```
+    /// use parserde::Record;
+    /// let mut record = Record::default();
+    /// record.set_description("it's");
+    /// let sql = record.to_sql_insert("records");
+    /// assert!(sql.starts_with("INSERT INTO \"records\" (TX_ID, TX_TYPE,"));
+    /// assert!(sql.ends_with(", 'it''s');"));
+    /// ```
+    pub fn to_sql_insert(&self, table_name: &str) -> String {
+        let values = self
+            .to_pairs()
+            .into_iter()
+            .zip(FieldName::ALL)
+            .map(|((_, value), name)| match name {
+                FieldName::TxType | FieldName::Status | FieldName::Description => {
+                    format!("'{}'", value.replace('\'', "''"))
+                }
+                _ => value,
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "INSERT INTO {} ({}) VALUES ({});",
+            sql_identifier(table_name),
+            FieldName::ALL.map(|name| name.as_str()).join(", "),
+            values.join(", ")
+        )
+    }
+
+    /// `CREATE TABLE` statement of a table holding records
+    /// with the columns of [`Record::to_sql_insert`]
+    pub fn to_sql_create_table(table_name: &str) -> String {
+        let columns = FieldName::ALL
+            .map(|name| format!("    {} {} NOT NULL", name.as_str(), name.sql_type()))
+            .join(",\n");
+        format!(
+            "CREATE TABLE {} (\n{}\n);",
+            sql_identifier(table_name),
+            columns
+        )
+    }
+}
+
+/// Quotes a table name for use in a statement
+pub(crate) fn sql_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+impl TryFrom<&HashMap<String, String>> for Record {
+    type Error = RecordProduceError;
+    fn try_from(map: &HashMap<String, String>) -> RecordProduceResult<Record> {
+        Record::from_map_with_options(map, &ParseOptions::default())
+    }
+}
+
+impl TryFrom<HashMap<String, String>> for Record {
+    type Error = RecordProduceError;
+    fn try_from(map: HashMap<String, String>) -> RecordProduceResult<Record> {
+        Record::from_map_with_options(map, &ParseOptions::default())
+    }
+}
+
+impl TryFrom<&BTreeMap<String, String>> for Record {
+    type Error = RecordProduceError;
+    fn try_from(map: &BTreeMap<String, String>) -> RecordProduceResult<Record> {
+        Record::from_map_with_options(map, &ParseOptions::default())
+    }
+}
+
+/// A record where only tx_id is required.
+/// Used as a patch containing the changed fields of a record
+#[derive(Debug, PartialEq, Default)]
+pub struct PartialRecord {
+    /// ID of the record to patch
+    pub tx_id: u64,
+    /// Transaction type
+    pub tx_type: Option<TxType>,
+    /// From user
+    pub from_user: Option<u64>,
+    /// To user
+    pub to_user: Option<u64>,
+    /// Amount
+    pub amount: Option<u64>,
+    /// Timestamp
+    pub timestamp: Option<u64>,
+    /// Status
+    pub status: Option<Status>,
+    /// Description
+    pub description: Option<String>,
+}
+
+impl PartialRecord {
+    /// Converts the patch into a full record if all fields are present
+    pub fn try_into_record(self) -> Result<Record, String> {
+        Ok(Record {
+            tx_id: self.tx_id,
+            tx_type: self.tx_type.ok_or("missing field tx_type")?,
+            from_user: self.from_user.ok_or("missing field from_user")?,
+            to_user: self.to_user.ok_or("missing field to_user")?,
+            amount: self.amount.ok_or("missing field amount")?,
+            timestamp: self.timestamp.ok_or("missing field timestamp")?,
+            status: self.status.ok_or("missing field status")?,
+            description: self.description.unwrap_or_default(),
+        })
+    }
+}
+
+impl TryFrom<Vec<FieldValue>> for PartialRecord {
+    type Error = String;
+    fn try_from(value: Vec<FieldValue>) -> Result<Self, Self::Error> {
+        let mut tx_id = None;
+        let mut partial = PartialRecord::default();
+        for fval in value {
+            match fval {
+                FieldValue::TxId(val) => tx_id = Some(val),
+                FieldValue::TxType(val) => partial.tx_type = Some(val),
+                FieldValue::Amount(val) => partial.amount = Some(val),
+                FieldValue::FromUser(val) => partial.from_user = Some(val),
+                FieldValue::ToUser(val) => partial.to_user = Some(val),
+                FieldValue::Timestamp(val) => partial.timestamp = Some(val),
+                FieldValue::Description(val) => partial.description = Some(val),
+                FieldValue::Status(val) => partial.status = Some(val),
+            }
+        }
+        partial.tx_id = tx_id.ok_or("missing field tx_id")?;
+        Ok(partial)
+    }
+}
+
+impl Record {
+    /// Overrides the fields specified in the patch.
+    /// Returns an error if tx_id of the patch differs from the record's one
+    pub fn apply(&mut self, patch: &PartialRecord) -> Result<(), String> {
+        if self.tx_id != patch.tx_id {
+            return Err(format!(
+                "tx_id mismatch: record {} patch {}",
+                self.tx_id, patch.tx_id
+            ));
+        }
+        if let Some(tx_type) = &patch.tx_type {
+            self.tx_type = *tx_type;
+        }
+        if let Some(from_user) = patch.from_user {
+            self.from_user = from_user;
+        }
+        if let Some(to_user) = patch.to_user {
+            self.to_user = to_user;
+        }
+        if let Some(amount) = patch.amount {
+            self.amount = amount;
+        }
+        if let Some(timestamp) = patch.timestamp {
+            self.timestamp = timestamp;
+        }
+        if let Some(status) = &patch.status {
+            self.status = *status;
+        }
+        if let Some(description) = &patch.description {
+            self.description = description.clone();
+        }
+        Ok(())
+    }
+
+    /// Replaces the field of the value and returns its previous value
+    ///
+    /// ```
+    /// use parserde::{FieldValue, Record, Status};
+    /// let mut record = Record::default();
+    /// let previous = record.set(FieldValue::Status(Status::Failure));
+    /// assert_eq!(previous, FieldValue::Status(Status::Pending));
+    /// assert_eq!(record.status(), Status::Failure);
+    /// ```
+    pub fn set(&mut self, value: FieldValue) -> FieldValue {
+        match value {
+            FieldValue::TxId(v) => FieldValue::TxId(std::mem::replace(&mut self.tx_id, v)),
+            FieldValue::TxType(v) => FieldValue::TxType(std::mem::replace(&mut self.tx_type, v)),
+            FieldValue::Status(v) => FieldValue::Status(std::mem::replace(&mut self.status, v)),
+            FieldValue::FromUser(v) => {
+                FieldValue::FromUser(std::mem::replace(&mut self.from_user, v))
+            }
+            FieldValue::ToUser(v) => FieldValue::ToUser(std::mem::replace(&mut self.to_user, v)),
+            FieldValue::Timestamp(v) => {
+                FieldValue::Timestamp(std::mem::replace(&mut self.timestamp, v))
+            }
+            FieldValue::Amount(v) => FieldValue::Amount(std::mem::replace(&mut self.amount, v)),
+            FieldValue::Description(v) => {
+                FieldValue::Description(std::mem::replace(&mut self.description, v))
+            }
+        }
+    }
+
+    /// Replaces the status and returns the previous one
+    pub fn set_status(&mut self, status: Status) -> Status {
+        std::mem::replace(&mut self.status, status)
+    }
+
+    /// Replaces the description and returns the previous one
+    pub fn set_description(&mut self, description: impl Into<String>) -> String {
+        std::mem::replace(&mut self.description, description.into())
+    }
+
+    /// Replaces the amount and returns the previous one
+    pub fn set_amount(&mut self, amount: u64) -> u64 {
+        std::mem::replace(&mut self.amount, amount)
+    }
+
+    /// Replaces the transaction id and returns the previous one
+    pub fn set_tx_id(&mut self, tx_id: u64) -> u64 {
+        std::mem::replace(&mut self.tx_id, tx_id)
+    }
+
+    /// Replaces the transaction type and returns the previous one
+    pub fn set_tx_type(&mut self, tx_type: TxType) -> TxType {
+        std::mem::replace(&mut self.tx_type, tx_type)
+    }
+
+    /// Replaces the sender and returns the previous one
+    pub fn set_from_user(&mut self, from_user: u64) -> u64 {
+        std::mem::replace(&mut self.from_user, from_user)
+    }
+
+    /// Replaces the receiver and returns the previous one
+    pub fn set_to_user(&mut self, to_user: u64) -> u64 {
+        std::mem::replace(&mut self.to_user, to_user)
+    }
+
+    /// Replaces the timestamp and returns the previous one
+    pub fn set_timestamp(&mut self, timestamp: u64) -> u64 {
+        std::mem::replace(&mut self.timestamp, timestamp)
+    }
+}
+
+/// Builder of a record by field names instead of the positional [`Record::new`].
+/// Every field must be set, a field set twice keeps the last value
+///
+/// ```
+/// use parserde::{RecordBuilder, Status, TxType};
+/// let record = RecordBuilder::new()
+///     .tx_id(1)
+///     .tx_type(TxType::Deposit)
+///     .from_user(0)
+///     .to_user(2)
+///     .amount(100)
+///     .timestamp(1633036860000)
+///     .status(Status::Success)
+///     .description("fee")
+///     .build()
+///     .unwrap();
+/// assert_eq!(record.amount(), 100);
+/// assert!(RecordBuilder::new().tx_id(1).build().is_err());
+/// ```
+///
+#[derive(Debug, Default, Clone)]
+pub struct RecordBuilder {
+    values: Vec<FieldValue>,
+}
+
+impl RecordBuilder {
+    /// Creates a builder with no fields set
+    pub fn new() -> RecordBuilder {
+        RecordBuilder::default()
+    }
+
+    /// Sets the field of the value
+    pub fn field(mut self, value: FieldValue) -> RecordBuilder {
+        self.values.push(value);
+        self
+    }
+
+    /// Sets TX_ID
+    pub fn tx_id(self, tx_id: u64) -> RecordBuilder {
+        self.field(FieldValue::TxId(tx_id))
+    }
+
+    /// Sets TX_TYPE
+    pub fn tx_type(self, tx_type: TxType) -> RecordBuilder {
+        self.field(FieldValue::TxType(tx_type))
+    }
+
+    /// Sets FROM_USER_ID
+    pub fn from_user(self, from_user: u64) -> RecordBuilder {
+        self.field(FieldValue::FromUser(from_user))
+    }
+
+    /// Sets TO_USER_ID
+    pub fn to_user(self, to_user: u64) -> RecordBuilder {
+        self.field(FieldValue::ToUser(to_user))
+    }
+
+    /// Sets AMOUNT
+    pub fn amount(self, amount: u64) -> RecordBuilder {
+        self.field(FieldValue::Amount(amount))
+    }
+
+    /// Sets TIMESTAMP
+    pub fn timestamp(self, timestamp: u64) -> RecordBuilder {
+        self.field(FieldValue::Timestamp(timestamp))
+    }
+
+    /// Sets STATUS
+    pub fn status(self, status: Status) -> RecordBuilder {
+        self.field(FieldValue::Status(status))
+    }
+
+    /// Sets DESCRIPTION
+    pub fn description(self, description: impl Into<String>) -> RecordBuilder {
+        self.field(FieldValue::Description(description.into()))
+    }
+
+    /// Builds the record. Fails with the first missing field, an absent description is empty
+    pub fn build(self) -> Result<Record, String> {
+        Record::try_from(self.values)
+    }
+}
+
+/// Trait for types that convert a record into bytes of some format
 pub trait RecordSerialize {
+    /// Serializes the record
     fn serialize(&self, record: &Record) -> RecordSerializeResult<Vec<u8>>;
 }
 
+/// Trait for types that write serialized records into some output
 pub trait RecordWriter {
+    /// Writes a header of the format if it has one.
+    /// The header is written once, repeated calls are a no-op.
+    /// Writers of formats with a header write it before the first record
+    /// when it wasn't written explicitly
     fn write_header(&mut self) -> RecordWriteResult<()> {
         Ok(())
     }
+    /// Writes a comment line. Only formats that have comments support it
+    fn write_comment(&mut self, text: &str) -> RecordWriteResult<()> {
+        Err(RecordWriteError {
+            text: format!("format doesn't support comments, can't write {}", text),
+            source: None,
+        })
+    }
+    /// Writes a serialized record
     fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()>;
+    /// Writes a record serialized from the given one.
+    /// Writers annotating records with their content override it
+    fn write_record(&mut self, _record: &Record, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.write(data)
+    }
+    /// Finalizes the output once all records are written
+    fn finish(&mut self) -> RecordWriteResult<()> {
+        Ok(())
+    }
+}
+
+impl<W: RecordWriter + ?Sized> RecordWriter for Box<W> {
+    fn write_header(&mut self) -> RecordWriteResult<()> {
+        (**self).write_header()
+    }
+
+    fn write_comment(&mut self, text: &str) -> RecordWriteResult<()> {
+        (**self).write_comment(text)
+    }
+
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        (**self).write(data)
+    }
+
+    fn write_record(&mut self, record: &Record, data: Vec<u8>) -> RecordWriteResult<()> {
+        (**self).write_record(record, data)
+    }
+
+    fn finish(&mut self) -> RecordWriteResult<()> {
+        (**self).finish()
+    }
+}
+
+/// Writer forwarding every call to both writers.
+/// The second writer is called also when the first one fails,
+/// the first error is returned
+///
+/// ```
+/// use parserde::{build_serializer, build_writer, Record, RecordSerialize, RecordWriter, TeeWriter};
+/// let first = build_writer(std::io::sink(), "csv").unwrap();
+/// let second = build_writer(std::io::sink(), "csv").unwrap();
+/// let mut tee = TeeWriter::new(first, second);
+/// let data = build_serializer("csv").unwrap().serialize(&Record::default()).unwrap();
+/// assert!(tee.write(data).is_ok());
+/// ```
+///
+pub struct TeeWriter<W1: RecordWriter, W2: RecordWriter> {
+    first: W1,
+    second: W2,
+}
+
+impl<W1: RecordWriter, W2: RecordWriter> TeeWriter<W1, W2> {
+    /// Creates a writer forwarding to both writers
+    pub fn new(first: W1, second: W2) -> TeeWriter<W1, W2> {
+        TeeWriter { first, second }
+    }
+
+    /// The two writers
+    pub fn into_inner(self) -> (W1, W2) {
+        (self.first, self.second)
+    }
+
+    fn both<F>(&mut self, mut f: F) -> RecordWriteResult<()>
+    where
+        F: FnMut(&mut dyn RecordWriter) -> RecordWriteResult<()>,
+    {
+        let first = f(&mut self.first);
+        let second = f(&mut self.second);
+        first.and(second)
+    }
+}
+
+impl<W1: RecordWriter, W2: RecordWriter> RecordWriter for TeeWriter<W1, W2> {
+    fn write_header(&mut self) -> RecordWriteResult<()> {
+        self.both(|writer| writer.write_header())
+    }
+
+    fn write_comment(&mut self, text: &str) -> RecordWriteResult<()> {
+        self.both(|writer| writer.write_comment(text))
+    }
+
+    fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.both(|writer| writer.write(data.clone()))
+    }
+
+    fn write_record(&mut self, record: &Record, data: Vec<u8>) -> RecordWriteResult<()> {
+        self.both(|writer| writer.write_record(record, data.clone()))
+    }
+
+    fn finish(&mut self) -> RecordWriteResult<()> {
+        self.both(|writer| writer.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_record() -> Record {
+        Record::new(
+            1,
+            TxType::Transfer,
+            2,
+            3,
+            100,
+            1633036860000,
+            Status::Pending,
+            "Record number 1".into(),
+        )
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let mut record = get_record();
+        let patch = PartialRecord {
+            tx_id: 1,
+            status: Some(Status::Success),
+            description: Some("fixed".into()),
+            ..Default::default()
+        };
+        record.apply(&patch).unwrap();
+        assert_eq!(record.status, Status::Success);
+        assert_eq!(record.description, "fixed");
+        assert_eq!(record.amount, 100);
+        assert_eq!(record.tx_type, TxType::Transfer);
+    }
+
+    #[test]
+    fn test_apply_patch_tx_id_mismatch() {
+        let mut record = get_record();
+        let patch = PartialRecord {
+            tx_id: 2,
+            amount: Some(5),
+            ..Default::default()
+        };
+        assert!(record.apply(&patch).is_err());
+        assert_eq!(record, get_record());
+    }
+
+    #[test]
+    fn test_partial_into_record() {
+        let patch = PartialRecord {
+            tx_id: 1,
+            status: Some(Status::Success),
+            ..Default::default()
+        };
+        assert!(patch.try_into_record().is_err());
+        let full = PartialRecord {
+            tx_id: 1,
+            tx_type: Some(TxType::Transfer),
+            from_user: Some(2),
+            to_user: Some(3),
+            amount: Some(100),
+            timestamp: Some(1633036860000),
+            status: Some(Status::Pending),
+            description: Some("Record number 1".into()),
+        };
+        assert_eq!(full.try_into_record().unwrap(), get_record());
+    }
+
+    fn get_map() -> HashMap<String, String> {
+        [
+            ("TX_ID", "1"),
+            ("TX_TYPE", "TRANSFER"),
+            ("FROM_USER_ID", "2"),
+            ("TO_USER_ID", "3"),
+            ("AMOUNT", "100"),
+            ("TIMESTAMP", "1633036860000"),
+            ("STATUS", "PENDING"),
+            ("DESCRIPTION", "Record number 1"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn test_from_field_map() {
+        let record = Record::new(
+            7,
+            TxType::Withdrawal,
+            1,
+            2,
+            300,
+            1633036860000,
+            Status::Failure,
+            "a, b".into(),
+        );
+        let mut map = HashMap::from([
+            ("TX_ID", "7"),
+            ("TX_TYPE", "WITHDRAWAL"),
+            ("FROM_USER_ID", "1"),
+            ("TO_USER_ID", "2"),
+            ("AMOUNT", "300"),
+            ("TIMESTAMP", "1633036860000"),
+            ("STATUS", "FAILURE"),
+            ("DESCRIPTION", "a, b"),
+        ]);
+        assert_eq!(Record::from_field_map(&map).unwrap(), record);
+        map.insert("NAME", "x");
+        let e = Record::from_field_map(&map).unwrap_err();
+        assert_eq!(e.kind(), RecordProduceErrorKind::MalformedRecord);
+        assert!(std::error::Error::source(&e).is_some_and(|e| e.is::<FieldParseError>()));
+    }
+
+    #[test]
+    fn test_from_map() {
+        let map = get_map();
+        assert_eq!(Record::try_from(&map).unwrap(), get_record());
+        assert_eq!(Record::try_from(map.clone()).unwrap(), get_record());
+        let sorted = map.clone().into_iter().collect::<BTreeMap<_, _>>();
+        assert_eq!(Record::try_from(&sorted).unwrap(), get_record());
+
+        let mut missing = map.clone();
+        missing.remove(FieldName::Amount.as_str());
+        let e = Record::try_from(&missing).unwrap_err();
+        assert!(e.to_string().contains("missing field amount"));
+
+        let mut extra = map;
+        extra.insert("FOO".into(), "bar".into());
+        let e = Record::try_from(&extra).unwrap_err();
+        assert!(e.to_string().contains("unknown field FOO"));
+        assert!(
+            std::error::Error::source(&e).is_some_and(|e| e.is::<FieldParseError>()),
+            "{}",
+            e
+        );
+        let lenient = ParseOptions {
+            unknown_fields: UnknownFields::Ignore,
+            ..Default::default()
+        };
+        assert_eq!(
+            Record::from_map_with_options(&extra, &lenient).unwrap(),
+            get_record()
+        );
+
+        let major = ParseOptions {
+            amount_unit: AmountUnit::Major { scale: 2 },
+            ..Default::default()
+        };
+        let mut map = get_map();
+        map.insert("AMOUNT".into(), "1.00".into());
+        assert_eq!(
+            Record::from_map_with_options(&map, &major).unwrap(),
+            get_record()
+        );
+    }
+
+    #[test]
+    fn test_to_pairs() {
+        let pairs = get_record().to_pairs();
+        let names = pairs.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        assert_eq!(names, FieldName::ALL.map(|name| name.as_str()).to_vec());
+        assert_eq!(
+            pairs[1],
+            (FieldName::TxType.as_str(), "TRANSFER".to_string())
+        );
+        assert_eq!(get_record().to_map()[FieldName::Status.as_str()], "PENDING");
+    }
+
+    #[test]
+    fn test_to_map_round_trip() {
+        let tx_types = [TxType::Deposit, TxType::Transfer, TxType::Withdrawal];
+        let statuses = [Status::Success, Status::Failure, Status::Pending];
+        let mut seed = 0x2545f4914f6cdd1du64;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        for _ in 0..500 {
+            let record = Record::new(
+                next(),
+                tx_types[(next() % 3) as usize],
+                next(),
+                next(),
+                next(),
+                next(),
+                statuses[(next() % 3) as usize],
+                format!(" description {}, \"{}\" ", next(), next() % 7),
+            );
+            let map = record
+                .to_map()
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect::<HashMap<_, _>>();
+            assert_eq!(Record::try_from(&map).unwrap(), record);
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_field_names() {
+        for field in FieldName::ALL {
+            assert_eq!(field.to_string().parse::<FieldName>(), Ok(field));
+            assert_eq!(field.as_bytes(), field.as_str().as_bytes());
+        }
+        assert_eq!(fields::str::FROM_USER, FieldName::FromUser.as_str());
+        assert_eq!(fields::byte::DESCRIPTION, b"DESCRIPTION");
+        assert!("FROM_USER".parse::<FieldName>().is_err());
+    }
+
+    #[test]
+    fn test_enum_string_round_trip() {
+        for &tx_type in TxType::all() {
+            let text = tx_type.to_string();
+            assert_eq!(<&str>::from(tx_type), text);
+            assert_eq!(text.parse::<TxType>(), Ok(tx_type));
+            assert_eq!(TxType::try_from(text.as_str()), Ok(tx_type));
+            assert_eq!(TxType::parse_lenient(&text.to_lowercase()), Ok(tx_type));
+            assert!(text.to_lowercase().parse::<TxType>().is_err());
+        }
+        for &status in Status::all() {
+            let text = status.to_string();
+            assert_eq!(<&str>::from(status), text);
+            assert_eq!(text.parse::<Status>(), Ok(status));
+            assert_eq!(Status::try_from(text.as_str()), Ok(status));
+            assert_eq!(Status::parse_lenient(&text.to_lowercase()), Ok(status));
+            assert!(text.to_lowercase().parse::<Status>().is_err());
+        }
+        let e = "DONE".parse::<Status>().unwrap_err();
+        assert_eq!(e.to_string(), "invalid status: DONE");
+        assert_eq!(e.value(), "DONE");
+        assert!(TxType::parse_lenient("refund").is_err());
+
+        for (code, tx_type) in TxType::all().iter().enumerate() {
+            assert_eq!(TxType::try_from(&(code as u8)), Ok(*tx_type));
+        }
+        for (code, status) in Status::all().iter().enumerate() {
+            assert_eq!(Status::try_from(&(code as u8)), Ok(*status));
+        }
+        assert!(TxType::try_from(&(TxType::all().len() as u8)).is_err());
+        assert!(Status::try_from(&(Status::all().len() as u8)).is_err());
+    }
+
+    #[test]
+    fn test_field_value_strings() {
+        let record = Record::new(
+            1,
+            TxType::Transfer,
+            2,
+            3,
+            400,
+            5,
+            Status::Pending,
+            "a, b".into(),
+        );
+        let values = record.to_field_values();
+        let mut names = values.iter().map(FieldValue::name).collect::<Vec<_>>();
+        names.sort();
+        let mut all = FieldName::ALL.map(|name| name.as_str()).to_vec();
+        all.sort();
+        assert_eq!(names, all);
+        let strings = values
+            .iter()
+            .map(|value| (value.name(), value.as_string()))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(strings, record.to_map());
+    }
+
+    #[test]
+    fn test_record_order() {
+        let record = |tx_id, timestamp, amount| {
+            Record::new(
+                tx_id,
+                TxType::Deposit,
+                0,
+                1,
+                amount,
+                timestamp,
+                Status::Success,
+                "fee".into(),
+            )
+        };
+        let sorted = vec![
+            record(1, 5, 9),
+            record(1, 6, 1),
+            record(2, 1, 3),
+            record(2, 1, 4),
+            record(3, 0, 0),
+        ];
+        let mut shuffled = vec![
+            sorted[3].clone(),
+            sorted[0].clone(),
+            sorted[4].clone(),
+            sorted[2].clone(),
+            sorted[1].clone(),
+        ];
+        shuffled.sort();
+        assert_eq!(shuffled, sorted);
+
+        let hash = |record: &Record| {
+            let mut hasher = std::hash::DefaultHasher::new();
+            std::hash::Hash::hash(record, &mut hasher);
+            std::hash::Hasher::finish(&hasher)
+        };
+        assert_eq!(hash(&record(1, 5, 9)), hash(&sorted[0].clone()));
+        let unique = shuffled
+            .into_iter()
+            .chain(sorted.clone())
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(unique.len(), sorted.len());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let record = get_record();
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            json,
+            r#"{"TX_ID":1,"TX_TYPE":"TRANSFER","FROM_USER_ID":2,"TO_USER_ID":3,"AMOUNT":100,"TIMESTAMP":1633036860000,"STATUS":"PENDING","DESCRIPTION":"Record number 1"}"#
+        );
+        assert_eq!(serde_json::from_str::<Record>(&json).unwrap(), record);
+        let mut reader =
+            crate::builder::build_reader(std::io::Cursor::new(json), "ndjson").unwrap();
+        assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+    }
+
+    #[test]
+    fn test_field_values_round_trip() {
+        let record = get_record();
+        let values = record.to_field_values();
+        let names = values.iter().map(FieldValue::name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                FieldName::TxId.as_str(),
+                FieldName::TxType.as_str(),
+                FieldName::Status.as_str(),
+                FieldName::FromUser.as_str(),
+                FieldName::ToUser.as_str(),
+                FieldName::Timestamp.as_str(),
+                FieldName::Amount.as_str(),
+                FieldName::Description.as_str(),
+            ]
+        );
+        assert_eq!(values[0].as_u64(), Some(1));
+        assert_eq!(values[1].as_tx_type(), Some(TxType::Transfer));
+        assert_eq!(values[2].as_status(), Some(Status::Pending));
+        assert_eq!(values[7].as_str(), Some("Record number 1"));
+        assert_eq!(values[7].as_u64(), None);
+        assert_eq!(record.clone().into_field_values(), values);
+        assert_eq!(Record::try_from(values).unwrap(), record);
+    }
+
+    #[test]
+    fn test_sql_statements() {
+        let mut record = get_record();
+        record.set_description("O'Brien's \"refund\"");
+        assert_eq!(
+            record.to_sql_insert("tx \"log\""),
+            "INSERT INTO \"tx \"\"log\"\"\" \
+            (TX_ID, TX_TYPE, FROM_USER_ID, TO_USER_ID, AMOUNT, TIMESTAMP, STATUS, DESCRIPTION) \
+            VALUES (1, 'TRANSFER', 2, 3, 100, 1633036860000, 'PENDING', 'O''Brien''s \"refund\"');"
+        );
+        let ddl = Record::to_sql_create_table("records");
+        assert!(ddl.starts_with("CREATE TABLE \"records\" (\n"));
+        assert!(ddl.contains("    TX_ID BIGINT NOT NULL,\n"));
+        assert!(ddl.contains("    STATUS VARCHAR(32) NOT NULL,\n"));
+        assert!(ddl.ends_with("    DESCRIPTION TEXT NOT NULL\n);"));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sql_statements_run_in_sqlite() {
+        let mut record = get_record();
+        record.set_description("it's");
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(&Record::to_sql_create_table("records"))
+            .unwrap();
+        connection
+            .execute_batch(&record.to_sql_insert("records"))
+            .unwrap();
+        let description: String = connection
+            .query_row("SELECT DESCRIPTION FROM records", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(description, "it's");
+    }
+
+    #[test]
+    fn test_set_fields() {
+        let serializer = crate::bin::RecordBytes::default();
+        let values = [
+            FieldValue::TxId(9),
+            FieldValue::TxType(TxType::Deposit),
+            FieldValue::Status(Status::Failure),
+            FieldValue::FromUser(20),
+            FieldValue::ToUser(30),
+            FieldValue::Timestamp(40),
+            FieldValue::Amount(50),
+            FieldValue::Description("patched".into()),
+        ];
+        for value in values {
+            let mut record = get_record();
+            let before = serializer.serialize(&record).unwrap();
+            let previous = record.set(value.clone());
+            assert_eq!(previous.name(), value.name());
+            assert!(get_record().to_field_values().contains(&previous));
+            assert!(record.to_field_values().contains(&value));
+            let after = serializer.serialize(&record).unwrap();
+            assert_ne!(before, after, "{}", value.name());
+            let mut reader = crate::bin::BinReader::new(std::io::Cursor::new(after)).unwrap();
+            assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+        }
+
+        let mut record = get_record();
+        assert_eq!(record.set_status(Status::Success), Status::Pending);
+        assert_eq!(record.set_description("new"), "Record number 1");
+        assert_eq!(record.set_amount(7), 100);
+        assert_eq!(
+            (record.status(), record.description(), record.amount()),
+            (Status::Success, "new", 7)
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        let records = (0..20)
+            .map(|i| {
+                Record::new(
+                    i,
+                    [TxType::Deposit, TxType::Transfer][i as usize % 2],
+                    0,
+                    1,
+                    i * 10,
+                    1000 + i,
+                    [Status::Success, Status::Failure, Status::Pending][i as usize % 3],
+                    "fee".into(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let serializer = crate::bin::RecordBytes::default();
+        let input = records
+            .iter()
+            .flat_map(|r| serializer.serialize(r).unwrap())
+            .collect::<Vec<_>>();
+        let kept = |filter: Box<dyn RecordFilter>| {
+            crate::builder::build_reader(std::io::Cursor::new(input.clone()), "bin")
+                .unwrap()
+                .filter(filter)
+                .into_iter()
+                .map(|r| r.unwrap().tx_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kept(Box::new(ByStatus(Status::Pending))),
+            [2, 5, 8, 11, 14, 17]
+        );
+        assert_eq!(kept(Box::new(ByTxType(TxType::Transfer))).len(), 10);
+        assert_eq!(
+            kept(Box::new(ByAmountRange { min: 30, max: 50 })),
+            [3, 4, 5]
+        );
+        assert_eq!(
+            kept(Box::new(ByTimestampRange {
+                start: 1018,
+                end: 2000
+            })),
+            [18, 19]
+        );
+        assert_eq!(
+            kept(Box::new(AndFilter(
+                Box::new(ByTxType(TxType::Deposit)),
+                Box::new(ByStatus(Status::Success))
+            ))),
+            [0, 6, 12, 18]
+        );
+
+        let mut failing = Filtered::new(
+            crate::builder::build_reader(std::io::Cursor::new("TX_ID,AMOUNT\nx,1\n"), "csv")
+                .unwrap(),
+            ByStatus(Status::Success),
+        );
+        assert!(failing.produce_record().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let record = get_record();
+        assert!(record.diff(&record.clone()).is_empty());
+
+        let mut changed = get_record();
+        changed.set_status(Status::Success);
+        assert_eq!(
+            record.diff(&changed),
+            [FieldDiff {
+                name: FieldName::Status.as_str(),
+                left: "PENDING".into(),
+                right: "SUCCESS".into(),
+            }]
+        );
+
+        let other = Record::new(
+            2,
+            TxType::Deposit,
+            0,
+            4,
+            5,
+            6,
+            Status::Failure,
+            "other".into(),
+        );
+        let names = record
+            .diff(&other)
+            .iter()
+            .map(|d| d.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            FieldName::ALL
+                .iter()
+                .map(|f| f.as_str())
+                .collect::<Vec<_>>()
+        );
+        let diff = record.diff_ignoring(
+            &other,
+            &[FieldName::Description.as_str(), FieldName::TxId.as_str()],
+        );
+        assert_eq!(diff.len(), 6);
+        assert!(
+            diff.iter()
+                .all(|d| d.name != FieldName::Description.as_str())
+        );
+        changed.set_description("fixed");
+        assert_eq!(
+            record
+                .diff_ignoring(&changed, &[FieldName::Description.as_str()])
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_record_diff() {
+        let record = get_record();
+        assert!(RecordDiff::compute(&record, &record).is_empty());
+        let mut changed = record.clone();
+        changed.set_amount(150);
+        changed.status = Status::Failure;
+        let diff = RecordDiff::compute(&record, &changed);
+        assert_eq!(
+            diff.fields
+                .iter()
+                .map(|field| (field.name, field.left.as_str(), field.right.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (FieldName::Amount.as_str(), "100", "150"),
+                (FieldName::Status.as_str(), "PENDING", "FAILURE")
+            ]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "AMOUNT: 100 != 150, STATUS: PENDING != FAILURE"
+        );
+    }
+
+    #[test]
+    fn test_record_builder() {
+        let builder = RecordBuilder::new()
+            .tx_id(1)
+            .tx_type(TxType::Transfer)
+            .from_user(2)
+            .to_user(3)
+            .amount(100)
+            .timestamp(1633036860000)
+            .status(Status::Pending);
+        assert_eq!(builder.clone().build().unwrap().description(), "");
+        assert_eq!(
+            RecordBuilder::new().tx_id(1).build().unwrap_err(),
+            "missing field tx_type"
+        );
+        let record = builder.description("Record number 1").build().unwrap();
+        assert_eq!(record, get_record());
+
+        // the last value of a field is kept
+        let record = RecordBuilder::new()
+            .field(FieldValue::Amount(1))
+            .amount(2)
+            .tx_id(1)
+            .tx_type(TxType::Deposit)
+            .from_user(0)
+            .to_user(1)
+            .timestamp(0)
+            .status(Status::Success)
+            .description("")
+            .build()
+            .unwrap();
+        assert_eq!(record.amount(), 2);
+
+        let mut record = get_record();
+        assert_eq!(record.set_tx_id(5), 1);
+        assert_eq!(record.set_tx_type(TxType::Deposit), TxType::Transfer);
+        assert_eq!(record.set_from_user(0), 2);
+        assert_eq!(record.set_to_user(4), 3);
+        assert_eq!(record.set_timestamp(6), 1633036860000);
+        assert_eq!(
+            (
+                record.tx_id(),
+                record.tx_type(),
+                record.from_user(),
+                record.to_user(),
+                record.timestamp()
+            ),
+            (5, TxType::Deposit, 0, 4, 6)
+        );
+    }
+
+    /// Writer collecting the written data, failing when it is full
+    struct Limited {
+        written: Vec<Vec<u8>>,
+        capacity: usize,
+    }
+
+    impl RecordWriter for Limited {
+        fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+            if self.written.len() == self.capacity {
+                return Err(crate::error::RecordWriteError {
+                    text: "writer is full".into(),
+                    source: None,
+                });
+            }
+            self.written.push(data);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_tee_writer() {
+        let limited = |capacity| Limited {
+            written: Vec::new(),
+            capacity,
+        };
+        let mut tee = TeeWriter::new(limited(1), limited(3));
+        tee.write_header().unwrap();
+        tee.write(b"a".to_vec()).unwrap();
+        let err = tee.write(b"b".to_vec()).unwrap_err();
+        assert_eq!(err.to_string(), "write error: writer is full");
+        tee.finish().unwrap();
+        let (first, second) = tee.into_inner();
+        assert_eq!(first.written, [b"a"]);
+        assert_eq!(second.written, [b"a", b"b"]);
+    }
+
+    #[test]
+    fn test_chained_producer() {
+        let open = |format: &str| {
+            let path = format!(
+                "{}/examples/source/records_example.{}",
+                env!("CARGO_MANIFEST_DIR"),
+                format
+            );
+            crate::builder::build_reader(std::fs::File::open(path).unwrap(), format).unwrap()
+        };
+        let read = |producer: Box<dyn DataProducer>| {
+            producer.into_iter().collect::<Result<Vec<_>, _>>().unwrap()
+        };
+        let mut expected = read(open("csv"));
+        let txt = read(open("txt"));
+        assert!(!txt.is_empty());
+        expected.extend(txt);
+
+        let chained = open("csv").chain(open("txt"));
+        let records = chained.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records, expected);
+
+        let mut empty = ChainedProducer::new(Vec::new()).chain(open("csv"));
+        assert_eq!(empty.produce_record().unwrap().unwrap(), expected[0]);
+    }
+
+    #[test]
+    fn test_vec_producer_csv_round_trip() {
+        use crate::builder::DEFAULT_CSV_FIELDS;
+        use crate::csv::{CsvSerialize, RecordWrite as CsvWrite};
+        use crate::options::SerializeOptions;
+
+        let records = (1..=3)
+            .map(|i| Record {
+                tx_id: i,
+                description: format!("Record number {}, \"quoted\"", i),
+                ..get_record()
+            })
+            .collect::<Vec<_>>();
+        let fields = DEFAULT_CSV_FIELDS
+            .iter()
+            .map(|&f| std::borrow::Cow::Borrowed(f))
+            .collect::<Vec<_>>();
+        let serializer =
+            CsvSerialize::with_options(fields.clone(), ",", &SerializeOptions::default());
+        let mut output = Vec::new();
+        let mut writer = CsvWrite::new(&mut output, fields, b',');
+        writer.write_header().unwrap();
+        for record in VecProducer::from_vec(records.clone()).into_iter() {
+            let record = record.unwrap();
+            writer
+                .write_record(&record, serializer.serialize(&record).unwrap())
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let read = crate::builder::build_reader(std::io::Cursor::new(output), "csv")
+            .unwrap()
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read, records);
+        assert!(VecProducer::from_vec(Vec::new()).produce_record().is_none());
+    }
+
+    #[test]
+    fn test_take_and_skip() {
+        for format in ["csv", "txt", "bin"] {
+            let open = || {
+                let path = format!(
+                    "{}/examples/source/records_example.{}",
+                    env!("CARGO_MANIFEST_DIR"),
+                    format
+                );
+                crate::builder::build_reader(std::fs::File::open(path).unwrap(), format).unwrap()
+            };
+            let expected = open().into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+            let window = open()
+                .skip_records(1)
+                .take_records(2)
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(window, expected[1..3], "{}", format);
+            let mut past_end = open().skip_records(expected.len()).take_records(2);
+            assert!(past_end.produce_record().is_none(), "{}", format);
+            assert!(open().take_records(0).produce_record().is_none());
+        }
+    }
+
+    #[test]
+    fn test_sample_every() {
+        let serializer = crate::bin::RecordBytes::default();
+        let input = (0..10)
+            .flat_map(|i| {
+                serializer
+                    .serialize(&Record {
+                        tx_id: i,
+                        ..Record::default()
+                    })
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let sample = |step| {
+            let reader = crate::bin::BinReader::new(std::io::Cursor::new(input.clone())).unwrap();
+            let mut sampler = reader.sample_every(NonZeroUsize::new(step).unwrap());
+            let mut ids = Vec::new();
+            while let Some(record) = sampler.produce_record() {
+                ids.push(record.unwrap().tx_id);
+            }
+            assert!(sampler.produce_record().is_none());
+            ids
+        };
+        assert_eq!(sample(3), [0, 3, 6, 9]);
+        assert_eq!(sample(4), [0, 4, 8]);
+        assert_eq!(sample(1), (0..10).collect::<Vec<_>>());
+        assert_eq!(sample(20), [0]);
+    }
+
+    #[test]
+    fn test_record_iterator() {
+        for format in ["csv", "txt", "bin"] {
+            let path = format!(
+                "{}/examples/source/records_example.{}",
+                env!("CARGO_MANIFEST_DIR"),
+                format
+            );
+            let open = || {
+                crate::builder::build_reader(std::fs::File::open(&path).unwrap(), format).unwrap()
+            };
+            let all = open().into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+            assert!(all.len() > 3);
+            let deposits = open()
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter(|r| r.tx_type == TxType::Deposit)
+                .map(|r| r.tx_id)
+                .take(2)
+                .collect::<Vec<_>>();
+            let expected = all
+                .iter()
+                .filter(|r| r.tx_type == TxType::Deposit)
+                .map(|r| r.tx_id)
+                .take(2)
+                .collect::<Vec<_>>();
+            assert_eq!(deposits, expected, "{}", format);
+            let (index, last) = open().into_iter().enumerate().last().unwrap();
+            assert_eq!(index, all.len() - 1);
+            assert_eq!(last.unwrap(), all[index]);
+        }
+        let mut failing = RecordIterator::new(
+            crate::builder::build_reader(std::io::Cursor::new("TX_ID,AMOUNT\nx,1\n"), "csv")
+                .unwrap(),
+        );
+        assert!(failing.next().unwrap().is_err());
+        assert!(failing.next().is_none());
+        assert!(failing.next().is_none());
+    }
 }
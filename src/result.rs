@@ -11,4 +11,5 @@ pub(crate) type FieldParseResult<T> = Result<T, FieldParseError>;
 pub(crate) type RecordParseResult<T> = Result<T, RecordParseError>;
 pub(crate) type RecordReadResult<T> = Result<T, RecordReadError>;
 pub(crate) type ReaderCreateResult<T> = Result<T, ReaderCreateError>;
-pub(crate) type RecordWriteResult<T> = Result<T, RecordWriteError>;
+/// Result of writing data
+pub type RecordWriteResult<T> = Result<T, RecordWriteError>;
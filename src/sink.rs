@@ -0,0 +1,289 @@
+use std::error::Error;
+
+use crate::builder::build_serializer_with_options;
+use crate::error::RecordWriteError;
+use crate::options::SerializeOptions;
+use crate::record::{Record, RecordSerialize, RecordWriter};
+use crate::result::RecordWriteResult;
+
+/// What a [`TeeSink`] does when one of its outputs fails
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SinkFailure {
+    /// The failure is returned right away
+    #[default]
+    Abort,
+    /// The failed output is dropped and the rest keep receiving records.
+    /// The failure is returned only when no outputs are left
+    Continue,
+}
+
+struct Sink {
+    /// Index of the format, none for the outputs taking records only
+    format: Option<usize>,
+    writer: Box<dyn RecordWriter>,
+    failure: Option<String>,
+}
+
+/// Writes every record to several outputs of possibly different formats.
+/// A record is serialized once per format no matter how many outputs share it
+///
+/// ```
+/// use parserde::{build_writer, SerializeOptions, SinkFailure, TeeSink};
+/// let mut tee = TeeSink::new(&SerializeOptions::default(), SinkFailure::Abort);
+/// tee.add("csv", build_writer(Vec::new(), "csv").unwrap()).unwrap();
+/// tee.add("bin", build_writer(Vec::new(), "bin").unwrap()).unwrap();
+/// tee.write_header().unwrap();
+/// tee.write(&parserde::Record::default()).unwrap();
+/// tee.finish().unwrap();
+/// ```
+///
+pub struct TeeSink {
+    options: SerializeOptions,
+    on_failure: SinkFailure,
+    formats: Vec<(String, Box<dyn RecordSerialize>)>,
+    sinks: Vec<Sink>,
+}
+
+impl TeeSink {
+    /// Creates a tee without outputs
+    pub fn new(options: &SerializeOptions, on_failure: SinkFailure) -> TeeSink {
+        TeeSink {
+            options: options.clone(),
+            on_failure,
+            formats: Vec::new(),
+            sinks: Vec::new(),
+        }
+    }
+
+    /// Adds an output receiving records serialized in the format
+    pub fn add(
+        &mut self,
+        format: &str,
+        writer: Box<dyn RecordWriter>,
+    ) -> Result<(), Box<dyn Error>> {
+        let format = match self.formats.iter().position(|(f, _)| f == format) {
+            Some(index) => index,
+            None => {
+                let serializer = build_serializer_with_options(format, &self.options)?;
+                self.formats.push((format.to_string(), serializer));
+                self.formats.len() - 1
+            }
+        };
+        self.sinks.push(Sink {
+            format: Some(format),
+            writer,
+            failure: None,
+        });
+        Ok(())
+    }
+
+    /// Adds an output writing records through [`RecordWriter::write_record`]
+    /// without serialized data, like a database table
+    pub fn add_record_writer(&mut self, writer: Box<dyn RecordWriter>) {
+        self.sinks.push(Sink {
+            format: None,
+            writer,
+            failure: None,
+        });
+    }
+
+    /// Failures of the outputs dropped under [`SinkFailure::Continue`]
+    /// as the output index and the error text
+    pub fn failures(&self) -> Vec<(usize, &str)> {
+        self.sinks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, sink)| sink.failure.as_deref().map(|f| (i, f)))
+            .collect()
+    }
+
+    /// Writes the header of every output
+    pub fn write_header(&mut self) -> RecordWriteResult<()> {
+        self.for_each_sink(|_, writer| writer.write_header())
+    }
+
+    /// Writes a comment line to every output
+    pub fn write_comment(&mut self, text: &str) -> RecordWriteResult<()> {
+        self.for_each_sink(|_, writer| writer.write_comment(text))
+    }
+
+    /// Serializes the record once per format and writes it to every output
+    pub fn write(&mut self, record: &Record) -> RecordWriteResult<()> {
+        let mut serialized = Vec::with_capacity(self.formats.len());
+        for (format, serializer) in &self.formats {
+            let uses_format = self.sinks.iter().any(|s| {
+                s.failure.is_none() && s.format.is_some_and(|f| self.formats[f].0 == *format)
+            });
+            if !uses_format {
+                serialized.push(None);
+                continue;
+            }
+            let bytes = serializer.serialize(record).map_err(|e| RecordWriteError {
+                text: format!("failed to serialize record to {}", format),
+                source: Some(Box::new(e)),
+            })?;
+            serialized.push(Some(bytes));
+        }
+        self.for_each_sink(|format, writer| match format {
+            Some(format) => match &serialized[format] {
+                Some(bytes) => writer.write_record(record, bytes.clone()),
+                None => Ok(()),
+            },
+            None => writer.write_record(record, Vec::new()),
+        })
+    }
+
+    /// Finishes every output
+    pub fn finish(&mut self) -> RecordWriteResult<()> {
+        self.for_each_sink(|_, writer| writer.finish())
+    }
+
+    fn for_each_sink<F>(&mut self, mut f: F) -> RecordWriteResult<()>
+    where
+        F: FnMut(Option<usize>, &mut dyn RecordWriter) -> RecordWriteResult<()>,
+    {
+        for (i, sink) in self.sinks.iter_mut().enumerate() {
+            if sink.failure.is_some() {
+                continue;
+            }
+            if let Err(e) = f(sink.format, sink.writer.as_mut()) {
+                match self.on_failure {
+                    SinkFailure::Abort => {
+                        return Err(RecordWriteError {
+                            text: format!("output {} failed", i + 1),
+                            source: Some(Box::new(e)),
+                        });
+                    }
+                    SinkFailure::Continue => sink.failure = Some(e.to_string()),
+                }
+            }
+        }
+        if !self.sinks.is_empty() && self.sinks.iter().all(|s| s.failure.is_some()) {
+            return Err(RecordWriteError {
+                text: "all outputs failed".into(),
+                source: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::builder::{build_serializer, build_writer};
+    use crate::record::{Status, TxType};
+
+    struct SharedWriter {
+        out: Rc<RefCell<Vec<u8>>>,
+        fail: bool,
+    }
+
+    impl RecordWriter for SharedWriter {
+        fn write(&mut self, data: Vec<u8>) -> RecordWriteResult<()> {
+            if self.fail {
+                return Err(RecordWriteError {
+                    text: "disk full".into(),
+                    source: None,
+                });
+            }
+            self.out.borrow_mut().extend(data);
+            Ok(())
+        }
+    }
+
+    fn shared(fail: bool) -> (Rc<RefCell<Vec<u8>>>, Box<dyn RecordWriter>) {
+        let out = Rc::new(RefCell::new(Vec::new()));
+        let writer = SharedWriter {
+            out: out.clone(),
+            fail,
+        };
+        (out, Box::new(writer))
+    }
+
+    fn record() -> Record {
+        Record::new(
+            1,
+            TxType::Deposit,
+            0,
+            2,
+            100,
+            5,
+            Status::Success,
+            "fee".into(),
+        )
+    }
+
+    #[test]
+    fn test_outputs_of_different_formats() {
+        let mut tee = TeeSink::new(&SerializeOptions::default(), SinkFailure::Abort);
+        let (bin1, w) = shared(false);
+        tee.add("bin", w).unwrap();
+        let (txt, w) = shared(false);
+        tee.add("txt", w).unwrap();
+        let (bin2, w) = shared(false);
+        tee.add("bin", w).unwrap();
+        assert_eq!(tee.formats.len(), 2);
+        tee.write(&record()).unwrap();
+
+        let expected = build_serializer("bin")
+            .unwrap()
+            .serialize(&record())
+            .unwrap();
+        assert_eq!(*bin1.borrow(), expected);
+        assert_eq!(*bin2.borrow(), expected);
+        assert!(txt.borrow().starts_with(b"TX_ID: 1\n"));
+    }
+
+    #[test]
+    fn test_failure_policy() {
+        let mut tee = TeeSink::new(&SerializeOptions::default(), SinkFailure::Abort);
+        tee.add("bin", shared(true).1).unwrap();
+        tee.add("csv", build_writer(Vec::new(), "csv").unwrap())
+            .unwrap();
+        assert!(tee.write(&record()).is_err());
+
+        let mut tee = TeeSink::new(&SerializeOptions::default(), SinkFailure::Continue);
+        tee.add("bin", shared(true).1).unwrap();
+        let (out, w) = shared(false);
+        tee.add("bin", w).unwrap();
+        tee.write(&record()).unwrap();
+        tee.write(&record()).unwrap();
+        assert_eq!(tee.failures(), vec![(0, "write error: disk full")]);
+        let expected = build_serializer("bin")
+            .unwrap()
+            .serialize(&record())
+            .unwrap();
+        assert_eq!(*out.borrow(), [expected.clone(), expected].concat());
+
+        let mut tee = TeeSink::new(&SerializeOptions::default(), SinkFailure::Continue);
+        tee.add("bin", shared(true).1).unwrap();
+        assert!(tee.write(&record()).is_err());
+    }
+
+    #[test]
+    fn test_record_writer() {
+        struct Records(Rc<RefCell<Vec<Record>>>);
+
+        impl RecordWriter for Records {
+            fn write(&mut self, _data: Vec<u8>) -> RecordWriteResult<()> {
+                unreachable!()
+            }
+
+            fn write_record(&mut self, record: &Record, data: Vec<u8>) -> RecordWriteResult<()> {
+                assert!(data.is_empty());
+                self.0.borrow_mut().push(record.clone());
+                Ok(())
+            }
+        }
+
+        let mut tee = TeeSink::new(&SerializeOptions::default(), SinkFailure::Abort);
+        let records = Rc::new(RefCell::new(Vec::new()));
+        tee.add_record_writer(Box::new(Records(records.clone())));
+        assert!(tee.formats.is_empty());
+        tee.write(&record()).unwrap();
+        assert_eq!(*records.borrow(), vec![record()]);
+    }
+}
@@ -0,0 +1,172 @@
+use std::{cmp::Reverse, str::FromStr};
+
+use crate::record::{FieldName, Record};
+
+/// Direction of a sort key component
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    /// Ascending
+    #[default]
+    Asc,
+    /// Descending
+    Desc,
+}
+
+/// Value of a single key component
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyValue {
+    /// Numeric fields
+    Number(u64),
+    /// Enum and text fields
+    Text(String),
+}
+
+/// A key component with the direction applied
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyPart {
+    /// Ascending component
+    Asc(KeyValue),
+    /// Descending component
+    Desc(Reverse<KeyValue>),
+}
+
+/// Orderable key extracted from a record by [`SortKey::extract`]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SortKeyValue(pub Vec<KeyPart>);
+
+/// Extracts a comparable key from a record.
+/// Shared by everything that orders records by some of its fields
+///
+/// ```
+/// use parserde::SortKey;
+/// let key: SortKey = "TIMESTAMP:desc,TX_ID".parse().unwrap();
+/// assert_eq!(key.components().len(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    components: Vec<(FieldName, SortDirection)>,
+}
+
+impl SortKey {
+    /// Creates a key from the fields in order of significance.
+    /// Fails if there are no fields or a field is repeated
+    pub fn new(components: Vec<(FieldName, SortDirection)>) -> Result<SortKey, String> {
+        if components.is_empty() {
+            return Err("sort key has no fields".into());
+        }
+        for (i, (name, _)) in components.iter().enumerate() {
+            if components[..i].iter().any(|(n, _)| n == name) {
+                return Err(format!("field {} is repeated in sort key", name.as_str()));
+            }
+        }
+        Ok(SortKey { components })
+    }
+
+    /// Fields of the key with their directions
+    pub fn components(&self) -> &[(FieldName, SortDirection)] {
+        &self.components
+    }
+
+    /// Extracts the key of the record
+    pub fn extract(&self, record: &Record) -> SortKeyValue {
+        SortKeyValue(
+            self.components
+                .iter()
+                .map(|(name, direction)| {
+                    let value = match name {
+                        FieldName::TxId => KeyValue::Number(record.tx_id),
+                        FieldName::FromUser => KeyValue::Number(record.from_user),
+                        FieldName::ToUser => KeyValue::Number(record.to_user),
+                        FieldName::Amount => KeyValue::Number(record.amount),
+                        FieldName::Timestamp => KeyValue::Number(record.timestamp),
+                        FieldName::TxType => KeyValue::Text(record.tx_type.to_string()),
+                        FieldName::Status => KeyValue::Text(record.status.to_string()),
+                        FieldName::Description => KeyValue::Text(record.description.clone()),
+                    };
+                    match direction {
+                        SortDirection::Asc => KeyPart::Asc(value),
+                        SortDirection::Desc => KeyPart::Desc(Reverse(value)),
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Sorts the records by the key. The sort is stable
+    pub fn sort(&self, records: &mut [Record]) {
+        records.sort_by_cached_key(|r| self.extract(r));
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+    /// Parses comma separated `FIELD[:asc|:desc]` components, e.g. `TIMESTAMP:desc,TX_ID`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let components = s
+            .split(',')
+            .map(|component| {
+                let (name, direction) = match component.trim().split_once(':') {
+                    Some((name, "asc")) => (name, SortDirection::Asc),
+                    Some((name, "desc")) => (name, SortDirection::Desc),
+                    Some((_, direction)) => {
+                        return Err(format!("unknown sort direction {}", direction));
+                    }
+                    None => (component.trim(), SortDirection::Asc),
+                };
+                Ok((name.parse::<FieldName>()?, direction))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        SortKey::new(components)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::{Status, TxType};
+
+    fn record(tx_id: u64, timestamp: u64, tx_type: TxType) -> Record {
+        Record::new(
+            tx_id,
+            tx_type,
+            0,
+            1,
+            10,
+            timestamp,
+            Status::Success,
+            "".into(),
+        )
+    }
+
+    #[test]
+    fn test_multi_component_order() {
+        let mut records = vec![
+            record(3, 100, TxType::Deposit),
+            record(1, 200, TxType::Transfer),
+            record(2, 100, TxType::Withdrawal),
+            record(4, 200, TxType::Deposit),
+        ];
+        let key: SortKey = "TIMESTAMP:desc,TX_ID".parse().unwrap();
+        key.sort(&mut records);
+        let ids = records.iter().map(|r| r.tx_id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 4, 2, 3]);
+
+        let key: SortKey = "TX_TYPE:asc,TX_ID:desc".parse().unwrap();
+        key.sort(&mut records);
+        let ids = records.iter().map(|r| r.tx_id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![4, 3, 1, 2]);
+    }
+
+    #[test]
+    fn test_from_str_errors() {
+        assert!("".parse::<SortKey>().is_err());
+        assert!("TX_ID,".parse::<SortKey>().is_err());
+        assert!("FOO".parse::<SortKey>().is_err());
+        assert!("TX_ID:down".parse::<SortKey>().is_err());
+        assert!("TX_ID,TX_ID:desc".parse::<SortKey>().is_err());
+        assert_eq!(
+            "AMOUNT:desc".parse::<SortKey>().unwrap().components(),
+            &[(FieldName::Amount, SortDirection::Desc)]
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::error::RecordWriteError;
+use crate::record::{Record, RecordFilter, RecordSerialize, RecordWriter};
+use crate::result::RecordWriteResult;
+
+/// Output of a [`RecordSplitter`] with the filter routing records to it
+pub type SplitRoute = (
+    Box<dyn RecordFilter>,
+    Box<dyn RecordWriter>,
+    Box<dyn RecordSerialize>,
+);
+
+/// Writes every record to the first output whose filter accepts it,
+/// or to the default output when none does
+///
+/// ```
+/// use parserde::{build_serializer, build_writer, ByStatus, Record, RecordSplitter};
+/// let mut splitter = RecordSplitter::new(
+///     vec![(
+///         Box::new(ByStatus(parserde::Status::Success)),
+///         build_writer(Vec::new(), "csv").unwrap(),
+///         build_serializer("csv").unwrap(),
+///     )],
+///     build_writer(Vec::new(), "bin").unwrap(),
+///     build_serializer("bin").unwrap(),
+/// );
+/// splitter.write_header().unwrap();
+/// splitter.write_record(&Record::default()).unwrap();
+/// splitter.finish().unwrap();
+/// ```
+pub struct RecordSplitter {
+    routes: Vec<SplitRoute>,
+    default: (Box<dyn RecordWriter>, Box<dyn RecordSerialize>),
+}
+
+impl RecordSplitter {
+    /// Creates a splitter trying the routes in order
+    pub fn new(
+        routes: Vec<SplitRoute>,
+        default_writer: Box<dyn RecordWriter>,
+        default_serializer: Box<dyn RecordSerialize>,
+    ) -> RecordSplitter {
+        RecordSplitter {
+            routes,
+            default: (default_writer, default_serializer),
+        }
+    }
+
+    /// Writes the header of every output
+    pub fn write_header(&mut self) -> RecordWriteResult<()> {
+        self.for_each_writer(|writer| writer.write_header())
+    }
+
+    /// Serializes the record for the output it is routed to and writes it there
+    pub fn write_record(&mut self, record: &Record) -> RecordWriteResult<()> {
+        let (writer, serializer) = match self
+            .routes
+            .iter_mut()
+            .find(|(filter, _, _)| filter.accept(record))
+        {
+            Some((_, writer, serializer)) => (writer, serializer),
+            None => (&mut self.default.0, &mut self.default.1),
+        };
+        let data = serializer.serialize(record).map_err(|e| RecordWriteError {
+            text: format!("failed to serialize record {}", record.tx_id),
+            source: Some(Box::new(e)),
+        })?;
+        writer.write_record(record, data)
+    }
+
+    /// Finishes every output
+    pub fn finish(&mut self) -> RecordWriteResult<()> {
+        self.for_each_writer(|writer| writer.finish())
+    }
+
+    fn for_each_writer<F>(&mut self, mut f: F) -> RecordWriteResult<()>
+    where
+        F: FnMut(&mut dyn RecordWriter) -> RecordWriteResult<()>,
+    {
+        for (_, writer, _) in &mut self.routes {
+            f(writer.as_mut())?;
+        }
+        f(self.default.0.as_mut())
+    }
+}
@@ -0,0 +1,270 @@
+use thiserror::Error;
+
+use crate::record::{Record, TxType};
+
+/// Semantic rules a record is checked against by [`Record::validate`].
+/// The default rules allow every transaction type and timestamp,
+/// reject zero amounts and self transfers and expect the system user 0
+/// on the outer side of deposits and withdrawals
+///
+/// ```
+/// use parserde::{Record, ValidationRules};
+/// let rules = ValidationRules {
+///     max_amount: Some(1_000),
+///     ..Default::default()
+/// };
+/// assert_eq!(Record::default().validate(&rules).unwrap_err().len(), 1);
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationRules {
+    /// Transaction types a record may have
+    pub allowed_tx_types: Vec<TxType>,
+    /// Least allowed AMOUNT
+    pub min_amount: u64,
+    /// Greatest allowed AMOUNT
+    pub max_amount: Option<u64>,
+    /// Earliest allowed TIMESTAMP
+    pub min_timestamp: Option<u64>,
+    /// Latest allowed TIMESTAMP
+    pub max_timestamp: Option<u64>,
+    /// Allow transfers whose sender is also the receiver
+    pub allow_self_transfer: bool,
+    /// Require deposits to come from user 0 and withdrawals to go to user 0
+    pub require_system_user: bool,
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        ValidationRules {
+            allowed_tx_types: vec![TxType::Deposit, TxType::Transfer, TxType::Withdrawal],
+            min_amount: 1,
+            max_amount: None,
+            min_timestamp: None,
+            max_timestamp: None,
+            allow_self_transfer: false,
+            require_system_user: true,
+        }
+    }
+}
+
+/// A rule of [`ValidationRules`] a record violates
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// TX_TYPE is not among the allowed ones
+    #[error("transaction type {0} is not allowed")]
+    TxTypeNotAllowed(TxType),
+    /// AMOUNT is below the minimum
+    #[error("amount {amount} is less than {min}")]
+    AmountTooSmall {
+        /// AMOUNT of the record
+        amount: u64,
+        /// Least allowed amount
+        min: u64,
+    },
+    /// AMOUNT is above the maximum
+    #[error("amount {amount} is greater than {max}")]
+    AmountTooLarge {
+        /// AMOUNT of the record
+        amount: u64,
+        /// Greatest allowed amount
+        max: u64,
+    },
+    /// TIMESTAMP is before the earliest allowed one
+    #[error("timestamp {timestamp} is before {min}")]
+    TimestampTooEarly {
+        /// TIMESTAMP of the record
+        timestamp: u64,
+        /// Earliest allowed timestamp
+        min: u64,
+    },
+    /// TIMESTAMP is after the latest allowed one
+    #[error("timestamp {timestamp} is after {max}")]
+    TimestampTooLate {
+        /// TIMESTAMP of the record
+        timestamp: u64,
+        /// Latest allowed timestamp
+        max: u64,
+    },
+    /// A transfer to the sender
+    #[error("user {0} transfers to itself")]
+    SelfTransfer(u64),
+    /// A deposit coming from another user than 0
+    #[error("deposit comes from user {0} instead of 0")]
+    DepositFromUser(u64),
+    /// A withdrawal going to another user than 0
+    #[error("withdrawal goes to user {0} instead of 0")]
+    WithdrawalToUser(u64),
+}
+
+impl Record {
+    /// Checks the record against the rules collecting every violation
+    pub fn validate(&self, rules: &ValidationRules) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        if !rules.allowed_tx_types.contains(&self.tx_type) {
+            errors.push(ValidationError::TxTypeNotAllowed(self.tx_type));
+        }
+        if self.amount < rules.min_amount {
+            errors.push(ValidationError::AmountTooSmall {
+                amount: self.amount,
+                min: rules.min_amount,
+            });
+        }
+        if let Some(max) = rules.max_amount
+            && self.amount > max
+        {
+            errors.push(ValidationError::AmountTooLarge {
+                amount: self.amount,
+                max,
+            });
+        }
+        if let Some(min) = rules.min_timestamp
+            && self.timestamp < min
+        {
+            errors.push(ValidationError::TimestampTooEarly {
+                timestamp: self.timestamp,
+                min,
+            });
+        }
+        if let Some(max) = rules.max_timestamp
+            && self.timestamp > max
+        {
+            errors.push(ValidationError::TimestampTooLate {
+                timestamp: self.timestamp,
+                max,
+            });
+        }
+        match self.tx_type {
+            TxType::Transfer if !rules.allow_self_transfer && self.from_user == self.to_user => {
+                errors.push(ValidationError::SelfTransfer(self.from_user));
+            }
+            TxType::Deposit if rules.require_system_user && self.from_user != 0 => {
+                errors.push(ValidationError::DepositFromUser(self.from_user));
+            }
+            TxType::Withdrawal if rules.require_system_user && self.to_user != 0 => {
+                errors.push(ValidationError::WithdrawalToUser(self.to_user));
+            }
+            _ => (),
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::Status;
+
+    fn record(tx_type: TxType, from_user: u64, to_user: u64, amount: u64) -> Record {
+        Record::new(
+            1,
+            tx_type,
+            from_user,
+            to_user,
+            amount,
+            1633036860000,
+            Status::Success,
+            "fee".into(),
+        )
+    }
+
+    fn errors(record: &Record, rules: &ValidationRules) -> Vec<ValidationError> {
+        record.validate(rules).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_each_rule() {
+        let rules = ValidationRules::default();
+        let valid = record(TxType::Transfer, 1, 2, 10);
+        assert_eq!(valid.validate(&rules), Ok(()));
+
+        let only_deposits = ValidationRules {
+            allowed_tx_types: vec![TxType::Deposit],
+            ..Default::default()
+        };
+        assert_eq!(
+            errors(&valid, &only_deposits),
+            [ValidationError::TxTypeNotAllowed(TxType::Transfer)]
+        );
+        assert_eq!(
+            errors(&record(TxType::Transfer, 1, 2, 0), &rules),
+            [ValidationError::AmountTooSmall { amount: 0, min: 1 }]
+        );
+        let bounded = ValidationRules {
+            max_amount: Some(5),
+            min_timestamp: Some(1633036860001),
+            ..Default::default()
+        };
+        assert_eq!(
+            errors(&valid, &bounded),
+            [
+                ValidationError::AmountTooLarge { amount: 10, max: 5 },
+                ValidationError::TimestampTooEarly {
+                    timestamp: 1633036860000,
+                    min: 1633036860001
+                }
+            ]
+        );
+        let until = ValidationRules {
+            max_timestamp: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            errors(&valid, &until),
+            [ValidationError::TimestampTooLate {
+                timestamp: 1633036860000,
+                max: 1
+            }]
+        );
+
+        let self_transfer = record(TxType::Transfer, 3, 3, 10);
+        assert_eq!(
+            errors(&self_transfer, &rules),
+            [ValidationError::SelfTransfer(3)]
+        );
+        let allow = ValidationRules {
+            allow_self_transfer: true,
+            ..Default::default()
+        };
+        assert_eq!(self_transfer.validate(&allow), Ok(()));
+
+        assert_eq!(
+            errors(&record(TxType::Deposit, 4, 2, 10), &rules),
+            [ValidationError::DepositFromUser(4)]
+        );
+        assert_eq!(
+            errors(&record(TxType::Withdrawal, 2, 5, 10), &rules),
+            [ValidationError::WithdrawalToUser(5)]
+        );
+        let any_user = ValidationRules {
+            require_system_user: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            record(TxType::Deposit, 4, 2, 10).validate(&any_user),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_several_violations() {
+        let rules = ValidationRules {
+            max_timestamp: Some(0),
+            ..Default::default()
+        };
+        let errors = errors(&record(TxType::Deposit, 7, 2, 0), &rules);
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            [
+                "amount 0 is less than 1",
+                "timestamp 1633036860000 is after 0",
+                "deposit comes from user 7 instead of 0",
+            ]
+        );
+    }
+}
@@ -0,0 +1,29 @@
+#![cfg(feature = "tokio")]
+
+use std::{fs, io::Cursor};
+
+use parserde::{RecordIterator, build_async_reader, build_reader};
+
+const SOURCE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/examples/source/records_example"
+);
+
+#[tokio::test]
+async fn test_same_records_as_sync() {
+    for format in ["csv", "txt", "bin"] {
+        let input = fs::read(format!("{}.{}", SOURCE, format)).unwrap();
+        let expected =
+            RecordIterator::new(build_reader(Cursor::new(input.clone()), format).unwrap())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        let mut reader = build_async_reader(Cursor::new(input), format).unwrap();
+        let mut records = Vec::new();
+        while let Some(record) = reader.produce_record().await {
+            records.push(record.unwrap());
+        }
+        assert!(!records.is_empty(), "{}", format);
+        assert_eq!(records, expected, "{}", format);
+    }
+    assert!(build_async_reader(Cursor::new(Vec::new()), "ndjson").is_err());
+}
@@ -0,0 +1,153 @@
+use std::{fs, path::Path, process::Command};
+
+const HEADER: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+
+fn compare_json(file1: &Path, file2: &Path) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_comparer"))
+        .arg("--file1")
+        .arg(file1)
+        .args(["--file1-format", "csv"])
+        .arg("--file2")
+        .arg(file2)
+        .args(["--file2-format", "csv", "--all", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_field_mismatch_histogram() {
+    let dir = tempfile::tempdir().unwrap();
+    let left = dir.path().join("left.csv");
+    let right = dir.path().join("right.csv");
+    fs::write(
+        &left,
+        format!(
+            "{}1,DEPOSIT,0,2,100,10,SUCCESS,a\n2,DEPOSIT,0,2,100,20,SUCCESS,b\n3,DEPOSIT,0,2,100,30,SUCCESS,c\n",
+            HEADER
+        ),
+    )
+    .unwrap();
+    fs::write(
+        &right,
+        format!(
+            "{}1,DEPOSIT,0,2,100,11,SUCCESS,a\n2,DEPOSIT,0,2,100,20,SUCCESS,b\n3,DEPOSIT,0,2,100,31,SUCCESS,c\n",
+            HEADER
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        compare_json(&left, &right).trim(),
+        "{\"compared\":3,\"differing\":2,\"unmatched\":0,\"field_mismatches\":{\"TX_ID\":0,\"TX_TYPE\":0,\"FROM_USER_ID\":0,\"TO_USER_ID\":0,\"AMOUNT\":0,\"TIMESTAMP\":2,\"STATUS\":0,\"DESCRIPTION\":0}}"
+    );
+
+    fs::write(
+        &right,
+        format!(
+            "{}1,DEPOSIT,0,2,150,11,SUCCESS,a\n2,DEPOSIT,0,2,100,20,FAILURE,b\n3,DEPOSIT,0,2,100,30,SUCCESS,x\n4,DEPOSIT,0,2,100,40,SUCCESS,d\n",
+            HEADER
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        compare_json(&left, &right).trim(),
+        "{\"compared\":3,\"differing\":3,\"unmatched\":1,\"field_mismatches\":{\"TX_ID\":0,\"TX_TYPE\":0,\"FROM_USER_ID\":0,\"TO_USER_ID\":0,\"AMOUNT\":1,\"TIMESTAMP\":1,\"STATUS\":1,\"DESCRIPTION\":1}}"
+    );
+}
+
+#[test]
+fn test_field_diff_mode() {
+    let dir = tempfile::tempdir().unwrap();
+    let left = dir.path().join("left.csv");
+    let right = dir.path().join("right.csv");
+    fs::write(&left, format!("{}1,DEPOSIT,0,2,100,10,SUCCESS,a\n", HEADER)).unwrap();
+    fs::write(
+        &right,
+        format!("{}1,DEPOSIT,0,2,150,10,FAILURE,a\n", HEADER),
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_comparer"))
+        .env("RUST_LOG", "info")
+        .arg("--file1")
+        .arg(&left)
+        .args(["--file1-format", "csv"])
+        .arg("--file2")
+        .arg(&right)
+        .args(["--file2-format", "csv", "--diff-mode", "field-diff"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let log = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        log.contains("AMOUNT: 100 in file1, 150 in file2"),
+        "{}",
+        log
+    );
+    assert!(
+        log.contains("STATUS: SUCCESS in file1, FAILURE in file2"),
+        "{}",
+        log
+    );
+    assert!(!log.contains("TIMESTAMP:"), "{}", log);
+}
+
+/// Converts the csv example to the format and compares the result with itself,
+/// then with a copy missing the last record
+fn compare_same_format(format: &str) {
+    let dir = tempfile::tempdir().unwrap();
+    let convert = |output: &Path, extra: &[&str]| {
+        let result = Command::new(env!("CARGO_BIN_EXE_converter"))
+            .args(["--input", "examples/source/records_example.csv"])
+            .args(["--input-format", "csv", "--output-format", format])
+            .arg("--output")
+            .arg(output)
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(result.status.success(), "{:?}", result);
+    };
+    let compare = |file1: &Path, file2: &Path| {
+        Command::new(env!("CARGO_BIN_EXE_comparer"))
+            .arg("--file1")
+            .arg(file1)
+            .args(["--file1-format", format])
+            .arg("--file2")
+            .arg(file2)
+            .args(["--file2-format", format, "--all", "--json"])
+            .output()
+            .unwrap()
+    };
+    let left = dir.path().join("left");
+    let right = dir.path().join("right");
+    let truncated = dir.path().join("truncated");
+    convert(&left, &[]);
+    convert(&right, &[]);
+    convert(&truncated, &["--limit", "2"]);
+
+    let output = compare(&left, &right);
+    assert!(output.status.success(), "{:?}", output);
+    let summary = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        summary.contains("\"differing\":0,\"unmatched\":0"),
+        "{}",
+        summary
+    );
+
+    let output = compare(&left, &truncated);
+    let summary = String::from_utf8(output.stdout).unwrap();
+    assert!(summary.contains("\"compared\":2"), "{}", summary);
+    assert!(!summary.contains("\"unmatched\":0"), "{}", summary);
+}
+
+#[test]
+fn test_same_format_without_raw_reader() {
+    compare_same_format("ndjson");
+    compare_same_format("bin64");
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn test_same_binary_format_without_raw_reader() {
+    compare_same_format("msgpack");
+}
@@ -0,0 +1,946 @@
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+};
+
+use parserde::{DataProducer, build_reader};
+
+const SOURCE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/examples/source/records_example"
+);
+
+fn convert(input_format: &str, output_format: &str, output: &Path, extra: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_converter"))
+        .arg("--input")
+        .arg(format!("{}.{}", SOURCE, input_format))
+        .args(["--input-format", input_format, "-o", output_format])
+        .arg("--output")
+        .arg(output)
+        .args(extra)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_resume_matches_uninterrupted_run() {
+    let dir = tempfile::tempdir().unwrap();
+    for (input_format, output_format) in [("csv", "bin"), ("txt", "csv"), ("bin", "txt")] {
+        let expected_path = dir.path().join("expected");
+        assert!(
+            convert(input_format, output_format, &expected_path, &[])
+                .status
+                .success()
+        );
+
+        let output = dir.path().join("output");
+        let checkpoint = dir.path().join("checkpoint");
+        let checkpoint_arg = checkpoint.to_str().unwrap();
+        let mut runs = 0;
+        loop {
+            runs += 1;
+            let result = convert(
+                input_format,
+                output_format,
+                &output,
+                &[
+                    "--checkpoint",
+                    checkpoint_arg,
+                    "--checkpoint-every",
+                    "100",
+                    "--limit",
+                    "350",
+                ],
+            );
+            assert!(result.status.success(), "{:?}", result);
+            if !checkpoint.exists() {
+                break;
+            }
+        }
+        assert_eq!(runs, 3);
+        assert_eq!(
+            fs::read(&output).unwrap(),
+            fs::read(&expected_path).unwrap()
+        );
+        fs::remove_file(&output).unwrap();
+    }
+}
+
+#[test]
+fn test_resume_drops_records_after_checkpoint() {
+    let dir = tempfile::tempdir().unwrap();
+    let expected_path = dir.path().join("expected");
+    assert!(convert("txt", "bin", &expected_path, &[]).status.success());
+
+    let output = dir.path().join("output");
+    let checkpoint = dir.path().join("checkpoint");
+    let args = [
+        "--checkpoint",
+        checkpoint.to_str().unwrap(),
+        "--limit",
+        "400",
+    ];
+    assert!(convert("txt", "bin", &output, &args).status.success());
+    let saved = fs::read(&checkpoint).unwrap();
+    assert!(convert("txt", "bin", &output, &args).status.success());
+    // as if the second run died right after the first checkpoint
+    fs::write(&checkpoint, saved).unwrap();
+
+    let result = convert("txt", "bin", &output, &args[..2]);
+    assert!(result.status.success(), "{:?}", result);
+    assert!(!checkpoint.exists());
+    assert_eq!(
+        fs::read(&output).unwrap(),
+        fs::read(&expected_path).unwrap()
+    );
+}
+
+#[test]
+fn test_resume_aborts_on_mismatch() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("output");
+    let checkpoint = dir.path().join("checkpoint");
+    let args = [
+        "--checkpoint",
+        checkpoint.to_str().unwrap(),
+        "--limit",
+        "10",
+    ];
+    assert!(convert("csv", "csv", &output, &args).status.success());
+
+    let mut truncated = fs::read(&output).unwrap();
+    truncated.truncate(truncated.len() / 2);
+    fs::write(&output, &truncated).unwrap();
+    let result = convert("csv", "csv", &output, &args);
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("can't resume from the checkpoint"));
+    assert_eq!(fs::read(&output).unwrap(), truncated);
+}
+
+#[test]
+fn test_resume_dictionary_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let csv = dir.path().join("input.csv");
+    let mut content =
+        "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n".to_string();
+    for i in 0..500 {
+        // the string table is defined by the first records and used by all
+        let description = ["Monthly fee", "Refund", "Salary"][i % 3];
+        content.push_str(&format!("{},DEPOSIT,0,1,10,5,SUCCESS,{}\n", i, description));
+    }
+    fs::write(&csv, content).unwrap();
+    let run = |input: &Path, from: &str, output: &Path, to: &str, extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(input)
+            .args(["--input-format", from, "-o", to, "--output"])
+            .arg(output)
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+    let input = dir.path().join("input.bin");
+    assert!(run(&csv, "csv", &input, "bin-dict", &[]).status.success());
+    let expected = dir.path().join("expected.csv");
+    assert!(run(&input, "bin", &expected, "csv", &[]).status.success());
+
+    let output = dir.path().join("output.csv");
+    let checkpoint = dir.path().join("checkpoint");
+    let args = [
+        "--checkpoint",
+        checkpoint.to_str().unwrap(),
+        "--checkpoint-every",
+        "100",
+        "--limit",
+        "350",
+    ];
+    let mut runs = 0;
+    loop {
+        runs += 1;
+        let result = run(&input, "bin", &output, "csv", &args);
+        assert!(result.status.success(), "{:?}", result);
+        if !checkpoint.exists() {
+            break;
+        }
+    }
+    assert_eq!(runs, 2);
+    assert_eq!(fs::read(&output).unwrap(), fs::read(&expected).unwrap());
+}
+
+#[test]
+fn test_resume_rejects_wrapped_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let checkpoint = dir.path().join("checkpoint");
+    for format in ["json", "xml", "md", "bin-dict"] {
+        let output = dir.path().join(format);
+        let result = convert(
+            "csv",
+            format,
+            &output,
+            &["--checkpoint", checkpoint.to_str().unwrap()],
+        );
+        assert!(!result.status.success(), "{}", format);
+        assert!(
+            String::from_utf8_lossy(&result.stderr).contains(&format!(
+                "--checkpoint is not supported for {} output",
+                format
+            )),
+            "{:?}",
+            result
+        );
+        assert!(!output.exists());
+    }
+}
+
+#[test]
+fn test_append() {
+    let dir = tempfile::tempdir().unwrap();
+    let once = dir.path().join("once.ndjson");
+    assert!(convert("csv", "ndjson", &once, &[]).status.success());
+    let output = dir.path().join("output.ndjson");
+    for _ in 0..2 {
+        let result = convert("csv", "ndjson", &output, &["--append"]);
+        assert!(result.status.success(), "{:?}", result);
+    }
+    let once = fs::read_to_string(&once).unwrap();
+    assert_eq!(fs::read_to_string(&output).unwrap(), once.repeat(2));
+
+    for format in ["json", "xml", "md", "html", "toml", "bin-dict"] {
+        let output = dir.path().join(format);
+        let result = convert("csv", format, &output, &["--append"]);
+        assert!(!result.status.success(), "{}", format);
+        assert!(
+            String::from_utf8_lossy(&result.stderr).contains("output can't be appended to"),
+            "{:?}",
+            result
+        );
+        assert!(!output.exists());
+    }
+}
+
+#[test]
+fn test_multiple_outputs_match_single_runs() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("single.bin");
+    let csv = dir.path().join("single.csv");
+    assert!(convert("txt", "bin", &bin, &[]).status.success());
+    assert!(convert("txt", "csv", &csv, &[]).status.success());
+
+    let multi_bin = dir.path().join("multi.bin");
+    let multi_csv = dir.path().join("multi.csv");
+    let result = convert(
+        "txt",
+        "bin",
+        &multi_bin,
+        &[
+            "--output",
+            multi_csv.to_str().unwrap(),
+            "--output-format",
+            "csv",
+        ],
+    );
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(fs::read(&multi_bin).unwrap(), fs::read(&bin).unwrap());
+    assert_eq!(fs::read(&multi_csv).unwrap(), fs::read(&csv).unwrap());
+
+    let result = convert("txt", "bin", &multi_bin, &["--output-format", "csv"]);
+    assert!(!result.status.success());
+}
+
+#[test]
+fn test_preserve_comments() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("banner.txt");
+    let source = fs::read_to_string(format!("{}.txt", SOURCE)).unwrap();
+    fs::write(
+        &input,
+        format!("# exported 2024-05-01 by batch 118\n{}", source),
+    )
+    .unwrap();
+    let output = dir.path().join("output.txt");
+    let result = Command::new(env!("CARGO_BIN_EXE_converter"))
+        .arg("--input")
+        .arg(&input)
+        .args(["--input-format", "txt", "-o", "txt", "--preserve-comments"])
+        .arg("--output")
+        .arg(&output)
+        .output()
+        .unwrap();
+    assert!(result.status.success(), "{:?}", result);
+    let output = fs::read_to_string(&output).unwrap();
+    assert!(
+        output.starts_with("# exported 2024-05-01 by batch 118\n# Record 1 (DEPOSIT)\nTX_ID: ")
+    );
+    assert_eq!(output.matches("TX_ID: ").count(), 1000);
+
+    let result = convert(
+        "txt",
+        "csv",
+        &dir.path().join("out.csv"),
+        &["--preserve-comments"],
+    );
+    assert!(!result.status.success());
+}
+
+#[test]
+fn test_empty_csv_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("output.bin");
+    let run = |input: &Path, extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(input)
+            .args(["--input-format", "csv", "-o", "bin"])
+            .arg("--output")
+            .arg(&output)
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+    let header_only = dir.path().join("header.csv");
+    fs::write(
+        &header_only,
+        "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n",
+    )
+    .unwrap();
+    let result = run(&header_only, &[]);
+    assert!(result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("0 records"));
+
+    let empty = dir.path().join("empty.csv");
+    fs::write(&empty, "").unwrap();
+    let result = run(&empty, &[]);
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("empty input, no header found"));
+    assert!(run(&empty, &["--allow-empty"]).status.success());
+    assert!(fs::read(&output).unwrap().is_empty());
+}
+
+#[test]
+fn test_canonicalize_fixed_point() {
+    let dir = tempfile::tempdir().unwrap();
+    for format in ["csv", "txt", "bin"] {
+        let once = dir.path().join(format!("once.{}", format));
+        let result = convert(format, format, &once, &["--canonicalize"]);
+        assert!(result.status.success(), "{:?}", result);
+        assert!(!String::from_utf8_lossy(&result.stderr).contains("warning"));
+
+        let twice = dir.path().join(format!("twice.{}", format));
+        let result = Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(&once)
+            .args(["--input-format", format, "-o", format, "--canonicalize"])
+            .arg("--output")
+            .arg(&twice)
+            .output()
+            .unwrap();
+        assert!(result.status.success(), "{:?}", result);
+        assert_eq!(fs::read(&once).unwrap(), fs::read(&twice).unwrap());
+    }
+
+    let result = convert("csv", "csv", &dir.path().join("out.csv"), &[]);
+    assert!(String::from_utf8_lossy(&result.stderr).contains("warning"));
+    let result = convert(
+        "csv",
+        "bin",
+        &dir.path().join("out.bin"),
+        &["--canonicalize"],
+    );
+    assert!(!result.status.success());
+}
+
+#[test]
+fn test_canonicalize_keeps_descriptions() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.csv");
+    fs::write(
+        &input,
+        "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+         1,DEPOSIT,0,1,10,5,SUCCESS,\"  lead\tin \"\n",
+    )
+    .unwrap();
+    let run = |input: &Path, from: &str, to: &str, output: &Path, extra: &[&str]| {
+        let result = Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(input)
+            .args(["--input-format", from, "-o", to])
+            .arg("--output")
+            .arg(output)
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(result.status.success(), "{:?}", result);
+    };
+    for format in ["csv", "txt", "bin"] {
+        let source = dir.path().join(format!("source.{}", format));
+        run(&input, "csv", format, &source, &[]);
+        let canonical = dir.path().join(format!("canonical.{}", format));
+        run(&source, format, format, &canonical, &["--canonicalize"]);
+        let records = build_reader(fs::File::open(&canonical).unwrap(), format)
+            .unwrap()
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].description(), "  lead\tin ", "{}", format);
+    }
+}
+
+#[test]
+fn test_csv_columns() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.csv");
+    fs::write(
+        &input,
+        "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+         1,DEPOSIT,0,1,10,1633036860000,SUCCESS,ok\n\
+         2,TRANSFER,1,2,5,951868799000,SUCCESS,ok\n",
+    )
+    .unwrap();
+    let output = dir.path().join("output.csv");
+    let run = |flag: &str, columns: &str| {
+        Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(&input)
+            .args(["--input-format", "csv", "-o", "csv", flag, columns])
+            .arg("--output")
+            .arg(&output)
+            .output()
+            .unwrap()
+    };
+    let result = run("--csv-columns", "TX_ID,DATE,TIME,AMOUNT,DIRECTION");
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "TX_ID,DATE,TIME,AMOUNT,DIRECTION\n\
+         1,2021-09-30,21:21:00,10,IN\n\
+         2,2000-02-29,23:59:59,5,OUT\n"
+    );
+    let result = run("--csv-columns", "TX_ID,WEEKDAY");
+    assert!(!result.status.success());
+    let result = run("--csv-fields", "AMOUNT,TX_ID");
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "AMOUNT,TX_ID\n10,1\n5,2\n"
+    );
+}
+
+#[test]
+fn test_field_defaults() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.csv");
+    fs::write(
+        &input,
+        "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS\n\
+         1,DEPOSIT,0,1,10,5,SUCCESS\n",
+    )
+    .unwrap();
+    let output = dir.path().join("output.csv");
+    let run = |defaults: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_converter"));
+        command
+            .arg("--input")
+            .arg(&input)
+            .args(["--input-format", "csv", "-o", "csv", "--output"])
+            .arg(&output);
+        for default in defaults {
+            command.args(["--default", default]);
+        }
+        command.output().unwrap()
+    };
+    let result = run(&["DESCRIPTION=n/a", "AMOUNT=0"]);
+    assert!(result.status.success(), "{:?}", result);
+    assert!(
+        fs::read_to_string(&output)
+            .unwrap()
+            .ends_with("1,DEPOSIT,0,1,10,5,SUCCESS,n/a\n")
+    );
+    assert!(run(&[]).status.success());
+    assert!(
+        fs::read_to_string(&output)
+            .unwrap()
+            .ends_with("1,DEPOSIT,0,1,10,5,SUCCESS,\n")
+    );
+    assert!(!run(&["DESCRIPTION"]).status.success());
+    assert!(!run(&["DESCRIPTION=", "TO_USER_ID=x"]).status.success());
+}
+
+#[test]
+fn test_atomic_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let header = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+    let good = format!(
+        "{}1,DEPOSIT,0,1,10,1,SUCCESS,ok\n2,DEPOSIT,0,1,10,2,SUCCESS,ok\n",
+        header
+    );
+    // the writer fails on the control character of the second record
+    let failing = format!(
+        "{}1,DEPOSIT,0,1,10,1,SUCCESS,ok\n2,DEPOSIT,0,1,10,2,SUCCESS,\"a\tb\"\n",
+        header
+    );
+    let output = dir.path().join("output.csv");
+    let run = |input: &str| {
+        let input_path = dir.path().join("input.csv");
+        fs::write(&input_path, input).unwrap();
+        Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(&input_path)
+            .args(["--input-format", "csv", "-o", "csv", "--atomic"])
+            .args(["--sanitize-descriptions", "error", "--output"])
+            .arg(&output)
+            .output()
+            .unwrap()
+    };
+    let files = || {
+        let mut names = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+
+    assert!(!run(&failing).status.success());
+    assert!(!output.exists());
+    assert_eq!(files(), ["input.csv"]);
+
+    let result = run(&good);
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(fs::read_to_string(&output).unwrap(), good);
+    assert_eq!(files(), ["input.csv", "output.csv"]);
+
+    assert!(!run(&failing).status.success());
+    assert_eq!(fs::read_to_string(&output).unwrap(), good);
+    assert_eq!(files(), ["input.csv", "output.csv"]);
+}
+
+#[test]
+fn test_input_encoding() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.csv");
+    let mut content =
+        b"TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n".to_vec();
+    content.extend_from_slice(b"1,DEPOSIT,0,1,10,5,SUCCESS,caf\xe9 \x80 \x93gr\xfc\xdf\x94\n");
+    fs::write(&input, content).unwrap();
+    let output = dir.path().join("output.csv");
+    let run = |encoding: &str| {
+        Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(&input)
+            .args([
+                "--input-format",
+                "csv",
+                "-o",
+                "csv",
+                "--input-encoding",
+                encoding,
+            ])
+            .arg("--output")
+            .arg(&output)
+            .output()
+            .unwrap()
+    };
+    assert!(!run("utf-8").status.success());
+    let result = run("windows-1252");
+    assert!(result.status.success(), "{:?}", result);
+    assert!(
+        fs::read_to_string(&output)
+            .unwrap()
+            .ends_with("1,DEPOSIT,0,1,10,5,SUCCESS,café € “grüß”\n")
+    );
+}
+
+#[test]
+fn test_ndjson_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.csv");
+    let content = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,1,10,5,SUCCESS,café\n";
+    fs::write(&input, content).unwrap();
+    let ndjson = dir.path().join("output.ndjson");
+    let output = dir.path().join("output.csv");
+    let run = |input: &std::path::Path, from: &str, output: &std::path::Path, to: &str| {
+        Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(input)
+            .args(["--input-format", from, "--output-format", to])
+            .arg("--output")
+            .arg(output)
+            .output()
+            .unwrap()
+    };
+    let result = run(&input, "csv", &ndjson, "ndjson");
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(
+        fs::read_to_string(&ndjson).unwrap(),
+        "{\"TX_ID\":1,\"TX_TYPE\":\"DEPOSIT\",\"FROM_USER_ID\":0,\"TO_USER_ID\":1,\"AMOUNT\":10,\"TIMESTAMP\":5,\"STATUS\":\"SUCCESS\",\"DESCRIPTION\":\"café\"}\n"
+    );
+    let result = run(&ndjson, "ndjson", &output, "csv");
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(fs::read_to_string(&output).unwrap(), content);
+}
+
+#[test]
+fn test_logfmt_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.csv");
+    let content = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n1,DEPOSIT,0,1,10,5,SUCCESS,\"say \"\"hi\"\"\"\n";
+    fs::write(&input, content).unwrap();
+    let logfmt = dir.path().join("output.logfmt");
+    let output = dir.path().join("output.csv");
+    let run = |input: &std::path::Path, from: &str, output: &std::path::Path, to: &str| {
+        Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(input)
+            .args(["--input-format", from, "--output-format", to])
+            .arg("--output")
+            .arg(output)
+            .output()
+            .unwrap()
+    };
+    let result = run(&input, "csv", &logfmt, "logfmt");
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(
+        fs::read_to_string(&logfmt).unwrap(),
+        "TX_ID=1 TX_TYPE=DEPOSIT FROM_USER_ID=0 TO_USER_ID=1 AMOUNT=10 TIMESTAMP=5 STATUS=SUCCESS DESCRIPTION=\"say \\\"hi\\\"\"\n"
+    );
+    let result = run(&logfmt, "logfmt", &output, "csv");
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(fs::read_to_string(&output).unwrap(), content);
+}
+
+#[test]
+fn test_pgcopy_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("output.sql");
+    let result = convert("csv", "pgcopy", &output, &["--pgcopy-table", "records"]);
+    assert!(result.status.success(), "{:?}", result);
+    let content = fs::read_to_string(&output).unwrap();
+    let lines = content.lines().collect::<Vec<_>>();
+    assert!(
+        lines[0].starts_with("COPY \"records\" (TX_ID, "),
+        "{}",
+        content
+    );
+    assert_eq!(lines.last(), Some(&"\\."));
+    assert!(
+        lines[1..lines.len() - 1]
+            .iter()
+            .all(|l| l.split('\t').count() == 8)
+    );
+
+    let result = convert("csv", "csv", &output, &["--pgcopy-table", "records"]);
+    assert!(!result.status.success());
+}
+
+#[test]
+fn test_json_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("output.json");
+    let result = convert("txt", "json", &json, &[]);
+    assert!(result.status.success(), "{:?}", result);
+    let content = fs::read_to_string(&json).unwrap();
+    assert!(content.starts_with("[\n{\"TX_ID\":"), "{}", content);
+    assert!(content.ends_with("}\n]\n"), "{}", content);
+
+    let expected = dir.path().join("expected.txt");
+    assert!(convert("txt", "txt", &expected, &[]).status.success());
+    let output = dir.path().join("output.txt");
+    let result = Command::new(env!("CARGO_BIN_EXE_converter"))
+        .arg("--input")
+        .arg(&json)
+        .args(["--input-format", "json", "--output-format", "txt"])
+        .arg("--output")
+        .arg(&output)
+        .output()
+        .unwrap();
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        fs::read_to_string(&expected).unwrap()
+    );
+}
+
+#[test]
+fn test_xml_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let xml = dir.path().join("output.xml");
+    let result = convert("csv", "xml", &xml, &[]);
+    assert!(result.status.success(), "{:?}", result);
+    let content = fs::read_to_string(&xml).unwrap();
+    assert!(content.starts_with("<?xml "), "{}", content);
+    assert!(content.ends_with("</transactions>\n"), "{}", content);
+
+    let expected = dir.path().join("expected.csv");
+    assert!(convert("csv", "csv", &expected, &[]).status.success());
+    let output = dir.path().join("output.csv");
+    let result = Command::new(env!("CARGO_BIN_EXE_converter"))
+        .arg("--input")
+        .arg(&xml)
+        .args(["--output-format", "csv", "--output"])
+        .arg(&output)
+        .output()
+        .unwrap();
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        fs::read_to_string(&expected).unwrap()
+    );
+}
+
+#[test]
+fn test_output_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("output.txt");
+    let result = Command::new(env!("CARGO_BIN_EXE_converter"))
+        .arg("--input")
+        .arg(format!("{}.bin", SOURCE))
+        .args(["--input-format", "bin", "--output-format", "txt"])
+        .arg("--output-file")
+        .arg(&output)
+        .output()
+        .unwrap();
+    assert!(result.status.success(), "{:?}", result);
+    let content = fs::read_to_string(&output).unwrap();
+    assert!(content.contains("TX_ID: "), "{}", content);
+    assert_eq!(String::from_utf8(result.stdout).unwrap(), content);
+
+    let result = convert("bin", "txt", &output, &["--output-file", "other.txt"]);
+    assert!(!result.status.success());
+}
+
+#[test]
+fn test_glob_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let parts = dir.path().join("parts");
+    fs::create_dir(&parts).unwrap();
+    let header = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+    let rows = (1..=6)
+        .map(|i| format!("{},DEPOSIT,0,1,10,{},SUCCESS,part row {}\n", i, i, i))
+        .collect::<Vec<_>>();
+    for (i, chunk) in rows.chunks(2).enumerate() {
+        let part = parts.join(format!("2024-05-0{}.csv", i + 1));
+        fs::write(part, format!("{}{}", header, chunk.concat())).unwrap();
+    }
+    fs::write(parts.join("notes.md"), "not an input").unwrap();
+    let concatenated = dir.path().join("all.csv");
+    fs::write(&concatenated, format!("{}{}", header, rows.concat())).unwrap();
+    let convert = |input: &Path, output: &Path| {
+        Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(input)
+            .args(["--input-format", "csv", "-o", "txt", "--output"])
+            .arg(output)
+            .output()
+            .unwrap()
+    };
+    let expected = dir.path().join("expected.txt");
+    assert!(convert(&concatenated, &expected).status.success());
+    let output = dir.path().join("output.txt");
+    let result = convert(&parts.join("2024-05-*.csv"), &output);
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(fs::read(&output).unwrap(), fs::read(&expected).unwrap());
+    assert!(!convert(&parts, &output).status.success());
+    assert!(
+        !convert(&parts.join("2024-06-*.csv"), &output)
+            .status
+            .success()
+    );
+
+    fs::write(
+        parts.join("2024-05-02.csv"),
+        format!("{}3,DEPOSIT,0,1,ten,3,SUCCESS,bad\n", header),
+    )
+    .unwrap();
+    let result = convert(&parts.join("2024-05-*.csv"), &output);
+    assert!(!result.status.success());
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    assert!(stderr.contains("2024-05-02.csv"), "{}", stderr);
+}
+
+#[test]
+fn test_error_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let header = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+    let bad = [
+        "2,DEPOSIT,0,1,ten,2,SUCCESS,\"bad, amount\"\n",
+        "4,GIFT,0,1,10,4,SUCCESS,bad type\n",
+    ];
+    let input = dir.path().join("input.csv");
+    fs::write(
+        &input,
+        format!(
+            "{}1,DEPOSIT,0,1,10,1,SUCCESS,ok\n{}3,DEPOSIT,0,1,10,3,SUCCESS,ok\n{}5,DEPOSIT,0,1,10,5,SUCCESS,ok\n",
+            header, bad[0], bad[1]
+        ),
+    )
+    .unwrap();
+    let run = |input: &Path, output: &Path, error_output: &Path| {
+        Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(input)
+            .args(["--input-format", "csv", "-o", "csv", "--error-output"])
+            .arg(error_output)
+            .arg("--output")
+            .arg(output)
+            .output()
+            .unwrap()
+    };
+    let output = dir.path().join("output.csv");
+    let rejected = dir.path().join("rejected.csv");
+    let result = run(&input, &output, &rejected);
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(
+        fs::read_to_string(&rejected).unwrap(),
+        format!("{}{}{}", header, bad[0], bad[1])
+    );
+    let output = fs::read_to_string(&output).unwrap();
+    assert_eq!(output.lines().count(), 4);
+    assert!(output.lines().skip(1).all(|l| l.ends_with(",ok")));
+    let index = fs::read_to_string(dir.path().join("rejected.csv.index")).unwrap();
+    assert_eq!(index.lines().count(), 2);
+    assert!(index.starts_with("input_record=2 "), "{}", index);
+
+    // the dead-letter file is a valid input of the same format
+    let again = dir.path().join("again.csv");
+    let result = run(&rejected, &dir.path().join("none.csv"), &again);
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(fs::read(&again).unwrap(), fs::read(&rejected).unwrap());
+}
+
+#[test]
+fn test_txt_error_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let block = |tx_id: u32, amount: &str| {
+        format!(
+            "TX_ID: {}\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 1\nAMOUNT: {}\nTIMESTAMP: 1\nSTATUS: SUCCESS\nDESCRIPTION: \"fee\"\n\n",
+            tx_id, amount
+        )
+    };
+    let input = dir.path().join("input.txt");
+    fs::write(
+        &input,
+        [block(1, "10"), block(2, "-3"), block(3, "10")].concat(),
+    )
+    .unwrap();
+    let rejected = dir.path().join("rejected.txt");
+    for (input, output) in [(&input, "output.bin"), (&rejected, "again.bin")] {
+        let result = Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(input)
+            .args(["--input-format", "txt", "-o", "bin", "--error-output"])
+            .arg(dir.path().join(format!("{}.txt", output)))
+            .arg("--output")
+            .arg(dir.path().join(output))
+            .output()
+            .unwrap();
+        assert!(result.status.success(), "{:?}", result);
+        if output == "output.bin" {
+            fs::rename(dir.path().join("output.bin.txt"), &rejected).unwrap();
+        }
+    }
+    assert_eq!(fs::read_to_string(&rejected).unwrap(), block(2, "-3"));
+    assert_eq!(
+        fs::read(dir.path().join("again.bin.txt")).unwrap(),
+        fs::read(&rejected).unwrap()
+    );
+    let output = fs::read(dir.path().join("output.bin")).unwrap();
+    assert_eq!(output.len(), 2 * (8 + 46 + 5 + 4));
+}
+
+#[test]
+fn test_detected_input_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let run = |input: &Path, output: &Path| {
+        Command::new(env!("CARGO_BIN_EXE_converter"))
+            .arg("--input")
+            .arg(input)
+            .args(["--output-format", "csv", "--output"])
+            .arg(output)
+            .output()
+            .unwrap()
+    };
+    for (format, name) in [
+        ("bin", "records"),
+        ("txt", "records.log"),
+        ("csv", "records.csv"),
+        ("bin", "records.ypbn"),
+    ] {
+        let expected = dir.path().join(format!("{}.expected.csv", name));
+        let result = convert(format, "csv", &expected, &[]);
+        assert!(result.status.success(), "{:?}", result);
+        let input = dir.path().join(name);
+        fs::copy(format!("{}.{}", SOURCE, format), &input).unwrap();
+        let output = dir.path().join(format!("{}.csv", name));
+        let result = run(&input, &output);
+        assert!(result.status.success(), "{}: {:?}", name, result);
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            fs::read_to_string(&expected).unwrap(),
+            "{}",
+            name
+        );
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_sqlite_output() {
+    use parserde::{RecordIterator, SqliteReader, build_reader_from_path};
+
+    let dir = tempfile::tempdir().unwrap();
+    let database = dir.path().join("records.db");
+    let output = format!("sqlite://{}#transfers", database.display());
+    for _ in 0..2 {
+        let result = convert("csv", "sqlite", Path::new(&output), &[]);
+        assert!(result.status.success(), "{:?}", result);
+    }
+    let expected =
+        RecordIterator::new(build_reader_from_path(Path::new(&format!("{}.csv", SOURCE))).unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+    let read = RecordIterator::new(SqliteReader::open(&database, "transfers").unwrap())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    // a second run appends to the existing table
+    assert_eq!(read, [expected.clone(), expected].concat());
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn test_parquet_output() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::Field;
+    use parserde::{RecordIterator, build_reader_from_path};
+
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("records.parquet");
+    let result = convert("csv", "parquet", &output, &["--atomic"]);
+    assert!(result.status.success(), "{:?}", result);
+    let expected =
+        RecordIterator::new(build_reader_from_path(Path::new(&format!("{}.csv", SOURCE))).unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+    let reader = SerializedFileReader::new(fs::File::open(&output).unwrap()).unwrap();
+    let rows = reader
+        .get_row_iter(None)
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(rows.len(), expected.len());
+    for (row, record) in rows.iter().zip(&expected) {
+        let columns = row.get_column_iter().collect::<Vec<_>>();
+        assert_eq!(
+            columns[0],
+            (&"TX_ID".to_string(), &Field::ULong(record.tx_id()))
+        );
+        assert_eq!(
+            columns[7],
+            (
+                &"DESCRIPTION".to_string(),
+                &Field::Str(record.description().to_string())
+            )
+        );
+    }
+}
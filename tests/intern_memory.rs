@@ -0,0 +1,84 @@
+//! Memory taken by records loaded with and without description interning.
+//! The allocator of this test binary counts the bytes in use, so the file
+//! holds a single test which nothing else allocates alongside
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use parserde::{Record, Status, StringInterner, TxType, VecProducer};
+
+struct Counting;
+
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        IN_USE.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        IN_USE.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        IN_USE.fetch_add(new_size, Ordering::Relaxed);
+        IN_USE.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const COUNT: usize = 100_000;
+const DESCRIPTIONS: [&str; 2] = ["Monthly fee", "Refund for order"];
+
+fn in_use() -> usize {
+    IN_USE.load(Ordering::Relaxed)
+}
+
+fn records() -> Vec<Record> {
+    let mut records = Vec::with_capacity(COUNT);
+    for i in 0..COUNT {
+        records.push(Record::new(
+            i as u64,
+            TxType::Withdrawal,
+            i as u64,
+            0,
+            100,
+            1633036860000,
+            Status::Success,
+            DESCRIPTIONS[i % 2].to_string(),
+        ));
+    }
+    records
+}
+
+#[test]
+fn test_interning_saves_memory() {
+    let start = in_use();
+    let owned = records();
+    let owned_bytes = in_use() - start;
+    drop(owned);
+
+    let interner = StringInterner::new();
+    let mut producer = VecProducer::from_vec(records());
+    let mut compact = interner.read_all(&mut producer).unwrap();
+    drop(producer);
+    // the owned records are allocated at their exact count
+    compact.shrink_to_fit();
+    let compact_bytes = in_use() - start;
+
+    let description_bytes = COUNT / 2 * DESCRIPTIONS.iter().map(|d| d.len()).sum::<usize>();
+    assert_eq!(compact.len(), COUNT);
+    // only the pool keeps a copy of the descriptions
+    assert!(
+        owned_bytes >= compact_bytes + description_bytes,
+        "{} bytes owned, {} bytes interned",
+        owned_bytes,
+        compact_bytes
+    );
+    assert!(size_of::<parserde::CompactRecord>() < size_of::<Record>());
+}
@@ -0,0 +1,169 @@
+use std::{fs, io::Cursor, panic};
+
+use parserde::{
+    InspectDepth, ParseOptions, RecordProduceErrorKind, build_partial_reader,
+    build_reader_with_options, inspect,
+};
+
+const SOURCE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/examples/source/records_example"
+);
+
+/// Count of leading fixture records mutated in every case
+const CORPUS_RECORDS: usize = 4;
+
+fn corpus(format: &str) -> Vec<u8> {
+    let mut source = fs::read(format!("{}.{}", SOURCE, format)).unwrap();
+    let mut reader = parserde::build_reader(Cursor::new(source.clone()), format).unwrap();
+    for _ in 0..CORPUS_RECORDS {
+        reader.produce_record().unwrap().unwrap();
+    }
+    source.truncate(reader.position().unwrap().byte as usize);
+    source
+}
+
+/// Drains every reader of the format over the input. Only Ok and Err are expected
+fn consume(input: &[u8], format: &str) {
+    let options = ParseOptions {
+        allow_empty: true,
+        ..Default::default()
+    };
+    if let Ok(mut reader) = build_reader_with_options(Cursor::new(input.to_vec()), format, &options)
+    {
+        drain(|| reader.produce_record().map(|r| r.is_ok()));
+    }
+    if let Ok(mut reader) = build_partial_reader(Cursor::new(input.to_vec()), format) {
+        drain(|| reader.produce_partial_record().map(|r| r.is_ok()));
+    }
+    let _ = inspect(Cursor::new(input.to_vec()), format, InspectDepth::Full);
+}
+
+/// Reads until the end or a run of errors which a reader may never recover from
+fn drain(mut next: impl FnMut() -> Option<bool>) {
+    let mut errors = 0;
+    while let Some(ok) = next() {
+        errors = if ok { 0 } else { errors + 1 };
+        if errors > 16 {
+            break;
+        }
+    }
+}
+
+fn assert_no_panic(input: &[u8], format: &str, case: &str) {
+    let result = panic::catch_unwind(|| consume(input, format));
+    assert!(
+        result.is_ok(),
+        "{} reader panicked on {} of {:?}",
+        format,
+        case,
+        String::from_utf8_lossy(input)
+    );
+}
+
+#[test]
+fn test_truncated_input() {
+    for format in ["csv", "txt", "bin"] {
+        let input = corpus(format);
+        for len in 0..input.len() {
+            assert_no_panic(&input[..len], format, &format!("truncation to {}", len));
+        }
+    }
+}
+
+#[test]
+fn test_mutated_input() {
+    let mut seed = 0x9e3779b97f4a7c15u64;
+    let mut next = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    for format in ["csv", "txt", "bin"] {
+        let corpus = corpus(format);
+        for case in 0..2000 {
+            let mut input = corpus.clone();
+            for _ in 0..1 + next() % 4 {
+                let at = (next() % input.len() as u64) as usize;
+                let byte = next() as u8;
+                match next() % 4 {
+                    0 => input[at] = byte,
+                    1 => input[at] ^= 1 << (byte % 8),
+                    2 => input.insert(at, byte),
+                    _ => {
+                        input.remove(at);
+                    }
+                }
+            }
+            assert_no_panic(&input, format, &format!("mutation {}", case));
+        }
+    }
+}
+
+/// Input failing on every read
+struct Failing;
+
+impl std::io::Read for Failing {
+    fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("disk on fire"))
+    }
+}
+
+#[test]
+fn test_error_kinds() {
+    let kind = |input: &[u8], format: &str| {
+        let mut reader = parserde::build_reader(Cursor::new(input.to_vec()), format).unwrap();
+        reader.produce_record().unwrap().unwrap_err().kind()
+    };
+    let parse_field = |name: &str| RecordProduceErrorKind::ParseField {
+        field_name: name.into(),
+    };
+    let missing_field = |name: &str| RecordProduceErrorKind::MissingField {
+        field_name: name.into(),
+    };
+
+    let header = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n";
+    let csv = |row: &str| format!("{}{}\n", header, row).into_bytes();
+    assert_eq!(
+        kind(&csv("1,DEPOSIT,0,2,lots,9,SUCCESS,fee"), "csv"),
+        parse_field("AMOUNT")
+    );
+    assert_eq!(
+        kind(&csv("1,DEPOSIT,0,2,5,9"), "csv"),
+        RecordProduceErrorKind::MalformedRecord
+    );
+    let no_status = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,DESCRIPTION\n1,DEPOSIT,0,2,5,9,fee\n";
+    assert_eq!(kind(no_status.as_bytes(), "csv"), missing_field("STATUS"));
+
+    let txt = "TX_ID: 1\nTX_TYPE: DEPOSIT\nFROM_USER_ID: 0\nTO_USER_ID: 2\nAMOUNT: 5\nTIMESTAMP: 9\nSTATUS: SUCCESS\n";
+    let no_status = txt.replace("STATUS: SUCCESS\n", "");
+    assert_eq!(kind(no_status.as_bytes(), "txt"), missing_field("STATUS"));
+    let bad_status = txt.replace("SUCCESS", "DONE");
+    assert_eq!(kind(bad_status.as_bytes(), "txt"), parse_field("STATUS"));
+    let unknown = format!("{}BATCH: 7\n", txt);
+    assert_eq!(
+        kind(unknown.as_bytes(), "txt"),
+        RecordProduceErrorKind::MalformedRecord
+    );
+    let mut reader = parserde::build_reader(Failing, "txt").unwrap();
+    let e = reader.produce_record().unwrap().unwrap_err();
+    assert_eq!(e.kind(), RecordProduceErrorKind::Io);
+
+    let mut bin = corpus("bin");
+    bin[12] ^= 0xff;
+    assert_eq!(kind(&bin, "bin"), RecordProduceErrorKind::MalformedRecord);
+    // a legacy frame has no checksum, so a bad byte reaches the field parser
+    let record = parserde::Record::default();
+    let mut frame = parserde::build_serializer("bin")
+        .unwrap()
+        .serialize(&record)
+        .unwrap();
+    frame.truncate(frame.len() - 4);
+    let body_size = frame.len() as u32 - 8;
+    frame[4..8].copy_from_slice(&body_size.to_be_bytes());
+    let mut reader = parserde::build_reader(Cursor::new(frame.clone()), "bin-legacy").unwrap();
+    assert_eq!(reader.produce_record().unwrap().unwrap(), record);
+    frame[16] = 9;
+    assert_eq!(kind(&frame, "bin-legacy"), parse_field("TX_TYPE"));
+}
@@ -0,0 +1,102 @@
+use std::cell::RefCell;
+use std::io::{Cursor, Write};
+use std::rc::Rc;
+
+use parserde::{
+    ByStatus, Record, RecordIterator, RecordSplitter, Status, TxType, build_reader,
+    build_serializer, build_writer,
+};
+
+/// Writer whose output stays readable after it's moved into the splitter
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn records(&self, format: &str) -> Vec<Record> {
+        let data = self.0.borrow().clone();
+        RecordIterator::new(build_reader(Cursor::new(data), format).unwrap())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+}
+
+fn record(tx_id: u64, status: Status) -> Record {
+    Record::new(
+        tx_id,
+        TxType::Deposit,
+        0,
+        tx_id + 1,
+        tx_id * 100,
+        1633036860000 + tx_id,
+        status,
+        format!("Record number {}", tx_id),
+    )
+}
+
+#[test]
+fn test_split_by_status() {
+    let succeeded = SharedBuffer::default();
+    let rest = SharedBuffer::default();
+    let mut splitter = RecordSplitter::new(
+        vec![(
+            Box::new(ByStatus(Status::Success)),
+            build_writer(succeeded.clone(), "csv").unwrap(),
+            build_serializer("csv").unwrap(),
+        )],
+        build_writer(rest.clone(), "bin").unwrap(),
+        build_serializer("bin").unwrap(),
+    );
+    let statuses = [Status::Success, Status::Failure, Status::Pending];
+    let records = (0..9)
+        .map(|i| record(i, statuses[i as usize % 3]))
+        .collect::<Vec<_>>();
+    splitter.write_header().unwrap();
+    for record in &records {
+        splitter.write_record(record).unwrap();
+    }
+    splitter.finish().unwrap();
+
+    let (expected_succeeded, expected_rest): (Vec<_>, Vec<_>) = records
+        .into_iter()
+        .partition(|r| r.status() == Status::Success);
+    assert_eq!(succeeded.records("csv"), expected_succeeded);
+    assert_eq!(rest.records("bin"), expected_rest);
+}
+
+#[test]
+fn test_first_matching_route_wins() {
+    let first = SharedBuffer::default();
+    let second = SharedBuffer::default();
+    let rest = SharedBuffer::default();
+    let mut splitter = RecordSplitter::new(
+        vec![
+            (
+                Box::new(ByStatus(Status::Failure)),
+                build_writer(first.clone(), "txt").unwrap(),
+                build_serializer("txt").unwrap(),
+            ),
+            (
+                Box::new(ByStatus(Status::Failure)),
+                build_writer(second.clone(), "txt").unwrap(),
+                build_serializer("txt").unwrap(),
+            ),
+        ],
+        build_writer(rest.clone(), "txt").unwrap(),
+        build_serializer("txt").unwrap(),
+    );
+    splitter.write_record(&record(1, Status::Failure)).unwrap();
+    splitter.write_record(&record(2, Status::Success)).unwrap();
+    assert_eq!(first.records("txt"), vec![record(1, Status::Failure)]);
+    assert!(second.records("txt").is_empty());
+    assert_eq!(rest.records("txt"), vec![record(2, Status::Success)]);
+}
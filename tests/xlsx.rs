@@ -0,0 +1,102 @@
+#![cfg(feature = "xlsx")]
+
+use std::path::Path;
+use std::process::Command;
+
+use parserde::{
+    DataProducer, Record, RecordIterator, Status, TxType, XlsxReader, build_reader_from_path,
+};
+
+const WORKBOOK: &str = "tests/data/transactions.xlsx";
+
+fn read(reader: impl DataProducer) -> Vec<Record> {
+    RecordIterator::new(reader)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+}
+
+fn expected() -> Vec<Record> {
+    vec![
+        Record::new(
+            1,
+            TxType::Deposit,
+            0,
+            101,
+            10000,
+            // the date cell 2021-10-01 12:00
+            1633089600000,
+            Status::Success,
+            "Salary for \"September\"".into(),
+        ),
+        Record::new(
+            2,
+            TxType::Transfer,
+            101,
+            102,
+            2550,
+            1633046400000,
+            Status::Pending,
+            "Rent, October".into(),
+        ),
+        Record::new(
+            3,
+            TxType::Withdrawal,
+            102,
+            0,
+            700,
+            1633132800000,
+            Status::Failure,
+            "ATM".into(),
+        ),
+    ]
+}
+
+#[test]
+fn test_first_sheet() {
+    let mut records = RecordIterator::new(XlsxReader::open(Path::new(WORKBOOK), None).unwrap());
+    assert_eq!(
+        records.by_ref().collect::<Result<Vec<_>, _>>().unwrap(),
+        expected()
+    );
+    // the header is row 1 and the empty row 4 is skipped
+    assert_eq!(records.into_inner().position().unwrap().line, 5);
+    assert_eq!(
+        read(build_reader_from_path(Path::new(WORKBOOK)).unwrap()),
+        expected()
+    );
+}
+
+#[test]
+fn test_named_sheet() {
+    let reader = XlsxReader::open(Path::new(WORKBOOK), Some("archive")).unwrap();
+    assert_eq!(
+        read(reader),
+        [Record::new(
+            9,
+            TxType::Deposit,
+            0,
+            1,
+            5,
+            1,
+            Status::Success,
+            "archived".into()
+        )]
+    );
+    assert!(XlsxReader::open(Path::new(WORKBOOK), Some("missing")).is_err());
+}
+
+#[test]
+fn test_convert_to_csv() {
+    let output = Command::new(env!("CARGO_BIN_EXE_converter"))
+        .args(["--input", WORKBOOK, "--output-format", "csv"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION\n\
+        1,DEPOSIT,0,101,10000,1633089600000,SUCCESS,\"Salary for \"\"September\"\"\"\n\
+        2,TRANSFER,101,102,2550,1633046400000,PENDING,\"Rent, October\"\n\
+        3,WITHDRAWAL,102,0,700,1633132800000,FAILURE,ATM\n"
+    );
+}